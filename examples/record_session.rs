@@ -0,0 +1,110 @@
+//! Record a session of the challenge for `tests/sessions.rs`.
+//!
+//! ```text
+//! cargo run --example record_session -- <name> [args...] < input
+//! ```
+//!
+//! The input is read from stdin up to its end and fed to the current
+//! build of the challenge, run with `args`. The exit code, the output
+//! and the report of the run are written to
+//! `tests/sessions/<name>.session`, replacing any session of that
+//! name. Check what was recorded, and describe the session in its
+//! first line, before committing it.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, ExitCode, Stdio};
+
+/// Run the challenge on `input` and format the session.
+fn record(args: &[String], input: &str) -> Result<String, String> {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+
+    // Build first, so that the output of the build is not recorded.
+    let built = Command::new(&cargo)
+        .args(["build", "-q", "--bin", "modern_legacy", "--manifest-path"])
+        .arg(&manifest)
+        .status()
+        .map_err(|err| format!("cannot run {}: {}", cargo, err))?;
+    if !built.success() {
+        return Err("cannot build the challenge".to_string());
+    }
+
+    let report_path = env::temp_dir().join(format!("record_session-{}.report", std::process::id()));
+    let mut child = Command::new(&cargo)
+        .args(["run", "-q", "--bin", "modern_legacy", "--manifest-path"])
+        .arg(&manifest)
+        .arg("--")
+        .args(args)
+        .arg("--report")
+        .arg(&report_path)
+        .env_remove("CHALLENGE_KEY")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("cannot run the challenge: {}", err))?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .map_err(|err| format!("cannot write the input: {}", err))?;
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("cannot run the challenge: {}", err))?;
+    let status = output
+        .status
+        .code()
+        .ok_or("the challenge was killed by a signal")?;
+    let report = fs::read_to_string(&report_path)
+        .map_err(|err| format!("cannot read the report: {}", err))?;
+    let _ = fs::remove_file(&report_path);
+
+    let mut session = String::from("# Recorded with `record_session`.\n");
+    if !args.is_empty() {
+        session.push_str(&format!("args: {}\n", args.join(" ")));
+    }
+    session.push_str(&format!("status: {}\n", status));
+    session.push_str("--- input\n");
+    session.push_str(input);
+    session.push_str("--- stdout\n");
+    session.push_str(&String::from_utf8_lossy(&output.stdout));
+    session.push_str("--- stderr\n");
+    session.push_str(&String::from_utf8_lossy(&output.stderr));
+    session.push_str("--- report\n");
+    session.push_str(&report);
+    Ok(session)
+}
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let Some(name) = args.next() else {
+        eprintln!("usage: record_session <name> [args...] < input");
+        return ExitCode::from(2);
+    };
+    let args: Vec<String> = args.collect();
+
+    let mut input = String::new();
+    if let Err(err) = io::stdin().read_to_string(&mut input) {
+        eprintln!("error: cannot read the input: {}", err);
+        return ExitCode::FAILURE;
+    }
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/sessions")
+        .join(format!("{}.session", name));
+    match record(&args, &input).and_then(|session| {
+        fs::write(&path, session).map_err(|err| format!("{}: {}", path.display(), err))
+    }) {
+        Ok(()) => {
+            println!("recorded {}", path.display());
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
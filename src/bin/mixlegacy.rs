@@ -0,0 +1,621 @@
+#![deny(clippy::all)]
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::result_unit_err)]
+#![allow(dead_code)]
+
+//! A command line front end for running and inspecting MIX memory
+//! images.
+//!
+//! ```text
+//! mixlegacy run <program> [--input FILE] [--entry ADDR] [--max-steps N]
+//! mixlegacy asm <source> -o <image> [--listing]
+//! mixlegacy disasm <image> [--from ADDR] [--to ADDR]
+//! mixlegacy dbg <program> [--input FILE] [--entry ADDR] [--max-steps N]
+//! mixlegacy batch <stream>
+//! mixlegacy selfcheck
+//! ```
+//!
+//! Exit codes: `0` on success, `1` if the machine or a file fails,
+//! `2` on usage errors.
+
+// Not every part of the runtime is used here.
+#[allow(unused_imports)]
+#[path = "../runtime/mod.rs"]
+mod runtime;
+use runtime::*;
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: mixlegacy <COMMAND> [OPTIONS]
+
+Commands:
+  run <program>    Run a memory image or MIXAL source
+  asm <source>     Assemble MIXAL source into a memory image
+  disasm <image>   Disassemble a memory image
+  dbg <program>    Debug a memory image or MIXAL source, reading commands
+                   from the standard input
+  batch <stream>   Run the jobs of a file, one per line
+  selfcheck        Check the instruction set for inconsistencies
+
+Programs whose name ends with .mixal are assembled first.
+
+Options for run, dbg and batch jobs:
+  --input FILE     Feed FILE to the card reader (unit 16), at most 1 MiB
+  --entry ADDR     Start at ADDR [default: 0, or the END of MIXAL source]
+  --max-steps N    Stop after N instructions [default: 10000000]
+
+Options for asm:
+  -o FILE          Write the memory image to FILE
+  --listing        Print the address and word of every line
+
+Options for disasm:
+  --from ADDR      First address to disassemble [default: 0]
+  --to ADDR        Last address to disassemble [default: 3999]
+
+Each line of a batch stream holds the options of a job, with paths
+relative to the stream. Blank lines and lines starting with # are
+skipped.
+
+General options:
+  -h, --help       Print help
+  -V, --version    Print version
+";
+
+/// Help text of `dbg`.
+const DBG_HELP: &str = "\
+Commands:
+  s, step [N]      Run N instructions [default: 1]
+  c, continue      Run until a breakpoint or a halt
+  b, break ADDR    Set a breakpoint at ADDR
+  d, delete ADDR   Remove the breakpoint at ADDR
+  r, regs          Print the registers
+  m, mem ADDR [N]  Print N words from ADDR [default: 1]
+  set ADDR WORD    Store WORD at ADDR, e.g. +1:2:3:4:5, =-12= or \"HELLO\"
+  h, help          Print help
+  q, quit          Stop debugging
+";
+
+/// The longest input file accepted by `run`, in bytes.
+const MAX_INPUT_LEN: usize = 1 << 20;
+
+/// The longest MIXAL source accepted, in bytes.
+const MAX_SOURCE_LEN: usize = 1 << 20;
+
+/// Errors reported to the user.
+#[derive(Debug)]
+enum CliError {
+    /// The command line is malformed.
+    Usage(String),
+
+    /// The command failed.
+    Failed(String),
+}
+
+/// Options of `run`, `dbg` and batch jobs.
+struct RunOptions {
+    /// The memory image or MIXAL source to run.
+    program: String,
+
+    /// The file to feed to the card reader.
+    input: Option<String>,
+
+    /// The address to start at, if not the default one.
+    entry: Option<u16>,
+
+    /// The number of instructions to run at most.
+    max_steps: u64,
+}
+
+/// Get the value of an option.
+fn option_value(flag: &str, value: Option<String>) -> Result<String, CliError> {
+    value.ok_or_else(|| CliError::Usage(format!("missing value for {}", flag)))
+}
+
+/// Parse an address argument.
+fn parse_addr(flag: &str, value: Option<String>) -> Result<u16, CliError> {
+    let value = option_value(flag, value)?;
+    match value.parse::<u16>() {
+        Ok(addr) if (addr as usize) < Mem::SIZE => Ok(addr),
+        _ => Err(CliError::Usage(format!(
+            "invalid address for {}: {}",
+            flag, value
+        ))),
+    }
+}
+
+/// Parse the options of `run`, `dbg` and batch jobs.
+fn parse_run_options(mut args: impl Iterator<Item = String>) -> Result<RunOptions, CliError> {
+    let mut program = None;
+    let mut input = None;
+    let mut entry = None;
+    let mut max_steps: u64 = 10_000_000;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => input = Some(option_value("--input", args.next())?),
+            "--entry" => entry = Some(parse_addr("--entry", args.next())?),
+            "--max-steps" => {
+                let value = option_value("--max-steps", args.next())?;
+                max_steps = value.parse().map_err(|_| {
+                    CliError::Usage(format!("invalid value for --max-steps: {}", value))
+                })?;
+            }
+            _ if arg.starts_with('-') => {
+                return Err(CliError::Usage(format!("unknown option: {}", arg)))
+            }
+            _ if program.is_none() => program = Some(arg),
+            _ => return Err(CliError::Usage(format!("unexpected argument: {}", arg))),
+        }
+    }
+    Ok(RunOptions {
+        program: program.ok_or_else(|| CliError::Usage("missing program".into()))?,
+        input,
+        entry,
+        max_steps,
+    })
+}
+
+/// Read a text file of at most `limit` bytes.
+fn read_text_file(path: &str, limit: usize) -> Result<String, CliError> {
+    fs::File::open(path)
+        .and_then(|file| formats::common::read_limited(file, limit))
+        .and_then(|data| {
+            String::from_utf8(data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        })
+        .map_err(|err| CliError::Failed(format!("{}: {}", path, err)))
+}
+
+/// Read a memory image from a file.
+fn read_image_file(path: &str) -> Result<Mem, CliError> {
+    let data = fs::File::open(path)
+        .and_then(|file| formats::common::read_limited(file, formats::image::IMAGE_MAX_LEN))
+        .map_err(|err| CliError::Failed(format!("{}: {}", path, err)))?;
+    formats::image::read_image(&data)
+        .map_err(|err| CliError::Failed(format!("{}: bad memory image: {:?}", path, err)))
+}
+
+/// Assemble a MIXAL source file, printing its warnings.
+fn assemble_file(path: &str) -> Result<(asm::Assembly, String), CliError> {
+    let source = read_text_file(path, MAX_SOURCE_LEN)?;
+    let assembly =
+        asm::assemble(&source).map_err(|err| CliError::Failed(format!("{}:{}", path, err)))?;
+    for warning in &assembly.warnings {
+        eprintln!("{}:{}", path, warning);
+    }
+    Ok((assembly, source))
+}
+
+/// Read a memory image, or assemble a MIXAL source file if its name
+/// ends with `.mixal`.
+///
+/// The entry is the operand of `END` for MIXAL sources.
+fn read_program_file(path: &str) -> Result<(Mem, Option<u16>), CliError> {
+    if path.ends_with(".mixal") {
+        let (assembly, _) = assemble_file(path)?;
+        Ok((assembly.mem, Some(assembly.entry)))
+    } else {
+        Ok((read_image_file(path)?, None))
+    }
+}
+
+/// Set up a machine with a line printer and, if asked, a card
+/// reader, and read the program to run on it.
+fn build_machine(options: &RunOptions) -> Result<(VM, MixProgram), CliError> {
+    let (mem, default_entry) = read_program_file(&options.program)?;
+    let entry = options.entry.or(default_entry).unwrap_or(0);
+
+    let mut mix = VM::new();
+    mix.report_end_of_input = true;
+    mix.attach_device(
+        LINE_PRINTER_UNIT,
+        Box::new(LinePrinterDevice::new(io::stdout())),
+    );
+    if let Some(path) = &options.input {
+        let text = read_text_file(path, MAX_INPUT_LEN)?;
+        // The reader is strict, so a bad card is reported here rather
+        // than as a failed `IN` in the middle of the run.
+        for (number, line) in text.lines().enumerate() {
+            encode_words(&line.to_ascii_uppercase(), InputPolicy::Strict).map_err(|err| {
+                CliError::Failed(format!(
+                    "{}:{}: character {:?} at offset {} is not in the MIX alphabet",
+                    path,
+                    number + 1,
+                    err.ch,
+                    err.offset
+                ))
+            })?;
+        }
+        mix.attach_device(
+            CARD_READER_UNIT,
+            Box::new(TextCardReaderDevice::new(&text, InputPolicy::Strict)),
+        );
+    }
+    Ok((mix, MixProgram::new(0, entry, mem[0..Mem::SIZE].to_vec())))
+}
+
+/// Describe why the machine stopped at `pc`.
+fn describe_stop(pc: u16, err: ErrorCode) -> String {
+    match err {
+        ErrorCode::EndOfInput => format!("machine stopped at {}: out of input cards", pc),
+        ErrorCode::InvalidFieldMode { opcode, field } => format!(
+            "machine stopped at {}: illegal field {} for instruction {}",
+            pc, field, opcode
+        ),
+        err => format!("machine stopped at {}: {:?}", pc, err),
+    }
+}
+
+/// Run a program to completion.
+fn run_job(options: &RunOptions) -> Result<(), CliError> {
+    let (mut mix, program) = build_machine(options)?;
+    match mix.load_and_run_with_timeout(&program, options.max_steps) {
+        Ok(_) => Ok(()),
+        Err(RunError::Timeout(steps)) => Err(CliError::Failed(format!(
+            "machine did not halt after {} steps",
+            steps
+        ))),
+        Err(RunError::ExecutionError(err)) => Err(CliError::Failed(describe_stop(mix.pc, err))),
+        Err(err) => Err(CliError::Failed(format!(
+            "machine stopped at {}: {:?}",
+            mix.pc, err
+        ))),
+    }
+}
+
+/// Handler for `run`.
+fn cmd_run(args: impl Iterator<Item = String>) -> Result<(), CliError> {
+    run_job(&parse_run_options(args)?)
+}
+
+/// Handler for `asm`.
+fn cmd_asm(mut args: impl Iterator<Item = String>) -> Result<(), CliError> {
+    let mut source = None;
+    let mut output = None;
+    let mut listing = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = Some(option_value("-o", args.next())?),
+            "--listing" => listing = true,
+            _ if arg.starts_with('-') => {
+                return Err(CliError::Usage(format!("unknown option: {}", arg)))
+            }
+            _ if source.is_none() => source = Some(arg),
+            _ => return Err(CliError::Usage(format!("unexpected argument: {}", arg))),
+        }
+    }
+    let source = source.ok_or_else(|| CliError::Usage("missing source".into()))?;
+    let output = output.ok_or_else(|| CliError::Usage("missing output image (-o)".into()))?;
+    let (assembly, text) = assemble_file(&source)?;
+    let image = formats::image::write_segmented_image(&assembly.mem, &assembly.segments())
+        .map_err(|err| CliError::Failed(format!("{}: cannot write image: {:?}", output, err)))?;
+    fs::write(&output, image).map_err(|err| CliError::Failed(format!("{}: {}", output, err)))?;
+
+    if listing {
+        let lines: Vec<&str> = text.lines().collect();
+        let mut stdout = io::stdout().lock();
+        for &(addr, word, line) in &assembly.listing {
+            writeln!(
+                stdout,
+                "{:04}  {}  {}",
+                addr,
+                word.bytes_as_hex(),
+                lines[line - 1]
+            )
+            .map_err(|err| CliError::Failed(err.to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Format the word at `addr` as a line of `disasm`.
+fn disasm_line(mix: &VM, addr: u16) -> String {
+    let text = mix.disassemble(addr).unwrap_or_else(|_| "CON".to_string());
+    let word = mix.memory()[addr];
+    format!("{:04}  {}  {}", addr, word.bytes_as_hex(), text)
+}
+
+/// Handler for `disasm`.
+fn cmd_disasm(mut args: impl Iterator<Item = String>) -> Result<(), CliError> {
+    let mut image = None;
+    let mut from = 0;
+    let mut to = Mem::SIZE as u16 - 1;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => from = parse_addr("--from", args.next())?,
+            "--to" => to = parse_addr("--to", args.next())?,
+            _ if arg.starts_with('-') => {
+                return Err(CliError::Usage(format!("unknown option: {}", arg)))
+            }
+            _ if image.is_none() => image = Some(arg),
+            _ => return Err(CliError::Usage(format!("unexpected argument: {}", arg))),
+        }
+    }
+    let image = image.ok_or_else(|| CliError::Usage("missing image".into()))?;
+    let mut mix = VM::new();
+    *mix.memory_mut() = read_image_file(&image)?;
+
+    let mut stdout = io::stdout().lock();
+    for addr in from..=to {
+        writeln!(stdout, "{}", disasm_line(&mix, addr))
+            .map_err(|err| CliError::Failed(err.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Run a command of `dbg`.
+///
+/// # Returns
+/// * [`Ok(true)`] - The command ran.
+/// * [`Ok(false)`] - The command is `quit`.
+/// * [`Err(String)`] - The command is malformed.
+fn debug_command(mix: &mut VM, command: &str, args: &[&str]) -> Result<bool, String> {
+    let addr = |arg: Option<&&str>| -> Result<u16, String> {
+        let arg = arg.ok_or("missing address")?;
+        match arg.parse::<u16>() {
+            Ok(addr) if (addr as usize) < Mem::SIZE => Ok(addr),
+            _ => Err(format!("invalid address: {}", arg)),
+        }
+    };
+    let count = |arg: Option<&&str>| -> Result<usize, String> {
+        arg.map_or(Ok(1), |arg| {
+            arg.parse().map_err(|_| format!("invalid count: {}", arg))
+        })
+    };
+    match command {
+        "s" | "step" => {
+            for _ in 0..count(args.first())? {
+                if mix.halted {
+                    println!("machine halted");
+                    break;
+                }
+                let pc = mix.pc;
+                println!("{}", disasm_line(mix, pc));
+                if let Err(err) = mix.step() {
+                    println!("{}", describe_stop(pc, err));
+                    break;
+                }
+            }
+        }
+        "c" | "continue" => match mix.run() {
+            Ok(StepEvent::Breakpoint(addr)) => println!("breakpoint at {}", addr),
+            Ok(StepEvent::Halted) => println!("machine halted"),
+            Ok(event) => println!("machine stopped: {:?}", event),
+            Err(err) => println!("{}", describe_stop(mix.pc, err)),
+        },
+        "b" | "break" => {
+            mix.add_breakpoint(addr(args.first())?);
+        }
+        "d" | "delete" => {
+            let addr = addr(args.first())?;
+            if !mix.remove_breakpoint(addr) {
+                return Err(format!("no breakpoint at {}", addr));
+            }
+        }
+        "r" | "regs" => {
+            let regs = mix.registers();
+            let index: Vec<i64> = regs.i[1..].iter().map(|r| r.to_i64().0).collect();
+            println!(
+                "pc={} rA={} rX={} rI={:?} rJ={} CI={:?} OV={}",
+                mix.pc,
+                regs.a,
+                regs.x,
+                index,
+                regs.j.to_i64().0,
+                regs.comp,
+                regs.overflow
+            );
+        }
+        "m" | "mem" => {
+            let from = addr(args.first())?;
+            let to = (from as usize)
+                .saturating_add(count(args.get(1))?)
+                .min(Mem::SIZE);
+            for addr in from..to as u16 {
+                println!("{}", disasm_line(mix, addr));
+            }
+        }
+        "set" => {
+            let addr = addr(args.first())?;
+            // Blanks in a quoted literal were split off with the words.
+            let literal = args.get(1..).unwrap_or_default().join(" ");
+            if literal.is_empty() {
+                return Err("missing word".to_string());
+            }
+            let word = FullWord::parse_literal(&literal)
+                .map_err(|err| format!("invalid word: {}: {}", literal, err))?;
+            mix.memory_mut()[addr] = word;
+        }
+        "h" | "help" => print!("{}", DBG_HELP),
+        "q" | "quit" => return Ok(false),
+        _ => return Err(format!("unknown command: {}", command)),
+    }
+    Ok(true)
+}
+
+/// Handler for `dbg`.
+fn cmd_dbg(args: impl Iterator<Item = String>) -> Result<(), CliError> {
+    let options = parse_run_options(args)?;
+    let (mut mix, program) = build_machine(&options)?;
+    program
+        .install(&mut mix)
+        .map_err(|err| CliError::Failed(format!("{}: {:?}", options.program, err)))?;
+    mix.reset();
+    mix.pc = program.entry;
+    mix.restart();
+    mix.set_step_limit(options.max_steps);
+
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|err| CliError::Failed(err.to_string()))?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((command, args)) = words.split_first() else {
+            continue;
+        };
+        match debug_command(&mut mix, command, args) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(msg) => println!("error: {}", msg),
+        }
+    }
+    mix.flush_devices()
+        .map_err(|err| CliError::Failed(format!("cannot flush devices: {:?}", err)))
+}
+
+/// Handler for `batch`.
+fn cmd_batch(mut args: impl Iterator<Item = String>) -> Result<(), CliError> {
+    let stream = args
+        .next()
+        .ok_or_else(|| CliError::Usage("missing stream".into()))?;
+    if let Some(arg) = args.next() {
+        return Err(CliError::Usage(format!("unexpected argument: {}", arg)));
+    }
+    let text = read_text_file(&stream, MAX_INPUT_LEN)?;
+    let dir = Path::new(&stream).parent().unwrap_or(Path::new(""));
+    let resolve = |path: &str| dir.join(path).to_string_lossy().into_owned();
+
+    // Check every job before running any.
+    let mut jobs = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let args = line.split_whitespace().map(String::from);
+        let mut options = parse_run_options(args).map_err(|err| match err {
+            CliError::Usage(msg) | CliError::Failed(msg) => {
+                CliError::Failed(format!("{}:{}: {}", stream, i + 1, msg))
+            }
+        })?;
+        options.program = resolve(&options.program);
+        options.input = options.input.as_deref().map(resolve);
+        jobs.push((i + 1, options));
+    }
+
+    let mut failed = 0;
+    for (line, options) in &jobs {
+        if let Err(CliError::Usage(msg) | CliError::Failed(msg)) = run_job(options) {
+            eprintln!("error: {}:{}: {}", stream, line, msg);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(CliError::Failed(format!(
+            "{} of {} jobs failed",
+            failed,
+            jobs.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Handler for `selfcheck`.
+fn cmd_selfcheck(mut args: impl Iterator<Item = String>) -> Result<(), CliError> {
+    if let Some(arg) = args.next() {
+        return Err(CliError::Usage(format!("unexpected argument: {}", arg)));
+    }
+    let found = conformance::check_instruction_set();
+    for disagreement in &found {
+        println!("{}", disagreement);
+    }
+    if !found.is_empty() {
+        return Err(CliError::Failed(format!(
+            "{} inconsistent instructions",
+            found.len()
+        )));
+    }
+    let violations = semantics::check_semantics();
+    for violation in &violations {
+        println!("{}", violation);
+    }
+    if !violations.is_empty() {
+        return Err(CliError::Failed(format!(
+            "{} problems with the semantics registry",
+            violations.len()
+        )));
+    }
+    conformance::check_primes_hash().map_err(|hash| {
+        CliError::Failed(format!(
+            "state hash of the primes example is {:#018x}, expected {:#018x}",
+            hash,
+            conformance::PRIMES_STATE_HASH
+        ))
+    })
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("run") => cmd_run(args),
+        Some("asm") => cmd_asm(args),
+        Some("disasm") => cmd_disasm(args),
+        Some("dbg") => cmd_dbg(args),
+        Some("batch") => cmd_batch(args),
+        Some("selfcheck") => cmd_selfcheck(args),
+        Some("-h" | "--help") => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        Some("-V" | "--version") => {
+            println!("mixlegacy {}", env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+        Some(cmd) => Err(CliError::Usage(format!("unknown command: {}", cmd))),
+        None => Err(CliError::Usage("missing command".into())),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Usage(msg)) => {
+            eprintln!("error: {}\n\n{}", msg, USAGE);
+            ExitCode::from(2)
+        }
+        Err(CliError::Failed(msg)) => {
+            eprintln!("error: {}", msg);
+            ExitCode::from(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debugger_sessions() {
+        let tokens = [
+            "s ", "c", "r", "b ", "d ", "m ", "set ", "h", "q", "2000", " ", "\n",
+        ];
+        runtime::fuzz::fuzz("dbg", &tokens, |session| {
+            let program = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/loop.mixal");
+            let options = parse_run_options(
+                [program, "--max-steps", "1000"]
+                    .into_iter()
+                    .map(String::from),
+            )
+            .unwrap();
+            let (mut mix, program) = build_machine(&options).unwrap();
+            program.install(&mut mix).unwrap();
+            mix.pc = program.entry;
+            mix.set_step_limit(options.max_steps);
+            for line in session.lines() {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                if let Some((command, args)) = words.split_first() {
+                    if let Ok(false) = debug_command(&mut mix, command, args) {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn batch_jobs() {
+        let tokens = ["--input ", "--entry ", "--max-steps ", "hello.mixal", " "];
+        runtime::fuzz::fuzz("batch", &tokens, |jobs| {
+            for line in jobs.lines() {
+                let _ = parse_run_options(line.split_whitespace().map(String::from));
+            }
+        });
+    }
+}
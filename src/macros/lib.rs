@@ -1,9 +1,10 @@
 use proc_macro::TokenStream;
 use proc_macro2::Span;
-use quote::quote;
+use quote::{format_ident, quote, quote_spanned};
 use std::iter::repeat;
 use syn::parse_macro_input;
 use syn::Ident;
+use syn::LitByteStr;
 use syn::LitStr;
 
 fn parse_char(ch: char) -> Result<&'static str, char> {
@@ -69,19 +70,82 @@ fn parse_char(ch: char) -> Result<&'static str, char> {
     }
 }
 
+/// Find the character a lookalike of an alphabet character stands for.
+///
+/// Returns the name of the lookalike and the character it stands for.
+fn parse_lookalike(ch: char) -> Option<(&'static str, char)> {
+    match ch {
+        '\u{2018}' => Some(("U+2018 LEFT SINGLE QUOTATION MARK", '\'')),
+        '\u{2019}' => Some(("U+2019 RIGHT SINGLE QUOTATION MARK", '\'')),
+        '\u{2032}' => Some(("U+2032 PRIME", '\'')),
+        '\u{201C}' => Some(("U+201C LEFT DOUBLE QUOTATION MARK", '"')),
+        '\u{201D}' => Some(("U+201D RIGHT DOUBLE QUOTATION MARK", '"')),
+        '\u{2033}' => Some(("U+2033 DOUBLE PRIME", '"')),
+        '\u{2010}' => Some(("U+2010 HYPHEN", '-')),
+        '\u{2011}' => Some(("U+2011 NON-BREAKING HYPHEN", '-')),
+        '\u{2012}' => Some(("U+2012 FIGURE DASH", '-')),
+        '\u{2013}' => Some(("U+2013 EN DASH", '-')),
+        '\u{2014}' => Some(("U+2014 EM DASH", '-')),
+        '\u{2212}' => Some(("U+2212 MINUS SIGN", '-')),
+        '\u{2024}' => Some(("U+2024 ONE DOT LEADER", '.')),
+        '\u{2215}' => Some(("U+2215 DIVISION SLASH", '/')),
+        '\u{00A0}' => Some(("U+00A0 NO-BREAK SPACE", ' ')),
+        '\u{2007}' => Some(("U+2007 FIGURE SPACE", ' ')),
+        '\u{202F}' => Some(("U+202F NARROW NO-BREAK SPACE", ' ')),
+        '\u{3000}' => Some(("U+3000 IDEOGRAPHIC SPACE", ' ')),
+        // Fullwidth forms of ASCII.
+        '\u{FF01}'..='\u{FF5E}' => Some((
+            "a fullwidth form",
+            char::from_u32(ch as u32 - 0xFEE0).unwrap(),
+        )),
+        _ => None,
+    }
+}
+
 #[proc_macro]
 pub fn alphabet_str(input: TokenStream) -> TokenStream {
-    let string = parse_macro_input!(input as LitStr).value();
-    if !string.is_ascii() {
-        panic!("Only ASCII characters are supported");
-    }
+    let lit = parse_macro_input!(input as LitStr);
+    let string = lit.value();
 
-    let chars = string.as_bytes();
-    let chunks = chars.chunks(5).map(|chunk| {
+    // Lookalikes are replaced, and each is reported by using a
+    // deprecated item, as stable proc macros cannot emit warnings.
+    let mut warnings = Vec::new();
+    let names: Vec<&'static str> = string
+        .chars()
+        .enumerate()
+        .map(|(offset, ch)| match parse_char(ch) {
+            Ok(name) => name,
+            Err(ch) => match parse_lookalike(ch).and_then(|(what, subst)| {
+                let name = parse_char(subst).ok()?;
+                Some((what, subst, name))
+            }) {
+                Some((what, subst, name)) => {
+                    let note = format!(
+                        "{:?} at offset {} is {}, not in the MIX alphabet; use {:?} instead",
+                        ch, offset, what, subst
+                    );
+                    let ident = format_ident!("LOOKALIKE_CHARACTER_{}", offset);
+                    warnings.push(quote_spanned! {lit.span()=>
+                        #[deprecated(note = #note)]
+                        const #ident: () = ();
+                        #[allow(clippy::let_unit_value)]
+                        let _ = #ident;
+                    });
+                    name
+                }
+                None => panic!(
+                    "Character {:?} at offset {} is not in the MIX alphabet",
+                    ch, offset
+                ),
+            },
+        })
+        .collect();
+
+    let chunks = names.chunks(5).map(|chunk| {
         let tokens = chunk
             .iter()
-            .map(|ch| {
-                let ident = Ident::new(parse_char(char::from(*ch)).unwrap(), Span::call_site());
+            .map(|name| {
+                let ident = Ident::new(name, Span::call_site());
                 quote! {
                     Alphabet::#ident as u8,
                 }
@@ -98,6 +162,44 @@ pub fn alphabet_str(input: TokenStream) -> TokenStream {
         }
     });
 
+    if warnings.is_empty() {
+        TokenStream::from(quote! {
+            [
+                #(#chunks)*
+            ]
+        })
+    } else {
+        TokenStream::from(quote! {
+            {
+                #(#warnings)*
+                [
+                    #(#chunks)*
+                ]
+            }
+        })
+    }
+}
+
+#[proc_macro]
+pub fn alphabet_str_raw(input: TokenStream) -> TokenStream {
+    let bytes = parse_macro_input!(input as LitByteStr).value();
+    if let Some(pos) = bytes.iter().position(|&b| b > 55) {
+        panic!(
+            "Byte {} at offset {} is not a valid Alphabet value",
+            bytes[pos], pos
+        );
+    }
+
+    let chunks = bytes.chunks(5).map(|chunk| {
+        let tokens = chunk.iter().copied().chain(repeat(0)).take(5);
+        quote! {
+            [
+                0u8, // zero sign
+                #(#tokens),*
+            ],
+        }
+    });
+
     TokenStream::from(quote! {
         [
             #(#chunks)*
@@ -3,15 +3,22 @@
 #![allow(clippy::result_unit_err)]
 #![allow(dead_code)]
 
+#[cfg(feature = "byte-64")]
+compile_error!("the challenge relies on 8-bit bytes; build only `mixlegacy` with `byte-64`");
+
 use modern_legacy_macros::alphabet_str;
 
 mod runtime;
 use runtime::*;
 
-use std::io::{self, Read, Write};
+use std::cell::RefCell;
+use std::io::{self, BufRead, Read, Write};
+use std::rc::Rc;
 
 struct LinePrinterDevice {
     lower_case: bool,
+    line: String,
+    out: Box<dyn Write>,
 }
 
 impl IODevice for LinePrinterDevice {
@@ -24,26 +31,10 @@ impl IODevice for LinePrinterDevice {
             return Err(0);
         }
 
-        let mut count_written: usize = 0;
-        // For each word...
-        for word in data {
-            // Each byte in a word...
-            for &byte in &word[1..=5] {
-                // Convert to char.
-                let ch: char = Alphabet::try_from(byte)
-                    .map_err(|_| count_written)?
-                    .try_into()
-                    .map_err(|_| count_written)?;
-                if self.lower_case && ch.is_ascii_uppercase() {
-                    print!("{}", ch.to_ascii_lowercase());
-                } else {
-                    print!("{}", ch);
-                }
-                count_written += 1;
-            }
-        }
-        println!();
-        Ok(())
+        let line = decode_block(data, self.lower_case)?;
+        self.line.push_str(&line);
+        self.line.push('\n');
+        self.flush().map_err(|_| line.chars().count())
     }
 
     fn control(&mut self, command: i16) -> Result<(), ()> {
@@ -56,7 +47,7 @@ impl IODevice for LinePrinterDevice {
                 self.lower_case = true;
                 Ok(())
             }
-            2 => io::stdout().flush().map_err(|_| ()),
+            2 => self.flush(),
             _ => Err(()),
         }
     }
@@ -72,24 +63,58 @@ impl IODevice for LinePrinterDevice {
     fn get_block_size(&self) -> usize {
         8
     }
+
+    fn flush(&mut self) -> Result<(), ()> {
+        self.out.write_all(self.line.as_bytes()).map_err(|_| ())?;
+        self.line.clear();
+        self.out.flush().map_err(|_| ())
+    }
+
+    fn name(&self) -> &str {
+        "line printer"
+    }
+
+    fn reset(&mut self) {
+        self.line.clear();
+    }
 }
 
-struct LineReaderDevice {}
+/// A reader feeding its input to the machine byte by byte.
+///
+/// Each input byte is taken as a Latin-1 character and encoded under
+/// `policy`. A byte rejected under [`InputPolicy::Strict`] fails the
+/// read, and a complaint naming it and its offset in the input is left
+/// in `complaint`.
+struct LineReaderDevice {
+    input: Box<dyn Read>,
+    policy: InputPolicy,
+    offset: usize,
+    complaint: Rc<RefCell<Option<String>>>,
+}
 
 impl IODevice for LineReaderDevice {
     fn read(&mut self, buffer: &mut [FullWord]) -> Result<(), ()> {
         if buffer.len() != self.get_block_size() {
             return Err(());
         }
-        let mut buf = [0; 5];
-        io::stdin().read_exact(&mut buf).map_err(|_| ())?;
-        let chars: [u8; 5] = buf.map(|b| {
-            u8::try_from(
-                Alphabet::try_from(char::from_u32(b as u32).unwrap_or('‚'))
-                    .unwrap_or(Alphabet::LowSQuote),
-            )
-            .unwrap_or(0xFFu8)
-        });
+        let mut chars = [0; 5];
+        let mut count_read: usize = 0;
+        while count_read < chars.len() {
+            let mut buf = [0; 1];
+            self.input.read_exact(&mut buf).map_err(|_| ())?;
+            let offset = self.offset;
+            self.offset += 1;
+            let encoded = self.policy.encode(char::from(buf[0])).map_err(|_| {
+                *self.complaint.borrow_mut() = Some(format!(
+                    "byte {:#04x} at offset {} is not in the MIX alphabet",
+                    buf[0], offset
+                ));
+            })?;
+            if let Some(byte) = encoded {
+                chars[count_read] = byte;
+                count_read += 1;
+            }
+        }
         buffer[0][0] = FullWord::POS;
         buffer[0][1..=5].copy_from_slice(&chars);
         Ok(())
@@ -114,10 +139,127 @@ impl IODevice for LineReaderDevice {
     fn get_block_size(&self) -> usize {
         1
     }
+
+    fn name(&self) -> &str {
+        "line reader"
+    }
+}
+
+/// A reader serving the answer of the player, normalized.
+///
+/// The answer is read as one line on the first read, so that the
+/// welcome banner is printed before the player is asked for it. An
+/// answer refused by [`normalize_answer()`] fails the read, and the
+/// complaint is left in `complaint`.
+struct AnswerReaderDevice {
+    input: Box<dyn BufRead>,
+    bytes: Option<std::vec::IntoIter<u8>>,
+    complaint: Rc<RefCell<Option<String>>>,
+}
+
+impl IODevice for AnswerReaderDevice {
+    fn read(&mut self, buffer: &mut [FullWord]) -> Result<(), ()> {
+        if buffer.len() != self.get_block_size() {
+            return Err(());
+        }
+        let bytes = match &mut self.bytes {
+            Some(bytes) => bytes,
+            None => {
+                let answer = read_answer(&mut *self.input).map_err(|msg| {
+                    *self.complaint.borrow_mut() = Some(msg);
+                })?;
+                self.bytes.insert(answer.into_iter())
+            }
+        };
+        let mut chars = [0; 5];
+        for byte in chars.iter_mut() {
+            *byte = bytes.next().ok_or(())?;
+        }
+        buffer[0][0] = FullWord::POS;
+        buffer[0][1..=5].copy_from_slice(&chars);
+        Ok(())
+    }
+
+    fn write(&mut self, _: &[FullWord]) -> Result<(), usize> {
+        Err(0)
+    }
+
+    fn control(&mut self, _: i16) -> Result<(), ()> {
+        Err(())
+    }
+
+    fn is_busy(&self) -> Result<bool, ()> {
+        Ok(false)
+    }
+
+    fn is_ready(&self) -> Result<bool, ()> {
+        Ok(true)
+    }
+
+    fn get_block_size(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> &str {
+        "line reader"
+    }
+
+    fn at_eof(&self) -> bool {
+        self.bytes.as_ref().is_some_and(|bytes| bytes.len() == 0)
+    }
+}
+
+/// Normalize an answer as pasted by a player.
+///
+/// Surrounding whitespace is trimmed and ASCII letters are upper-cased.
+/// The answer must then be exactly `INPUT_WORDS * 5` characters of
+/// [`Alphabet`].
+fn normalize_answer(input: &str) -> Result<Vec<u8>, String> {
+    let answer = input.trim().to_ascii_uppercase();
+    let mut bytes = Vec::with_capacity(answer.len());
+    for (pos, ch) in answer.chars().enumerate() {
+        let alpha = Alphabet::try_from(ch).map_err(|_| {
+            format!(
+                "character '{}' at position {} is not in the MIX alphabet",
+                ch,
+                pos + 1
+            )
+        })?;
+        bytes.push(alpha as u8);
+    }
+    let expected = INPUT_WORDS as usize * 5;
+    if bytes.len() != expected {
+        return Err(format!(
+            "the answer must be {} characters long, not {}",
+            expected,
+            bytes.len()
+        ));
+    }
+    Ok(bytes)
+}
+
+/// Read one line of answer and normalize it.
+///
+/// Input that is not UTF-8 is taken as Latin-1, as in raw mode.
+fn read_answer(reader: &mut dyn BufRead) -> Result<Vec<u8>, String> {
+    let mut input = Vec::new();
+    reader
+        .read_until(b'\n', &mut input)
+        .map_err(|err| err.to_string())?;
+    let text = match String::from_utf8(input) {
+        Ok(text) => text,
+        Err(err) => err.into_bytes().iter().map(|&b| char::from(b)).collect(),
+    };
+    normalize_answer(&text)
 }
 
 const INPUT_WORDS: i16 = 7;
 
+/// The [`Mem::checksum_range()`] of [`PROGRAM`] once loaded.
+const PROGRAM_CHECKSUM: u64 = 0x644e_79bb_b4ef_0912;
+
+const MAX_STEPS: u64 = 1_000_000;
+
 const PROGRAM_START: i16 = 0;
 const CONST_START: i16 = 3000;
 const TEMP_START: i16 = 3100;
@@ -133,11 +275,12 @@ const LOC_MAIN_O_L: i16 = LOC_MAIN + 5;
 const LOC_MAIN_O_P: i16 = LOC_MAIN + 6;
 const LOC_MAIN_I_L: i16 = LOC_MAIN + 14;
 const LOC_MAIN_I_R: i16 = LOC_MAIN + 15;
-const LOC_MAIN_ENC: i16 = LOC_MAIN + 20;
-const LOC_MAIN_VERIF_LOOP: i16 = LOC_MAIN + 26;
-const LOC_MAIN_VERIF_CONT: i16 = LOC_MAIN + 30;
-const LOC_MAIN_VERIF_N: i16 = LOC_MAIN + 35;
-const LOC_MAIN_END: i16 = LOC_MAIN + 41;
+const LOC_MAIN_K_R: i16 = LOC_MAIN + 20;
+const LOC_MAIN_ENC: i16 = LOC_MAIN + 22;
+const LOC_MAIN_VERIF_LOOP: i16 = LOC_MAIN + 34;
+const LOC_MAIN_VERIF_CONT: i16 = LOC_MAIN + 38;
+const LOC_MAIN_VERIF_N: i16 = LOC_MAIN + 43;
+const LOC_MAIN_END: i16 = LOC_MAIN + 49;
 
 const LOC_CONST_EQ3: i16 = CONST_START;
 const LOC_CONST_DELTA: i16 = LOC_CONST_EQ3 + 1;
@@ -158,11 +301,17 @@ const LOC_TMP_6: i16 = TEMP_START + 7;
 const LOC_TMP_7: i16 = TEMP_START + 8;
 const LOC_TMP_8: i16 = TEMP_START + 9;
 const LOC_TMP_9: i16 = TEMP_START + 10;
+const LOC_TMP_K: i16 = TEMP_START + 11;
 
 const LOC_ARG_V: i16 = IO_START;
-const LOC_ARG_K: i16 = LOC_XTEA + 76;
 
-const PROGRAM: [Instruction; 124] = [
+/// Unit number of the [`SealedKeyDevice`] holding the key.
+const UNIT_KEY: u8 = 16;
+
+/// Number of words in the key.
+const KEY_WORDS: i16 = 4;
+
+const PROGRAM: [Instruction; 132] = [
     Instruction::new(LOC_XTEA_RET, 2, 0, Opcode::StJ),
     Instruction::new(32, 2, 0, Opcode::ModifyX),
     Instruction::new(0, 2, 0, Opcode::ModifyA),
@@ -188,7 +337,7 @@ const PROGRAM: [Instruction; 124] = [
     Instruction::new(LOC_CONST_EQ3, 10, 0, Opcode::Special),
     Instruction::new(LOC_TMP_5, 45, 0, Opcode::StA),
     Instruction::new(LOC_TMP_5, 45, 0, Opcode::Ld2),
-    Instruction::new(LOC_ARG_K, 13, 2, Opcode::LdA),
+    Instruction::new(LOC_TMP_K, 13, 2, Opcode::LdA),
     Instruction::new(LOC_TMP_6, 13, 0, Opcode::StA),
     Instruction::new(LOC_TMP_6, 13, 0, Opcode::LdA),
     Instruction::new(LOC_TMP_SUM, 13, 0, Opcode::Add),
@@ -226,7 +375,7 @@ const PROGRAM: [Instruction; 124] = [
     Instruction::new(LOC_CONST_EQ3, 10, 0, Opcode::Special),
     Instruction::new(LOC_TMP_6, 45, 0, Opcode::StA),
     Instruction::new(LOC_TMP_6, 45, 0, Opcode::Ld2),
-    Instruction::new(LOC_ARG_K, 13, 2, Opcode::LdA),
+    Instruction::new(LOC_TMP_K, 13, 2, Opcode::LdA),
     Instruction::new(LOC_TMP_7, 13, 0, Opcode::StA),
     Instruction::new(LOC_TMP_7, 13, 0, Opcode::LdA),
     Instruction::new(LOC_TMP_SUM, 13, 0, Opcode::Add),
@@ -262,11 +411,19 @@ const PROGRAM: [Instruction; 124] = [
     Instruction::new(1, 0, 0, Opcode::Modify4),
     Instruction::new(1, 1, 0, Opcode::ModifyX),
     Instruction::new(LOC_MAIN_I_L, 2, 0, Opcode::JX),
+    Instruction::new(LOC_TMP_K, UNIT_KEY, 0, Opcode::In),
+    Instruction::new(LOC_MAIN_K_R, UNIT_KEY, 0, Opcode::Jbus),
     Instruction::new(0, 2, 0, Opcode::Modify1),
     Instruction::new(LOC_XTEA, 0, 0, Opcode::Jmp),
     Instruction::new(1, 0, 0, Opcode::Modify1),
     Instruction::new(LOC_CONST_ENC_LOOPS, 5, 0, Opcode::Cmp1),
     Instruction::new(LOC_MAIN_ENC, 4, 0, Opcode::Jmp),
+    Instruction::new(LOC_TMP_K, 5, 0, Opcode::StZ),
+    Instruction::new(LOC_TMP_K + 1, 5, 0, Opcode::StZ),
+    Instruction::new(LOC_TMP_K + 2, 5, 0, Opcode::StZ),
+    Instruction::new(LOC_TMP_K + 3, 5, 0, Opcode::StZ),
+    Instruction::new(LOC_TMP_6, 5, 0, Opcode::StZ),
+    Instruction::new(LOC_TMP_7, 5, 0, Opcode::StZ),
     Instruction::new(INPUT_WORDS, 2, 0, Opcode::ModifyX),
     Instruction::new(INPUT_WORDS - 1, 2, 0, Opcode::Modify2),
     Instruction::new(LOC_ARG_V, 13, 2, Opcode::LdA),
@@ -297,13 +454,24 @@ WHAT DID YOU UNCOVER, ELITE RUSTACEAN >>\
 );
 
 const CIPHER: [[u8; 6]; INPUT_WORDS as usize] = [
-    [0, 5, 139, 14, 94, 218],
-    [0, 244, 138, 250, 182, 187],
-    [0, 244, 123, 251, 140, 191],
-    [0, 95, 176, 194, 183, 102],
-    [0, 138, 101, 40, 247, 89],
-    [0, 122, 206, 163, 121, 181],
-    [0, 192, 133, 13, 8, 206],
+    [0, 220, 238, 235, 132, 0],
+    [0, 18, 158, 65, 76, 200],
+    [0, 148, 150, 192, 137, 203],
+    [0, 177, 21, 165, 213, 214],
+    [0, 14, 221, 74, 212, 241],
+    [0, 129, 137, 162, 239, 39],
+    [0, 198, 248, 50, 200, 142],
+];
+
+/// The XTEA key the cipher text was made with, served by
+/// [`SealedKeyDevice`] and never part of the memory image.
+///
+/// This is only the fallback of [`challenge_key()`].
+const KEY: [[u8; 6]; KEY_WORDS as usize] = [
+    [0, 0x3d, 0x7a, 0x19, 0xc4, 0x52],
+    [0, 0xe1, 0x08, 0x9f, 0x6b, 0x27],
+    [0, 0x5c, 0xb3, 0x40, 0xd6, 0x8e],
+    [0, 0x91, 0x2f, 0xa8, 0x73, 0x0b],
 ];
 
 const WRONG: [[u8; 6]; 8] = alphabet_str!(
@@ -318,39 +486,233 @@ NOW MARCH BEYOND, AND REVIVE THE LEGACY.\
 "
 );
 
-fn main() {
-    let mut mix = VM::new();
-    mix.reset();
+/// Build the memory map of the challenge.
+///
+/// `PROGRAM` is written against the `LOC_*` constants, so the
+/// layout is checked to agree with them.
+///
+/// # Returns
+/// * [`Ok(Layout)`] - The memory map.
+/// * [`Err(LayoutError)`] - Some regions collide, or a region is not at its
+///   `LOC_*` constant.
+fn challenge_layout() -> Result<Layout, LayoutError> {
+    let layout = LayoutBuilder::new()
+        .region_at("xtea", PROGRAM_START as u16, (LOC_MAIN - LOC_XTEA) as u16)
+        .region("main", PROGRAM.len() as u16 - (LOC_MAIN - LOC_XTEA) as u16)
+        .region_at("const.eq3", CONST_START as u16, 1)
+        .region("const.delta", 1)
+        .region("const.welcome", WELCOME.len() as u16)
+        .region("const.enc_loops", 1)
+        .region("const.cipher", CIPHER.len() as u16)
+        .region("const.wrong", WRONG.len() as u16)
+        .region("const.right", RIGHT.len() as u16)
+        .region_at(
+            "tmp",
+            TEMP_START as u16,
+            (LOC_TMP_9 - TEMP_START + 1) as u16,
+        )
+        .region("tmp.key", KEY_WORDS as u16)
+        .region_at("arg.v", IO_START as u16, INPUT_WORDS as u16)
+        .build()?;
+    layout.check_addrs(&[
+        ("xtea", LOC_XTEA as u16),
+        ("main", LOC_MAIN as u16),
+        ("const.eq3", LOC_CONST_EQ3 as u16),
+        ("const.delta", LOC_CONST_DELTA as u16),
+        ("const.welcome", LOC_CONST_WELCOME as u16),
+        ("const.enc_loops", LOC_CONST_ENC_LOOPS as u16),
+        ("const.cipher", LOC_CONST_C as u16),
+        ("const.wrong", LOC_CONST_WRONG as u16),
+        ("const.right", LOC_CONST_RIGHT as u16),
+        ("tmp", LOC_TMP_SUM as u16),
+        ("tmp.key", LOC_TMP_K as u16),
+        ("arg.v", LOC_ARG_V as u16),
+    ])?;
+    Ok(layout)
+}
 
-    for (i, instr) in PROGRAM.iter().enumerate() {
-        mix.mem[PROGRAM_START as u16 + i as u16] = FullWord::from(*instr);
+/// The environment variable [`challenge_key()`] reads the key from.
+const KEY_VAR: &str = "CHALLENGE_KEY";
+
+/// Choose where the key comes from, when the process starts.
+///
+/// The key is read from `key_file` if given, else from the variable
+/// [`KEY_VAR`] if it is set, as word literals. Without either, it is
+/// [`KEY`]. Any other key than the one the cipher text was made with
+/// turns every answer down.
+fn challenge_key(key_file: Option<&str>) -> SealedKeyDevice {
+    let words = KEY_WORDS as usize;
+    match key_file {
+        Some(path) => SealedKeyDevice::from_file(words, path),
+        None if std::env::var_os(KEY_VAR).is_some() => SealedKeyDevice::from_env(words, KEY_VAR),
+        None => SealedKeyDevice::from_bytes(words, KEY.concat()),
     }
+}
+
+/// Set up a machine with the challenge installed.
+///
+/// The answer is read from `reader` on unit 19, the banners are
+/// printed to `printer` on unit 18, and the key is read from `key` on
+/// [`UNIT_KEY`].
+fn build_challenge(
+    reader: Box<dyn IODevice>,
+    printer: Box<dyn IODevice>,
+    key: SealedKeyDevice,
+) -> Result<VM, ChallengeError> {
+    let layout = challenge_layout().map_err(ChallengeError::Layout)?;
 
-    mix.mem[LOC_CONST_EQ3 as u16].set_all([0, 0, 0, 0, 0, 3]);
-    mix.mem[LOC_CONST_DELTA as u16].set_all([0, 0x9e, 0x38, 0x53, 0x8a, 0x49]);
+    let mut mix = VM::new();
+    mix.reset();
+
+    let mem = mix.memory_mut();
+    mem[layout.addr("const.eq3")].set_all([0, 0, 0, 0, 0, 3]);
+    mem[layout.addr("const.delta")].set_all([0, 0x9e, 0x38, 0x53, 0x8a, 0x49]);
     for (i, bytes) in WELCOME.iter().enumerate() {
-        mix.mem[LOC_CONST_WELCOME as u16 + i as u16].set_all(*bytes);
+        mem[layout.addr("const.welcome") + i as u16].set_all(*bytes);
     }
     for (i, bytes) in CIPHER.iter().enumerate() {
-        mix.mem[LOC_CONST_C as u16 + i as u16].set_all(*bytes);
+        mem[layout.addr("const.cipher") + i as u16].set_all(*bytes);
     }
     for (i, bytes) in WRONG.iter().enumerate() {
-        mix.mem[LOC_CONST_WRONG as u16 + i as u16].set_all(*bytes);
+        mem[layout.addr("const.wrong") + i as u16].set_all(*bytes);
     }
     for (i, bytes) in RIGHT.iter().enumerate() {
-        mix.mem[LOC_CONST_RIGHT as u16 + i as u16].set_all(*bytes);
+        mem[layout.addr("const.right") + i as u16].set_all(*bytes);
     }
-    mix.mem[LOC_CONST_ENC_LOOPS as u16] = FullWord::from_i64(INPUT_WORDS as i64 - 1).0;
+    mem[layout.addr("const.enc_loops")] = FullWord::from_i64(INPUT_WORDS as i64 - 1).0;
+
+    mix.attach_device(18, printer);
+    mix.attach_device(19, reader);
+    mix.attach_device(UNIT_KEY as usize, Box::new(key));
+
+    let program = MixProgram::new(
+        layout.addr("xtea"),
+        layout.addr("main"),
+        PROGRAM.iter().map(|&instr| FullWord::from(instr)).collect(),
+    );
+    program.install(&mut mix).map_err(ChallengeError::Run)?;
+    Ok(mix)
+}
+
+/// Why [`build_challenge()`] or [`run_challenge()`] failed.
+#[derive(Debug)]
+enum ChallengeError {
+    /// The memory map is inconsistent, see [`challenge_layout()`].
+    Layout(LayoutError),
 
-    mix.io_devices[18] = Some(Box::new(LinePrinterDevice { lower_case: false }));
-    mix.io_devices[19] = Some(Box::new(LineReaderDevice {}));
+    /// The program differs from [`PROGRAM`].
+    Patched,
 
-    mix.pc = LOC_MAIN as u16;
+    /// The program cannot be installed, or the machine did not halt
+    /// normally.
+    Run(RunError),
+}
 
-    mix.restart();
+/// Run the installed challenge, unless its program has been patched
+/// since it was installed.
+///
+/// The program is checked right before the run, so that any write to
+/// it after [`build_challenge()`] is caught.
+fn run_challenge(mix: &mut VM) -> Result<(), ChallengeError> {
+    let start = challenge_layout()
+        .map_err(ChallengeError::Layout)?
+        .addr("xtea");
+    if !mix.verify_program_integrity(PROGRAM_CHECKSUM, start, PROGRAM.len() as u16) {
+        return Err(ChallengeError::Patched);
+    }
+    mix.run_with_timeout(MAX_STEPS)
+        .map(|_| ())
+        .map_err(ChallengeError::Run)
+}
 
-    while !mix.halted {
-        mix.step().unwrap();
+/// Describe how a run of the challenge ended, for `--report`.
+///
+/// The first line is the halt reason, the next ones the final
+/// contents of the input and of the key, one cell per line.
+fn challenge_report(mix: &VM, outcome: &Result<(), ChallengeError>) -> String {
+    let mut report = match outcome {
+        Ok(()) => "halt: halted\n".to_string(),
+        Err(err) => format!("halt: {:?}\n", err),
+    };
+    let cells = [
+        ("arg.v", LOC_ARG_V, INPUT_WORDS),
+        ("tmp.key", LOC_TMP_K, KEY_WORDS),
+    ];
+    for (name, start, len) in cells {
+        for i in 0..len {
+            let word = &mix.memory()[(start + i) as u16];
+            report.push_str(&format!("{}+{}: {}\n", name, i, word));
+        }
+    }
+    report
+}
+
+/// Get the value following `flag` in `args`.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let i = args.iter().position(|arg| arg == flag)?;
+    args.get(i + 1).map(String::as_str)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // `--raw` feeds stdin to the machine as is, as older versions did.
+    let raw = args.iter().any(|arg| arg == "--raw");
+    // `--report FILE` writes a [`challenge_report()`] to FILE.
+    let report = flag_value(&args, "--report");
+    // `--key-file FILE` reads the key from FILE, see [`challenge_key()`].
+    let key = challenge_key(flag_value(&args, "--key-file"));
+    let complaint = Rc::new(RefCell::new(None));
+
+    let reader: Box<dyn IODevice> = if raw {
+        Box::new(LineReaderDevice {
+            input: Box::new(io::stdin()),
+            policy: InputPolicy::Replace(Alphabet::LowSQuote),
+            offset: 0,
+            complaint: Rc::clone(&complaint),
+        })
+    } else {
+        Box::new(AnswerReaderDevice {
+            input: Box::new(io::stdin().lock()),
+            bytes: None,
+            complaint: Rc::clone(&complaint),
+        })
+    };
+    let printer = Box::new(LinePrinterDevice {
+        lower_case: false,
+        line: String::new(),
+        out: Box::new(io::stdout()),
+    });
+    let mut mix = match build_challenge(reader, printer, key) {
+        Ok(mix) => mix,
+        Err(err) => {
+            eprintln!("Cannot install the program: {:?}", err);
+            std::process::exit(1);
+        }
+    };
+    let outcome = run_challenge(&mut mix);
+    if let Some(path) = report {
+        if let Err(err) = std::fs::write(path, challenge_report(&mix, &outcome)) {
+            eprintln!("Cannot write the report: {}", err);
+            std::process::exit(1);
+        }
+    }
+    match outcome {
+        Ok(()) => {}
+        Err(ChallengeError::Layout(err)) => {
+            eprintln!("Inconsistent memory layout: {:?}", err);
+            std::process::exit(1);
+        }
+        Err(ChallengeError::Patched) => {
+            eprintln!("Program integrity check failed");
+            std::process::exit(1);
+        }
+        Err(ChallengeError::Run(err)) => {
+            match complaint.borrow_mut().take() {
+                Some(msg) => eprintln!("Invalid answer: {}", msg),
+                None => eprintln!("Machine stopped abnormally: {:?}", err),
+            }
+            std::process::exit(1);
+        }
     }
 
     // Flag: D3CTF(TECH-EV0LVE,EMBR@C3-PR0GR3SS)
@@ -358,16 +720,354 @@ fn main() {
     if cfg!(feature = "csmantle") {
         println!("--- Input after XTEA");
         for i in 0..INPUT_WORDS {
-            println!("{:?}", mix.mem[LOC_ARG_V as u16 + i as u16]);
+            println!("{:?}", mix.memory()[LOC_ARG_V as u16 + i as u16]);
         }
         println!("--- Expected cipher");
         for i in 0..INPUT_WORDS {
-            println!("{:?}", mix.mem[LOC_CONST_C as u16 + i as u16]);
+            println!("{:?}", mix.memory()[LOC_CONST_C as u16 + i as u16]);
+        }
+        println!("--- Key after zeroization");
+        for i in 0..KEY_WORDS {
+            println!("{:?}", mix.memory()[LOC_TMP_K as u16 + i as u16]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLAG: &str = "D3CTF(TECH-EV0LVE,EMBR@C3-PR0GR3SS)";
+
+    /// A buffer shared with a [`LinePrinterDevice`].
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Set up the challenge with `answer` as input.
+    fn challenge(answer: &str) -> (VM, SharedOutput) {
+        let key = SealedKeyDevice::from_bytes(KEY_WORDS as usize, KEY.concat());
+        challenge_with_key(answer, key)
+    }
+
+    /// Set up the challenge with `answer` as input and another key.
+    fn challenge_with_key(answer: &str, key: SealedKeyDevice) -> (VM, SharedOutput) {
+        let output = SharedOutput::default();
+        let reader = Box::new(AnswerReaderDevice {
+            input: Box::new(io::Cursor::new(format!("{}\n", answer).into_bytes())),
+            bytes: None,
+            complaint: Rc::new(RefCell::new(None)),
+        });
+        let printer = Box::new(LinePrinterDevice {
+            lower_case: false,
+            line: String::new(),
+            out: Box::new(output.clone()),
+        });
+        (build_challenge(reader, printer, key).unwrap(), output)
+    }
+
+    /// A tab, a lowercase letter and the UTF-8 encoding of `é`.
+    const MIXED_INPUT: &[u8] = b"AB\tcD\xc3\xa9EFGHIJK";
+
+    /// Read `count` words from `input` with a [`LineReaderDevice`].
+    ///
+    /// Returns the words read before the first failure, and the
+    /// complaint left by the device.
+    fn line_reader_words(
+        input: &[u8],
+        policy: InputPolicy,
+        count: usize,
+    ) -> (Vec<[u8; 6]>, Option<String>) {
+        let complaint = Rc::new(RefCell::new(None));
+        let mut reader = LineReaderDevice {
+            input: Box::new(io::Cursor::new(input.to_vec())),
+            policy,
+            offset: 0,
+            complaint: Rc::clone(&complaint),
+        };
+        let mut words = Vec::new();
+        for _ in 0..count {
+            let mut buffer = [FullWord::new()];
+            if reader.read(&mut buffer).is_err() {
+                break;
+            }
+            words.push(*buffer[0].as_array());
+        }
+        let complaint = complaint.borrow_mut().take();
+        (words, complaint)
+    }
+
+    /// Encode `text` as it should be read.
+    fn line_words(text: &str) -> Vec<[u8; 6]> {
+        encode_words(text, InputPolicy::Strict)
+            .unwrap()
+            .iter()
+            .map(|word| *word.as_array())
+            .collect()
+    }
+
+    /// Check that no word of [`KEY`] is anywhere in the memory.
+    fn key_is_absent(mix: &VM) -> bool {
+        KEY.iter()
+            .all(|word| mix.memory().find_bytes(&word[1..]).is_empty())
+    }
+
+    /// Run the challenge with the flag, taking a checkpoint and a full
+    /// snapshot every `interval` steps.
+    fn checkpointed_run(interval: u64) -> (checkpoint::CheckpointStore, Vec<VMStateSnapshot>) {
+        let (mut mix, _) = challenge(FLAG);
+        mix.reset();
+        mix.pc = mix.entry().unwrap();
+        mix.restart();
+        let mut store = checkpoint::CheckpointStore::new();
+        let mut full = Vec::new();
+        while !mix.halted {
+            if mix.step_count() % interval == 0 {
+                store.push(&mix);
+                full.push(mix.snapshot());
+            }
+            mix.step().unwrap();
+        }
+        (store, full)
+    }
+
+    #[test]
+    fn checkpoints_of_the_run_are_compact() {
+        let (store, full) = checkpointed_run(1000);
+        let full_size = full.len() * core::mem::size_of::<Mem>();
+        let used = store.bytes_used();
+        assert!(used * 10 <= full_size, "{} bytes", used);
+        for (i, snapshot) in full.iter().enumerate() {
+            let rebuilt = store.get(i).unwrap();
+            assert_eq!(rebuilt.mem.as_byte_slice(), snapshot.mem.as_byte_slice());
+            assert_eq!(rebuilt.pc, snapshot.pc);
+        }
+    }
+
+    /// Measure the checkpoints of runs and the time the runs take, with
+    /// `cargo test --release --bin modern_legacy -- --ignored`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn checkpoint_benchmark() {
+        for interval in [1000, 100, 10] {
+            let start = std::time::Instant::now();
+            let (store, full) = checkpointed_run(interval);
+            let elapsed = start.elapsed();
+            let full_size = full.len() * core::mem::size_of::<Mem>();
+            println!(
+                "every {} steps: {} checkpoints, {} bytes ({:.1}x smaller), {:?}",
+                interval,
+                store.len(),
+                store.bytes_used(),
+                full_size as f64 / store.bytes_used() as f64,
+                elapsed
+            );
+        }
+    }
+
+    #[test]
+    fn key_is_not_loaded_before_the_run() {
+        let (mix, _) = challenge(FLAG);
+        assert!(key_is_absent(&mix));
+    }
+
+    #[test]
+    fn key_is_zeroized_after_the_run() {
+        for answer in [FLAG, "D3CTF(TECH-EV0LVE,EMBR@C3-PR0GR3SX)"] {
+            let (mut mix, _) = challenge(answer);
+            run_challenge(&mut mix).unwrap();
+            assert!(key_is_absent(&mix), "{}", answer);
+            let key = challenge_layout().unwrap().addr("tmp.key");
+            for i in 0..KEY_WORDS as u16 {
+                assert_eq!(mix.memory()[key + i].to_i64().0, 0);
+            }
         }
-        println!("--- Key");
-        println!("{:?}", mix.mem[LOC_ARG_K as u16 /* + 0 */]);
-        println!("{:?}", mix.mem[LOC_ARG_K as u16 + 1]);
-        println!("{:?}", mix.mem[LOC_ARG_K as u16 + 2]);
-        println!("{:?}", mix.mem[LOC_ARG_K as u16 + 3]);
+    }
+
+    #[test]
+    fn challenge_accepts_the_flag() {
+        let (mut mix, output) = challenge(FLAG);
+        run_challenge(&mut mix).unwrap();
+        let printed = String::from_utf8(output.0.take()).unwrap();
+        assert!(printed.contains("NOW MARCH BEYOND, AND REVIVE THE LEGACY."));
+    }
+
+    #[test]
+    fn patched_program_is_not_run() {
+        let (mut mix, output) = challenge(FLAG);
+        let addr = challenge_layout().unwrap().addr("main");
+        mix.memory_mut()[addr] = FullWord::from(Instruction::new(0, 2, 0, Opcode::Special));
+        assert!(matches!(
+            run_challenge(&mut mix),
+            Err(ChallengeError::Patched)
+        ));
+        assert_eq!(mix.step_count(), 0);
+        assert!(output.0.borrow().is_empty());
+    }
+
+    #[test]
+    fn strict_line_reader_names_the_offending_byte() {
+        let (words, complaint) = line_reader_words(MIXED_INPUT, InputPolicy::Strict, 2);
+        assert!(words.is_empty());
+        assert_eq!(
+            complaint.as_deref(),
+            Some("byte 0x09 at offset 2 is not in the MIX alphabet")
+        );
+
+        let (_, complaint) = line_reader_words(b"ABCDEFGHc", InputPolicy::Strict, 2);
+        assert_eq!(
+            complaint.as_deref(),
+            Some("byte 0x63 at offset 8 is not in the MIX alphabet")
+        );
+
+        let (words, complaint) = line_reader_words(b"ABCDE\xc3\xa9", InputPolicy::Strict, 2);
+        assert_eq!(words, line_words("ABCDE"));
+        assert_eq!(
+            complaint.as_deref(),
+            Some("byte 0xc3 at offset 5 is not in the MIX alphabet")
+        );
+    }
+
+    #[test]
+    fn line_reader_replaces_or_skips_foreign_bytes() {
+        let replace = InputPolicy::Replace(Alphabet::Space);
+        let (words, complaint) = line_reader_words(MIXED_INPUT, replace, 2);
+        assert_eq!(words, line_words("AB  D  EFG"));
+        assert_eq!(complaint, None);
+
+        let (words, complaint) = line_reader_words(MIXED_INPUT, InputPolicy::Skip, 2);
+        assert_eq!(words, line_words("ABDEFGHIJK"));
+        assert_eq!(complaint, None);
+    }
+
+    #[test]
+    fn challenge_layout_agrees_with_the_constants() {
+        let layout = challenge_layout().unwrap();
+        assert_eq!(layout.addr("xtea"), PROGRAM_START as u16);
+        assert_eq!(
+            layout.region_of(LOC_TMP_9 as u16).map(|r| r.name.as_str()),
+            Some("tmp")
+        );
+        assert_eq!(layout.addr("tmp.key"), LOC_TMP_K as u16);
+    }
+
+    #[test]
+    fn patched_verification_accepts_a_wrong_answer() {
+        let (mut mix, output) = challenge("D3CTF(TECH-EV0LVE,EMBR@C3-PR0GR3SX)");
+        // Never take the `JANZ` to the mismatch path, whatever its
+        // address.
+        let nop = FullWord::from(Instruction::new(0, 0, 0, Opcode::Nop));
+        let pattern = [None, None, None, None, Some(4), Some(Opcode::JA as u8)];
+        let sites = mix.find_and_patch(&pattern, &[nop]).unwrap();
+        assert_eq!(sites, [LOC_MAIN_VERIF_LOOP as u16 + 2]);
+
+        // The checksum catches the patch...
+        assert!(matches!(
+            run_challenge(&mut mix),
+            Err(ChallengeError::Patched)
+        ));
+        assert!(output.0.borrow().is_empty());
+
+        // ...which would otherwise let any answer through.
+        mix.run_with_timeout(MAX_STEPS).unwrap();
+        let printed = String::from_utf8(output.0.take()).unwrap();
+        assert!(printed.contains("NOW MARCH BEYOND, AND REVIVE THE LEGACY."));
+        assert!(!printed.contains("THAT IS NOT CORRECT"));
+    }
+
+    #[test]
+    fn cipher_constants_are_found_in_memory() {
+        let (mix, _) = challenge(FLAG);
+        let mem = mix.memory();
+        assert_eq!(mem.as_flat_bytes().len(), Mem::SIZE * 6);
+        for (i, bytes) in CIPHER.iter().enumerate() {
+            let addr = LOC_CONST_C as u16 + i as u16;
+            assert!(mem.find_bytes(bytes).contains(&(addr, 0)), "{}", i);
+            assert_eq!(mem[addr].as_array(), bytes);
+        }
+        let cipher: Vec<u8> = CIPHER.concat();
+        assert_eq!(mem.find_bytes(&cipher), [(LOC_CONST_C as u16, 0)]);
+    }
+
+    /// Read a section of the recorded session `name` in `tests/sessions`.
+    fn recorded_section(name: &str, section: &str) -> String {
+        let path = format!(
+            "{}/tests/sessions/{}.session",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        let text = std::fs::read_to_string(path).unwrap();
+        let (_, rest) = text.split_once(&format!("--- {}\n", section)).unwrap();
+        rest.split("--- ").next().unwrap().to_string()
+    }
+
+    #[test]
+    fn recorded_sessions_catch_program_changes() {
+        let stdout = recorded_section("right", "stdout");
+        let report = recorded_section("right", "report");
+        let (mut mix, output) = challenge(FLAG);
+        let outcome = run_challenge(&mut mix);
+        assert_eq!(String::from_utf8(output.0.take()).unwrap(), stdout);
+        assert_eq!(challenge_report(&mix, &outcome), report);
+
+        // Shift by one bit less in each round of XTEA.
+        let mut program = PROGRAM;
+        program[8] = Instruction::new(3, 6, 0, Opcode::Shift);
+        let (mut mix, output) = challenge(FLAG);
+        let start = challenge_layout().unwrap().addr("xtea");
+        for (i, &instr) in program.iter().enumerate() {
+            mix.memory_mut()[start + i as u16] = FullWord::from(instr);
+        }
+
+        // The checksum stops the run...
+        let outcome = run_challenge(&mut mix);
+        assert!(challenge_report(&mix, &outcome).starts_with("halt: Patched\n"));
+
+        // ...and were it updated, the output would diverge anyway.
+        let outcome = mix
+            .run_with_timeout(MAX_STEPS)
+            .map(|_| ())
+            .map_err(ChallengeError::Run);
+        let printed = String::from_utf8(output.0.take()).unwrap();
+        assert_ne!(printed, stdout);
+        assert_ne!(challenge_report(&mix, &outcome), report);
+    }
+
+    #[test]
+    fn keys_are_read_from_files() {
+        let path = std::env::temp_dir().join(format!("modern_legacy-{}.key", std::process::id()));
+        std::fs::write(&path, KEY.concat()).unwrap();
+        let (mut mix, output) = challenge_with_key(FLAG, challenge_key(path.to_str()));
+        std::fs::remove_file(&path).unwrap();
+        run_challenge(&mut mix).unwrap();
+        let printed = String::from_utf8(output.0.take()).unwrap();
+        assert!(printed.contains("NOW MARCH BEYOND, AND REVIVE THE LEGACY."));
+
+        // Another key makes the flag wrong.
+        let mut key = KEY.concat();
+        key[1] ^= 1;
+        std::fs::write(&path, key).unwrap();
+        let (mut mix, output) = challenge_with_key(FLAG, challenge_key(path.to_str()));
+        std::fs::remove_file(&path).unwrap();
+        run_challenge(&mut mix).unwrap();
+        let printed = String::from_utf8(output.0.take()).unwrap();
+        assert!(printed.contains("THAT IS NOT CORRECT"));
+
+        // So does a missing one, without running past the `IN`.
+        let (mut mix, _) = challenge_with_key(FLAG, challenge_key(path.to_str()));
+        assert!(matches!(
+            run_challenge(&mut mix),
+            Err(ChallengeError::Run(RunError::ExecutionError(_)))
+        ));
+        assert!(key_is_absent(&mix));
     }
 }
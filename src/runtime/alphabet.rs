@@ -1,3 +1,5 @@
+use super::FullWord;
+
 /// The common alphabet used in [`MixVM`].
 ///
 /// See D. E. Knuth, *The Art of Computer Programming*, Volume 1, pp 140
@@ -329,3 +331,74 @@ impl TryFrom<char> for Alphabet {
         }
     }
 }
+
+/// Policies of handling input characters not in [`Alphabet`].
+///
+/// Used by reader-style devices and [`encode_words()`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputPolicy {
+    /// Reject the input.
+    Strict,
+
+    /// Replace the character with the given one.
+    Replace(Alphabet),
+
+    /// Drop the character.
+    Skip,
+}
+
+/// An input character rejected under [`InputPolicy::Strict`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InputError {
+    /// The offending character.
+    pub ch: char,
+
+    /// The byte offset of the character in the input.
+    pub offset: usize,
+}
+
+impl InputPolicy {
+    /// Encode a character under the policy.
+    ///
+    /// # Returns
+    /// * [`Ok(Some(u8))`] - The encoded byte, always a valid [`Alphabet`] byte.
+    /// * [`Ok(None)`] - The character is dropped.
+    /// * [`Err(())`] - The character is rejected.
+    pub fn encode(self, ch: char) -> Result<Option<u8>, ()> {
+        match (Alphabet::try_from(ch), self) {
+            (Ok(alpha), _) => Ok(Some(alpha as u8)),
+            (Err(_), InputPolicy::Strict) => Err(()),
+            (Err(_), InputPolicy::Replace(alpha)) => Ok(Some(alpha as u8)),
+            (Err(_), InputPolicy::Skip) => Ok(None),
+        }
+    }
+}
+
+/// Encode text into words of [`Alphabet`] bytes.
+///
+/// Each word holds 5 characters and a positive sign. The last
+/// word is padded with [`Alphabet::Space`].
+///
+/// # Arguments
+/// * `text` - The text to encode.
+/// * `policy` - The policy of handling characters not in [`Alphabet`].
+///
+/// # Returns
+/// * [`Ok(Vec<FullWord>)`] - The encoded words.
+/// * [`Err(InputError)`] - A character is rejected under [`InputPolicy::Strict`].
+pub fn encode_words(text: &str, policy: InputPolicy) -> Result<Vec<FullWord>, InputError> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for (offset, ch) in text.char_indices() {
+        if let Some(byte) = policy.encode(ch).map_err(|_| InputError { ch, offset })? {
+            bytes.push(byte);
+        }
+    }
+    Ok(bytes
+        .chunks(5)
+        .map(|chunk| {
+            let mut word = FullWord::new();
+            word[1..=chunk.len()].copy_from_slice(chunk);
+            word
+        })
+        .collect())
+}
@@ -0,0 +1,222 @@
+//! Static and dynamic analyses of MIX programs.
+
+use super::*;
+
+/// A loop found by [`VM::detect_infinite_loops()`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LoopReport {
+    /// The address of the first instruction of the loop.
+    pub loop_start_pc: u16,
+
+    /// The number of instructions in one iteration of the loop.
+    pub loop_length: u64,
+
+    /// The number of instructions run before the loop is detected.
+    pub iterations_before_detection: u64,
+}
+
+/// Find static jumps to words that are not instructions.
+///
+/// Every word is decoded as an instruction, skipping words that do
+/// not decode. Jumps without index registers have a target known
+/// without running the program, and are reported if the word at
+/// their target does not decode either, which hints at a bug or a
+/// jump into data. Targets outside `words` are not reported.
+///
+/// # Arguments
+/// * `words` - The words to check, starting from address 0.
+///
+/// # Returns
+/// * [`Vec<(u16, u16)>`] - The address and the target of each bad jump, by address.
+pub fn analyze_jump_targets(words: &[FullWord]) -> Vec<(u16, u16)> {
+    words
+        .iter()
+        .enumerate()
+        .filter_map(|(addr, &word)| {
+            let instr = Instruction::try_from(word).ok()?;
+            if !instr.opcode.is_jump() || instr.index != 0 || instr.validate().is_err() {
+                return None;
+            }
+            let target = u16::try_from(instr.addr).ok()?;
+            let target_word = *words.get(target as usize)?;
+            Instruction::try_from(target_word)
+                .is_err()
+                .then_some((addr as u16, target))
+        })
+        .collect()
+}
+
+/// The part of the state compared by [`VM::detect_infinite_loops()`].
+type LoopState = (u16, i64, i64, i64);
+
+impl VM {
+    /// Find all jumps to an address.
+    ///
+    /// Every non-zero word in the memory is decoded as an instruction,
+    /// skipping words that do not decode. Index registers are ignored,
+    /// so only the `A` part of each jump is compared.
+    ///
+    /// # Arguments
+    /// * `target_addr` - The address jumped to.
+    ///
+    /// # Returns
+    /// * [`Vec<u16>`] - The addresses of the jumps, in ascending order.
+    pub fn jmp_target_analysis(&self, target_addr: u16) -> Vec<u16> {
+        (0..Mem::SIZE as u16)
+            .filter(|&addr| {
+                let word = self.mem[addr];
+                if word[..].iter().all(|&b| b == 0) {
+                    return false;
+                }
+                match Instruction::try_from(word) {
+                    Ok(instr) => instr.opcode.is_jump() && instr.addr == target_addr as i16,
+                    Err(_) => false,
+                }
+            })
+            .collect()
+    }
+
+    /// Find values known without running the program.
+    ///
+    /// Words in `start..start + len` are decoded as instructions,
+    /// skipping words that do not decode. Two kinds of values are
+    /// reported:
+    ///
+    /// * `ENTA`, `ENTX` and `ENT1` to `ENT6` without index registers
+    ///   yield the address of the instruction and the value entered.
+    /// * `STZ` without index registers and with field `(0:5)` yields
+    ///   the address it clears and a zero word.
+    ///
+    /// # Arguments
+    /// * `start` - The first address to scan.
+    /// * `len` - The number of words to scan. Words out of memory are ignored.
+    ///
+    /// # Returns
+    /// * [`Vec<(u16, FullWord)>`] - Addresses and their known values, in scan order.
+    pub fn constant_fold_check(&self, start: u16, len: u16) -> Vec<(u16, FullWord)> {
+        let end = (start as usize + len as usize).min(Mem::SIZE) as u16;
+        (start..end)
+            .filter_map(|addr| {
+                let instr = Instruction::try_from(self.mem[addr]).ok()?;
+                if instr.index != 0 {
+                    return None;
+                }
+                match instr.opcode {
+                    Opcode::ModifyA
+                    | Opcode::Modify1
+                    | Opcode::Modify2
+                    | Opcode::Modify3
+                    | Opcode::Modify4
+                    | Opcode::Modify5
+                    | Opcode::Modify6
+                    | Opcode::ModifyX
+                        if instr.field == 2 =>
+                    {
+                        Some((addr, FullWord::from_i64(instr.addr as i64).0))
+                    }
+                    Opcode::StZ
+                        if instr.field == 5
+                            && instr.addr >= 0
+                            && (instr.addr as usize) < Mem::SIZE =>
+                    {
+                        Some((instr.addr as u16, FullWord::new()))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Run the machine until it repeats a state, indicating a loop.
+    ///
+    /// The state is approximated by `pc`, `rA`, `rX` and `rI1`. Loops
+    /// are detected with Brent's algorithm while the states are
+    /// recorded, and the start of a loop is then found in the record.
+    /// A repeated state may be a false positive if the loop changes
+    /// other registers or the memory.
+    ///
+    /// The machine runs from its current state and is left where the
+    /// detection stops.
+    ///
+    /// # Arguments
+    /// * `max_trace_len` - The maximum number of instructions to run.
+    ///
+    /// # Returns
+    /// * [`Some(LoopReport)`] - A state is repeated.
+    /// * [`None`] - The machine halted, failed or ran `max_trace_len`
+    ///   instructions without repeating a state.
+    pub fn detect_infinite_loops(&mut self, max_trace_len: usize) -> Option<LoopReport> {
+        let state = |vm: &VM| -> LoopState {
+            (
+                vm.pc,
+                vm.r_a.to_i64().0,
+                vm.r_x.to_i64().0,
+                vm.r_in[1].to_i64().0,
+            )
+        };
+        let mut trace: Vec<LoopState> = vec![state(self)];
+        let mut tortoise = trace[0];
+        let mut power: usize = 1;
+        let mut lam: usize = 0;
+        while trace.len() <= max_trace_len {
+            if self.halted || self.step().is_err() {
+                return None;
+            }
+            let hare = state(self);
+            trace.push(hare);
+            lam += 1;
+            if hare == tortoise {
+                let mu = (0..trace.len() - lam).find(|&i| trace[i] == trace[i + lam])?;
+                return Some(LoopReport {
+                    loop_start_pc: trace[mu].0,
+                    loop_length: lam as u64,
+                    iterations_before_detection: (trace.len() - 1) as u64,
+                });
+            }
+            if lam == power {
+                tortoise = hare;
+                power *= 2;
+                lam = 0;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jumps_into_data_are_found() {
+        // With 6-bit bytes every byte is an opcode, so no word fails to
+        // decode.
+        let (data, _) = FullWord::from_i64(100);
+        let words = [
+            // JMP 3
+            FullWord::from(Instruction::new(3, 0, 0, Opcode::Jmp)),
+            // JMP 5
+            FullWord::from(Instruction::new(5, 0, 0, Opcode::Jmp)),
+            // JMP 5,1
+            FullWord::from(Instruction::new(5, 0, 1, Opcode::Jmp)),
+            // HLT
+            FullWord::from(Instruction::new(0, 2, 0, Opcode::Special)),
+            // JAN 5
+            FullWord::from(Instruction::new(5, 0, 0, Opcode::JA)),
+            data,
+            // JMP 3000
+            FullWord::from(Instruction::new(3000, 0, 0, Opcode::Jmp)),
+            // JMP 5(40)
+            FullWord::from(Instruction::new(5, 40, 0, Opcode::Jmp)),
+        ];
+        let expected: &[(u16, u16)] = if cfg!(feature = "byte-64") {
+            &[]
+        } else {
+            &[(1, 5), (4, 5)]
+        };
+        assert_eq!(Instruction::try_from(data).is_err(), !expected.is_empty());
+        assert_eq!(analyze_jump_targets(&words), expected);
+        assert_eq!(analyze_jump_targets(&words[..4]), []);
+        assert_eq!(analyze_jump_targets(&[]), []);
+    }
+}
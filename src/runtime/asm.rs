@@ -0,0 +1,1108 @@
+//! Support for assembling MIXAL source.
+//!
+//! Source may come from untrusted files, so its lines are at most
+//! [`MAX_LINE_LEN`] characters long and its expressions have at most
+//! [`MAX_EXPR_DEPTH`] operators. Expressions are evaluated without
+//! recursion.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::rc::Rc;
+
+use super::{
+    ErrorCode, FullWord, InputPolicy, Instruction, LinePrinterDevice, Mem, MixProgram, Opcode,
+    RunError, SymbolTable, TextCardReaderDevice, WordParseError, BYTE_BITS, CARD_READER_UNIT,
+    LINE_PRINTER_UNIT, VM,
+};
+
+/// The most characters in a line of source, see [`assemble()`].
+pub const MAX_LINE_LEN: usize = 256;
+
+/// The most binary operators in an expression, see [`evaluate_expr()`].
+///
+/// Operators are applied from left to right, so each of them nests
+/// the value so far one level deeper: `1+2*3` is `(1+2)*3`.
+pub const MAX_EXPR_DEPTH: usize = 64;
+
+/// Kinds of [`AsmError`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AsmErrorKind {
+    /// The operation is not a known mnemonic or pseudo-operation.
+    UnknownMnemonic(String),
+
+    /// The `F` part is not legal for the operation.
+    BadField(String),
+
+    /// A symbol is used but never defined.
+    UndefinedSymbol(String),
+
+    /// A label is defined more than once.
+    DuplicateLabel(String),
+
+    /// An expression is malformed.
+    ExprError(String),
+
+    /// A value does not fit where it is used.
+    OutOfRange(i128),
+
+    /// A line is longer than [`MAX_LINE_LEN`], with its length in
+    /// characters.
+    LineTooLong(usize),
+
+    /// An expression has more than [`MAX_EXPR_DEPTH`] operators.
+    ExprTooDeep,
+}
+
+impl fmt::Display for AsmErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsmErrorKind::UnknownMnemonic(name) => write!(f, "unknown mnemonic `{}`", name),
+            AsmErrorKind::BadField(field) => write!(f, "bad field `{}`", field),
+            AsmErrorKind::UndefinedSymbol(name) => write!(f, "undefined symbol `{}`", name),
+            AsmErrorKind::DuplicateLabel(name) => write!(f, "duplicate label `{}`", name),
+            AsmErrorKind::ExprError(msg) => write!(f, "bad expression: {}", msg),
+            AsmErrorKind::OutOfRange(value) => write!(f, "value {} is out of range", value),
+            AsmErrorKind::LineTooLong(len) => write!(
+                f,
+                "line is {} characters long, more than {}",
+                len, MAX_LINE_LEN
+            ),
+            AsmErrorKind::ExprTooDeep => {
+                write!(f, "expression has more than {} operators", MAX_EXPR_DEPTH)
+            }
+        }
+    }
+}
+
+/// An error found in MIXAL source.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AsmError {
+    /// The line of the error, starting from 1.
+    pub line: usize,
+
+    /// The columns of the error in the line, starting from 1.
+    pub columns: Range<usize>,
+
+    /// What is wrong.
+    pub kind: AsmErrorKind,
+}
+
+impl AsmError {
+    /// Create a new error.
+    ///
+    /// # Arguments
+    /// * `line` - The line of the error, starting from 1.
+    /// * `columns` - The columns of the error in the line, starting from 1.
+    /// * `kind` - What is wrong.
+    pub fn new(line: usize, columns: Range<usize>, kind: AsmErrorKind) -> Self {
+        AsmError {
+            line,
+            columns,
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for AsmError {
+    /// Format the error as `line:col: message`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.columns.start, self.kind)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Kinds of [`AsmWarning`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AsmWarningKind {
+    /// A part of a W-value overwrites a field set by an earlier part.
+    OverlappingField(String),
+}
+
+impl fmt::Display for AsmWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsmWarningKind::OverlappingField(part) => {
+                write!(f, "`{}` overwrites an earlier part", part)
+            }
+        }
+    }
+}
+
+/// Something legal but suspicious found in MIXAL source.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AsmWarning {
+    /// The line of the warning, starting from 1.
+    pub line: usize,
+
+    /// The columns of the warning in the line, starting from 1.
+    pub columns: Range<usize>,
+
+    /// What is suspicious.
+    pub kind: AsmWarningKind,
+}
+
+impl fmt::Display for AsmWarning {
+    /// Format the warning as `line:col: warning: message`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: warning: {}",
+            self.line, self.columns.start, self.kind
+        )
+    }
+}
+
+/// A binary operator of MIXAL expressions.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum BinOp {
+    /// `+`.
+    Add,
+
+    /// `-`.
+    Sub,
+
+    /// `*`.
+    Mul,
+
+    /// `/`, truncating toward zero.
+    Div,
+
+    /// `//`, dividing `lhs` shifted 5 bytes left.
+    FracDiv,
+
+    /// `:`, i.e. `8 * lhs + rhs`.
+    Field,
+}
+
+impl BinOp {
+    /// Apply the operator.
+    ///
+    /// Both operands fit in a word, so no step overflows an `i128`,
+    /// and the result is checked before it is narrowed.
+    fn apply(self, lhs: i64, rhs: i64) -> Result<i64, AsmErrorKind> {
+        let (lhs, rhs) = (lhs as i128, rhs as i128);
+        let value = match self {
+            BinOp::Add => lhs + rhs,
+            BinOp::Sub => lhs - rhs,
+            BinOp::Mul => lhs * rhs,
+            BinOp::Div | BinOp::FracDiv if rhs == 0 => {
+                return Err(AsmErrorKind::ExprError("division by zero".into()))
+            }
+            BinOp::Div => lhs / rhs,
+            BinOp::FracDiv => (lhs << (5 * BYTE_BITS)) / rhs,
+            BinOp::Field => lhs * 8 + rhs,
+        };
+        if value.unsigned_abs() >= 1 << (5 * BYTE_BITS) {
+            return Err(AsmErrorKind::OutOfRange(value));
+        }
+        Ok(value as i64)
+    }
+}
+
+/// Evaluate an atomic expression.
+fn evaluate_atom(atom: &str, symbols: &SymbolTable, location: u16) -> Result<i64, AsmErrorKind> {
+    if atom == "*" {
+        Ok(location as i64)
+    } else if atom.bytes().all(|b| b.is_ascii_digit()) {
+        atom.parse()
+            .ok()
+            .filter(|value| !FullWord::from_i64(*value).1)
+            .ok_or_else(|| AsmErrorKind::ExprError(format!("number `{}` is too large", atom)))
+    } else {
+        symbols
+            .get(atom)
+            .map(|&addr| addr as i64)
+            .ok_or_else(|| AsmErrorKind::UndefinedSymbol(atom.into()))
+    }
+}
+
+/// Evaluate a MIXAL expression.
+///
+/// Atoms are numbers, symbols, and `*` for the location. Binary
+/// operators `+`, `-`, `*`, `/`, `//` and `:` are applied strictly
+/// from left to right, after an optional unary `+` or `-`.
+///
+/// # Arguments
+/// * `text` - The expression.
+/// * `symbols` - The defined symbols and their values.
+/// * `location` - The value of `*`.
+///
+/// # Returns
+/// * [`Ok(i64)`] - The value.
+/// * [`Err(AsmErrorKind::UndefinedSymbol)`] - A symbol is not in `symbols`.
+/// * [`Err(AsmErrorKind::OutOfRange)`] - A value does not fit in a word.
+/// * [`Err(AsmErrorKind::ExprTooDeep)`] - The expression has more than [`MAX_EXPR_DEPTH`]
+///   operators.
+/// * [`Err(AsmErrorKind::ExprError)`] - The expression is malformed.
+pub fn evaluate_expr(
+    text: &str,
+    symbols: &SymbolTable,
+    location: u16,
+) -> Result<i64, AsmErrorKind> {
+    let malformed = || AsmErrorKind::ExprError(format!("malformed expression `{}`", text));
+    let mut rest = text;
+    let mut value = 0;
+    let mut depth = 0;
+    let mut op = match rest.as_bytes().first() {
+        Some(b'+') => {
+            rest = &rest[1..];
+            BinOp::Add
+        }
+        Some(b'-') => {
+            rest = &rest[1..];
+            BinOp::Sub
+        }
+        _ => BinOp::Add,
+    };
+    loop {
+        let len = if rest.starts_with('*') {
+            1
+        } else {
+            rest.find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len())
+        };
+        if len == 0 {
+            return Err(malformed());
+        }
+        let (atom, after) = rest.split_at(len);
+        value = op.apply(value, evaluate_atom(atom, symbols, location)?)?;
+        rest = after;
+        let (next, len) = match rest.as_bytes() {
+            [] => return Ok(value),
+            [b'+', ..] => (BinOp::Add, 1),
+            [b'-', ..] => (BinOp::Sub, 1),
+            [b'/', b'/', ..] => (BinOp::FracDiv, 2),
+            [b'/', ..] => (BinOp::Div, 1),
+            [b'*', ..] => (BinOp::Mul, 1),
+            [b':', ..] => (BinOp::Field, 1),
+            _ => return Err(malformed()),
+        };
+        depth += 1;
+        if depth > MAX_EXPR_DEPTH {
+            return Err(AsmErrorKind::ExprTooDeep);
+        }
+        op = next;
+        rest = &rest[len..];
+    }
+}
+
+/// A W-value and the parts found overlapping while evaluating it.
+#[derive(Clone, Debug)]
+pub struct WValue {
+    /// The word.
+    pub word: FullWord,
+
+    /// The indices of the parts whose field overlaps that of an
+    /// earlier part, which they overwrite, starting from 0.
+    pub overlaps: Vec<usize>,
+}
+
+/// Evaluate a MIXAL W-value, e.g. `1000(1:3),500(4:5)`.
+///
+/// The word starts as `+0`, and each comma-separated part `E(F)`
+/// stores `E` into the field `F` of the word, as `STA` does. `F`
+/// defaults to `(0:5)` when omitted.
+///
+/// # Arguments
+/// * `text` - The W-value.
+/// * `symbols` - The defined symbols and their values.
+/// * `location` - The value of `*`.
+///
+/// # Returns
+/// * [`Ok(WValue)`] - The word.
+/// * [`Err(AsmErrorKind::BadField)`] - A field is not `(L:R)` with `0 <= L <= R <= 5`.
+/// * [`Err(AsmErrorKind)`] - An expression is wrong, see [`evaluate_expr()`].
+pub fn evaluate_wvalue(
+    text: &str,
+    symbols: &SymbolTable,
+    location: u16,
+) -> Result<WValue, AsmErrorKind> {
+    let mut word = FullWord::new();
+    let mut written = [false; 6];
+    let mut overlaps = Vec::new();
+    for (index, part) in text.split(',').enumerate() {
+        let (expr, field) = match part.strip_suffix(')').and_then(|p| p.rsplit_once('(')) {
+            Some((expr, field)) => {
+                let value = evaluate_expr(field, symbols, location)?;
+                (expr, value)
+            }
+            None => (part, 5),
+        };
+        let (l, r) = (field / 8, field % 8);
+        if !(0..=5).contains(&r) || !(0..=r).contains(&l) {
+            return Err(AsmErrorKind::BadField(part.into()));
+        }
+        let (l, r) = (l as usize, r as usize);
+        let value = FullWord::from_i64(evaluate_expr(expr, symbols, location)?).0;
+        if written[l..=r].iter().any(|&w| w) {
+            overlaps.push(index);
+        }
+        written[l..=r].fill(true);
+        if l == 0 {
+            word[0] = value[0];
+        }
+        let start = l.max(1);
+        let len = r + 1 - start;
+        word[start..=r].copy_from_slice(&value[6 - len..=5]);
+    }
+    Ok(WValue { word, overlaps })
+}
+
+/// A program assembled by [`assemble()`].
+#[derive(Clone, Debug)]
+pub struct Assembly {
+    /// The memory holding the program, `+0` elsewhere.
+    pub mem: Mem,
+
+    /// The operand of `END`, or 0 if there is none.
+    pub entry: u16,
+
+    /// The labels and their values.
+    pub symbols: SymbolTable,
+
+    /// The address, the word and the line, starting from 1, of every
+    /// assembled word, in source order.
+    pub listing: Vec<(u16, FullWord, usize)>,
+
+    /// The warnings, in source order.
+    pub warnings: Vec<AsmWarning>,
+}
+
+impl Assembly {
+    /// Get the runs of consecutive assembled addresses, in ascending
+    /// order, e.g. to write a segmented image.
+    pub fn segments(&self) -> Vec<Range<u16>> {
+        let mut addrs: Vec<u16> = self.listing.iter().map(|&(addr, _, _)| addr).collect();
+        addrs.sort_unstable();
+        addrs.dedup();
+        let mut segments: Vec<Range<u16>> = Vec::new();
+        for addr in addrs {
+            match segments.last_mut() {
+                Some(last) if last.end == addr => last.end += 1,
+                _ => segments.push(addr..addr + 1),
+            }
+        }
+        segments
+    }
+}
+
+/// A field of a MIXAL line and its first column, starting from 1.
+type Part<'a> = (&'a str, usize);
+
+/// The fields of a MIXAL line.
+struct Line<'a> {
+    label: Option<Part<'a>>,
+    op: Part<'a>,
+    operand: Part<'a>,
+
+    /// The text after the operation, for `ALF`.
+    rest: &'a str,
+}
+
+impl Line<'_> {
+    /// Create an error about a field of the line.
+    fn error(&self, line: usize, (text, column): Part, kind: AsmErrorKind) -> AsmError {
+        AsmError::new(line, column..column + text.len(), kind)
+    }
+}
+
+/// Split a line into its fields, separated by blanks.
+///
+/// A line starting with a blank has no label. Blank lines and
+/// lines starting with `*` are comments, yielding [`None`].
+fn split_line(text: &str) -> Option<Line<'_>> {
+    if text.trim().is_empty() || text.starts_with('*') {
+        return None;
+    }
+    let column = |part: &str| part.as_ptr() as usize - text.as_ptr() as usize + 1;
+    let mut parts = text.split_whitespace().map(|part| (part, column(part)));
+    let label = if text.starts_with(char::is_whitespace) {
+        None
+    } else {
+        parts.next()
+    };
+    let end = ("", text.len() + 1);
+    let op = parts.next().unwrap_or(end);
+    let operand = parts.next().unwrap_or(end);
+    let rest = &text[op.1 - 1 + op.0.len()..];
+    Some(Line {
+        label,
+        op,
+        operand,
+        rest,
+    })
+}
+
+/// Find the opcode of a mnemonic, and the `F` part it implies for
+/// opcodes with field modes.
+fn lookup_mnemonic(name: &str) -> Option<(Opcode, Option<u8>)> {
+    (0..=u8::MAX)
+        .filter_map(|code| Opcode::try_from(code).ok())
+        .find_map(|opcode| {
+            if opcode.has_field_modes() {
+                (0..=u8::MAX)
+                    .find(|&field| opcode.mnemonic(field) == Some(name))
+                    .map(|field| (opcode, Some(field)))
+            } else {
+                (opcode.mnemonic(0) == Some(name)).then_some((opcode, None))
+            }
+        })
+}
+
+/// Check that a value fits in a byte.
+fn to_byte(value: i64) -> Result<u8, AsmErrorKind> {
+    u8::try_from(value)
+        .ok()
+        .filter(|&byte| (byte as u32) < 1 << BYTE_BITS)
+        .ok_or(AsmErrorKind::OutOfRange(value.into()))
+}
+
+/// Assemble an instruction from its mnemonic and its operand
+/// `A,I(F)`, where each part is optional.
+fn assemble_instruction(
+    opcode: Opcode,
+    implied: Option<u8>,
+    operand: &str,
+    symbols: &SymbolTable,
+    location: u16,
+) -> Result<FullWord, AsmErrorKind> {
+    let (rest, field) = match operand.strip_suffix(')').and_then(|p| p.rsplit_once('(')) {
+        Some((rest, field)) => (rest, Some(evaluate_expr(field, symbols, location)?)),
+        None => (operand, None),
+    };
+    let (addr, index) = match rest.rsplit_once(',') {
+        Some((addr, index)) => (addr, evaluate_expr(index, symbols, location)?),
+        None => (rest, 0),
+    };
+    let addr = match addr {
+        "" => 0,
+        _ => evaluate_expr(addr, symbols, location)?,
+    };
+    let field = match field {
+        Some(field) => to_byte(field)?,
+        None => implied.unwrap_or(opcode.default_field()),
+    };
+    let addr = i16::try_from(addr)
+        .ok()
+        .filter(|addr| (addr.unsigned_abs() as u32) < 1 << (2 * BYTE_BITS))
+        .ok_or(AsmErrorKind::OutOfRange(addr.into()))?;
+    Ok(FullWord::from(Instruction::new(
+        addr,
+        field,
+        to_byte(index)?,
+        opcode,
+    )))
+}
+
+/// Encode the operand of `ALF`.
+///
+/// The operand is the 5 characters after the blanks following `ALF`,
+/// padded with blanks, or the characters between double quotes,
+/// which may start with blanks. Either is read as a quoted literal of
+/// [`Word::parse_literal()`].
+fn assemble_alf(rest: &str) -> Result<FullWord, AsmErrorKind> {
+    let text = rest.trim_start();
+    let text = match text.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+        None => text.get(..5).unwrap_or(text),
+    };
+    FullWord::parse_literal(&format!("\"{:<5}\"", text)).map_err(|err| match err {
+        WordParseError::BadChar(ch) => {
+            AsmErrorKind::ExprError(format!("`{}` is not in the MIX alphabet", ch))
+        }
+        _ => AsmErrorKind::ExprError(format!("`{}` is longer than 5 characters", text)),
+    })
+}
+
+/// Convert a value to an address or a symbol value.
+fn to_location(value: i64) -> Result<u16, AsmErrorKind> {
+    u16::try_from(value).map_err(|_| AsmErrorKind::OutOfRange(value.into()))
+}
+
+/// Assemble MIXAL source.
+///
+/// Each line has a label, an operation and an operand separated by
+/// blanks, followed by an optional comment. Lines without a label
+/// start with a blank, and lines starting with `*` are comments. The
+/// operations are the mnemonics of [`Opcode::mnemonic()`] with an
+/// operand `A,I(F)`, and the pseudo-operations:
+///
+/// ```text
+/// LABEL  EQU  1000        ; LABEL = 1000
+///        ORIG 3000        ; continue at 3000
+///        CON  1(1:1),2    ; a W-value, see evaluate_wvalue()
+///        ALF  HELLO       ; 5 characters
+///        END  START       ; the entry
+/// ```
+///
+/// Symbols may be used before they are defined, except in the
+/// operands of `EQU` and `ORIG`. Local symbols and literal
+/// constants are not supported. Lines longer than [`MAX_LINE_LEN`]
+/// are rejected before any of them is assembled. Parts of a `CON`
+/// overwriting earlier ones are reported in [`Assembly::warnings`].
+///
+/// # Arguments
+/// * `source` - The MIXAL source.
+///
+/// # Returns
+/// * [`Ok(Assembly)`] - The assembled program.
+/// * [`Err(AsmError)`] - The first error in the source.
+pub fn assemble(source: &str) -> Result<Assembly, AsmError> {
+    for (i, text) in source.lines().enumerate() {
+        let len = text.chars().count();
+        if len > MAX_LINE_LEN {
+            let kind = AsmErrorKind::LineTooLong(len);
+            return Err(AsmError::new(i + 1, MAX_LINE_LEN + 1..len + 1, kind));
+        }
+    }
+    let lines: Vec<_> = source
+        .lines()
+        .enumerate()
+        .filter_map(|(i, text)| split_line(text).map(|line| (i + 1, line)))
+        .collect();
+
+    // Pass 1: define the labels.
+    let mut symbols = SymbolTable::new();
+    let mut location = 0;
+    for (number, line) in &lines {
+        let value = match line.op.0 {
+            "EQU" => evaluate_expr(line.operand.0, &symbols, location)
+                .and_then(to_location)
+                .map_err(|kind| line.error(*number, line.operand, kind))?,
+            _ => location,
+        };
+        if let Some(label) = line.label {
+            if symbols.insert(label.0.to_string(), value).is_some() {
+                let kind = AsmErrorKind::DuplicateLabel(label.0.into());
+                return Err(line.error(*number, label, kind));
+            }
+        }
+        match line.op.0 {
+            "EQU" => {}
+            "ORIG" => {
+                location = evaluate_expr(line.operand.0, &symbols, location)
+                    .and_then(to_location)
+                    .map_err(|kind| line.error(*number, line.operand, kind))?
+            }
+            "END" => break,
+            _ => location = location.saturating_add(1),
+        }
+    }
+
+    // Pass 2: assemble the words.
+    let mut mem = Mem::new();
+    let mut listing = Vec::new();
+    let mut warnings = Vec::new();
+    let mut entry = 0;
+    location = 0;
+    for (number, line) in &lines {
+        let (text, column) = line.operand;
+        let word = match line.op.0 {
+            "EQU" => continue,
+            "ORIG" => {
+                location = evaluate_expr(text, &symbols, location)
+                    .and_then(to_location)
+                    .map_err(|kind| line.error(*number, line.operand, kind))?;
+                continue;
+            }
+            "END" => {
+                if !text.is_empty() {
+                    entry = evaluate_expr(text, &symbols, location)
+                        .and_then(to_location)
+                        .map_err(|kind| line.error(*number, line.operand, kind))?;
+                }
+                break;
+            }
+            "CON" => evaluate_wvalue(text, &symbols, location).map(|wvalue| {
+                let parts: Vec<&str> = text.split(',').collect();
+                for index in wvalue.overlaps {
+                    let part = parts[index];
+                    let start = column + parts[..index].iter().map(|p| p.len() + 1).sum::<usize>();
+                    warnings.push(AsmWarning {
+                        line: *number,
+                        columns: start..start + part.len(),
+                        kind: AsmWarningKind::OverlappingField(part.into()),
+                    });
+                }
+                wvalue.word
+            }),
+            "ALF" => assemble_alf(line.rest),
+            name => match lookup_mnemonic(name) {
+                Some((opcode, implied)) => {
+                    assemble_instruction(opcode, implied, text, &symbols, location)
+                }
+                None => {
+                    let kind = AsmErrorKind::UnknownMnemonic(name.into());
+                    return Err(line.error(*number, line.op, kind));
+                }
+            },
+        }
+        .map_err(|kind| line.error(*number, (text, column), kind))?;
+        if location as usize >= Mem::SIZE {
+            let kind = AsmErrorKind::OutOfRange(location.into());
+            return Err(line.error(*number, line.op, kind));
+        }
+        mem[location] = word;
+        listing.push((location, word, *number));
+        location += 1;
+    }
+    Ok(Assembly {
+        mem,
+        entry,
+        symbols,
+        listing,
+        warnings,
+    })
+}
+
+/// The number of instructions [`assemble_and_run()`] runs at most.
+pub const RUN_MAX_STEPS: u64 = 10_000_000;
+
+/// A buffer shared with a [`LinePrinterDevice`] put in a machine.
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Assemble MIXAL source and run it to its halt, with `input` in the
+/// card reader.
+///
+/// A [`TextCardReaderDevice`] holding a card for each line of
+/// `input` is put on [`CARD_READER_UNIT`] and a
+/// [`LinePrinterDevice`] on [`LINE_PRINTER_UNIT`]. The program runs
+/// from the operand of `END` for at most [`RUN_MAX_STEPS`]
+/// instructions, and out of cards is [`ErrorCode::EndOfInput`].
+///
+/// ```text
+/// let source = "\
+/// * Print a card.
+///          ORIG 1000
+/// START    IN   BUF(16)
+///          OUT  BUF(18)
+///          HLT
+/// BUF      ORIG *+24
+///          END  START";
+/// assert_eq!(asm::assemble_and_run(source, "hello mix")?, "HELLO MIX\n");
+/// ```
+///
+/// # Arguments
+/// * `source` - The MIXAL source.
+/// * `input` - The cards, one per line.
+///
+/// # Returns
+/// * [`Ok(String)`] - The lines printed, without trailing blanks.
+/// * [`Err(ErrorCode::Generic)`] - The source does not assemble, see [`assemble()`].
+/// * [`Err(ErrorCode::IOError)`] - A card has a character outside the alphabet.
+/// * [`Err(ErrorCode::StepLimitExceeded)`] - The program did not halt in time.
+/// * [`Err(ErrorCode)`] - The machine encountered another error.
+pub fn assemble_and_run(source: &str, input: &str) -> Result<String, ErrorCode> {
+    let assembly = assemble(source).map_err(|_| ErrorCode::Generic)?;
+    let output = SharedBuffer::default();
+
+    let mut mix = VM::new();
+    mix.report_end_of_input = true;
+    mix.attach_device(
+        CARD_READER_UNIT,
+        Box::new(TextCardReaderDevice::new(input, InputPolicy::Strict)),
+    );
+    mix.attach_device(
+        LINE_PRINTER_UNIT,
+        Box::new(LinePrinterDevice::new(output.clone())),
+    );
+    let program = MixProgram::new(0, assembly.entry, assembly.mem[0..Mem::SIZE].to_vec());
+    match mix.load_and_run_with_timeout(&program, RUN_MAX_STEPS) {
+        Ok(_) => {}
+        Err(RunError::ExecutionError(err)) => return Err(err),
+        Err(RunError::Timeout(_)) => return Err(ErrorCode::StepLimitExceeded),
+        Err(RunError::ProgramLoadError) => return Err(ErrorCode::InvalidAddress),
+    }
+    let bytes = output.0.borrow().clone();
+    // The printer only writes characters of the alphabet.
+    Ok(String::from_utf8(bytes).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{encode_words, InputPolicy};
+    use super::*;
+
+    const SOURCE: &str = "\
+* Add two numbers.
+TWO      EQU  2
+         ORIG 100
+START    LDA  X
+         ADD  X+1(0:5)
+         STA  SUM,TWO
+         JMP  DONE
+X        CON  40
+         CON  TWO
+SUM      CON  0
+TEXT     ALF  SUM:
+DONE     HLT
+         END  START
+";
+
+    fn word(instr: Instruction) -> [u8; 6] {
+        *FullWord::from(instr).as_array()
+    }
+
+    fn alf(text: &str) -> FullWord {
+        encode_words(text, InputPolicy::Strict).unwrap()[0]
+    }
+
+    fn error_of(source: &str) -> (usize, Range<usize>, AsmErrorKind) {
+        let err = assemble(source).unwrap_err();
+        (err.line, err.columns, err.kind)
+    }
+
+    #[test]
+    fn assembles_a_program() {
+        let asm = assemble(SOURCE).unwrap();
+        assert_eq!(asm.entry, 100);
+        assert_eq!(asm.symbols["TWO"], 2);
+        assert_eq!(asm.symbols["X"], 104);
+        assert_eq!(asm.symbols["DONE"], 108);
+        let listing: Vec<_> = asm
+            .listing
+            .iter()
+            .map(|&(addr, word, line)| (addr, *word.as_array(), line))
+            .collect();
+        let expected = [
+            (100, word(Instruction::new(104, 5, 0, Opcode::LdA)), 4),
+            (101, word(Instruction::new(105, 5, 0, Opcode::Add)), 5),
+            (102, word(Instruction::new(106, 5, 2, Opcode::StA)), 6),
+            (103, word(Instruction::new(108, 0, 0, Opcode::Jmp)), 7),
+            (104, *FullWord::from_i64(40).0.as_array(), 8),
+            (105, *FullWord::from_i64(2).0.as_array(), 9),
+            (106, *FullWord::new().as_array(), 10),
+            (107, *alf("SUM: ").as_array(), 11),
+            (108, word(Instruction::new(0, 2, 0, Opcode::Special)), 12),
+        ];
+        assert_eq!(listing, expected);
+        assert_eq!(asm.mem[99].as_array(), FullWord::new().as_array());
+    }
+
+    #[test]
+    fn fields_and_indices_are_optional() {
+        let operands = [
+            ("LDA  1000", Instruction::new(1000, 5, 0, Opcode::LdA)),
+            ("LDA  1000,1", Instruction::new(1000, 5, 1, Opcode::LdA)),
+            ("LDA  1000(1:3)", Instruction::new(1000, 11, 0, Opcode::LdA)),
+            (
+                "LDA  -1000,6(0:0)",
+                Instruction::new(-1000, 0, 6, Opcode::LdA),
+            ),
+            ("STJ  1000", Instruction::new(1000, 2, 0, Opcode::StJ)),
+            ("MOVE 1000(3)", Instruction::new(1000, 3, 0, Opcode::Move)),
+            ("OUT  1000(18)", Instruction::new(1000, 18, 0, Opcode::Out)),
+            ("ENT1 *", Instruction::new(0, 2, 0, Opcode::Modify1)),
+            ("JAZ  *+2", Instruction::new(2, 1, 0, Opcode::JA)),
+            ("SRAX 5", Instruction::new(5, 3, 0, Opcode::Shift)),
+            ("HLT", Instruction::new(0, 2, 0, Opcode::Special)),
+        ];
+        for (line, instr) in operands {
+            let asm = assemble(&format!(" {}", line)).unwrap();
+            assert_eq!(asm.mem[0].as_array(), &word(instr), "{}", line);
+        }
+    }
+
+    #[test]
+    fn errors_point_at_the_field() {
+        assert_eq!(
+            error_of(" LDA 0\n FOO 1"),
+            (2, 2..5, AsmErrorKind::UnknownMnemonic("FOO".into()))
+        );
+        assert_eq!(
+            error_of("A NOP\nA NOP"),
+            (2, 1..2, AsmErrorKind::DuplicateLabel("A".into()))
+        );
+        assert_eq!(
+            error_of(" JMP NOWHERE"),
+            (1, 6..13, AsmErrorKind::UndefinedSymbol("NOWHERE".into()))
+        );
+        assert_eq!(
+            error_of("A EQU B\nB EQU 1"),
+            (1, 7..8, AsmErrorKind::UndefinedSymbol("B".into()))
+        );
+        assert_eq!(
+            error_of(" LDA 40000"),
+            (1, 6..11, AsmErrorKind::OutOfRange(40000))
+        );
+        assert_eq!(
+            error_of(" LDA 0,256"),
+            (1, 6..11, AsmErrorKind::OutOfRange(256))
+        );
+        assert_eq!(
+            error_of(" ORIG 3999\n NOP\n NOP"),
+            (3, 2..5, AsmErrorKind::OutOfRange(4000))
+        );
+        assert!(matches!(
+            error_of(" ALF \"TOOLONG\"").2,
+            AsmErrorKind::ExprError(_)
+        ));
+    }
+
+    #[test]
+    fn alf_takes_five_characters() {
+        let asm = assemble(" ALF HELLO\n ALF  AB   COMMENT\n ALF \"  X\"").unwrap();
+        assert_eq!(asm.mem[0].as_array(), alf("HELLO").as_array());
+        assert_eq!(asm.mem[1].as_array(), alf("AB   ").as_array());
+        assert_eq!(asm.mem[2].as_array(), alf("  X  ").as_array());
+    }
+
+    #[test]
+    fn lines_after_end_are_ignored() {
+        let asm = assemble(" NOP\n END\n FOO").unwrap();
+        assert_eq!(asm.entry, 0);
+        assert_eq!(asm.listing.len(), 1);
+    }
+
+    /// Read a fixture from `tests/fixtures`.
+    fn fixture(name: &str) -> String {
+        let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn long_lines_are_rejected() {
+        let err = assemble(&fixture("long_line.mixal")).unwrap_err();
+        assert_eq!(
+            err,
+            AsmError::new(2, 257..301, AsmErrorKind::LineTooLong(300))
+        );
+        assert_eq!(
+            err.to_string(),
+            "2:257: line is 300 characters long, more than 256"
+        );
+
+        let longest = format!("*{}\n         HLT\n", "-".repeat(MAX_LINE_LEN - 1));
+        assert!(assemble(&longest).is_ok());
+    }
+
+    #[test]
+    fn deep_expressions_are_rejected() {
+        let err = assemble(&fixture("deep_expr.mixal")).unwrap_err();
+        assert_eq!((err.line, err.kind), (2, AsmErrorKind::ExprTooDeep));
+
+        let symbols = SymbolTable::new();
+        let deepest = vec!["1"; MAX_EXPR_DEPTH + 1].join("+");
+        assert_eq!(
+            evaluate_expr(&deepest, &symbols, 0),
+            Ok(MAX_EXPR_DEPTH as i64 + 1)
+        );
+        assert_eq!(
+            evaluate_expr(&format!("{}+1", deepest), &symbols, 0),
+            Err(AsmErrorKind::ExprTooDeep)
+        );
+    }
+
+    #[test]
+    fn huge_products_are_out_of_range() {
+        let max = (1i64 << (5 * BYTE_BITS)) - 1;
+        let symbols = SymbolTable::new();
+        let square = format!("-{}*{}", max, max);
+        assert_eq!(
+            evaluate_expr(&square, &symbols, 0),
+            Err(AsmErrorKind::OutOfRange(-(max as i128 * max as i128)))
+        );
+        assert_eq!(
+            evaluate_expr(&format!("{}+1", max), &symbols, 0),
+            Err(AsmErrorKind::OutOfRange(max as i128 + 1))
+        );
+        assert_eq!(evaluate_expr(&format!("-{}", max), &symbols, 0), Ok(-max));
+        assert_eq!(
+            error_of(&format!("  CON {}", square)),
+            (
+                1,
+                7..7 + square.len(),
+                AsmErrorKind::OutOfRange(-(max as i128 * max as i128))
+            )
+        );
+    }
+
+    /// Assemble a single `CON` and get its word and warnings.
+    fn con(wvalue: &str) -> ([u8; 6], Vec<(Range<usize>, AsmWarningKind)>) {
+        let asm = assemble(&format!(" CON  {}", wvalue)).unwrap();
+        let warnings = asm
+            .warnings
+            .into_iter()
+            .map(|warning| {
+                assert_eq!(warning.line, 1);
+                (warning.columns, warning.kind)
+            })
+            .collect();
+        (*asm.mem[0].as_array(), warnings)
+    }
+
+    #[test]
+    fn wvalue_parts_store_into_their_fields() {
+        const NEG: u8 = FullWord::NEG;
+        const POS: u8 = FullWord::POS;
+        let thousand = FullWord::from_i64(1000).0;
+        let five_hundred = FullWord::from_i64(500).0;
+        assert_eq!(
+            con("1000(1:3),500(4:5)"),
+            (
+                [
+                    POS,
+                    thousand[3],
+                    thousand[4],
+                    thousand[5],
+                    five_hundred[4],
+                    five_hundred[5]
+                ],
+                vec![]
+            )
+        );
+
+        // (0:0) sets only the sign.
+        assert_eq!(con("-1(0:0)"), ([NEG, 0, 0, 0, 0, 0], vec![]));
+        assert_eq!(
+            con("-1(0:0),7"),
+            (
+                [POS, 0, 0, 0, 0, 7],
+                vec![(15..16, AsmWarningKind::OverlappingField("7".into()))]
+            )
+        );
+        assert_eq!(con("7,-1(0:0)").0, [NEG, 0, 0, 0, 0, 7]);
+    }
+
+    #[test]
+    fn later_wvalue_parts_override_earlier_ones() {
+        let (word, warnings) = con("1(1:5),2(5:5),3(4:4)");
+        assert_eq!(word, [FullWord::POS, 0, 0, 0, 3, 2]);
+        assert_eq!(
+            warnings,
+            [
+                (14..20, AsmWarningKind::OverlappingField("2(5:5)".into())),
+                (21..27, AsmWarningKind::OverlappingField("3(4:4)".into())),
+            ]
+        );
+
+        // Disjoint parts do not warn, whatever their order.
+        assert_eq!(con("1(5:5),2(1:1)").1, []);
+    }
+
+    #[test]
+    fn overlap_warnings_are_displayed_with_their_position() {
+        let asm = assemble(" NOP\nX CON  1(1:2),2(2:3)").unwrap();
+        let messages: Vec<String> = asm.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            ["2:15: warning: `2(2:3)` overwrites an earlier part"]
+        );
+        assert!(assemble(SOURCE).unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn segments_are_runs_of_assembled_words() {
+        let segments = assemble(SOURCE).unwrap().segments();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0], 100..109);
+
+        let source = "\
+* Out of order.
+         ORIG 10
+         CON  1
+         CON  2
+         ORIG 5
+         CON  3
+         ORIG 11
+         CON  4
+";
+        assert_eq!(assemble(source).unwrap().segments(), [5..6, 10..12]);
+    }
+
+    #[test]
+    fn every_error_kind_has_a_position() {
+        let table = [
+            (
+                " CON  1(6)",
+                (1, 7..11, AsmErrorKind::BadField("1(6)".into())),
+            ),
+            (
+                " NOP\n CON  1(3:2)",
+                (2, 7..13, AsmErrorKind::BadField("1(3:2)".into())),
+            ),
+            (
+                " LDA  1/0",
+                (1, 7..10, AsmErrorKind::ExprError("division by zero".into())),
+            ),
+            (
+                " LDA  1++2",
+                (
+                    1,
+                    7..11,
+                    AsmErrorKind::ExprError("malformed expression `1++2`".into()),
+                ),
+            ),
+            (
+                " ALF  \"AB_DE\"",
+                (
+                    1,
+                    7..14,
+                    AsmErrorKind::ExprError("`_` is not in the MIX alphabet".into()),
+                ),
+            ),
+        ];
+        for (source, expected) in table {
+            assert_eq!(error_of(source), expected, "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn errors_are_displayed_with_their_position() {
+        let table = [
+            (" LDA 0\n FOO 1", "2:2: unknown mnemonic `FOO`"),
+            ("A NOP\nA NOP", "2:1: duplicate label `A`"),
+            (" JMP NOWHERE", "1:6: undefined symbol `NOWHERE`"),
+            (" LDA 40000", "1:6: value 40000 is out of range"),
+            (" CON  1(6)", "1:7: bad field `1(6)`"),
+            (" LDA  1/0", "1:7: bad expression: division by zero"),
+        ];
+        for (source, message) in table {
+            assert_eq!(assemble(source).unwrap_err().to_string(), message);
+        }
+    }
+
+    #[test]
+    fn programs_are_assembled_and_run() {
+        let source = "\
+* Print a card.
+         ORIG 1000
+START    IN   BUF(16)
+         OUT  BUF(18)
+         HLT
+BUF      ORIG *+24
+         END  START";
+        assert_eq!(
+            assemble_and_run(source, "hello mix"),
+            Ok("HELLO MIX\n".to_string())
+        );
+        assert_eq!(assemble_and_run(source, ""), Err(ErrorCode::EndOfInput));
+        assert_eq!(
+            assemble_and_run(source, "caf\u{e9}"),
+            Err(ErrorCode::IOError)
+        );
+        assert_eq!(
+            assemble_and_run(" LDA NOWHERE\n", ""),
+            Err(ErrorCode::Generic)
+        );
+        assert_eq!(
+            assemble_and_run("START JMP START\n END START\n", ""),
+            Err(ErrorCode::StepLimitExceeded)
+        );
+    }
+}
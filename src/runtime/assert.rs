@@ -0,0 +1,44 @@
+use super::*;
+
+impl VM {
+    /// Assert that the overflow toggle is off.
+    ///
+    /// # Panics
+    /// Panics if the overflow toggle is on.
+    #[track_caller]
+    pub fn assert_no_overflow(&self) {
+        assert!(
+            !self.overflow,
+            "Expected no overflow but overflow flag is set"
+        );
+    }
+
+    /// Assert that the overflow toggle is on.
+    ///
+    /// # Panics
+    /// Panics if the overflow toggle is off.
+    #[track_caller]
+    pub fn assert_overflow(&self) {
+        assert!(
+            self.overflow,
+            "Expected overflow but overflow flag is not set"
+        );
+    }
+
+    /// Assert that the comparison indicator has the expected value.
+    ///
+    /// # Arguments
+    /// * `expected` - The expected value.
+    ///
+    /// # Panics
+    /// Panics if the comparison indicator differs from `expected`.
+    #[track_caller]
+    pub fn assert_comparison_is(&self, expected: CompIndicator) {
+        assert!(
+            self.comp == expected,
+            "Expected comparison indicator {:?} but found {:?}",
+            expected,
+            self.comp
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use core::mem::size_of;
+use std::collections::BTreeMap;
+
+use super::*;
+
+/// The registers of a [`VMStateSnapshot`], and the counters restored
+/// with them.
+#[derive(Clone, Copy, Debug)]
+struct Registers {
+    set: RegisterSet,
+    halted: bool,
+    pc: u16,
+    step_count: u64,
+    faulted: bool,
+}
+
+impl Registers {
+    /// Take the registers of a machine.
+    fn of(vm: &VM) -> Self {
+        Registers {
+            set: vm.registers(),
+            halted: vm.halted,
+            pc: vm.pc,
+            step_count: vm.step_count,
+            faulted: vm.faulted,
+        }
+    }
+
+    /// Build a snapshot from the registers and a memory.
+    fn with_mem(self, mem: Mem) -> VMStateSnapshot {
+        VMStateSnapshot {
+            r_a: self.set.a,
+            r_x: self.set.x,
+            r_in: self.set.i,
+            r_j: self.set.j,
+            comp: self.set.comp,
+            overflow: self.set.overflow,
+            halted: self.halted,
+            pc: self.pc,
+            mem,
+        }
+    }
+}
+
+/// A checkpoint in a [`CheckpointStore`].
+#[derive(Clone, Debug)]
+struct Checkpoint {
+    /// The number of steps run when the checkpoint was taken.
+    steps: u64,
+
+    /// The registers.
+    regs: Registers,
+
+    /// The words changed since the previous checkpoint.
+    changes: Vec<(u16, FullWord)>,
+}
+
+/// A sequence of checkpoints of a [`MixVM`], with memory stored as
+/// differences.
+///
+/// The first checkpoint keeps the whole memory. Every later one keeps
+/// only the words changed since the checkpoint before it, so that a
+/// long run with frequent checkpoints costs little more than the
+/// words it writes. Only the blocks reported by
+/// [`Mem::written_since()`] are compared. Full snapshots are rebuilt
+/// on demand.
+///
+/// ```text
+/// let mut store = CheckpointStore::new();
+/// while !mix.halted {
+///     if mix.step_count() % 1000 == 0 {
+///         store.push(&mix);
+///     }
+///     mix.step()?;
+/// }
+/// store.rewind_to(&mut mix, 3);
+/// ```
+///
+/// [`MixVM`]: crate::MixVM
+#[derive(Clone, Debug, Default)]
+pub struct CheckpointStore {
+    /// The memory of the first checkpoint.
+    base: Option<Box<Mem>>,
+
+    /// The words of the last checkpoint that differ from `base`, to
+    /// compare against.
+    latest: BTreeMap<u16, FullWord>,
+
+    /// The [`Mem::generation()`] of the machine at the last checkpoint.
+    generation: u64,
+
+    /// The checkpoints, oldest first.
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl CheckpointStore {
+    /// Create a new store with no checkpoint.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of checkpoints.
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    /// Check if there is no checkpoint.
+    pub fn is_empty(&self) -> bool {
+        self.checkpoints.is_empty()
+    }
+
+    /// Take a checkpoint of a machine.
+    ///
+    /// # Returns
+    /// * [`usize`] - The index of the checkpoint.
+    pub fn push(&mut self, vm: &VM) -> usize {
+        let regs = Registers::of(vm);
+        let changes = match self.base.as_deref() {
+            Some(base) => {
+                // A memory replaced as a whole is compared in full.
+                let addrs: Vec<u16> = if vm.mem.generation() < self.generation {
+                    (0..Mem::SIZE as u16).collect()
+                } else {
+                    vm.mem
+                        .written_since(self.generation)
+                        .into_iter()
+                        .flatten()
+                        .collect()
+                };
+                let mut changes = Vec::new();
+                for addr in addrs {
+                    let last = self.latest.get(&addr).unwrap_or(&base[addr]);
+                    if last.as_array() != vm.mem[addr].as_array() {
+                        changes.push((addr, vm.mem[addr]));
+                        self.latest.insert(addr, vm.mem[addr]);
+                    }
+                }
+                changes
+            }
+            None => {
+                self.base = Some(Box::new(vm.mem.clone()));
+                Vec::new()
+            }
+        };
+        self.generation = vm.mem.generation();
+        self.checkpoints.push(Checkpoint {
+            steps: vm.step_count(),
+            regs,
+            changes,
+        });
+        self.checkpoints.len() - 1
+    }
+
+    /// Get the number of steps run when a checkpoint was taken.
+    pub fn steps_at(&self, index: usize) -> Option<u64> {
+        self.checkpoints.get(index).map(|c| c.steps)
+    }
+
+    /// Rebuild the full snapshot of a checkpoint.
+    ///
+    /// # Returns
+    /// * [`Some(VMStateSnapshot)`] - The snapshot.
+    /// * [`None`] - There is no checkpoint at `index`.
+    pub fn get(&self, index: usize) -> Option<VMStateSnapshot> {
+        let checkpoint = self.checkpoints.get(index)?;
+        let mut mem = self.base.as_deref()?.clone();
+        for c in &self.checkpoints[1..=index] {
+            for &(addr, word) in &c.changes {
+                mem[addr] = word;
+            }
+        }
+        Some(checkpoint.regs.with_mem(mem))
+    }
+
+    /// Restore a machine to a checkpoint.
+    ///
+    /// The step count and the error state are restored along with
+    /// the snapshot. Checkpoints after `index` are kept. IO devices
+    /// are not affected.
+    ///
+    /// # Returns
+    /// * `true` - The machine is restored.
+    /// * `false` - There is no checkpoint at `index`.
+    pub fn rewind_to(&self, vm: &mut VM, index: usize) -> bool {
+        match self.get(index) {
+            Some(snapshot) => {
+                let regs = self.checkpoints[index].regs;
+                vm.restore(&snapshot);
+                vm.step_count = regs.step_count;
+                vm.faulted = regs.faulted;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the approximate number of bytes used by the checkpoints.
+    pub fn bytes_used(&self) -> usize {
+        let base = self.base.as_ref().map_or(0, |_| size_of::<Mem>());
+        size_of::<Self>()
+            + base
+            + self.latest.len() * size_of::<(u16, FullWord)>()
+            + self
+                .checkpoints
+                .iter()
+                .map(|c| {
+                    size_of::<Checkpoint>() + c.changes.capacity() * size_of::<(u16, FullWord)>()
+                })
+                .sum::<usize>()
+    }
+}
+
+impl VM {
+    /// Restore the registers and the memory from a snapshot.
+    ///
+    /// The memory is overwritten with [`Mem::copy_from()`]. The step
+    /// count, the error state, IO devices and settings are not
+    /// affected, see [`CheckpointStore::rewind_to()`].
+    pub fn restore(&mut self, snapshot: &VMStateSnapshot) {
+        self.r_a = snapshot.r_a;
+        self.r_x = snapshot.r_x;
+        self.r_in = snapshot.r_in;
+        self.r_j = snapshot.r_j;
+        self.comp = snapshot.comp;
+        self.overflow = snapshot.overflow;
+        self.halted = snapshot.halted;
+        self.pc = snapshot.pc;
+        self.mem.copy_from(&snapshot.mem);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A loop storing `rI1` at `1000 + rI1` and adding 7 to `rI1`.
+    const FILL: [Instruction; 3] = [
+        Instruction::new(1000, 5, 1, Opcode::St1),
+        Instruction::new(7, 0, 0, Opcode::Modify1),
+        Instruction::new(0, 0, 0, Opcode::Jmp),
+    ];
+
+    fn fill_machine() -> VM {
+        let mut mix = VM::new();
+        mix.reset();
+        mix.load_program(&MixProgram::from(&FILL[..])).unwrap();
+        mix.halted = false;
+        mix
+    }
+
+    /// Hash a snapshot as [`VM::state_hash()`] does.
+    fn hash(snapshot: &VMStateSnapshot) -> u64 {
+        let mut mix = VM::new();
+        mix.restore(snapshot);
+        mix.state_hash()
+    }
+
+    #[test]
+    fn reconstruction_matches_full_snapshots() {
+        let mut mix = fill_machine();
+        let mut store = CheckpointStore::new();
+        let mut full = Vec::new();
+        for i in 0..300 {
+            if i % 25 == 0 {
+                store.push(&mix);
+                full.push(mix.snapshot());
+            }
+            mix.step().unwrap();
+        }
+        assert_eq!(store.len(), full.len());
+        for (i, snapshot) in full.iter().enumerate() {
+            assert_eq!(
+                hash(&store.get(i).unwrap()),
+                hash(snapshot),
+                "checkpoint {}",
+                i
+            );
+        }
+        assert!(store.get(full.len()).is_none());
+    }
+
+    #[test]
+    fn host_writes_between_checkpoints_are_kept() {
+        let mut mix = fill_machine();
+        let mut store = CheckpointStore::new();
+        store.push(&mix);
+        mix.mem.copy_range(0, 3990, 3).unwrap();
+        mix.mem.swap_words(0, 2000).unwrap();
+        mix.mem.as_byte_slice_mut()[6 * 3500 + 5] = 9;
+        store.push(&mix);
+        assert_eq!(hash(&store.get(1).unwrap()), hash(&mix.snapshot()));
+    }
+
+    #[test]
+    fn replaced_memory_is_compared_in_full() {
+        let mut mix = fill_machine();
+        let mut store = CheckpointStore::new();
+        for _ in 0..30 {
+            mix.step().unwrap();
+        }
+        store.push(&mix);
+        // An assignment resets the generation of the memory.
+        let mut other = Mem::new();
+        other[3999] = FullWord::from_i64(5).0;
+        mix.mem = other;
+        store.push(&mix);
+        assert_eq!(hash(&store.get(1).unwrap()), hash(&mix.snapshot()));
+    }
+
+    #[test]
+    fn unchanged_memory_costs_no_words() {
+        let mut mix = fill_machine();
+        let mut store = CheckpointStore::new();
+        store.push(&mix);
+        let used = store.bytes_used();
+        // Writing the value already there is no change.
+        mix.mem[100] = FullWord::new();
+        store.push(&mix);
+        assert!(store.checkpoints[1].changes.is_empty());
+        assert!(store.bytes_used() - used < size_of::<Mem>() / 100);
+    }
+
+    #[test]
+    fn rewind_restores_counters() {
+        let mut mix = fill_machine();
+        let mut store = CheckpointStore::new();
+        for _ in 0..10 {
+            mix.step().unwrap();
+        }
+        store.push(&mix);
+        mix.mem[1] = FullWord::from(Instruction::new(4000, 5, 0, Opcode::LdA));
+        assert_eq!(mix.step(), Err(ErrorCode::InvalidAddress));
+        assert!(mix.faulted);
+
+        assert!(store.rewind_to(&mut mix, 0));
+        assert_eq!(mix.step_count(), 10);
+        assert!(!mix.faulted);
+        assert!(!mix.halted);
+        assert_eq!(mix.pc, 1);
+        assert_eq!(mix.mem[1].as_array(), FullWord::from(FILL[1]).as_array());
+    }
+}
@@ -0,0 +1,683 @@
+//! Self-checks of the machine.
+//!
+//! Which `F` parts are legal for an opcode is written down in three
+//! places: the mnemonic table of [`Opcode::mnemonic()`], the static
+//! check of [`Instruction::validate()`], and the field matches in the
+//! handlers of [`VM`]. [`check_instruction_set()`] tries every opcode
+//! with every `F` part against all three and reports where they part.
+//!
+//! [`run_lockstep()`] compares two machines step by step, and
+//! [`check_primes_hash()`] compares a canonical run against its
+//! known [`VM::state_hash()`]. [`check_golden_cases()`] runs the
+//! cases of a golden file, such as the instruction examples of TAOCP
+//! in `tests/fixtures/golden.txt`, against their documented results.
+//!
+//! [`VM`]: crate::VM
+
+use std::fmt;
+use std::ops::Range;
+
+use super::*;
+
+/// A place where the legal `F` parts of an opcode are written down.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Layer {
+    /// The conversion between [`Instruction`] and [`FullWord`].
+    Encoding,
+
+    /// The mnemonic table of [`Opcode::mnemonic()`].
+    Metadata,
+
+    /// The static check of [`Instruction::validate()`].
+    Decoder,
+
+    /// The handlers of the [`VM`].
+    ///
+    /// [`VM`]: crate::VM
+    Handler,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Layer::Encoding => "encoding",
+            Layer::Metadata => "metadata",
+            Layer::Decoder => "decoder",
+            Layer::Handler => "handler",
+        };
+        f.write_str(name)
+    }
+}
+
+/// An instruction the layers disagree on.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Disagreement {
+    /// The opcode.
+    pub opcode: Opcode,
+
+    /// The `F` part.
+    pub field: u8,
+
+    /// The layer at odds with the others.
+    pub layer: Layer,
+
+    /// Whether the metadata declares the instruction legal, if it
+    /// describes the `F` part of the opcode at all.
+    pub declared: Option<bool>,
+
+    /// The result of [`Instruction::validate()`].
+    pub decoded: Result<(), ErrorCode>,
+
+    /// The result of running the instruction.
+    pub executed: Result<(), ErrorCode>,
+}
+
+impl fmt::Display for Disagreement {
+    /// Format the disagreement as `NAME(F): layer disagrees; details`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}({}): {} disagrees",
+            self.opcode, self.field, self.layer
+        )?;
+        if let Some(declared) = self.declared {
+            let verdict = if declared { "legal" } else { "illegal" };
+            write!(f, "; metadata: {}", verdict)?;
+        }
+        write!(
+            f,
+            "; decoder: {:?}; handler: {:?}",
+            self.decoded, self.executed
+        )
+    }
+}
+
+/// Check if an error rejects the instruction itself, rather than
+/// what it operates on.
+#[allow(deprecated)]
+fn is_rejection(err: &ErrorCode) -> bool {
+    matches!(
+        err,
+        ErrorCode::IllegalInstruction
+            | ErrorCode::InvalidField
+            | ErrorCode::InvalidFieldRange { .. }
+            | ErrorCode::InvalidFieldMode { .. }
+            | ErrorCode::InvalidIndex
+            | ErrorCode::UnknownDevice
+    )
+}
+
+/// Run an instruction on a fresh machine with a tape on every unit.
+fn execute(instr: Instruction) -> Result<(), ErrorCode> {
+    let mut mix = VM::new();
+    mix.reset();
+    for unit in 0..IO_UNITS {
+        mix.attach_device(unit, Box::new(TapeDevice::new()));
+    }
+    mix.memory_mut()[0] = FullWord::from(instr);
+    mix.halted = false;
+    mix.step()
+}
+
+/// Check every opcode with every `F` part against all layers.
+///
+/// Each instruction is encoded to a [`FullWord`] and decoded back,
+/// then checked by [`Instruction::validate()`] and run on a fresh
+/// machine. The metadata is consulted for opcodes whose `F` part
+/// selects an operation. Errors that concern the operands, such as
+/// [`ErrorCode::InvalidAddress`] or [`ErrorCode::IOError`], count as
+/// the instruction being accepted.
+///
+/// # Returns
+/// * [`Vec<Disagreement>`] - The disagreements, in opcode and field order.
+pub fn check_instruction_set() -> Vec<Disagreement> {
+    let mut found = Vec::new();
+    for code in 0..64 {
+        let Ok(opcode) = Opcode::try_from(code) else {
+            continue;
+        };
+        for field in 0..64 {
+            let instr = Instruction::new(100, field, 0, opcode);
+            let decoded = instr.validate();
+            let executed = execute(instr);
+            let declared = opcode
+                .has_field_modes()
+                .then(|| opcode.mnemonic(field).is_some() && !instr.is_disabled_extension());
+            let round_trip = Instruction::try_from(FullWord::from(instr)).is_ok_and(|back| {
+                back.opcode == opcode && back.field == field && back.index == 0 && back.addr == 100
+            });
+
+            let accepted = decoded.is_ok();
+            let ran = !executed.as_ref().is_err_and(is_rejection);
+            let layer = if !round_trip {
+                Layer::Encoding
+            } else if accepted == ran {
+                match declared {
+                    Some(declared) if declared != accepted => Layer::Metadata,
+                    _ => continue,
+                }
+            } else if declared == Some(ran) {
+                Layer::Decoder
+            } else {
+                Layer::Handler
+            };
+            found.push(Disagreement {
+                opcode,
+                field,
+                layer,
+                declared,
+                decoded,
+                executed,
+            });
+        }
+    }
+    found
+}
+
+/// Where two machines run in lockstep first differ.
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    /// The number of steps run when the states differed.
+    pub step: u64,
+
+    /// The registers of both machines.
+    pub registers: (RegisterSet, RegisterSet),
+
+    /// `pc` of both machines.
+    pub pc: (u16, u16),
+
+    /// The running state of both machines.
+    pub halted: (bool, bool),
+
+    /// The addresses whose words differ.
+    pub addresses: Vec<u16>,
+}
+
+/// Run two machines in lockstep, comparing their states after every
+/// step.
+///
+/// States are compared by [`VM::state_hash()`], and only diffed in
+/// full when the hashes differ. A step that fails halts its machine,
+/// which is then compared like any other state.
+///
+/// # Arguments
+/// * `left` - The first machine.
+/// * `right` - The second machine.
+/// * `max_steps` - The number of steps after which to stop.
+///
+/// # Returns
+/// * [`Ok(u64)`] - The number of steps run before both halted, or `max_steps`.
+/// * [`Err(Divergence)`] - The states differ.
+pub fn run_lockstep(left: &mut VM, right: &mut VM, max_steps: u64) -> Result<u64, Divergence> {
+    for step in 0..max_steps {
+        if left.halted && right.halted {
+            return Ok(step);
+        }
+        let _ = left.step();
+        let _ = right.step();
+        if left.state_hash() != right.state_hash() {
+            let addresses = (0..Mem::SIZE as u16)
+                .filter(|&addr| left.mem[addr].as_array() != right.mem[addr].as_array())
+                .collect();
+            return Err(Divergence {
+                step: step + 1,
+                registers: (left.registers(), right.registers()),
+                pc: (left.pc, right.pc),
+                halted: (left.halted, right.halted),
+                addresses,
+            });
+        }
+    }
+    Ok(max_steps)
+}
+
+/// The state hash of [`examples::primes()`] once it halts.
+#[cfg(not(feature = "byte-64"))]
+pub const PRIMES_STATE_HASH: u64 = 0x3a18_7a20_dafd_ff15;
+
+/// The state hash of [`examples::primes()`] once it halts.
+#[cfg(feature = "byte-64")]
+pub const PRIMES_STATE_HASH: u64 = 0x9b1b_0cc4_2f26_4ea9;
+
+/// A printer throwing its lines away.
+struct NullPrinter;
+
+impl IODevice for NullPrinter {
+    fn read(&mut self, _: &mut [FullWord]) -> Result<(), ()> {
+        Err(())
+    }
+
+    fn write(&mut self, _: &[FullWord]) -> Result<(), usize> {
+        Ok(())
+    }
+
+    fn control(&mut self, _: i16) -> Result<(), ()> {
+        Ok(())
+    }
+
+    fn is_busy(&self) -> Result<bool, ()> {
+        Ok(false)
+    }
+
+    fn is_ready(&self) -> Result<bool, ()> {
+        Ok(true)
+    }
+
+    fn get_block_size(&self) -> usize {
+        24
+    }
+
+    fn name(&self) -> &str {
+        "null printer"
+    }
+}
+
+/// Run [`examples::primes()`] and compare its final state hash with
+/// [`PRIMES_STATE_HASH`].
+///
+/// # Returns
+/// * [`Ok(())`] - The hashes are equal.
+/// * [`Err(u64)`] - The hash of the run, which differs.
+pub fn check_primes_hash() -> Result<(), u64> {
+    let mut mix = VM::new();
+    mix.attach_device(examples::PRINTER as usize, Box::new(NullPrinter));
+    let _ = mix.load_and_run_with_timeout(&examples::primes(), 10_000_000);
+    match mix.state_hash() {
+        PRIMES_STATE_HASH => Ok(()),
+        hash => Err(hash),
+    }
+}
+
+/// A part of the machine set or checked by a [`GoldenCase`].
+#[derive(Clone, Copy, Debug)]
+enum GoldenPart {
+    /// `rA=WORD`.
+    A(FullWord),
+
+    /// `rX=WORD`.
+    X(FullWord),
+
+    /// `rIn=VALUE`.
+    I(usize, i64),
+
+    /// `rJ=VALUE`.
+    J(i64),
+
+    /// `pc=ADDR`.
+    Pc(u16),
+
+    /// `ADDR=WORD`.
+    Cell(u16, FullWord),
+}
+
+impl GoldenPart {
+    /// Parse a part written as `KEY=VALUE`.
+    fn parse(text: &str) -> Result<Self, String> {
+        let (key, value) = text
+            .split_once('=')
+            .ok_or_else(|| format!("`{}` is not KEY=VALUE", text))?;
+        let word = || {
+            FullWord::parse_literal(value).map_err(|err| format!("bad word `{}`: {}", value, err))
+        };
+        let addr = |text: &str| {
+            text.parse::<u16>()
+                .ok()
+                .filter(|&addr| (addr as usize) < Mem::SIZE)
+                .ok_or_else(|| format!("bad address `{}`", text))
+        };
+        let half = |range: Range<i64>| {
+            value
+                .parse::<i64>()
+                .ok()
+                .filter(|value| range.contains(value))
+                .ok_or_else(|| format!("bad value `{}` of {}", value, key))
+        };
+        let max = 1 << (2 * BYTE_BITS);
+        match key {
+            "rA" => Ok(GoldenPart::A(word()?)),
+            "rX" => Ok(GoldenPart::X(word()?)),
+            "rJ" => Ok(GoldenPart::J(half(0..max)?)),
+            "pc" => Ok(GoldenPart::Pc(addr(value)?)),
+            _ => match key.strip_prefix("rI").and_then(|n| n.parse::<usize>().ok()) {
+                Some(n @ 1..=6) => Ok(GoldenPart::I(n, half(1 - max..max)?)),
+                Some(_) => Err(format!("no register `{}`", key)),
+                None => Ok(GoldenPart::Cell(addr(key)?, word()?)),
+            },
+        }
+    }
+
+    /// Set the part of a machine.
+    fn apply(self, mix: &mut VM) -> Result<(), ErrorCode> {
+        match self {
+            GoldenPart::A(word) => mix.r_a = word,
+            GoldenPart::X(word) => mix.r_x = word,
+            GoldenPart::I(n, value) => {
+                mix.set_index(n, value)?;
+            }
+            GoldenPart::J(value) => {
+                mix.set_j(value);
+            }
+            GoldenPart::Pc(pc) => mix.pc = pc,
+            GoldenPart::Cell(addr, word) => mix.poke(addr, word)?,
+        }
+        Ok(())
+    }
+}
+
+/// An instruction with the state before it runs and the state it
+/// leaves, read by [`parse_golden_cases()`].
+#[derive(Clone)]
+pub struct GoldenCase {
+    /// The line of the case, starting from 1.
+    pub line: usize,
+
+    /// The instruction as written.
+    pub text: String,
+
+    /// The instruction.
+    pub instr: Instruction,
+
+    /// The parts set before the instruction.
+    before: Vec<GoldenPart>,
+
+    /// The parts changed by the instruction.
+    after: Vec<GoldenPart>,
+}
+
+/// A line of a golden file that cannot be read.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GoldenParseError {
+    /// The line, starting from 1.
+    pub line: usize,
+
+    /// What is wrong.
+    pub message: String,
+}
+
+impl fmt::Display for GoldenParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Read the cases of a golden file.
+///
+/// Each line is a case `INSTRUCTION | BEFORE | AFTER`, where the
+/// instruction is MIXAL and each state is a blank-separated list of
+/// `KEY=VALUE` parts:
+///
+/// ```text
+/// # Comments and blank lines are skipped.
+/// LDA 2000(1:5)   | 2000=-1:16:3:5:4 | rA=+1:16:3:5:4
+/// JMP 100         | | pc=100 rJ=1
+/// ```
+///
+/// `rA`, `rX` and memory addresses take word literals, see
+/// [`Word::parse_literal()`], and `rI1` to `rI6`, `rJ` and `pc`
+/// take numbers.
+///
+/// # Returns
+/// * [`Ok(Vec<GoldenCase>)`] - The cases, in order.
+/// * [`Err(GoldenParseError)`] - The first malformed line.
+pub fn parse_golden_cases(text: &str) -> Result<Vec<GoldenCase>, GoldenParseError> {
+    let mut cases = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: String| GoldenParseError {
+            line: i + 1,
+            message,
+        };
+        let columns: Vec<&str> = line.split('|').map(str::trim).collect();
+        let [text, before, after] = columns[..] else {
+            return Err(error("expected INSTRUCTION | BEFORE | AFTER".into()));
+        };
+        let parts = |state: &str| -> Result<Vec<GoldenPart>, GoldenParseError> {
+            state
+                .split_whitespace()
+                .map(GoldenPart::parse)
+                .collect::<Result<_, _>>()
+                .map_err(error)
+        };
+        let word = asm::assemble(&format!(" {}", text))
+            .map_err(|err| error(err.kind.to_string()))?
+            .mem[0];
+        cases.push(GoldenCase {
+            line: i + 1,
+            text: text.to_string(),
+            instr: Instruction::try_from(word)
+                .map_err(|_| error(format!("`{}` is not an instruction", text)))?,
+            before: parts(before)?,
+            after: parts(after)?,
+        });
+    }
+    Ok(cases)
+}
+
+/// A golden case the machine does not reproduce.
+#[derive(Clone, Debug)]
+pub struct GoldenMismatch {
+    /// The line of the case.
+    pub line: usize,
+
+    /// The instruction as written.
+    pub text: String,
+
+    /// The result of running the instruction.
+    pub executed: Result<(), ErrorCode>,
+
+    /// The expected registers and `pc`.
+    pub expected: (RegisterSet, u16),
+
+    /// The registers and `pc` the instruction left.
+    pub actual: (RegisterSet, u16),
+
+    /// The addresses whose words differ.
+    pub addresses: Vec<u16>,
+}
+
+impl fmt::Display for GoldenMismatch {
+    /// Format the mismatch as `LINE: INSTRUCTION: result; ...`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: {:?}; expected {:?}; actual {:?}; words differ at {:?}",
+            self.line, self.text, self.executed, self.expected, self.actual, self.addresses
+        )
+    }
+}
+
+/// Set up a fresh machine with the parts of a state.
+fn golden_machine(parts: &[&[GoldenPart]]) -> Result<VM, ErrorCode> {
+    let mut mix = VM::new();
+    mix.reset();
+    for part in parts.iter().copied().flatten() {
+        part.apply(&mut mix)?;
+    }
+    mix.halted = false;
+    Ok(mix)
+}
+
+/// Run golden cases and compare the states they leave.
+///
+/// Each case [`VM::execute()`]s its instruction at address 0 of a
+/// fresh machine set up with its state before. The result is compared
+/// by [`VM::state_eq()`] with a fresh machine set up with both its
+/// states, at `pc` 1 unless the case says otherwise.
+///
+/// # Returns
+/// * [`Vec<GoldenMismatch>`] - The cases that fail or leave another state, in order.
+pub fn check_golden_cases(cases: &[GoldenCase]) -> Vec<GoldenMismatch> {
+    cases
+        .iter()
+        .filter_map(|case| {
+            let next = [GoldenPart::Pc(1)];
+            let expected = golden_machine(&[&case.before, &next, &case.after]);
+            let (executed, actual) = match golden_machine(&[&case.before]) {
+                Ok(mut mix) => (mix.execute(case.instr), Ok(mix)),
+                Err(err) => (Err(err), Err(err)),
+            };
+            match (&expected, &actual) {
+                (Ok(expected), Ok(actual)) if executed.is_ok() && actual.state_eq(expected) => {
+                    return None
+                }
+                _ => {}
+            }
+            let view = |mix: &Result<VM, ErrorCode>| match mix {
+                Ok(mix) => (mix.registers(), mix.pc),
+                Err(_) => (VM::new().registers(), 0),
+            };
+            let addresses = match (&expected, &actual) {
+                (Ok(expected), Ok(actual)) => (0..Mem::SIZE as u16)
+                    .filter(|&addr| expected.mem[addr].as_array() != actual.mem[addr].as_array())
+                    .collect(),
+                _ => Vec::new(),
+            };
+            Some(GoldenMismatch {
+                line: case.line,
+                text: case.text.clone(),
+                executed,
+                expected: view(&expected),
+                actual: view(&actual),
+                addresses,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOLDEN: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/golden.txt"
+    ));
+
+    #[test]
+    fn layers_agree_on_the_instruction_set() {
+        let found: Vec<String> = check_instruction_set()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert!(found.is_empty(), "{:#?}", found);
+    }
+
+    #[test]
+    fn declared_fields_run_and_others_are_rejected() {
+        let mut declared = 0;
+        let mut undeclared = 0;
+        for opcode in (0..64).filter_map(|code| Opcode::try_from(code).ok()) {
+            if !opcode.has_field_modes() {
+                continue;
+            }
+            for field in 0..64 {
+                let instr = Instruction::new(100, field, 0, opcode);
+                let executed = execute(instr);
+                if opcode.mnemonic(field).is_some() && !instr.is_disabled_extension() {
+                    declared += 1;
+                    assert_eq!(instr.validate(), Ok(()), "{:?}({})", opcode, field);
+                    assert!(
+                        !executed.as_ref().is_err_and(is_rejection),
+                        "{:?}({}): {:?}",
+                        opcode,
+                        field,
+                        executed
+                    );
+                } else {
+                    undeclared += 1;
+                    assert!(instr.validate().is_err(), "{:?}({})", opcode, field);
+                    assert!(
+                        executed.as_ref().is_err_and(is_rejection),
+                        "{:?}({}): {:?}",
+                        opcode,
+                        field,
+                        executed
+                    );
+                }
+            }
+        }
+        assert!(declared > 40, "{} declared", declared);
+        assert!(undeclared > 0);
+    }
+
+    #[test]
+    fn disagreements_name_the_layer() {
+        let disagreement = Disagreement {
+            opcode: Opcode::Shift,
+            field: 12,
+            layer: Layer::Handler,
+            declared: Some(false),
+            decoded: Err(ErrorCode::InvalidFieldMode {
+                opcode: Opcode::Shift,
+                field: 12,
+            }),
+            executed: Ok(()),
+        };
+        assert_eq!(
+            disagreement.to_string(),
+            "SLA(12): handler disagrees; metadata: illegal; \
+             decoder: Err(InvalidFieldMode { opcode: Shift, field: 12 }); handler: Ok(())"
+        );
+
+        let disagreement = Disagreement {
+            opcode: Opcode::LdA,
+            field: 6,
+            layer: Layer::Decoder,
+            declared: None,
+            decoded: Ok(()),
+            executed: Err(ErrorCode::IllegalInstruction),
+        };
+        assert_eq!(
+            disagreement.to_string(),
+            "LDA(6): decoder disagrees; decoder: Ok(()); handler: Err(IllegalInstruction)"
+        );
+    }
+
+    #[test]
+    fn golden_cases_are_reproduced() {
+        let cases = parse_golden_cases(GOLDEN).unwrap();
+        assert!(cases.len() >= 40, "{} cases", cases.len());
+        let mismatches: Vec<String> = check_golden_cases(&cases)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert!(mismatches.is_empty(), "{:#?}", mismatches);
+    }
+
+    #[test]
+    fn wrong_expectations_are_reported() {
+        let cases = parse_golden_cases(
+            "ENTA 5 | | rA=+0:0:0:0:6\nSTA 2000 | rA=+0:0:0:0:1 | 2001=+0:0:0:0:1",
+        )
+        .unwrap();
+        let mismatches = check_golden_cases(&cases);
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(mismatches[0].line, 1);
+        assert_eq!(mismatches[0].executed, Ok(()));
+        assert!(mismatches[0].addresses.is_empty());
+        assert_eq!(mismatches[1].addresses, [2000, 2001]);
+    }
+
+    #[test]
+    fn malformed_cases_are_rejected() {
+        let errors = [
+            ("LDA 2000 | rA=+0", "expected INSTRUCTION | BEFORE | AFTER"),
+            ("FOO 2000 | |", "unknown mnemonic `FOO`"),
+            ("LDA 2000 | rA=12 |", "bad word `12`"),
+            ("LDA 2000 | rI7=1 |", "no register `rI7`"),
+            ("LDA 2000 | | 4000=+0:0:0:0:0", "bad address `4000`"),
+            ("LDA 2000 | rA |", "`rA` is not KEY=VALUE"),
+        ];
+        for (line, message) in errors {
+            let text = format!("# A comment.\n\n{}", line);
+            let Err(err) = parse_golden_cases(&text) else {
+                panic!("`{}` is accepted", line);
+            };
+            assert_eq!(err.line, 3, "{}", line);
+            assert!(err.message.starts_with(message), "{}: {}", line, err);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::*;
+
+/// The unit number of the console switches.
+///
+/// It is the first unit after the standard ones, so the paper tape
+/// on unit 20 is left alone. See [`Console::set_switches()`].
+pub const CONSOLE_SWITCH_UNIT: usize = 21;
+
+/// Reasons for [`VM::run()`] to return.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StepEvent {
+    /// The machine halted.
+    Halted,
+
+    /// The operator pressed STOP.
+    ///
+    /// The machine is still running and will continue from the
+    /// next instruction once [`VM::run()`] is called again.
+    OperatorStop,
+
+    /// The machine reached a breakpoint at the given address.
+    ///
+    /// The instruction there is not run yet. It runs first once
+    /// [`VM::run()`] is called again.
+    Breakpoint(u16),
+}
+
+/// Errors returned by symbolic breakpoint operations.
+///
+/// See [`VM::add_breakpoint_on_symbol()`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum BreakpointError {
+    /// The symbol is not in the table from [`VM::load_symbols()`].
+    UnknownSymbol(String),
+}
+
+/// A handle to the STOP switch of a [`MixVM`], which may be sent
+/// to other threads.
+///
+/// See [`VM::stop_handle()`].
+///
+/// [`MixVM`]: crate::MixVM
+#[derive(Clone, Debug, Default)]
+pub struct StopHandle {
+    /// Whether STOP is pressed.
+    flag: Arc<AtomicBool>,
+}
+
+impl StopHandle {
+    /// Press STOP.
+    ///
+    /// The machine stops at the next instruction boundary.
+    pub fn stop(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    /// Release STOP so that the machine may run again.
+    pub fn go(&self) {
+        self.flag.store(false, Ordering::SeqCst);
+    }
+
+    /// Check if STOP is pressed.
+    pub fn is_stopped(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+}
+
+/// The operator console of a [`MixVM`].
+///
+/// See [`VM::console()`].
+///
+/// [`MixVM`]: crate::MixVM
+pub struct Console<'a> {
+    /// The machine.
+    vm: &'a mut VM,
+}
+
+impl Console<'_> {
+    /// Deposit a word into memory.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The word is deposited.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - `addr` is out of memory.
+    pub fn deposit(&mut self, addr: u16, word: FullWord) -> Result<(), ErrorCode> {
+        if addr as usize >= Mem::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        self.vm.mem[addr] = word;
+        Ok(())
+    }
+
+    /// Set the console switches.
+    ///
+    /// Programs read the switches with a one-word `IN` from
+    /// [`CONSOLE_SWITCH_UNIT`], where a [`SwitchDevice`] is plugged,
+    /// replacing any device on that unit.
+    pub fn set_switches(&mut self, switches: FullWord) {
+        self.vm.attach_device(
+            CONSOLE_SWITCH_UNIT,
+            std::boxed::Box::new(SwitchDevice { switches }),
+        );
+    }
+
+    /// Get a handle to the STOP switch.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.vm.stop_handle()
+    }
+
+    /// Press STOP.
+    pub fn stop(&mut self) {
+        self.vm.stop_handle().stop();
+    }
+
+    /// Release STOP and press GO, running the machine until it
+    /// halts or STOP is pressed again.
+    ///
+    /// See [`VM::run()`].
+    pub fn go(&mut self) -> Result<StepEvent, ErrorCode> {
+        self.vm.stop_handle().go();
+        self.vm.restart();
+        self.vm.run()
+    }
+}
+
+impl VM {
+    /// Access the operator console.
+    pub fn console(&mut self) -> Console<'_> {
+        Console { vm: self }
+    }
+}
+
+/// A device reading the console switches.
+///
+/// Each `IN` yields a block of one word with the switches.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SwitchDevice {
+    /// The switch word.
+    pub switches: FullWord,
+}
+
+impl IODevice for SwitchDevice {
+    fn read(&mut self, buffer: &mut [FullWord]) -> Result<(), ()> {
+        if buffer.len() != self.get_block_size() {
+            return Err(());
+        }
+        buffer[0] = self.switches;
+        Ok(())
+    }
+
+    fn write(&mut self, _: &[FullWord]) -> Result<(), usize> {
+        Err(0)
+    }
+
+    fn control(&mut self, _: i16) -> Result<(), ()> {
+        Err(())
+    }
+
+    fn is_busy(&self) -> Result<bool, ()> {
+        Ok(false)
+    }
+
+    fn is_ready(&self) -> Result<bool, ()> {
+        Ok(true)
+    }
+
+    fn get_block_size(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> &str {
+        "console switches"
+    }
+}
+
+impl VM {
+    /// Set a breakpoint, stopping [`VM::run()`] before the
+    /// instruction at `addr`.
+    ///
+    /// # Returns
+    /// * `true` - The breakpoint is new.
+    /// * `false` - The breakpoint is already set.
+    pub fn add_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.insert(addr)
+    }
+
+    /// Clear a breakpoint.
+    ///
+    /// # Returns
+    /// * `true` - The breakpoint is cleared.
+    /// * `false` - No breakpoint is set at `addr`.
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    /// Iterate over the breakpoints in ascending order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Set a breakpoint at the address of a symbol.
+    ///
+    /// ```text
+    /// vm.load_symbols(layout.symbols());
+    /// vm.add_breakpoint_on_symbol("xtea")?;
+    /// ```
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The breakpoint is set.
+    /// * [`Err(BreakpointError::UnknownSymbol)`] - The symbol is not loaded.
+    pub fn add_breakpoint_on_symbol(&mut self, symbol: &str) -> Result<(), BreakpointError> {
+        let addr = self.helper_resolve_symbol(symbol)?;
+        self.add_breakpoint(addr);
+        Ok(())
+    }
+
+    /// Clear the breakpoint at the address of a symbol.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - No breakpoint is left at the address.
+    /// * [`Err(BreakpointError::UnknownSymbol)`] - The symbol is not loaded.
+    pub fn remove_breakpoint_on_symbol(&mut self, symbol: &str) -> Result<(), BreakpointError> {
+        let addr = self.helper_resolve_symbol(symbol)?;
+        self.remove_breakpoint(addr);
+        Ok(())
+    }
+
+    /// Get the address of a symbol for a breakpoint.
+    fn helper_resolve_symbol(&self, symbol: &str) -> Result<u16, BreakpointError> {
+        self.address_of(symbol)
+            .ok_or_else(|| BreakpointError::UnknownSymbol(symbol.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+
+    /// `IN 100(21)`, then `rX` is 2 if the switch word is positive
+    /// and 1 otherwise.
+    fn switch_program() -> VM {
+        let mut mix = VM::new();
+        let program = [
+            Instruction::new(100, CONSOLE_SWITCH_UNIT as u8, 0, Opcode::In),
+            Instruction::new(100, 5, 0, Opcode::LdA),
+            Instruction::new(5, 2, 0, Opcode::JA),
+            Instruction::new(1, 2, 0, Opcode::ModifyX),
+            Instruction::new(0, 2, 0, Opcode::Special),
+            Instruction::new(2, 2, 0, Opcode::ModifyX),
+            Instruction::new(0, 2, 0, Opcode::Special),
+        ];
+        for (addr, instr) in program.into_iter().enumerate() {
+            mix.mem[addr as u16] = FullWord::from(instr);
+        }
+        mix.reset();
+        mix
+    }
+
+    #[test]
+    fn program_branches_on_the_switch_word() {
+        for (switches, x) in [(5, 2), (-5, 1)] {
+            let mut mix = switch_program();
+            mix.console().set_switches(FullWord::from_i64(switches).0);
+            assert_eq!(mix.console().go(), Ok(StepEvent::Halted));
+            assert_eq!(mix.r_a.to_i64().0, switches);
+            assert_eq!(mix.r_x.to_i64().0, x);
+        }
+    }
+
+    #[test]
+    fn switches_leave_the_paper_tape_alone() {
+        let mut mix = VM::new();
+        mix.attach_device(20, std::boxed::Box::new(TapeDevice::new()));
+        mix.console().set_switches(FullWord::from_i64(7).0);
+        assert_eq!(mix.attached_device_units(), [20, CONSOLE_SWITCH_UNIT]);
+        assert_eq!(mix.device(20).unwrap().name(), "tape");
+        assert_eq!(
+            mix.device(CONSOLE_SWITCH_UNIT).unwrap().name(),
+            "console switches"
+        );
+    }
+
+    #[test]
+    fn stop_and_go_keep_the_state() {
+        // INCA 1; JMP 0
+        let mut mix = VM::new();
+        mix.mem[0] = FullWord::from(Instruction::new(1, 0, 0, Opcode::ModifyA));
+        mix.mem[1] = FullWord::from(Instruction::new(0, 0, 0, Opcode::Jmp));
+        mix.reset();
+
+        let mut counts = Vec::new();
+        for _ in 0..2 {
+            let handle = mix.console().stop_handle();
+            let stopper = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                handle.stop();
+            });
+            assert_eq!(mix.console().go(), Ok(StepEvent::OperatorStop));
+            stopper.join().unwrap();
+            assert!(!mix.halted);
+            assert!(mix.pc <= 1);
+            counts.push(mix.r_a.to_i64().0);
+        }
+        // The second run goes on from where the first one stopped.
+        assert!(0 < counts[0] && counts[0] < counts[1]);
+        assert!(mix.console().stop_handle().is_stopped());
+    }
+
+    #[test]
+    fn switch_unit_is_the_last_unit() {
+        let unit = CONSOLE_SWITCH_UNIT as u8;
+        assert_eq!(CONSOLE_SWITCH_UNIT, IO_UNITS - 1);
+        assert_eq!(Instruction::new(0, unit, 0, Opcode::In).validate(), Ok(()));
+        assert_eq!(
+            Instruction::new(0, unit + 1, 0, Opcode::In).validate(),
+            Err(ErrorCode::UnknownDevice)
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use super::*;
+
+/// A set of memory addresses executed by a [`MixVM`].
+///
+/// See [`VM::enable_coverage_tracking()`].
+///
+/// [`MixVM`]: crate::MixVM
+#[derive(Clone, Debug)]
+pub struct CoverageMap {
+    /// One bit for each address, LSB first.
+    bits: [u64; Self::CHUNKS],
+}
+
+impl CoverageMap {
+    /// Number of `u64` chunks needed to cover the memory.
+    const CHUNKS: usize = Mem::SIZE.div_ceil(64);
+
+    /// Create a new map with no address executed.
+    pub const fn new() -> Self {
+        CoverageMap {
+            bits: [0; Self::CHUNKS],
+        }
+    }
+
+    /// Mark an address as executed.
+    ///
+    /// Addresses out of memory are ignored.
+    pub fn set(&mut self, addr: u16) {
+        let addr = addr as usize;
+        if addr < Mem::SIZE {
+            self.bits[addr / 64] |= 1 << (addr % 64);
+        }
+    }
+
+    /// Check if an address is executed.
+    pub fn get(&self, addr: u16) -> bool {
+        let addr = addr as usize;
+        addr < Mem::SIZE && self.bits[addr / 64] & (1 << (addr % 64)) != 0
+    }
+
+    /// Get the number of executed addresses.
+    pub fn count(&self) -> usize {
+        self.bits.iter().map(|c| c.count_ones() as usize).sum()
+    }
+
+    /// Iterate over the executed addresses in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..Mem::SIZE as u16).filter(|&addr| self.get(addr))
+    }
+
+    /// Clear all addresses.
+    pub fn clear(&mut self) {
+        self.bits = [0; Self::CHUNKS];
+    }
+}
+
+impl Default for CoverageMap {
+    /// Create a new map with no address executed.
+    ///
+    /// Equivalent to [`CoverageMap::new()`].
+    fn default() -> Self {
+        Self::new()
+    }
+}
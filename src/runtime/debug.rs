@@ -0,0 +1,91 @@
+use super::*;
+
+impl VM {
+    /// Replace the instruction at an address.
+    ///
+    /// # Arguments
+    /// * `addr` - The address to patch.
+    /// * `new_instr` - The instruction to write.
+    ///
+    /// # Returns
+    /// * [`Ok(Some(Instruction))`] - The instruction previously at `addr`.
+    /// * [`Ok(None)`] - The word previously at `addr` is not an instruction.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - `addr` is out of memory.
+    pub fn apply_patch(
+        &mut self,
+        addr: u16,
+        new_instr: Instruction,
+    ) -> Result<Option<Instruction>, ErrorCode> {
+        if addr as usize >= Mem::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        let old = Instruction::try_from(self.mem[addr]).ok();
+        self.mem[addr] = FullWord::from(new_instr);
+        Ok(old)
+    }
+
+    /// Restore a word replaced by [`VM::apply_patch()`].
+    ///
+    /// # Arguments
+    /// * `addr` - The address to restore.
+    /// * `original` - The word previously at `addr`.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The word is restored.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - `addr` is out of memory.
+    pub fn unapply_patch(&mut self, addr: u16, original: FullWord) -> Result<(), ErrorCode> {
+        if addr as usize >= Mem::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        self.mem[addr] = original;
+        Ok(())
+    }
+
+    /// Read a word of the memory.
+    ///
+    /// Memory-mapped devices are not loaded from, as in
+    /// [`VM::current_instruction()`].
+    ///
+    /// # Returns
+    /// * [`Ok(FullWord)`] - The word.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - `addr` is out of memory.
+    pub fn peek(&self, addr: u16) -> Result<FullWord, ErrorCode> {
+        if addr as usize >= Mem::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        Ok(self.mem[addr])
+    }
+
+    /// Write a word of the memory.
+    ///
+    /// Memory-mapped devices are not stored to, and protected ranges
+    /// may be written.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The word is written.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - `addr` is out of memory.
+    pub fn poke(&mut self, addr: u16, word: FullWord) -> Result<(), ErrorCode> {
+        if addr as usize >= Mem::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        self.mem[addr] = word;
+        Ok(())
+    }
+
+    /// Decode the instruction at `pc` without running it.
+    ///
+    /// This is the fetch done by [`VM::step()`], except that
+    /// memory-mapped devices are not loaded from, as loads may have
+    /// side effects. Only the memory is read.
+    ///
+    /// # Returns
+    /// * [`Ok(Instruction)`] - The instruction to run next.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - `pc` is out of memory.
+    /// * [`Err(ErrorCode::IllegalInstruction)`] - The word at `pc` is not an instruction.
+    pub fn current_instruction(&self) -> Result<Instruction, ErrorCode> {
+        if self.pc as usize >= Mem::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        Instruction::try_from(self.mem[self.pc]).map_err(|_| ErrorCode::IllegalInstruction)
+    }
+}
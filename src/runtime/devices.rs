@@ -0,0 +1,193 @@
+//! Text-backed devices on the standard units.
+
+use std::io::Write;
+
+use super::{decode_block, encode_words, FullWord, IODevice, InputPolicy};
+
+/// Unit number of the card reader.
+pub const CARD_READER_UNIT: usize = 16;
+
+/// Unit number of the line printer.
+pub const LINE_PRINTER_UNIT: usize = 18;
+
+/// A card reader yielding 16-word cards, one per line of text.
+///
+/// Lines are upper-cased and encoded by [`encode_words()`], then
+/// padded with `+0` or cut to a card.
+pub struct TextCardReaderDevice {
+    lines: std::vec::IntoIter<String>,
+    policy: InputPolicy,
+}
+
+impl TextCardReaderDevice {
+    /// Create a card reader holding a card for each line of `text`.
+    ///
+    /// # Arguments
+    /// * `text` - The cards.
+    /// * `policy` - How to encode characters outside the alphabet.
+    pub fn new(text: &str, policy: InputPolicy) -> Self {
+        let lines: Vec<String> = text.lines().map(String::from).collect();
+        Self {
+            lines: lines.into_iter(),
+            policy,
+        }
+    }
+
+    /// Punch a line of text into a card.
+    fn punch(&self, line: &str, buffer: &mut [FullWord]) -> Result<(), ()> {
+        let words = encode_words(&line.to_ascii_uppercase(), self.policy).map_err(|_| ())?;
+        for (i, word) in buffer.iter_mut().enumerate() {
+            *word = words.get(i).copied().unwrap_or_default();
+        }
+        Ok(())
+    }
+}
+
+impl IODevice for TextCardReaderDevice {
+    fn read(&mut self, buffer: &mut [FullWord]) -> Result<(), ()> {
+        if buffer.len() != self.get_block_size() {
+            return Err(());
+        }
+        // A card that cannot be punched stays in the hopper.
+        let line = self.lines.as_slice().first().ok_or(())?;
+        self.punch(line, buffer)?;
+        self.lines.next();
+        Ok(())
+    }
+
+    fn peek(&self, buffer: &mut [FullWord]) -> Result<usize, ()> {
+        if buffer.len() != self.get_block_size() {
+            return Err(());
+        }
+        let line = self.lines.as_slice().first().ok_or(())?;
+        self.punch(line, buffer)?;
+        Ok(buffer.len())
+    }
+
+    fn write(&mut self, _: &[FullWord]) -> Result<(), usize> {
+        Err(0)
+    }
+
+    fn control(&mut self, _: i16) -> Result<(), ()> {
+        Err(())
+    }
+
+    fn is_busy(&self) -> Result<bool, ()> {
+        Ok(false)
+    }
+
+    fn is_ready(&self) -> Result<bool, ()> {
+        Ok(true)
+    }
+
+    fn get_block_size(&self) -> usize {
+        16
+    }
+
+    fn name(&self) -> &str {
+        "card reader"
+    }
+
+    fn at_eof(&self) -> bool {
+        self.lines.len() == 0
+    }
+}
+
+/// A line printer writing 24-word lines to a [`Write`], without
+/// trailing blanks.
+///
+/// Lines are written out on every `OUT` and `IOC`.
+pub struct LinePrinterDevice<W: Write> {
+    out: W,
+    line: String,
+}
+
+impl<W: Write> LinePrinterDevice<W> {
+    /// Create a line printer writing to `out`.
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            line: String::new(),
+        }
+    }
+}
+
+impl<W: Write> IODevice for LinePrinterDevice<W> {
+    fn read(&mut self, _: &mut [FullWord]) -> Result<(), ()> {
+        Err(())
+    }
+
+    fn write(&mut self, data: &[FullWord]) -> Result<(), usize> {
+        if data.len() != self.get_block_size() {
+            return Err(0);
+        }
+        self.line.push_str(&decode_block(data, false)?);
+        let trimmed_len = self.line.trim_end().len();
+        self.line.truncate(trimmed_len);
+        self.line.push('\n');
+        self.flush().map_err(|_| data.len())
+    }
+
+    fn control(&mut self, _: i16) -> Result<(), ()> {
+        self.flush()
+    }
+
+    fn is_busy(&self) -> Result<bool, ()> {
+        Ok(false)
+    }
+
+    fn is_ready(&self) -> Result<bool, ()> {
+        Ok(true)
+    }
+
+    fn get_block_size(&self) -> usize {
+        24
+    }
+
+    fn flush(&mut self) -> Result<(), ()> {
+        self.out.write_all(self.line.as_bytes()).map_err(|_| ())?;
+        self.line.clear();
+        self.out.flush().map_err(|_| ())
+    }
+
+    fn name(&self) -> &str {
+        "line printer"
+    }
+
+    fn reset(&mut self) {
+        self.line.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cards_are_read_one_per_line() {
+        let mut reader = TextCardReaderDevice::new("hello\nMIX", InputPolicy::Strict);
+        let mut card = [FullWord::default(); 16];
+        assert!(!reader.at_eof());
+        assert_eq!(reader.peek(&mut card), Ok(16));
+        reader.read(&mut card).unwrap();
+        let hello = encode_words("HELLO", InputPolicy::Strict).unwrap();
+        assert_eq!(card[0].as_array(), hello[0].as_array());
+        assert_eq!(card[1], 0);
+        reader.read(&mut card).unwrap();
+        assert!(reader.at_eof());
+        assert_eq!(reader.read(&mut card), Err(()));
+        assert_eq!(reader.read(&mut [FullWord::default(); 15]), Err(()));
+    }
+
+    #[test]
+    fn lines_are_printed_without_trailing_blanks() {
+        let mut out = Vec::new();
+        let mut printer = LinePrinterDevice::new(&mut out);
+        let mut line = encode_words(&" ".repeat(120), InputPolicy::Strict).unwrap();
+        line[0] = encode_words("HI", InputPolicy::Strict).unwrap()[0];
+        printer.write(&line).unwrap();
+        printer.write(&line).unwrap();
+        assert_eq!(printer.write(&line[1..]), Err(0));
+        assert_eq!(String::from_utf8(out).unwrap(), "HI\nHI\n");
+    }
+}
@@ -0,0 +1,326 @@
+use std::fmt;
+
+use super::*;
+
+impl Opcode {
+    /// Get the MIXAL mnemonic of the operation selected by `field`.
+    ///
+    /// Opcodes that use `F` to select among several operations
+    /// (e.g. [`Opcode::Special`] or [`Opcode::Jmp`]) yield the name
+    /// of the selected operation. All other opcodes yield their only
+    /// mnemonic regardless of `field`.
+    ///
+    /// # Arguments
+    /// * `field` - The `F` part of the instruction.
+    ///
+    /// # Returns
+    /// * [`Some(&str)`] - The mnemonic.
+    /// * [`None`] - `field` selects no known operation.
+    pub fn mnemonic(self, field: u8) -> Option<&'static str> {
+        let names: &[&str] = match self {
+            Opcode::Special => &[
+                "NUM", "CHAR", "HLT", "F32CVTF322I4B", "F32CVTF322I2B", "F32CVTF322I1B",
+                "F32CVTI4B2F32", "F32CVTI2B2F32", "F32CVTI1B2F32", "NOT", "AND", "OR", "XOR",
+                "POPCNT", "CLZ", "IDCAP",
+            ],
+            Opcode::Shift => &["SLA", "SRA", "SLAX", "SRAX", "SLC", "SRC", "SLB", "SRB"],
+            Opcode::Jmp => &[
+                "JMP", "JSJ", "JOV", "JNOV", "JL", "JE", "JG", "JGE", "JNE", "JLE", "F32JORD",
+                "F32JUNORD",
+            ],
+            Opcode::JA => &["JAN", "JAZ", "JAP", "JANN", "JANZ", "JANP", "JAE", "JAO"],
+            Opcode::J1 => &["J1N", "J1Z", "J1P", "J1NN", "J1NZ", "J1NP"],
+            Opcode::J2 => &["J2N", "J2Z", "J2P", "J2NN", "J2NZ", "J2NP"],
+            Opcode::J3 => &["J3N", "J3Z", "J3P", "J3NN", "J3NZ", "J3NP"],
+            Opcode::J4 => &["J4N", "J4Z", "J4P", "J4NN", "J4NZ", "J4NP"],
+            Opcode::J5 => &["J5N", "J5Z", "J5P", "J5NN", "J5NZ", "J5NP"],
+            Opcode::J6 => &["J6N", "J6Z", "J6P", "J6NN", "J6NZ", "J6NP"],
+            Opcode::JX => &["JXN", "JXZ", "JXP", "JXNN", "JXNZ", "JXNP", "JXE", "JXO"],
+            Opcode::ModifyA => &["INCA", "DECA", "ENTA", "ENNA"],
+            Opcode::Modify1 => &["INC1", "DEC1", "ENT1", "ENN1"],
+            Opcode::Modify2 => &["INC2", "DEC2", "ENT2", "ENN2"],
+            Opcode::Modify3 => &["INC3", "DEC3", "ENT3", "ENN3"],
+            Opcode::Modify4 => &["INC4", "DEC4", "ENT4", "ENN4"],
+            Opcode::Modify5 => &["INC5", "DEC5", "ENT5", "ENN5"],
+            Opcode::Modify6 => &["INC6", "DEC6", "ENT6", "ENN6"],
+            Opcode::ModifyX => &["INCX", "DECX", "ENTX", "ENNX"],
+            _ => return Some(self.base_mnemonic()),
+        };
+        names.get(field as usize).copied()
+    }
+
+    /// Get the mnemonic of the primary operation of the opcode.
+    ///
+    /// This is the only mnemonic of most opcodes. Opcodes whose `F`
+    /// part selects among several operations yield their first one,
+    /// e.g. `JMP` or `INCA`, except [`Opcode::Special`], which yields
+    /// `HLT`.
+    pub fn primary_mnemonic(self) -> &'static str {
+        let field = if self == Opcode::Special { 2 } else { 0 };
+        self.mnemonic(field).unwrap_or_else(|| unreachable!())
+    }
+
+    /// Get the mnemonic of an opcode whose `F` part does not
+    /// select among several operations.
+    fn base_mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Nop => "NOP",
+            Opcode::Add => "ADD",
+            Opcode::Sub => "SUB",
+            Opcode::Mul => "MUL",
+            Opcode::Div => "DIV",
+            Opcode::Move => "MOVE",
+            Opcode::LdA => "LDA",
+            Opcode::Ld1 => "LD1",
+            Opcode::Ld2 => "LD2",
+            Opcode::Ld3 => "LD3",
+            Opcode::Ld4 => "LD4",
+            Opcode::Ld5 => "LD5",
+            Opcode::Ld6 => "LD6",
+            Opcode::LdX => "LDX",
+            Opcode::LdAN => "LDAN",
+            Opcode::Ld1N => "LD1N",
+            Opcode::Ld2N => "LD2N",
+            Opcode::Ld3N => "LD3N",
+            Opcode::Ld4N => "LD4N",
+            Opcode::Ld5N => "LD5N",
+            Opcode::Ld6N => "LD6N",
+            Opcode::LdXN => "LDXN",
+            Opcode::StA => "STA",
+            Opcode::St1 => "ST1",
+            Opcode::St2 => "ST2",
+            Opcode::St3 => "ST3",
+            Opcode::St4 => "ST4",
+            Opcode::St5 => "ST5",
+            Opcode::St6 => "ST6",
+            Opcode::StX => "STX",
+            Opcode::StJ => "STJ",
+            Opcode::StZ => "STZ",
+            Opcode::Jbus => "JBUS",
+            Opcode::Ioc => "IOC",
+            Opcode::In => "IN",
+            Opcode::Out => "OUT",
+            Opcode::Jred => "JRED",
+            Opcode::CmpA => "CMPA",
+            Opcode::Cmp1 => "CMP1",
+            Opcode::Cmp2 => "CMP2",
+            Opcode::Cmp3 => "CMP3",
+            Opcode::Cmp4 => "CMP4",
+            Opcode::Cmp5 => "CMP5",
+            Opcode::Cmp6 => "CMP6",
+            Opcode::CmpX => "CMPX",
+            _ => unreachable!(),
+        }
+    }
+
+    /// Check if the `F` part of this opcode is an IO unit number.
+    ///
+    /// This is the case for `JBUS`, `IOC`, `IN`, `OUT` and `JRED`.
+    pub const fn is_io(self) -> bool {
+        matches!(
+            self,
+            Opcode::Jbus | Opcode::Ioc | Opcode::In | Opcode::Out | Opcode::Jred
+        )
+    }
+
+    /// Check if this opcode may transfer control to its address.
+    ///
+    /// This is the case for `JBUS`, `JRED` and all `Jx` opcodes.
+    pub const fn is_jump(self) -> bool {
+        matches!(
+            self,
+            Opcode::Jbus
+                | Opcode::Jred
+                | Opcode::Jmp
+                | Opcode::JA
+                | Opcode::J1
+                | Opcode::J2
+                | Opcode::J3
+                | Opcode::J4
+                | Opcode::J5
+                | Opcode::J6
+                | Opcode::JX
+        )
+    }
+
+    /// Check if the address of this opcode refers to a memory cell.
+    ///
+    /// This is the case for loads, stores, arithmetic, comparisons,
+    /// `MOVE`, `IN` and `OUT`.
+    pub const fn is_memory_ref(self) -> bool {
+        matches!(
+            self,
+            Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Move
+                | Opcode::In
+                | Opcode::Out
+        ) || (self as u8 >= Opcode::LdA as u8 && self as u8 <= Opcode::StZ as u8)
+            || (self as u8 >= Opcode::CmpA as u8 && self as u8 <= Opcode::CmpX as u8)
+    }
+
+    /// Check if the `F` part of this opcode selects an operation
+    /// instead of describing a field or a count.
+    pub const fn has_field_modes(self) -> bool {
+        matches!(
+            self,
+            Opcode::Special
+                | Opcode::Shift
+                | Opcode::Jmp
+                | Opcode::JA
+                | Opcode::J1
+                | Opcode::J2
+                | Opcode::J3
+                | Opcode::J4
+                | Opcode::J5
+                | Opcode::J6
+                | Opcode::JX
+                | Opcode::ModifyA
+                | Opcode::Modify1
+                | Opcode::Modify2
+                | Opcode::Modify3
+                | Opcode::Modify4
+                | Opcode::Modify5
+                | Opcode::Modify6
+                | Opcode::ModifyX
+        )
+    }
+
+    /// Get the `F` part MIXAL assumes when none is written.
+    ///
+    /// This is `(0:5)` for opcodes taking a field, except `STJ`
+    /// which takes `(0:2)`, 1 for `MOVE`, and 0 otherwise, i.e. the
+    /// first operation of opcodes with field modes and unit 0 of IO
+    /// opcodes.
+    pub const fn default_field(self) -> u8 {
+        match self {
+            Opcode::StJ => 2,
+            Opcode::Move => 1,
+            _ if self.is_memory_ref() && !self.is_io() => 5,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for Opcode {
+    /// Format the opcode as its [`Opcode::primary_mnemonic()`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.primary_mnemonic())
+    }
+}
+
+impl Instruction {
+    /// Render the instruction in MIXAL notation.
+    ///
+    /// The `F` part is rendered according to its meaning for the
+    /// opcode:
+    ///
+    /// ```text
+    /// LDA  2000,1(1:5)    ; F is a field (L:R)
+    /// IN   1000,0(18)     ; F is an IO unit
+    /// MOVE 1000,0(3)      ; F is a word count
+    /// ENTA 0,0            ; F is implied by the mnemonic
+    /// ```
+    ///
+    /// Unknown operations are rendered with a mnemonic of `???`.
+    pub fn to_mixal(self) -> String {
+        let operand = format!("{},{}", self.addr, self.index);
+        match self.opcode.mnemonic(self.field) {
+            Some(name) if self.opcode.has_field_modes() => format!("{:<4} {}", name, operand),
+            Some(name) if self.opcode.is_io() || self.opcode == Opcode::Move => {
+                format!("{:<4} {}({})", name, operand, self.field)
+            }
+            Some(name) => format!(
+                "{:<4} {}({}:{})",
+                name,
+                operand,
+                self.field / 8,
+                self.field % 8
+            ),
+            None => format!("{:<4} {}({})", "???", operand, self.field),
+        }
+    }
+}
+
+impl VM {
+    /// Disassemble the word at `addr` into MIXAL notation.
+    ///
+    /// See [`Instruction::to_mixal()`] for the format. Jumps and
+    /// memory references to an unindexed address with a name from
+    /// [`VM::load_symbols()`] are annotated with it.
+    ///
+    /// ```text
+    /// JMP  0,0            ; xtea
+    /// ```
+    ///
+    /// # Returns
+    /// * [`Ok(String)`] - The disassembled instruction.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - `addr` is out of memory.
+    /// * [`Err(ErrorCode::IllegalInstruction)`] - The word is not an instruction.
+    pub fn disassemble(&self, addr: u16) -> Result<String, ErrorCode> {
+        let instr = self.helper_decode_at(addr)?;
+        let text = instr.to_mixal();
+        Ok(match self.helper_symbol_of(&instr) {
+            Some(name) => format!("{:<20}; {}", text, name),
+            None => text,
+        })
+    }
+
+    /// Disassemble the word at `addr`, annotating IO instructions with
+    /// the name of the device attached to their unit.
+    ///
+    /// Names of addresses are annotated as in [`VM::disassemble()`].
+    ///
+    /// ```text
+    /// OUT  3002,3(18)     ; line printer
+    /// OUT  3002,0(18)     ; const.welcome, line printer
+    /// ```
+    ///
+    /// # Returns
+    /// * [`Ok(String)`] - The disassembled instruction.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - `addr` is out of memory.
+    /// * [`Err(ErrorCode::IllegalInstruction)`] - The word is not an instruction.
+    pub fn disassemble_with_devices(&self, addr: u16) -> Result<String, ErrorCode> {
+        let instr = self.helper_decode_at(addr)?;
+        let text = instr.to_mixal();
+        let symbol = self.helper_symbol_of(&instr);
+        if !instr.opcode.is_io() {
+            return Ok(match symbol {
+                Some(name) => format!("{:<20}; {}", text, name),
+                None => text,
+            });
+        }
+        let unit = instr.field as usize;
+        let name = match self.device(unit) {
+            _ if instr.opcode == Opcode::Ioc && instr.field == IOC_BARRIER_UNIT => "barrier",
+            Some(dev) => dev.name(),
+            None if unit < IO_UNITS => "no device",
+            None => "no such unit",
+        };
+        Ok(match symbol {
+            Some(symbol) => format!("{:<20}; {}, {}", text, symbol, name),
+            None => format!("{:<20}; {}", text, name),
+        })
+    }
+
+    /// Get the name of the address an instruction jumps to or refers
+    /// to, if it is not indexed.
+    fn helper_symbol_of(&self, instr: &Instruction) -> Option<&str> {
+        if instr.index != 0 || !(instr.opcode.is_jump() || instr.opcode.is_memory_ref()) {
+            return None;
+        }
+        self.symbol_at(u16::try_from(instr.addr).ok()?)
+    }
+
+    /// Decode the word at `addr` into an [`Instruction`].
+    fn helper_decode_at(&self, addr: u16) -> Result<Instruction, ErrorCode> {
+        if addr as usize >= Mem::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        self.mem[addr]
+            .try_into()
+            .map_err(|_| ErrorCode::IllegalInstruction)
+    }
+}
@@ -0,0 +1,54 @@
+use super::*;
+
+/// A significant event of a [`MixVM`].
+///
+/// See [`VM::enable_event_log()`]. Hosts feeding a logging framework,
+/// such as `tracing`, drain the log with [`VM::take_events()`]: the
+/// crate depends on no such framework.
+///
+/// [`MixVM`]: crate::MixVM
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// A block is transferred by `IN` or `OUT`.
+    Io {
+        /// The unit number of the device.
+        unit: u8,
+
+        /// The words transferred.
+        words: Vec<FullWord>,
+    },
+
+    /// An instruction sets the overflow toggle.
+    Overflow {
+        /// The address of the instruction.
+        pc: u16,
+    },
+
+    /// The host writes memory in a transaction or a patch.
+    ///
+    /// See [`VM::with_mem_transaction()`] and [`VM::write_patch()`].
+    MemoryWrite {
+        /// The range of addresses touched.
+        range: core::ops::RangeInclusive<u16>,
+    },
+
+    /// The machine halts.
+    Halt {
+        /// Why the machine halts.
+        ///
+        /// This is never [`metrics::HaltReason::Timeout`]: a step limit
+        /// halts with [`ErrorCode::StepLimitExceeded`].
+        reason: metrics::HaltReason,
+    },
+}
+
+/// An [`Event`] and the time it happened.
+#[derive(Clone, Debug)]
+pub struct EventRecord {
+    /// The [`VM::cycle_count()`] when the instruction causing the
+    /// event started.
+    pub cycle: u64,
+
+    /// The event.
+    pub event: Event,
+}
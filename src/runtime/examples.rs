@@ -0,0 +1,192 @@
+//! Sample programs to try the machine with.
+//!
+//! Each program comes as its instructions and a function building the
+//! whole [`MixProgram`], data included. All of them print to a line
+//! printer of 24-word lines on unit [`PRINTER`].
+//!
+//! ```text
+//! mix.attach_device(examples::PRINTER as usize, Box::new(printer));
+//! mix.load_and_run_with_timeout(&examples::primes(), 10_000_000)?;
+//! ```
+
+use super::*;
+
+/// The unit number of the line printer used by the programs.
+pub const PRINTER: u8 = 18;
+
+/// The address where [`HELLO`] is loaded and entered.
+pub const HELLO_ORIGIN: u16 = 0;
+
+/// Print `HELLO, WORLD` and halt.
+///
+/// The message follows the instructions.
+pub const HELLO: [Instruction; 3] = [
+    Instruction::new(HELLO_ORIGIN as i16 + 3, PRINTER, 0, Opcode::Out),
+    Instruction::new(HELLO_ORIGIN as i16 + 1, PRINTER, 0, Opcode::Jbus),
+    Instruction::new(0, 2, 0, Opcode::Special),
+];
+
+/// Build [`HELLO`] with its message.
+pub fn hello() -> MixProgram {
+    let mut words: Vec<FullWord> = HELLO.iter().map(|&i| FullWord::from(i)).collect();
+    words.extend(encode_words("HELLO, WORLD", InputPolicy::Strict).unwrap());
+    words.resize(HELLO.len() + 24, FullWord::new());
+    MixProgram::new(HELLO_ORIGIN, HELLO_ORIGIN, words)
+}
+
+/// The address where [`PRIMES`] is loaded and entered.
+pub const PRIMES_ORIGIN: u16 = 3000;
+
+/// The number of primes printed by [`PRIMES`].
+const L: i16 = 500;
+
+/// The base of the table of primes, exclusive.
+const PRIME: i16 = -1;
+
+/// The first print buffer.
+const BUF0: i16 = 2000;
+
+/// The second print buffer.
+const BUF1: i16 = BUF0 + 25;
+
+/// The title line.
+const TITLE: i16 = BUF0 - 5;
+
+/// Print a table of the first 500 primes.
+///
+/// This is Program P of Knuth, TAOCP Vol. 1, 1.3.2, with the literals
+/// `=1-L=` and `=3=` replaced by `ENT1` and `ENT2`.
+pub const PRIMES: [Instruction; 30] = [
+    // Start table.
+    Instruction::new(0, PRINTER, 0, Opcode::Ioc),
+    Instruction::new(1 - L, 2, 0, Opcode::Modify1),
+    Instruction::new(3, 2, 0, Opcode::Modify2),
+    // N is prime.
+    Instruction::new(1, 0, 0, Opcode::Modify1),
+    Instruction::new(PRIME + L, 5, 1, Opcode::St2),
+    Instruction::new(PRIMES_ORIGIN as i16 + 16, 1, 0, Opcode::J1),
+    // Advance N.
+    Instruction::new(2, 0, 0, Opcode::Modify2),
+    // K <- 2.
+    Instruction::new(2, 2, 0, Opcode::Modify3),
+    // PRIME[K] \ N?
+    Instruction::new(0, 2, 0, Opcode::ModifyA),
+    Instruction::new(0, 2, 2, Opcode::ModifyX),
+    Instruction::new(PRIME, 5, 3, Opcode::Div),
+    Instruction::new(PRIMES_ORIGIN as i16 + 6, 1, 0, Opcode::JX),
+    // PRIME[K] large?
+    Instruction::new(PRIME, 5, 3, Opcode::CmpA),
+    Instruction::new(1, 0, 0, Opcode::Modify3),
+    Instruction::new(PRIMES_ORIGIN as i16 + 8, 6, 0, Opcode::Jmp),
+    Instruction::new(PRIMES_ORIGIN as i16 + 3, 0, 0, Opcode::Jmp),
+    // Print title.
+    Instruction::new(TITLE, PRINTER, 0, Opcode::Out),
+    Instruction::new(BUF1 + 10, 2, 0, Opcode::Modify4),
+    Instruction::new(-50, 2, 0, Opcode::Modify5),
+    // Set up line.
+    Instruction::new(L + 1, 0, 0, Opcode::Modify5),
+    // Convert PRIME[M] to decimal.
+    Instruction::new(PRIME, 5, 5, Opcode::LdA),
+    Instruction::new(0, 1, 0, Opcode::Special),
+    Instruction::new(0, 12, 4, Opcode::StX),
+    Instruction::new(1, 1, 0, Opcode::Modify4),
+    Instruction::new(50, 1, 0, Opcode::Modify5),
+    Instruction::new(PRIMES_ORIGIN as i16 + 20, 2, 0, Opcode::J5),
+    // Print line.
+    Instruction::new(0, PRINTER, 4, Opcode::Out),
+    Instruction::new(24, 5, 4, Opcode::Ld4),
+    Instruction::new(PRIMES_ORIGIN as i16 + 19, 0, 0, Opcode::J5),
+    Instruction::new(0, 2, 0, Opcode::Special),
+];
+
+/// Build [`PRIMES`] with its table and buffers.
+///
+/// The program covers addresses 0 to 3029, as the table of primes
+/// starts at 0 and the buffers at 1995.
+pub fn primes() -> MixProgram {
+    let mut words = vec![FullWord::new(); PRIMES_ORIGIN as usize + PRIMES.len()];
+    words[(PRIME + 1) as usize] = FullWord::from_i64(2).0;
+    let title = encode_words("FIRST FIVE HUNDRED PRIMES", InputPolicy::Strict).unwrap();
+    words[TITLE as usize..BUF0 as usize].copy_from_slice(&title);
+    words[(BUF0 + 24) as usize] = FullWord::from_i64((BUF1 + 10) as i64).0;
+    words[(BUF1 + 24) as usize] = FullWord::from_i64((BUF0 + 10) as i64).0;
+    for (i, &instr) in PRIMES.iter().enumerate() {
+        words[PRIMES_ORIGIN as usize + i] = FullWord::from(instr);
+    }
+    MixProgram::new(0, PRIMES_ORIGIN, words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::io::decode_block;
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A line printer keeping its lines, without trailing blanks.
+    struct Printer(Rc<RefCell<Vec<String>>>);
+
+    impl IODevice for Printer {
+        fn read(&mut self, _: &mut [FullWord]) -> Result<(), ()> {
+            Err(())
+        }
+
+        fn write(&mut self, data: &[FullWord]) -> Result<(), usize> {
+            let line = decode_block(data, false)?;
+            self.0.borrow_mut().push(line.trim_end().to_string());
+            Ok(())
+        }
+
+        fn control(&mut self, _: i16) -> Result<(), ()> {
+            Ok(())
+        }
+
+        fn is_busy(&self) -> Result<bool, ()> {
+            Ok(false)
+        }
+
+        fn is_ready(&self) -> Result<bool, ()> {
+            Ok(true)
+        }
+
+        fn get_block_size(&self) -> usize {
+            24
+        }
+    }
+
+    /// Run a program and get the lines it prints.
+    fn run(program: &MixProgram) -> Vec<String> {
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let mut mix = VM::new();
+        mix.attach_device(PRINTER as usize, Box::new(Printer(lines.clone())));
+        let state = mix.load_and_run_with_timeout(program, 10_000_000).unwrap();
+        assert!(state.halted);
+        let lines = lines.borrow().clone();
+        lines
+    }
+
+    #[test]
+    fn hello_prints_its_message() {
+        assert_eq!(run(&hello()), ["HELLO, WORLD"]);
+    }
+
+    #[test]
+    fn primes_prints_the_table_of_knuth() {
+        let lines = run(&primes());
+        assert_eq!(lines.len(), 51);
+        assert_eq!(lines[0], "FIRST FIVE HUNDRED PRIMES");
+        // The first and last lines of the table in TAOCP.
+        assert_eq!(
+            lines[1],
+            "     0002 0233 0547 0877 1229 1597 1993 2371 2749 3187"
+        );
+        assert_eq!(
+            lines[50],
+            "     0229 0541 0863 1223 1583 1987 2357 2741 3181 3571"
+        );
+        for line in &lines[1..] {
+            assert_eq!(line.split_whitespace().count(), 10, "{}", line);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use super::*;
+
+/// A set of extensions enabled in a [`MixVM`].
+///
+/// Each extension is a bit, as reported to programs by `IDCAP`.
+/// Operations of extensions not in [`VM::extensions`] are rejected
+/// like operations unknown to MIX.
+///
+/// [`MixVM`]: crate::MixVM
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ExtensionSet {
+    bits: u8,
+}
+
+impl ExtensionSet {
+    /// No extension, as in Knuth's MIX.
+    pub const NONE: Self = ExtensionSet { bits: 0 };
+
+    /// `x-ieee754`: IEEE 754 `binary32` arithmetic.
+    pub const IEEE754: Self = ExtensionSet { bits: 1 << 0 };
+
+    /// `x-binarith`: bitwise operations in `Special`.
+    ///
+    /// Without the feature, `NOT`, `AND`, `OR` and `XOR` are part of
+    /// the base machine, as before the extension existed, and `POPCNT`
+    /// and `CLZ` are not available.
+    pub const BINARITH: Self = ExtensionSet { bits: 1 << 1 };
+
+    /// `x-binary`: the binary shifts `SLB` and `SRB`.
+    pub const BINARY_SHIFTS: Self = ExtensionSet { bits: 1 << 2 };
+
+    /// Knuth's floating point operations. Reserved.
+    pub const MIXFLOAT: Self = ExtensionSet { bits: 1 << 3 };
+
+    /// Condition flags. Reserved.
+    pub const FLAGS: Self = ExtensionSet { bits: 1 << 4 };
+
+    /// Calls into the host. Reserved.
+    pub const HOSTCALL: Self = ExtensionSet { bits: 1 << 5 };
+
+    /// A random number source. Reserved.
+    pub const RAND: Self = ExtensionSet { bits: 1 << 6 };
+
+    /// Get the extensions compiled into this build.
+    pub const fn available() -> Self {
+        let mut bits = Self::BINARY_SHIFTS.bits;
+        if cfg!(feature = "x-ieee754") {
+            bits |= Self::IEEE754.bits;
+        }
+        if cfg!(feature = "x-binarith") {
+            bits |= Self::BINARITH.bits;
+        }
+        ExtensionSet { bits }
+    }
+
+    /// Get the bitmask of the set.
+    pub const fn bits(self) -> u8 {
+        self.bits
+    }
+
+    /// Check if the set has no extension.
+    pub const fn is_empty(self) -> bool {
+        self.bits == 0
+    }
+
+    /// Check if all extensions of `other` are in the set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.bits & other.bits == other.bits
+    }
+
+    /// Get the extensions in either set.
+    pub const fn union(self, other: Self) -> Self {
+        ExtensionSet {
+            bits: self.bits | other.bits,
+        }
+    }
+
+    /// Get the extensions in both sets.
+    pub const fn intersection(self, other: Self) -> Self {
+        ExtensionSet {
+            bits: self.bits & other.bits,
+        }
+    }
+
+    /// Get the extensions in the set but not in `other`.
+    pub const fn difference(self, other: Self) -> Self {
+        ExtensionSet {
+            bits: self.bits & !other.bits,
+        }
+    }
+}
+
+impl Default for ExtensionSet {
+    /// Get the extensions compiled into this build.
+    ///
+    /// Equivalent to [`ExtensionSet::available()`].
+    fn default() -> Self {
+        Self::available()
+    }
+}
+
+impl Instruction {
+    /// Get the extension the instruction belongs to.
+    ///
+    /// # Returns
+    /// * [`Some(ExtensionSet)`] - The extension of the operation.
+    /// * [`None`] - The operation is in Knuth's MIX, or is `IDCAP`.
+    pub const fn extension(&self) -> Option<ExtensionSet> {
+        if self.is_ieee754() {
+            Some(ExtensionSet::IEEE754)
+        } else if self.is_binarith() && cfg!(feature = "x-binarith") {
+            Some(ExtensionSet::BINARITH)
+        } else if matches!(self.opcode, Opcode::Shift) && (self.field == 6 || self.field == 7) {
+            Some(ExtensionSet::BINARY_SHIFTS)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shift `rAX` right by one byte, with `SRB` if the machine has
+    /// binary shifts and with `SRAX` otherwise.
+    const SELECT_SHIFT: [Instruction; 13] = [
+        Instruction::new(0, 15, 0, Opcode::Special),
+        Instruction::new(201, 5, 0, Opcode::StX),
+        Instruction::new(202, 10, 0, Opcode::Special),
+        Instruction::new(9, 1, 0, Opcode::JA),
+        Instruction::new(201, 5, 0, Opcode::Ld1),
+        Instruction::new(100, 5, 0, Opcode::LdA),
+        Instruction::new(101, 5, 0, Opcode::LdX),
+        Instruction::new(0, 7, 1, Opcode::Shift),
+        Instruction::new(0, 2, 0, Opcode::Special),
+        Instruction::new(100, 5, 0, Opcode::LdA),
+        Instruction::new(101, 5, 0, Opcode::LdX),
+        Instruction::new(1, 3, 0, Opcode::Shift),
+        Instruction::new(0, 2, 0, Opcode::Special),
+    ];
+
+    fn run_select_shift(extensions: ExtensionSet) -> (VM, Result<(), ErrorCode>) {
+        let mut mix = VM::new();
+        mix.extensions = extensions;
+        mix.reset();
+        mix.load_program(&MixProgram::from(&SELECT_SHIFT[..]))
+            .unwrap();
+        mix.mem[100].set_all([FullWord::POS, 1, 2, 3, 4, 5]);
+        mix.mem[101].set_all([FullWord::POS, 6, 7, 8, 9, 10]);
+        mix.mem[202] = FullWord::from_i64(ExtensionSet::BINARY_SHIFTS.bits() as i64).0;
+        mix.restart();
+        let result = mix.run_until_halt();
+        (mix, result)
+    }
+
+    #[test]
+    fn available_follows_features() {
+        let available = ExtensionSet::available();
+        assert_eq!(
+            available.contains(ExtensionSet::IEEE754),
+            cfg!(feature = "x-ieee754")
+        );
+        assert_eq!(
+            available.contains(ExtensionSet::BINARITH),
+            cfg!(feature = "x-binarith")
+        );
+        assert!(available.contains(ExtensionSet::BINARY_SHIFTS));
+        assert_eq!(ExtensionSet::default(), available);
+    }
+
+    #[test]
+    fn idcap_reports_extensions_and_byte_size() {
+        let (mix, result) = run_select_shift(ExtensionSet::available());
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.mem[201].to_i64().0, BYTE_BITS as i64);
+        assert_eq!(
+            mix.r_a.as_array(),
+            &[FullWord::POS, 0, 1, 2, 3, 4],
+            "rA after the shift"
+        );
+        assert_eq!(mix.r_x.as_array(), &[FullWord::POS, 5, 6, 7, 8, 9]);
+        // The SRB path halts first.
+        assert_eq!(mix.pc, 9);
+    }
+
+    #[test]
+    fn select_shift_falls_back_to_srax() {
+        let extensions = ExtensionSet::available().difference(ExtensionSet::BINARY_SHIFTS);
+        let (mix, result) = run_select_shift(extensions);
+        if extensions.is_empty() {
+            assert_eq!(result, Err(ErrorCode::IllegalInstruction));
+            return;
+        }
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.r_a.as_array(), &[FullWord::POS, 0, 1, 2, 3, 4]);
+        assert_eq!(mix.r_x.as_array(), &[FullWord::POS, 5, 6, 7, 8, 9]);
+        assert_eq!(mix.pc, 13);
+    }
+
+    #[test]
+    fn strict_machine_has_no_idcap() {
+        let (mix, result) = run_select_shift(ExtensionSet::NONE);
+        assert_eq!(result, Err(ErrorCode::IllegalInstruction));
+        assert_eq!(mix.pc, 1);
+    }
+
+    #[test]
+    fn bit_counts_need_binarith() {
+        let popcnt = Instruction::new(0, 13, 0, Opcode::Special);
+        let xor = Instruction::new(0, 12, 0, Opcode::Special);
+        if cfg!(feature = "x-binarith") {
+            assert_eq!(popcnt.extension(), Some(ExtensionSet::BINARITH));
+            assert_eq!(xor.extension(), Some(ExtensionSet::BINARITH));
+        } else {
+            assert!(popcnt.is_disabled_extension());
+            assert_eq!(xor.extension(), None);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Bit-stable IEEE 754 `binary32` helpers for the `x-ieee754`
+//! extension.
+//!
+//! A `binary32` value occupies bytes 2 to 5 of a [`FullWord`],
+//! big-endian, with the sign byte mirroring its sign bit. The sign
+//! bit is authoritative: readers ignore the sign byte, which only
+//! keeps the integer view of [`Word::to_i64()`] signed as the value
+//! is, and `-0.0` is stored with [`Word::NEG`]. See
+//! [`Word::from_f32()`] and [`Word::to_f32()`].
+//!
+//! Arithmetic is done with host [`f32`], which is exactly rounded on
+//! every supported target. The remaining platform dependencies are
+//! pinned here, so that the same program produces the same words
+//! everywhere:
+//!
+//! * Every NaN stored into a word is replaced by [`CANONICAL_NAN`].
+//! * Conversions to integers truncate toward zero and saturate at an
+//!   explicit maximum magnitude, with NaN converting to 0.
+
+use super::*;
+
+/// The bit pattern of the single quiet NaN stored by the machine.
+pub const CANONICAL_NAN: u32 = 0x7FC0_0000;
+
+/// Replace any NaN by [`CANONICAL_NAN`].
+///
+/// Other values, including signed zeros and infinities, are returned
+/// unchanged.
+pub fn canonicalize(value: f32) -> f32 {
+    if value.is_nan() {
+        f32::from_bits(CANONICAL_NAN)
+    } else {
+        value
+    }
+}
+
+/// Read a value from bytes 2 to 5 of a word.
+///
+/// The sign byte is ignored.
+pub fn from_word(word: &FullWord) -> f32 {
+    f32::from_be_bytes([word[2], word[3], word[4], word[5]])
+}
+
+/// Pack a value into a word.
+///
+/// NaNs are canonicalized, and the sign byte is set from the sign bit.
+pub fn to_word(value: f32) -> FullWord {
+    let value = canonicalize(value);
+    let bytes = value.to_be_bytes();
+    let sign = if value.is_sign_positive() {
+        FullWord::POS
+    } else {
+        FullWord::NEG
+    };
+    FullWord::from_bytes([sign, 0, bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+impl FullWord {
+    /// Create a word holding a `binary32` value.
+    ///
+    /// The sign byte is set from the sign bit, so `-0.0` gets
+    /// [`Word::NEG`]. NaNs are canonicalized. See [`to_word()`].
+    pub fn from_f32(value: f32) -> Self {
+        to_word(value)
+    }
+
+    /// Read the `binary32` value held by the word.
+    ///
+    /// The sign byte is ignored. See [`from_word()`].
+    pub fn to_f32(self) -> f32 {
+        from_word(&self)
+    }
+}
+
+/// Convert the magnitude of a value to an integer.
+///
+/// The magnitude is truncated toward zero and saturates at `max`.
+/// NaN converts to 0.
+///
+/// # Arguments
+/// * `value` - The value to convert.
+/// * `max` - The largest magnitude representable.
+pub fn to_magnitude_saturating(value: f32, max: u32) -> u32 {
+    let magnitude = value.abs();
+    if value.is_nan() {
+        0
+    } else if magnitude >= max as f32 {
+        max
+    } else {
+        // In range, so the cast is exact after truncation.
+        magnitude.trunc() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positive_zero_has_a_positive_sign_byte() {
+        let word = FullWord::from_f32(0.0);
+        assert_eq!(word[0], FullWord::POS);
+        assert_eq!(word.to_f32().to_bits(), 0.0f32.to_bits());
+    }
+
+    #[test]
+    fn negative_zero_has_a_negative_sign_byte() {
+        let word = FullWord::from_f32(-0.0);
+        assert_eq!(word[0], FullWord::NEG);
+        assert_eq!(word[1], 0);
+        assert_eq!(word.to_f32().to_bits(), (-0.0f32).to_bits());
+    }
+
+    #[test]
+    fn sign_byte_follows_the_sign_bit() {
+        for value in [
+            1.0f32,
+            -1.0,
+            -1.5,
+            2.0,
+            -f32::MIN_POSITIVE,
+            f32::NEG_INFINITY,
+        ] {
+            let word = FullWord::from_f32(value);
+            assert_eq!(word.is_negative(), value.is_sign_negative(), "{}", value);
+            assert_eq!(word[2..=5], value.to_be_bytes(), "{}", value);
+            assert_eq!(word.to_f32().to_bits(), value.to_bits(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn reading_ignores_the_sign_byte() {
+        for value in [0.0f32, -0.0, -1.5, 2.0] {
+            let mut word = FullWord::from_f32(value);
+            word.flip_sign();
+            assert_eq!(word.to_f32().to_bits(), value.to_bits(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn every_nan_is_stored_as_the_canonical_nan() {
+        for bits in [
+            0x7FC0_0000,
+            0xFFC0_0000,
+            0x7F80_0001,
+            0xFFFF_FFFF,
+            0x7FC1_2345,
+        ] {
+            let value = f32::from_bits(bits);
+            assert!(value.is_nan());
+            assert_eq!(canonicalize(value).to_bits(), CANONICAL_NAN, "{:#x}", bits);
+            let word = to_word(value);
+            assert_eq!(word.to_f32().to_bits(), CANONICAL_NAN, "{:#x}", bits);
+            assert!(word.is_positive(), "{:#x}", bits);
+        }
+        #[allow(clippy::zero_divided_by_zero)]
+        let quotient = 0.0f32 / 0.0;
+        assert_eq!(to_word(quotient).to_f32().to_bits(), CANONICAL_NAN);
+    }
+
+    #[test]
+    fn other_values_are_kept_bit_for_bit() {
+        for value in [
+            0.0f32,
+            -0.0,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            1.0e-45,
+            -3.5,
+        ] {
+            assert_eq!(canonicalize(value).to_bits(), value.to_bits(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn conversions_truncate_and_saturate() {
+        let table = [
+            (1.9f32, u32::MAX, 1),
+            (-1.9, u32::MAX, 1),
+            (0.5, u32::MAX, 0),
+            (-0.0, u32::MAX, 0),
+            (255.0, 255, 255),
+            (255.9, 255, 255),
+            (256.0, 255, 255),
+            (1.0e10, u16::MAX as u32, u16::MAX as u32),
+            (-1.0e10, u16::MAX as u32, u16::MAX as u32),
+            (4_294_967_040.0, u32::MAX, 4_294_967_040),
+            (4_294_967_296.0, u32::MAX, u32::MAX),
+            (f32::INFINITY, 100, 100),
+            (f32::NEG_INFINITY, 100, 100),
+            (f32::NAN, u32::MAX, 0),
+        ];
+        for (value, max, expected) in table {
+            assert_eq!(
+                to_magnitude_saturating(value, max),
+                expected,
+                "{} {}",
+                value,
+                max
+            );
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! Helpers shared by all on-disk formats.
+//!
+//! A file starts with a header of 8 bytes:
+//!
+//! ```text
+//! +-------+-------+-------+
+//! | magic | major | minor |
+//! +-------+-------+-------+
+//! | 4B    | 2B LE | 2B LE |
+//! +-------+-------+-------+
+//! ```
+//!
+//! A loader accepts files with a major version no higher than the
+//! one it knows. Minor versions only add optional data at the end
+//! and are always accepted.
+//!
+//! Every format has a largest valid length, so files should be read
+//! with [`read_limited()`] rather than in full, lest a hostile file
+//! exhaust the memory of the host.
+
+use std::io::{self, Read};
+
+use crate::runtime::Word;
+
+/// Errors returned by format loaders.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FormatError {
+    /// The data ends before a complete value can be read.
+    UnexpectedEof,
+
+    /// The magic number does not match the expected format.
+    BadMagic,
+
+    /// The data is produced by an unsupported version.
+    UnsupportedVersion {
+        /// The major version in the header.
+        major: u16,
+
+        /// The minor version in the header.
+        minor: u16,
+    },
+
+    /// The data contains a value that is out of range.
+    InvalidData,
+}
+
+/// The header at the start of every format.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FormatHeader {
+    /// The magic number identifying the format.
+    pub magic: [u8; 4],
+
+    /// The major version. Changes between major versions are not compatible.
+    pub major: u16,
+
+    /// The minor version. Changes between minor versions are compatible.
+    pub minor: u16,
+}
+
+/// Write a header to `out`.
+pub fn write_header(out: &mut Vec<u8>, header: &FormatHeader) {
+    out.extend_from_slice(&header.magic);
+    write_u16(out, header.major);
+    write_u16(out, header.minor);
+}
+
+/// Read and check a header.
+///
+/// # Arguments
+/// * `reader` - The reader to read from.
+/// * `magic` - The expected magic number.
+/// * `max_major` - The highest supported major version.
+///
+/// # Returns
+/// * [`Ok(FormatHeader)`] - The header.
+/// * [`Err(FormatError::BadMagic)`] - The magic number does not match.
+/// * [`Err(FormatError::UnsupportedVersion)`] - The major version is zero or higher than `max_major`.
+/// * [`Err(FormatError::UnexpectedEof)`] - The header is truncated.
+pub fn read_header(
+    reader: &mut ByteReader,
+    magic: [u8; 4],
+    max_major: u16,
+) -> Result<FormatHeader, FormatError> {
+    let found_magic: [u8; 4] = reader.read_array()?;
+    if found_magic != magic {
+        return Err(FormatError::BadMagic);
+    }
+    let major = reader.read_u16()?;
+    let minor = reader.read_u16()?;
+    if major == 0 || major > max_major {
+        return Err(FormatError::UnsupportedVersion { major, minor });
+    }
+    Ok(FormatHeader {
+        magic: found_magic,
+        major,
+        minor,
+    })
+}
+
+/// Read all bytes of `reader`, up to `limit`.
+///
+/// No more than `limit + 1` bytes are read from `reader`.
+///
+/// # Returns
+/// * [`Ok(Vec<u8>)`] - The bytes.
+/// * [`Err(io::Error)`] - `reader` fails, or has more than `limit` bytes, with [`io::ErrorKind::FileTooLarge`].
+pub fn read_limited(reader: impl Read, limit: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.take(limit as u64 + 1).read_to_end(&mut data)?;
+    if data.len() > limit {
+        return Err(io::ErrorKind::FileTooLarge.into());
+    }
+    Ok(data)
+}
+
+/// Write a little-endian `u16` to `out`.
+pub fn write_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Write a little-endian `u32` to `out`.
+pub fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Write a little-endian `u64` to `out`.
+pub fn write_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Write a word to `out`, starting from the sign byte.
+pub fn write_word<const N: usize, const P: bool>(out: &mut Vec<u8>, word: &Word<N, P>) {
+    out.extend_from_slice(&word[..]);
+}
+
+/// A cursor over a byte slice.
+#[derive(Clone, Debug)]
+pub struct ByteReader<'a> {
+    /// The data to read from.
+    data: &'a [u8],
+
+    /// The position of the next byte to read.
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    /// Create a new reader at the start of `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        ByteReader { data, pos: 0 }
+    }
+
+    /// Get the bytes not read yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+
+    /// Read `len` bytes.
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], FormatError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(FormatError::UnexpectedEof)?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Read an array of `N` bytes.
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], FormatError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+
+    /// Read a `u8`.
+    pub fn read_u8(&mut self) -> Result<u8, FormatError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    /// Read a little-endian `u16`.
+    pub fn read_u16(&mut self) -> Result<u16, FormatError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    /// Read a little-endian `u32`.
+    pub fn read_u32(&mut self) -> Result<u32, FormatError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    /// Read a little-endian `u64`.
+    pub fn read_u64(&mut self) -> Result<u64, FormatError> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    /// Read a word, starting from the sign byte.
+    ///
+    /// The sign byte must be either [`Word::POS`] or [`Word::NEG`].
+    pub fn read_word<const N: usize, const P: bool>(
+        &mut self,
+    ) -> Result<Word<N, P>, FormatError> {
+        let bytes: [u8; N] = self.read_array()?;
+        if bytes[0] != Word::<N, P>::POS && bytes[0] != Word::<N, P>::NEG {
+            return Err(FormatError::InvalidData);
+        }
+        Ok(Word::from_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::{FullWord, PosHalfWord};
+
+    const MAGIC: [u8; 4] = *b"TEST";
+
+    #[test]
+    fn integers_are_little_endian() {
+        let mut out = Vec::new();
+        write_u16(&mut out, 0x0102);
+        write_u32(&mut out, 0x0304_0506);
+        write_u64(&mut out, 0x0708_090A_0B0C_0D0E);
+        assert_eq!(
+            out,
+            [2, 1, 6, 5, 4, 3, 0x0E, 0x0D, 0x0C, 0x0B, 0x0A, 9, 8, 7]
+        );
+
+        let mut reader = ByteReader::new(&out);
+        assert_eq!(reader.read_u16(), Ok(0x0102));
+        assert_eq!(reader.read_u32(), Ok(0x0304_0506));
+        assert_eq!(reader.read_u64(), Ok(0x0708_090A_0B0C_0D0E));
+        assert!(reader.remaining().is_empty());
+        assert_eq!(reader.read_u8(), Err(FormatError::UnexpectedEof));
+    }
+
+    #[test]
+    fn headers_are_checked() {
+        let header = |major, minor| {
+            let mut out = Vec::new();
+            write_header(
+                &mut out,
+                &FormatHeader {
+                    magic: MAGIC,
+                    major,
+                    minor,
+                },
+            );
+            out
+        };
+        assert_eq!(header(2, 3), *b"TEST\x02\x00\x03\x00");
+
+        let read = |data: &[u8]| read_header(&mut ByteReader::new(data), MAGIC, 2);
+        assert_eq!(
+            read(&header(2, 9)),
+            Ok(FormatHeader {
+                magic: MAGIC,
+                major: 2,
+                minor: 9
+            })
+        );
+        assert!(read(&header(1, 0)).is_ok());
+        assert_eq!(
+            read(&header(3, 0)),
+            Err(FormatError::UnsupportedVersion { major: 3, minor: 0 })
+        );
+        assert_eq!(
+            read(&header(0, 1)),
+            Err(FormatError::UnsupportedVersion { major: 0, minor: 1 })
+        );
+        assert_eq!(read(b"MIXI\x01\x00\x00\x00"), Err(FormatError::BadMagic));
+        assert_eq!(read(b"TEST\x01\x00\x00"), Err(FormatError::UnexpectedEof));
+    }
+
+    #[test]
+    fn words_keep_their_sign_byte() {
+        let mut out = Vec::new();
+        write_word(
+            &mut out,
+            &FullWord::from_bytes([FullWord::NEG, 1, 2, 3, 4, 5]),
+        );
+        write_word(&mut out, &PosHalfWord::from_bytes([PosHalfWord::POS, 6, 7]));
+        assert_eq!(out, [FullWord::NEG, 1, 2, 3, 4, 5, PosHalfWord::POS, 6, 7]);
+
+        let mut reader = ByteReader::new(&out);
+        let word: FullWord = reader.read_word().unwrap();
+        assert_eq!(word.as_array(), &[FullWord::NEG, 1, 2, 3, 4, 5]);
+        let word: PosHalfWord = reader.read_word().unwrap();
+        assert_eq!(word.as_array(), &[PosHalfWord::POS, 6, 7]);
+
+        let mut reader = ByteReader::new(&[2, 0, 0]);
+        assert_eq!(
+            reader.read_word::<3, true>().unwrap_err(),
+            FormatError::InvalidData
+        );
+    }
+
+    #[test]
+    fn reads_are_limited() {
+        assert_eq!(read_limited(&[1u8, 2, 3][..], 3).unwrap(), [1, 2, 3]);
+        assert_eq!(
+            read_limited(&[1u8, 2, 3][..], 2).unwrap_err().kind(),
+            io::ErrorKind::FileTooLarge
+        );
+        // Reads stop one byte past the limit.
+        let mut reader = &[0u8; 100][..];
+        assert!(read_limited(&mut reader, 10).is_err());
+        assert_eq!(reader.len(), 89);
+    }
+}
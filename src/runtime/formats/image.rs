@@ -0,0 +1,357 @@
+//! The memory image format.
+//!
+//! ```text
+//! +--------+-------+---------------+
+//! | header | count | words         |
+//! +--------+-------+---------------+
+//! | 8B     | 2B LE | count * 6B    |
+//! +--------+-------+---------------+
+//! ```
+//!
+//! Words are stored from address 0, each starting from its sign
+//! byte. Addresses not covered by the image are zero.
+//!
+//! Images of major version 2, written by [`write_segmented_image()`],
+//! only store the segments of memory in use:
+//!
+//! ```text
+//! +--------+-------+----------------------------------------------+
+//! | header | count | segments                                     |
+//! +--------+-------+----------------------------------------------+
+//! | 8B     | 2B LE | count * (2B LE base, 2B LE len, len * 6B)    |
+//! +--------+-------+----------------------------------------------+
+//! ```
+//!
+//! Segments are in ascending order of address, do not overlap, and
+//! there are at most [`MAX_SEGMENTS`] of them.
+
+use std::ops::Range;
+
+use super::common::*;
+use crate::runtime::{FullWord, Mem};
+
+/// Errors returned by [`read_image()`] and [`write_segmented_image()`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageError {
+    /// The image is malformed or unsupported.
+    Format(FormatError),
+
+    /// The image has more than [`MAX_SEGMENTS`] segments.
+    SegmentCountExceeded {
+        /// The number of segments.
+        count: usize,
+    },
+}
+
+impl From<FormatError> for ImageError {
+    fn from(err: FormatError) -> Self {
+        ImageError::Format(err)
+    }
+}
+
+/// The magic number of memory images.
+pub const IMAGE_MAGIC: [u8; 4] = *b"MIXI";
+
+/// The major version of memory images written by [`write_image()`].
+pub const IMAGE_MAJOR: u16 = 1;
+
+/// The major version of memory images written by
+/// [`write_segmented_image()`].
+pub const SEGMENTED_IMAGE_MAJOR: u16 = 2;
+
+/// The most segments in an image of major version 2.
+///
+/// It bounds the length of an image, see [`IMAGE_MAX_LEN`].
+pub const MAX_SEGMENTS: usize = 256;
+
+/// The minor version of memory images written by this version.
+pub const IMAGE_MINOR: u16 = 0;
+
+/// Serialize the memory into an image.
+pub fn write_image(mem: &Mem) -> Vec<u8> {
+    let mut out = Vec::with_capacity(IMAGE_LEN);
+    write_header(
+        &mut out,
+        &FormatHeader {
+            magic: IMAGE_MAGIC,
+            major: IMAGE_MAJOR,
+            minor: IMAGE_MINOR,
+        },
+    );
+    write_image_body(&mut out, mem);
+    out
+}
+
+/// The length of an image written by [`write_image()`].
+pub const IMAGE_LEN: usize = 8 + 2 + Mem::SIZE * 6;
+
+/// The length of the longest valid image of any version.
+pub const IMAGE_MAX_LEN: usize = IMAGE_LEN + MAX_SEGMENTS * 4;
+
+/// Serialize the memory into an image, writing to `writer`
+/// without allocating.
+pub fn write_image_to<W: std::io::Write>(writer: &mut W, mem: &Mem) -> std::io::Result<()> {
+    writer.write_all(&IMAGE_MAGIC)?;
+    writer.write_all(&IMAGE_MAJOR.to_le_bytes())?;
+    writer.write_all(&IMAGE_MINOR.to_le_bytes())?;
+    writer.write_all(&(Mem::SIZE as u16).to_le_bytes())?;
+    writer.write_all(mem.as_byte_slice())
+}
+
+/// Serialize the given segments of the memory into an image of
+/// major version 2.
+///
+/// # Returns
+/// * [`Ok(Vec<u8>)`] - The image.
+/// * [`Err(ImageError::SegmentCountExceeded)`] - There are more than [`MAX_SEGMENTS`] segments.
+/// * [`Err(ImageError::Format(FormatError::InvalidData))`] - The segments are not in
+///   ascending order, overlap, or are out of memory.
+pub fn write_segmented_image(mem: &Mem, segments: &[Range<u16>]) -> Result<Vec<u8>, ImageError> {
+    if segments.len() > MAX_SEGMENTS {
+        return Err(ImageError::SegmentCountExceeded {
+            count: segments.len(),
+        });
+    }
+    let mut next = 0;
+    for segment in segments {
+        if segment.start < next || segment.start > segment.end || segment.end as usize > Mem::SIZE {
+            return Err(FormatError::InvalidData.into());
+        }
+        next = segment.end;
+    }
+    let mut out = Vec::new();
+    write_header(
+        &mut out,
+        &FormatHeader {
+            magic: IMAGE_MAGIC,
+            major: SEGMENTED_IMAGE_MAJOR,
+            minor: IMAGE_MINOR,
+        },
+    );
+    write_u16(&mut out, segments.len() as u16);
+    for segment in segments {
+        write_u16(&mut out, segment.start);
+        write_u16(&mut out, segment.end - segment.start);
+        let bytes = segment.start as usize * 6..segment.end as usize * 6;
+        out.extend_from_slice(&mem.as_byte_slice()[bytes]);
+    }
+    Ok(out)
+}
+
+/// Deserialize an image of either version into the memory.
+///
+/// # Returns
+/// * [`Ok(Mem)`] - The memory.
+/// * [`Err(ImageError::SegmentCountExceeded)`] - The image has more than [`MAX_SEGMENTS`]
+///   segments.
+/// * [`Err(ImageError::Format)`] - The image is malformed or unsupported.
+pub fn read_image(data: &[u8]) -> Result<Mem, ImageError> {
+    let mut reader = ByteReader::new(data);
+    let header = read_header(&mut reader, IMAGE_MAGIC, SEGMENTED_IMAGE_MAJOR)?;
+    if header.major < SEGMENTED_IMAGE_MAJOR {
+        return Ok(read_image_body(&mut reader)?);
+    }
+    let count = reader.read_u16()? as usize;
+    if count > MAX_SEGMENTS {
+        return Err(ImageError::SegmentCountExceeded { count });
+    }
+    let mut mem = Mem::new();
+    let mut next = 0;
+    for _ in 0..count {
+        let base = reader.read_u16()? as usize;
+        let len = reader.read_u16()? as usize;
+        if base < next || base + len > Mem::SIZE {
+            return Err(FormatError::InvalidData.into());
+        }
+        next = base + len;
+        let words = read_words(&mut reader, len)?;
+        mem.as_byte_slice_mut()[base * 6..next * 6].copy_from_slice(words);
+    }
+    Ok(mem)
+}
+
+/// Write the body of an image, i.e. the word count and the words.
+///
+/// Also used by formats embedding a memory image.
+pub fn write_image_body(out: &mut Vec<u8>, mem: &Mem) {
+    write_u16(out, Mem::SIZE as u16);
+    out.extend_from_slice(mem.as_byte_slice());
+}
+
+/// Read the body of an image, i.e. the word count and the words.
+///
+/// Also used by formats embedding a memory image.
+pub fn read_image_body(reader: &mut ByteReader) -> Result<Mem, FormatError> {
+    let count = reader.read_u16()? as usize;
+    if count > Mem::SIZE {
+        return Err(FormatError::InvalidData);
+    }
+    let words = read_words(reader, count)?;
+    let mut mem = Mem::new();
+    mem.as_byte_slice_mut()[..words.len()].copy_from_slice(words);
+    Ok(mem)
+}
+
+/// Read the bytes of `count` words, checking their signs.
+fn read_words<'a>(reader: &mut ByteReader<'a>, count: usize) -> Result<&'a [u8], FormatError> {
+    let words = reader.read_bytes(count * 6)?;
+    if words
+        .chunks_exact(6)
+        .any(|w| w[0] != FullWord::POS && w[0] != FullWord::NEG)
+    {
+        return Err(FormatError::InvalidData);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Mem {
+        let mut mem = Mem::new();
+        mem[0] = FullWord::from_i64(1).0;
+        mem[100] = FullWord::from_i64(-2).0;
+        mem[101] = FullWord::from_i64(3).0;
+        mem[Mem::SIZE as u16 - 1] = FullWord::from_i64(4).0;
+        mem
+    }
+
+    #[test]
+    fn images_of_both_versions_are_read() {
+        let mem = sample();
+        let dense = write_image(&mem);
+        assert_eq!(dense.len(), IMAGE_LEN);
+        assert_eq!(
+            read_image(&dense).unwrap().as_byte_slice(),
+            mem.as_byte_slice()
+        );
+
+        let last = Mem::SIZE as u16 - 1;
+        let segmented = write_segmented_image(&mem, &[0..1, 100..102, last..last + 1]).unwrap();
+        assert_eq!(segmented.len(), 8 + 2 + 3 * 4 + 4 * 6);
+        let read = read_image(&segmented).unwrap();
+        assert_eq!(read.as_byte_slice(), mem.as_byte_slice());
+
+        // Words outside the segments are not stored.
+        let segment = 100..101;
+        let segmented = write_segmented_image(&mem, std::slice::from_ref(&segment)).unwrap();
+        let read = read_image(&segmented).unwrap();
+        assert_eq!(read[100].to_i64().0, -2);
+        assert_eq!(read[0].to_i64().0, 0);
+        assert_eq!(read[101].to_i64().0, 0);
+    }
+
+    #[test]
+    fn segment_count_is_bounded() {
+        let data = include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/many_segments.img"
+        ));
+        assert_eq!(
+            read_image(data).unwrap_err(),
+            ImageError::SegmentCountExceeded { count: 257 }
+        );
+
+        let mem = Mem::new();
+        let segments: Vec<_> = (0..MAX_SEGMENTS as u16 + 1)
+            .map(|i| 2 * i..2 * i + 1)
+            .collect();
+        assert_eq!(
+            write_segmented_image(&mem, &segments).unwrap_err(),
+            ImageError::SegmentCountExceeded {
+                count: MAX_SEGMENTS + 1
+            }
+        );
+        let image = write_segmented_image(&mem, &segments[..MAX_SEGMENTS]).unwrap();
+        assert!(image.len() <= IMAGE_MAX_LEN);
+        assert!(read_image(&image).is_ok());
+    }
+
+    #[test]
+    fn bad_segments_are_rejected() {
+        let mem = Mem::new();
+        let invalid = ImageError::Format(FormatError::InvalidData);
+        let write = |segments: &[Range<u16>]| write_segmented_image(&mem, segments).unwrap_err();
+        assert_eq!(write(&[10..20, 15..30]), invalid);
+        assert_eq!(write(&[10..20, 0..5]), invalid);
+        let size = Mem::SIZE as u16;
+        assert_eq!(write(&[size - 1..size, size..size + 1]), invalid);
+
+        let mut image = write_segmented_image(&mem, &[10..12, 20..21]).unwrap();
+        // Move the second segment into the first one.
+        let second = 8 + 2 + 4 + 2 * 6;
+        image[second..second + 2].copy_from_slice(&11u16.to_le_bytes());
+        assert_eq!(read_image(&image).unwrap_err(), invalid);
+        image[second..second + 2].copy_from_slice(&20u16.to_le_bytes());
+        assert!(read_image(&image).is_ok());
+        image.truncate(image.len() - 1);
+        assert_eq!(
+            read_image(&image).unwrap_err(),
+            ImageError::Format(FormatError::UnexpectedEof)
+        );
+    }
+
+    /// The segments of [`sample()`].
+    const SAMPLE_SEGMENTS: [Range<u16>; 3] =
+        [0..1, 100..102, Mem::SIZE as u16 - 1..Mem::SIZE as u16];
+
+    #[test]
+    fn frozen_images_are_read() {
+        // Produced by the first versions of both formats. They must
+        // keep loading, and writing the same memory must reproduce
+        // them byte for byte.
+        let dense = include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/formats/image-v1.img"
+        ));
+        let segmented = include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/formats/image-v2.img"
+        ));
+        let mem = sample();
+        for data in [&dense[..], &segmented[..]] {
+            assert_eq!(
+                read_image(data).unwrap().as_byte_slice(),
+                mem.as_byte_slice()
+            );
+        }
+        assert_eq!(write_image(&mem), dense);
+        assert_eq!(
+            write_segmented_image(&mem, &SAMPLE_SEGMENTS).unwrap(),
+            segmented
+        );
+    }
+
+    #[test]
+    fn newer_major_versions_are_rejected() {
+        let mut image = write_image(&sample());
+        image[4..6].copy_from_slice(&3u16.to_le_bytes());
+        assert_eq!(
+            read_image(&image).unwrap_err(),
+            ImageError::Format(FormatError::UnsupportedVersion { major: 3, minor: 0 })
+        );
+        image[4..6].copy_from_slice(&0u16.to_le_bytes());
+        assert_eq!(
+            read_image(&image).unwrap_err(),
+            ImageError::Format(FormatError::UnsupportedVersion { major: 0, minor: 0 })
+        );
+
+        // Newer minor versions are compatible.
+        image[4..6].copy_from_slice(&IMAGE_MAJOR.to_le_bytes());
+        image[6..8].copy_from_slice(&7u16.to_le_bytes());
+        assert!(read_image(&image).is_ok());
+        image[0] = b'X';
+        assert_eq!(
+            read_image(&image).unwrap_err(),
+            ImageError::Format(FormatError::BadMagic)
+        );
+    }
+
+    #[test]
+    fn streamed_images_match_the_buffered_ones() {
+        let mut out = Vec::new();
+        write_image_to(&mut out, &sample()).unwrap();
+        assert_eq!(out, write_image(&sample()));
+    }
+}
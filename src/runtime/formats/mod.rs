@@ -0,0 +1,15 @@
+//! On-disk formats of a [`MixVM`].
+//!
+//! All formats start with a header produced by
+//! [`common::write_header()`] and store multi-byte integers in
+//! little-endian order.
+//!
+//! [`MixVM`]: crate::MixVM
+
+pub mod common;
+
+pub mod image;
+
+pub mod scratch;
+
+pub mod snapshot;
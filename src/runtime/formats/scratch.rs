@@ -0,0 +1,135 @@
+//! The scratch store format.
+//!
+//! ```text
+//! +--------+-------+--------------------------------+
+//! | header | count | slots                          |
+//! +--------+-------+--------------------------------+
+//! | 8B     | 1B    | count * (1B + BLOCK_SIZE * 6B) |
+//! +--------+-------+--------------------------------+
+//! ```
+//!
+//! Only filled slots are stored, each as its slot number followed
+//! by the words of its block. See [`ScratchStoreDevice`].
+//!
+//! [`ScratchStoreDevice`]: crate::runtime::ScratchStoreDevice
+
+use super::common::*;
+use crate::runtime::{FullWord, STORAGE_BLOCK_SIZE};
+
+/// The magic number of scratch stores.
+pub const SCRATCH_MAGIC: [u8; 4] = *b"MIXS";
+
+/// The major version of scratch stores written by this version.
+pub const SCRATCH_MAJOR: u16 = 1;
+
+/// The minor version of scratch stores written by this version.
+pub const SCRATCH_MINOR: u16 = 0;
+
+/// The number of slots in a scratch store.
+pub const SCRATCH_SLOTS: usize = 64;
+
+/// The number of words in a slot.
+pub const SCRATCH_BLOCK_SIZE: usize = STORAGE_BLOCK_SIZE;
+
+/// The length of a scratch store with all slots filled, which no
+/// valid store exceeds.
+pub const SCRATCH_MAX_LEN: usize = 8 + 1 + SCRATCH_SLOTS * (1 + SCRATCH_BLOCK_SIZE * 6);
+
+/// The slots of a scratch store, by slot number.
+///
+/// Empty slots are `None`. Filled slots hold exactly
+/// [`SCRATCH_BLOCK_SIZE`] words.
+pub type ScratchSlots = Vec<Option<Vec<FullWord>>>;
+
+/// Errors returned by scratch stores.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScratchError {
+    /// The header or the slot table is malformed.
+    Format(FormatError),
+
+    /// The data ends before every slot in the table is read.
+    Truncated {
+        /// The number of the slot the data ends in, or `None` if it
+        /// ends before the number of a slot.
+        slot: Option<u8>,
+    },
+
+    /// The file cannot be accessed.
+    Io(std::io::ErrorKind),
+}
+
+impl From<FormatError> for ScratchError {
+    fn from(err: FormatError) -> Self {
+        ScratchError::Format(err)
+    }
+}
+
+/// Create the slots of an empty store.
+pub fn empty_slots() -> ScratchSlots {
+    vec![None; SCRATCH_SLOTS]
+}
+
+/// Serialize the slots into a scratch store.
+///
+/// # Arguments
+/// * `slots` - At most [`SCRATCH_SLOTS`] slots, each empty or of [`SCRATCH_BLOCK_SIZE`] words.
+pub fn write_scratch(slots: &[Option<Vec<FullWord>>]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_header(
+        &mut out,
+        &FormatHeader {
+            magic: SCRATCH_MAGIC,
+            major: SCRATCH_MAJOR,
+            minor: SCRATCH_MINOR,
+        },
+    );
+    let filled = slots
+        .iter()
+        .take(SCRATCH_SLOTS)
+        .enumerate()
+        .filter_map(|(slot, words)| Some((slot as u8, words.as_deref()?)))
+        .collect::<Vec<_>>();
+    out.push(filled.len() as u8);
+    for (slot, words) in filled {
+        out.push(slot);
+        for word in words {
+            write_word(&mut out, word);
+        }
+    }
+    out
+}
+
+/// Deserialize a scratch store.
+///
+/// # Returns
+/// * [`Ok(ScratchSlots)`] - The [`SCRATCH_SLOTS`] slots.
+/// * [`Err(ScratchError::Truncated)`] - The data ends before every slot is read.
+/// * [`Err(ScratchError::Format)`] - The store is malformed or unsupported.
+pub fn read_scratch(data: &[u8]) -> Result<ScratchSlots, ScratchError> {
+    let mut reader = ByteReader::new(data);
+    read_header(&mut reader, SCRATCH_MAGIC, SCRATCH_MAJOR)?;
+    let count = reader.read_u8()? as usize;
+    if count > SCRATCH_SLOTS {
+        return Err(FormatError::InvalidData.into());
+    }
+    let mut slots = empty_slots();
+    let truncated = |slot| {
+        move |err| match err {
+            FormatError::UnexpectedEof => ScratchError::Truncated { slot },
+            err => ScratchError::Format(err),
+        }
+    };
+    for _ in 0..count {
+        let slot = reader.read_u8().map_err(truncated(None))?;
+        let entry = slots
+            .get_mut(slot as usize)
+            .filter(|entry| entry.is_none())
+            .ok_or(FormatError::InvalidData)?;
+        let words = (0..SCRATCH_BLOCK_SIZE)
+            .map(|_| reader.read_word())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(truncated(Some(slot)))?;
+        *entry = Some(words);
+    }
+    Ok(slots)
+}
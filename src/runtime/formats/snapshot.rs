@@ -0,0 +1,207 @@
+//! The machine snapshot format.
+//!
+//! ```text
+//! +--------+-----+-----+-----------+-----+------+----------+--------+-------+------------+
+//! | header | rA  | rX  | rI1..rI6  | rJ  | comp | overflow | halted | pc    | image body |
+//! +--------+-----+-----+-----------+-----+------+----------+--------+-------+------------+
+//! | 8B     | 6B  | 6B  | 6 * 3B    | 3B  | 1B   | 1B       | 1B     | 2B LE | see image  |
+//! +--------+-----+-----+-----------+-----+------+----------+--------+-------+------------+
+//! ```
+//!
+//! `comp` is `0` for [`CompIndicator::Equal`], `1` for
+//! [`CompIndicator::Less`], `2` for [`CompIndicator::Greater`] and
+//! `3` for [`CompIndicator::Unordered`]. Booleans are `0` or `1`.
+
+use super::common::*;
+use super::image::{read_image_body, write_image_body};
+use crate::runtime::{CompIndicator, HalfWord, VMStateSnapshot};
+
+/// The magic number of snapshots.
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"MIXS";
+
+/// The major version of snapshots written by this version.
+pub const SNAPSHOT_MAJOR: u16 = 1;
+
+/// The minor version of snapshots written by this version.
+pub const SNAPSHOT_MINOR: u16 = 0;
+
+/// Serialize a snapshot.
+pub fn write_snapshot(snapshot: &VMStateSnapshot) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_header(
+        &mut out,
+        &FormatHeader {
+            magic: SNAPSHOT_MAGIC,
+            major: SNAPSHOT_MAJOR,
+            minor: SNAPSHOT_MINOR,
+        },
+    );
+    write_word(&mut out, &snapshot.r_a);
+    write_word(&mut out, &snapshot.r_x);
+    for reg in &snapshot.r_in[1..=6] {
+        write_word(&mut out, reg);
+    }
+    write_word(&mut out, &snapshot.r_j);
+    out.push(match snapshot.comp {
+        CompIndicator::Equal => 0,
+        CompIndicator::Less => 1,
+        CompIndicator::Greater => 2,
+        CompIndicator::Unordered => 3,
+    });
+    out.push(u8::from(snapshot.overflow));
+    out.push(u8::from(snapshot.halted));
+    write_u16(&mut out, snapshot.pc);
+    write_image_body(&mut out, &snapshot.mem);
+    out
+}
+
+/// Deserialize a snapshot.
+///
+/// # Returns
+/// * [`Ok(VMStateSnapshot)`] - The snapshot.
+/// * [`Err(FormatError)`] - The snapshot is malformed or unsupported.
+pub fn read_snapshot(data: &[u8]) -> Result<VMStateSnapshot, FormatError> {
+    let mut reader = ByteReader::new(data);
+    read_header(&mut reader, SNAPSHOT_MAGIC, SNAPSHOT_MAJOR)?;
+    let r_a = reader.read_word()?;
+    let r_x = reader.read_word()?;
+    let mut r_in: [HalfWord; 7] = Default::default();
+    for reg in &mut r_in[1..=6] {
+        *reg = reader.read_word()?;
+    }
+    let r_j = reader.read_word()?;
+    let comp = match reader.read_u8()? {
+        0 => CompIndicator::Equal,
+        1 => CompIndicator::Less,
+        2 => CompIndicator::Greater,
+        3 => CompIndicator::Unordered,
+        _ => return Err(FormatError::InvalidData),
+    };
+    let overflow = read_bool(&mut reader)?;
+    let halted = read_bool(&mut reader)?;
+    let pc = reader.read_u16()?;
+    let mem = read_image_body(&mut reader)?;
+    Ok(VMStateSnapshot {
+        r_a,
+        r_x,
+        r_in,
+        r_j,
+        comp,
+        overflow,
+        halted,
+        pc,
+        mem,
+    })
+}
+
+/// Read a boolean stored as `0` or `1`.
+fn read_bool(reader: &mut ByteReader) -> Result<bool, FormatError> {
+    match reader.read_u8()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(FormatError::InvalidData),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::image::write_image;
+    use super::*;
+    use crate::runtime::{FullWord, Mem, PosHalfWord};
+
+    /// A snapshot with every register set, using only bytes below 64
+    /// so that it means the same with the `byte-64` feature.
+    fn sample() -> VMStateSnapshot {
+        let mut r_in: [HalfWord; 7] = Default::default();
+        for (i, reg) in r_in.iter_mut().enumerate().skip(1) {
+            *reg = HalfWord::from_bytes([(i % 2) as u8, i as u8, 10 + i as u8]);
+        }
+        let mut mem = Mem::new();
+        mem[0] = FullWord::from_bytes([FullWord::NEG, 1, 2, 3, 4, 5]);
+        mem[3999] = FullWord::from_bytes([FullWord::POS, 63, 0, 63, 0, 63]);
+        VMStateSnapshot {
+            r_a: FullWord::from_bytes([FullWord::NEG, 10, 20, 30, 40, 50]),
+            r_x: FullWord::from_bytes([FullWord::POS, 5, 4, 3, 2, 1]),
+            r_in,
+            r_j: PosHalfWord::from_bytes([PosHalfWord::POS, 31, 7]),
+            comp: CompIndicator::Greater,
+            overflow: true,
+            halted: false,
+            pc: 3000,
+            mem,
+        }
+    }
+
+    fn assert_same(left: &VMStateSnapshot, right: &VMStateSnapshot) {
+        assert_eq!(left.r_a.as_array(), right.r_a.as_array());
+        assert_eq!(left.r_x.as_array(), right.r_x.as_array());
+        for (l, r) in left.r_in.iter().zip(&right.r_in) {
+            assert_eq!(l.as_array(), r.as_array());
+        }
+        assert_eq!(left.r_j.as_array(), right.r_j.as_array());
+        assert_eq!(left.comp, right.comp);
+        assert_eq!(left.overflow, right.overflow);
+        assert_eq!(left.halted, right.halted);
+        assert_eq!(left.pc, right.pc);
+        assert_eq!(left.mem.as_byte_slice(), right.mem.as_byte_slice());
+    }
+
+    #[test]
+    fn frozen_snapshot_is_read() {
+        // Produced by the first version of the format. It must keep
+        // loading, and writing the same state must reproduce it byte
+        // for byte.
+        let frozen = include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/formats/snapshot-v1.snap"
+        ));
+        assert_same(&read_snapshot(frozen).unwrap(), &sample());
+        assert_eq!(write_snapshot(&sample()), frozen);
+    }
+
+    #[test]
+    fn every_comparison_round_trips() {
+        for comp in [
+            CompIndicator::Equal,
+            CompIndicator::Less,
+            CompIndicator::Greater,
+            CompIndicator::Unordered,
+        ] {
+            let snapshot = VMStateSnapshot { comp, ..sample() };
+            assert_same(
+                &read_snapshot(&write_snapshot(&snapshot)).unwrap(),
+                &snapshot,
+            );
+        }
+    }
+
+    #[test]
+    fn bad_snapshots_are_rejected() {
+        let data = write_snapshot(&sample());
+        let mut newer = data.clone();
+        newer[4..6].copy_from_slice(&(SNAPSHOT_MAJOR + 1).to_le_bytes());
+        assert_eq!(
+            read_snapshot(&newer).unwrap_err(),
+            FormatError::UnsupportedVersion {
+                major: SNAPSHOT_MAJOR + 1,
+                minor: SNAPSHOT_MINOR
+            }
+        );
+
+        // Offsets of the fields after the registers.
+        let comp = 8 + 6 + 6 + 6 * 3 + 3;
+        for (offset, value) in [(comp, 4), (comp + 1, 2), (comp + 2, 2), (8, 2)] {
+            let mut bad = data.clone();
+            bad[offset] = value;
+            assert_eq!(read_snapshot(&bad).unwrap_err(), FormatError::InvalidData);
+        }
+        assert_eq!(
+            read_snapshot(&data[..comp]).unwrap_err(),
+            FormatError::UnexpectedEof
+        );
+        assert_eq!(
+            read_snapshot(&write_image(&Mem::new())).unwrap_err(),
+            FormatError::BadMagic
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! A small mutation fuzzer for the text formats.
+//!
+//! Each format has a corpus in `tests/fuzz/<format>`, one input per
+//! file. [`fuzz()`] feeds every input of the corpus to a target, then
+//! as many inputs mutated from them as `MIX_FUZZ_ITERATIONS` asks for,
+//! 1000 by default. The mutations are drawn from a fixed seed, so a
+//! failure reproduces. Targets must return normally on any input:
+//! rejecting it with an error is fine, panicking is not.
+//!
+//! An input found to panic belongs in the corpus once it is fixed.
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+use super::*;
+
+/// Tokens worth splicing into any format.
+const TOKENS: &[&str] = &[
+    "0",
+    "-",
+    "+",
+    "*",
+    "/",
+    "//",
+    ":",
+    ",",
+    "(",
+    ")",
+    "=",
+    "\"",
+    " ",
+    "\n",
+    "#",
+    "3999",
+    "4000",
+    "65535",
+    "1099511627775",
+    "-1099511627775",
+    "9223372036854775807",
+    "-9223372036854775808",
+    "18446744073709551615",
+    "99999999999999999999999",
+    "(0:0)",
+    "(5:0)",
+    "(1:5)",
+    "\u{e9}",
+];
+
+/// A linear congruential generator.
+struct Rng(u64);
+
+impl Rng {
+    /// Get a number below `bound`, which must not be 0.
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 33) as usize % bound
+    }
+}
+
+/// Mutate an input a few times.
+fn mutate(rng: &mut Rng, input: &str, tokens: &[&str]) -> String {
+    let mut chars: Vec<char> = input.chars().collect();
+    for _ in 0..1 + rng.below(4) {
+        let at = rng.below(chars.len() + 1);
+        match rng.below(5) {
+            0 if at < chars.len() => chars[at] = (b' ' + rng.below(95) as u8) as char,
+            1 if at < chars.len() => {
+                let len = 1 + rng.below((chars.len() - at).min(8));
+                chars.drain(at..at + len);
+            }
+            2 if at < chars.len() => {
+                let len = 1 + rng.below((chars.len() - at).min(16));
+                let copy: Vec<char> = chars[at..at + len].to_vec();
+                chars.splice(at..at, copy);
+            }
+            3 => {
+                let token = tokens[rng.below(tokens.len())];
+                chars.splice(at..at, token.chars());
+            }
+            _ => {
+                let token = TOKENS[rng.below(TOKENS.len())];
+                chars.splice(at..at, token.chars());
+            }
+        }
+    }
+    chars.into_iter().collect()
+}
+
+/// Read the corpus of a format, in the order of the file names.
+fn corpus(format: &str) -> Vec<String> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fuzz")
+        .join(format);
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .unwrap_or_else(|err| panic!("{}: {}", dir.display(), err))
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| fs::read_to_string(path).unwrap())
+        .collect()
+}
+
+/// Run a target on the corpus of a format and on mutations of it,
+/// panicking with the inputs that made the target panic.
+///
+/// # Arguments
+/// * `format` - The name of the corpus directory.
+/// * `tokens` - Tokens of the format worth splicing into inputs.
+/// * `target` - The code under test.
+pub(crate) fn fuzz(format: &str, tokens: &[&str], mut target: impl FnMut(&str)) {
+    let corpus = corpus(format);
+    assert!(!corpus.is_empty(), "empty corpus for {}", format);
+    let iterations = std::env::var("MIX_FUZZ_ITERATIONS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(1000);
+    let tokens = if tokens.is_empty() { TOKENS } else { tokens };
+
+    let mut rng = Rng(0x5EED ^ format.len() as u64);
+    let mutated = (0..iterations).map(|_| {
+        let input = &corpus[rng.below(corpus.len())];
+        mutate(&mut rng, input, tokens)
+    });
+    let inputs: Vec<String> = corpus.iter().cloned().chain(mutated).collect();
+
+    let failures: Vec<&String> = inputs
+        .iter()
+        .filter(|input| panic::catch_unwind(AssertUnwindSafe(|| target(input))).is_err())
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "{} inputs of {} panic, the first ones:\n{:#?}",
+        failures.len(),
+        format,
+        &failures[..failures.len().min(5)]
+    );
+}
+
+#[test]
+fn mixal_sources() {
+    let tokens = [
+        " CON  ",
+        " ORIG ",
+        " EQU  ",
+        " END  ",
+        " ALF  ",
+        " LDA  ",
+        " JMP  ",
+        "X",
+        "*",
+        "1(1:3)",
+        ",",
+        "//",
+        "1099511627775",
+        "\n",
+    ];
+    fuzz("mixal", &tokens, |source| {
+        if let Ok(assembly) = asm::assemble(source) {
+            assembly.segments();
+        }
+    });
+}
+
+#[test]
+fn word_literals() {
+    fuzz("literal", &[], |text| {
+        if let Ok(word) = FullWord::parse_literal(text) {
+            let again = FullWord::parse_literal(&word.to_string()).unwrap();
+            assert_eq!(again.as_array(), word.as_array());
+        }
+    });
+}
+
+#[test]
+fn golden_cases() {
+    let tokens = [
+        " | ",
+        "rA=",
+        "rI1=",
+        "rJ=",
+        "pc=",
+        "4000=",
+        "+0:0:0:0:1",
+        "LDA ",
+        "\n",
+    ];
+    fuzz("golden", &tokens, |text| {
+        if let Ok(cases) = conformance::parse_golden_cases(text) {
+            conformance::check_golden_cases(&cases);
+        }
+    });
+}
+
+#[test]
+fn card_decks() {
+    fuzz("cards", &[], |text| {
+        for policy in [
+            InputPolicy::Strict,
+            InputPolicy::Skip,
+            InputPolicy::Replace(Alphabet::Space),
+        ] {
+            if let Ok(words) = encode_words(text, policy) {
+                assert!(words.iter().all(|word| word.is_positive()));
+            }
+        }
+    });
+}
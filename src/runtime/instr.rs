@@ -1,6 +1,8 @@
 use core::ops::RangeInclusive;
 
+use super::io::{IOC_BARRIER_UNIT, IO_UNITS};
 use super::mem::FullWord;
+use super::vm::ErrorCode;
 
 /// An instruction in [`MixVM`].
 ///
@@ -42,6 +44,210 @@ impl Instruction {
             opcode,
         }
     }
+
+    /// Get the `F` part as a field `(L:R)`.
+    ///
+    /// See [`ToRangeInclusive::to_range_inclusive()`].
+    pub fn field_as_range(&self) -> RangeInclusive<usize> {
+        self.field.to_range_inclusive()
+    }
+
+    /// Get the `F` part as a field `(L:R)`, removing the sign byte
+    /// from the range if necessary.
+    ///
+    /// See [`ToRangeInclusive::to_range_inclusive_signless()`].
+    pub fn field_as_range_signless(&self) -> (RangeInclusive<usize>, bool) {
+        self.field.to_range_inclusive_signless()
+    }
+
+    /// Check if the instruction is an `x-ieee754` operation.
+    pub const fn is_ieee754(&self) -> bool {
+        match self.opcode {
+            Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div | Opcode::CmpA | Opcode::CmpX => {
+                self.field == 7
+            }
+            Opcode::Special => self.field >= 3 && self.field <= 8,
+            Opcode::Jmp => self.field == 10 || self.field == 11,
+            _ => false,
+        }
+    }
+
+    /// Check if the instruction is an `x-binarith` operation.
+    pub const fn is_binarith(&self) -> bool {
+        matches!(self.opcode, Opcode::Special) && self.field >= 9 && self.field <= 14
+    }
+
+    /// Check if the instruction belongs to an extension disabled in
+    /// this build.
+    ///
+    /// `NOT`, `AND`, `OR` and `XOR` predate the `x-binarith` feature
+    /// and are always enabled.
+    pub const fn is_disabled_extension(&self) -> bool {
+        (self.is_ieee754() && !cfg!(feature = "x-ieee754"))
+            || (self.is_binarith() && self.field >= 13 && !cfg!(feature = "x-binarith"))
+    }
+
+    /// Check statically if the instruction is legal.
+    ///
+    /// Except for `NOP`, which is never examined, the `I` part must
+    /// name an index register, and the `F` part must be legal for the
+    /// opcode:
+    ///
+    /// * Arithmetic, loads, stores and comparisons take a field `(L:R)`
+    ///   with `L <= R <= 5`, except `STJ`, whose field must lie within
+    ///   `(0:2)` as `rJ` has only 2 bytes. Comparisons and arithmetic
+    ///   also take 7 for their `x-ieee754` counterparts if the feature
+    ///   is enabled.
+    /// * Operations of disabled extensions are rejected.
+    /// * IO instructions take a unit number below [`IO_UNITS`]. `IOC` also takes
+    ///   [`IOC_BARRIER_UNIT`].
+    /// * Jumps, shifts, address transfers and `Special` take a defined mode.
+    /// * `MOVE` takes any word count.
+    ///
+    /// The effective address is not checked, as it depends on the
+    /// index registers at runtime.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The instruction is legal.
+    /// * [`Err(ErrorCode::InvalidIndex)`] - The `I` part is not in `0..=6`.
+    /// * [`Err(ErrorCode::InvalidFieldRange)`] - The `F` part is not a legal field.
+    /// * [`Err(ErrorCode::InvalidFieldMode)`] - The `F` part selects no operation.
+    /// * [`Err(ErrorCode::UnknownDevice)`] - The `F` part is not a unit number.
+    pub fn validate(&self) -> Result<(), ErrorCode> {
+        if self.opcode == Opcode::Nop {
+            return Ok(());
+        }
+        if self.index > 6 {
+            return Err(ErrorCode::InvalidIndex);
+        }
+        match self.opcode {
+            Opcode::Move => Ok(()),
+            _ if self.opcode.has_field_modes() => match self.opcode.mnemonic(self.field) {
+                Some(_) if !self.is_disabled_extension() => Ok(()),
+                _ => Err(ErrorCode::InvalidFieldMode {
+                    opcode: self.opcode,
+                    field: self.field,
+                }),
+            },
+            _ if self.opcode.is_io() => {
+                if (self.field as usize) < IO_UNITS
+                    || (self.opcode == Opcode::Ioc && self.field == IOC_BARRIER_UNIT)
+                {
+                    Ok(())
+                } else {
+                    Err(ErrorCode::UnknownDevice)
+                }
+            }
+            _ if self.is_ieee754() && cfg!(feature = "x-ieee754") => Ok(()),
+            _ => {
+                let (l, r) = (self.field / 8, self.field % 8);
+                let max = if self.opcode == Opcode::StJ { 2 } else { 5 };
+                if l > r || r > max {
+                    Err(ErrorCode::InvalidFieldRange { field: self.field })
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+/// A builder of [`Instruction`]s taking the default `F` part of
+/// the opcode unless told otherwise.
+///
+/// ```text
+/// let instr = InstructionBuilder::new(Opcode::LdA).at(1000).indexed(1)?.build();
+/// assert_eq!(instr.field, 5);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct InstructionBuilder {
+    /// The signed address, `A`.
+    addr: i16,
+
+    /// The field, `F`, if given.
+    field: Option<u8>,
+
+    /// The index, `I`.
+    index: u8,
+
+    /// The operation code, `C`.
+    opcode: Opcode,
+}
+
+impl InstructionBuilder {
+    /// Create a new builder of an unindexed instruction at address 0.
+    pub const fn new(opcode: Opcode) -> Self {
+        InstructionBuilder {
+            addr: 0,
+            field: None,
+            index: 0,
+            opcode,
+        }
+    }
+
+    /// Set the address, `A`.
+    pub const fn at(mut self, addr: i16) -> Self {
+        self.addr = addr;
+        self
+    }
+
+    /// Set the index, `I`.
+    ///
+    /// # Returns
+    /// * [`Ok(InstructionBuilder)`] - The builder.
+    /// * [`Err(())`] - `index` is not in `0..=6`.
+    pub const fn indexed(mut self, index: u8) -> Result<Self, ()> {
+        if index > 6 {
+            return Err(());
+        }
+        self.index = index;
+        Ok(self)
+    }
+
+    /// Set the field, `F`, instead of the default of the opcode.
+    pub const fn with_field(mut self, field: u8) -> Self {
+        self.field = Some(field);
+        self
+    }
+
+    /// Build the instruction.
+    ///
+    /// The `F` part is [`Opcode::default_field()`] unless set by
+    /// [`InstructionBuilder::with_field()`]. The instruction is not
+    /// validated, see [`Instruction::validate()`].
+    pub const fn build(self) -> Instruction {
+        let field = match self.field {
+            Some(field) => field,
+            None => self.opcode.default_field(),
+        };
+        Instruction::new(self.addr, field, self.index, self.opcode)
+    }
+}
+
+/// Check statically if every word of a program is a legal
+/// instruction.
+///
+/// See [`Instruction::validate()`] for the rules. Words that do
+/// not decode to an instruction are reported as
+/// [`ErrorCode::IllegalInstruction`], so data words should be
+/// excluded from `words`.
+///
+/// # Arguments
+/// * `words` - The words to check, starting from offset 0.
+///
+/// # Returns
+/// * [`Vec<(u16, ErrorCode)>`] - The offset and the error of each illegal word.
+pub fn validate_program(words: &[FullWord]) -> Vec<(u16, ErrorCode)> {
+    words
+        .iter()
+        .enumerate()
+        .filter_map(|(offset, &word)| {
+            let result = Instruction::try_from(word)
+                .map_err(|_| ErrorCode::IllegalInstruction)
+                .and_then(|instr| instr.validate());
+            result.err().map(|err| (offset as u16, err))
+        })
+        .collect()
 }
 
 impl TryFrom<FullWord> for Instruction {
@@ -160,6 +366,22 @@ pub enum Opcode {
     /// ```text
     /// rA <- rA OP V
     /// ```
+    ///
+    /// * `POPCNT(13)` (`x-binarith`): Count the one bits in the magnitude of `rA`.
+    /// * `CLZ(14)` (`x-binarith`): Count the leading zero bits in the 5-byte magnitude of `rA`.
+    ///
+    /// ```text
+    /// rA <- +OP(rA)
+    /// ```
+    ///
+    /// ------
+    ///
+    /// * `IDCAP(15)`: Report the enabled extensions. Fails with
+    ///   [`ErrorCode::IllegalInstruction`] if none is enabled.
+    ///
+    /// ```text
+    /// rA <- ExtensionSet bits; rX <- bits per byte
+    /// ```
     Special = 5,
 
     /// * `SLA(0)` - Shift left `rA`.
@@ -343,7 +565,7 @@ pub enum Opcode {
     /// ```
     StX = 31,
 
-    /// * `STJ(0:2)` - Store `rJ`.
+    /// * `STJ(0:2)` - Store `rJ`. The field must lie within `(0:2)`.
     ///
     /// ```text
     /// M(F) <- rJ
@@ -688,6 +910,102 @@ pub enum Opcode {
     CmpX = 63,
 }
 
+impl Opcode {
+    /// Convert a byte to an [`Opcode`] in `const` contexts.
+    ///
+    /// Equivalent to `Opcode::try_from(n).ok()`, which is not `const`.
+    ///
+    /// ```text
+    /// const _: () = assert!(Opcode::from_u8_const(8).is_some());
+    /// ```
+    ///
+    /// # Returns
+    /// * [`Some(Opcode)`] - The opcode numbered `n`.
+    /// * [`None`] - `n` is not in `0..=63`.
+    pub const fn from_u8_const(n: u8) -> Option<Opcode> {
+        match n {
+            0 => Some(Opcode::Nop),
+            1 => Some(Opcode::Add),
+            2 => Some(Opcode::Sub),
+            3 => Some(Opcode::Mul),
+            4 => Some(Opcode::Div),
+            5 => Some(Opcode::Special),
+            6 => Some(Opcode::Shift),
+            7 => Some(Opcode::Move),
+            8 => Some(Opcode::LdA),
+            9 => Some(Opcode::Ld1),
+            10 => Some(Opcode::Ld2),
+            11 => Some(Opcode::Ld3),
+            12 => Some(Opcode::Ld4),
+            13 => Some(Opcode::Ld5),
+            14 => Some(Opcode::Ld6),
+            15 => Some(Opcode::LdX),
+            16 => Some(Opcode::LdAN),
+            17 => Some(Opcode::Ld1N),
+            18 => Some(Opcode::Ld2N),
+            19 => Some(Opcode::Ld3N),
+            20 => Some(Opcode::Ld4N),
+            21 => Some(Opcode::Ld5N),
+            22 => Some(Opcode::Ld6N),
+            23 => Some(Opcode::LdXN),
+            24 => Some(Opcode::StA),
+            25 => Some(Opcode::St1),
+            26 => Some(Opcode::St2),
+            27 => Some(Opcode::St3),
+            28 => Some(Opcode::St4),
+            29 => Some(Opcode::St5),
+            30 => Some(Opcode::St6),
+            31 => Some(Opcode::StX),
+            32 => Some(Opcode::StJ),
+            33 => Some(Opcode::StZ),
+            34 => Some(Opcode::Jbus),
+            35 => Some(Opcode::Ioc),
+            36 => Some(Opcode::In),
+            37 => Some(Opcode::Out),
+            38 => Some(Opcode::Jred),
+            39 => Some(Opcode::Jmp),
+            40 => Some(Opcode::JA),
+            41 => Some(Opcode::J1),
+            42 => Some(Opcode::J2),
+            43 => Some(Opcode::J3),
+            44 => Some(Opcode::J4),
+            45 => Some(Opcode::J5),
+            46 => Some(Opcode::J6),
+            47 => Some(Opcode::JX),
+            48 => Some(Opcode::ModifyA),
+            49 => Some(Opcode::Modify1),
+            50 => Some(Opcode::Modify2),
+            51 => Some(Opcode::Modify3),
+            52 => Some(Opcode::Modify4),
+            53 => Some(Opcode::Modify5),
+            54 => Some(Opcode::Modify6),
+            55 => Some(Opcode::ModifyX),
+            56 => Some(Opcode::CmpA),
+            57 => Some(Opcode::Cmp1),
+            58 => Some(Opcode::Cmp2),
+            59 => Some(Opcode::Cmp3),
+            60 => Some(Opcode::Cmp4),
+            61 => Some(Opcode::Cmp5),
+            62 => Some(Opcode::Cmp6),
+            63 => Some(Opcode::CmpX),
+            _ => None,
+        }
+    }
+}
+
+// `Opcode::from_u8_const()` must agree with the discriminants.
+const _: () = {
+    let mut n = 0;
+    while n < 64 {
+        match Opcode::from_u8_const(n) {
+            Some(opcode) => assert!(opcode as u8 == n),
+            None => panic!("opcode missing from `Opcode::from_u8_const()`"),
+        }
+        n += 1;
+    }
+    assert!(Opcode::from_u8_const(64).is_none());
+};
+
 /// Used when converting a type to a [`RangeInclusive<T>`].
 pub trait ToRangeInclusive<T> {
     /// Convert some value to [`RangeInclusive<T>`].
@@ -1,4 +1,47 @@
-use super::FullWord;
+use super::{Alphabet, FullWord};
+
+/// The number of IO units of a [`MixVM`].
+///
+/// Units 0-20 are the standard devices of MIX, the last of them
+/// the paper tape. Unit 21 is [`CONSOLE_SWITCH_UNIT`].
+///
+/// [`MixVM`]: crate::MixVM
+pub const IO_UNITS: usize = 22;
+
+/// The unit whose `IOC` the machine handles as a barrier.
+///
+/// Every `IN` and `OUT` hands its block to the device when it runs,
+/// so devices see transfers in instruction order. A transfer then
+/// keeps its unit busy for the time it takes, see
+/// [`IODevice::bandwidth()`], and units are busy independently of
+/// each other: a fast unit may be ready again while a slow one
+/// started earlier is still busy. `IOC 0(63)` waits until no unit is
+/// busy and flushes every device, so that whatever a program wrote
+/// before it is complete and written out before it goes on.
+///
+/// No device is ever attached to the unit. Its `M` is reserved and
+/// should be 0. 63 is the largest `F` with 64-value bytes.
+pub const IOC_BARRIER_UNIT: u8 = 63;
+
+/// Causes of a failed [`IODevice::read_nonblocking()`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NonBlockingIOCause {
+    /// No complete block is available yet.
+    WouldBlock,
+
+    /// The device encountered an error.
+    Error,
+}
+
+/// Details of a failed [`IODevice::read_nonblocking()`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NonBlockingIOResult {
+    /// The cause of the failure.
+    pub cause: NonBlockingIOCause,
+
+    /// The amount of words already written into the buffer.
+    pub partial_words: usize,
+}
 
 /// A device plugged into a [`MixVM`] to perform IO
 /// operations.
@@ -21,6 +64,45 @@ pub trait IODevice {
     /// * `buffer` - The buffer to read into.
     fn read(&mut self, buffer: &mut [FullWord]) -> Result<(), ()>;
 
+    /// Read a block of [`FullWord`]s from the device into the buffer
+    /// without waiting for data to arrive.
+    ///
+    /// If no complete block is available, this method fails with
+    /// [`NonBlockingIOCause::WouldBlock`], and [`IODevice::is_busy()`]
+    /// should return `true` until it is.
+    ///
+    /// The default implementation calls [`IODevice::read()`], which
+    /// may block.
+    ///
+    /// # Arguments
+    /// * `buffer` - The buffer to read into.
+    fn read_nonblocking(&mut self, buffer: &mut [FullWord]) -> Result<(), NonBlockingIOResult> {
+        self.read(buffer).map_err(|_| NonBlockingIOResult {
+            cause: NonBlockingIOCause::Error,
+            partial_words: 0,
+        })
+    }
+
+    /// Read the next block of [`FullWord`]s into the buffer without
+    /// consuming it.
+    ///
+    /// A following [`IODevice::read()`] reads the same block, so a
+    /// host may check that a complete block is available where
+    /// [`IODevice::is_ready()`] does not tell.
+    ///
+    /// The default implementation fails, as not every device can
+    /// look ahead.
+    ///
+    /// # Arguments
+    /// * `buffer` - The buffer to read into.
+    ///
+    /// # Returns
+    /// * [`Ok(usize)`] - The number of words read.
+    /// * [`Err(())`] - The device cannot look ahead or has no block to read.
+    fn peek(&self, _buffer: &mut [FullWord]) -> Result<usize, ()> {
+        Err(())
+    }
+
     /// Write a block of [`FullWord`]s out through the device.
     ///
     /// This method will always try to write a whole block. It will fail
@@ -50,4 +132,132 @@ pub trait IODevice {
     /// Get the count of [`FullWord`]s in a device block,
     /// that is, read or written in a single operation.
     fn get_block_size(&self) -> usize;
+
+    /// Write out any output buffered by the device.
+    ///
+    /// The machine flushes all devices when it executes `HLT`, and
+    /// [`VM::run_until_halt()`] flushes them on every exit path, so
+    /// that no buffered output is lost.
+    ///
+    /// The default implementation does nothing.
+    ///
+    /// [`VM::run_until_halt()`]: super::VM::run_until_halt
+    fn flush(&mut self) -> Result<(), ()> {
+        Ok(())
+    }
+
+    /// Get a human-readable name of the device.
+    ///
+    /// The name is used to annotate disassembly and diagnostics.
+    fn name(&self) -> &str {
+        "unnamed device"
+    }
+
+    /// Return the device to its initial state.
+    ///
+    /// Buffered input and output are discarded without being
+    /// transferred. See [`VM::reset_devices()`].
+    ///
+    /// The default implementation does nothing.
+    ///
+    /// [`VM::reset_devices()`]: super::VM::reset_devices
+    fn reset(&mut self) {}
+
+    /// Check if the device has no more input.
+    ///
+    /// A failed read of a device at its end of input is reported as
+    /// [`ErrorCode::EndOfInput`] if [`VM::report_end_of_input`] is set.
+    ///
+    /// The default implementation returns `false`.
+    ///
+    /// [`ErrorCode::EndOfInput`]: super::ErrorCode::EndOfInput
+    /// [`VM::report_end_of_input`]: super::VM::report_end_of_input
+    fn at_eof(&self) -> bool {
+        false
+    }
+
+    /// Position the device at a block, for random-access devices.
+    ///
+    /// The machine calls this method for `IOC 0` on disk units, with
+    /// the block number taken from `rX`.
+    ///
+    /// The default implementation fails, as the device does not
+    /// support seeking.
+    ///
+    /// # Arguments
+    /// * `block` - The number of the block.
+    fn seek(&mut self, block: u64) -> Result<(), ()> {
+        let _ = block;
+        Err(())
+    }
+
+    /// Get the transfer rate of the device, in words per cycle.
+    ///
+    /// If given, the machine keeps the device busy after each `IN`
+    /// or `OUT` for as many cycles as the block takes to transfer,
+    /// in addition to [`IODevice::is_busy()`]. An `IN`, `OUT` or `IOC`
+    /// on a busy device waits until it is free.
+    ///
+    /// The default implementation returns [`None`], so that
+    /// transfers take no time.
+    fn bandwidth(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Statistics of the transfers on an IO unit.
+///
+/// See [`VM::device_stats()`].
+///
+/// [`VM::device_stats()`]: super::VM::device_stats
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct DeviceStats {
+    /// The number of blocks read by `IN`.
+    pub blocks_read: u64,
+
+    /// The number of words read by `IN`.
+    pub words_read: u64,
+
+    /// The number of blocks written by `OUT`.
+    pub blocks_written: u64,
+
+    /// The number of words written by `OUT`.
+    pub words_written: u64,
+
+    /// The number of `IOC` commands.
+    pub controls: u64,
+
+    /// The number of cycles the unit was kept busy by transfers.
+    ///
+    /// See [`IODevice::bandwidth()`].
+    pub busy_cycles: u64,
+}
+
+/// Decode a block of words into a string of [`Alphabet`] characters.
+///
+/// Bytes 1 to 5 of each word are decoded in order, and the sign
+/// bytes are ignored. This is the translation a printer-style
+/// [`IODevice::write()`] needs.
+///
+/// # Arguments
+/// * `words` - The words to decode.
+/// * `lower_case` - Whether to decode letters in lower case.
+///
+/// # Returns
+/// * [`Ok(String)`] - The decoded string.
+/// * [`Err(usize)`] - The amount of characters decoded before a byte not in [`Alphabet`].
+pub fn decode_block(words: &[FullWord], lower_case: bool) -> Result<String, usize> {
+    let mut string = String::with_capacity(words.len() * 5);
+    for (count_written, &byte) in words.iter().flat_map(|w| &w[1..=5]).enumerate() {
+        let ch: char = Alphabet::try_from(byte)
+            .map_err(|_| count_written)?
+            .try_into()
+            .map_err(|_| count_written)?;
+        if lower_case {
+            string.push(ch.to_ascii_lowercase());
+        } else {
+            string.push(ch);
+        }
+    }
+    Ok(string)
 }
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use super::*;
+
+/// A table mapping symbol names to addresses.
+pub type SymbolTable = HashMap<String, u16>;
+
+/// Errors returned by [`LayoutBuilder::build()`] and
+/// [`Layout::check_addrs()`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum LayoutError {
+    /// Two regions share the same name.
+    DuplicateName(String),
+
+    /// A region does not fit in the memory.
+    OutOfMemory {
+        /// The name of the region.
+        name: String,
+
+        /// The addresses of the region.
+        range: Range<usize>,
+    },
+
+    /// Two regions overlap.
+    Overlap {
+        /// The name of the region declared first.
+        first: String,
+
+        /// The addresses of the region declared first.
+        first_range: Range<usize>,
+
+        /// The name of the region declared second.
+        second: String,
+
+        /// The addresses of the region declared second.
+        second_range: Range<usize>,
+    },
+
+    /// A region is missing or not at the address expected of it.
+    ///
+    /// See [`Layout::check_addrs()`].
+    Misplaced {
+        /// The name of the region.
+        name: String,
+
+        /// The expected first address.
+        expected: u16,
+
+        /// The region found at the expected address, if any.
+        occupant: Option<String>,
+    },
+}
+
+/// A named range of memory in a [`Layout`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Region {
+    /// The name of the region.
+    pub name: String,
+
+    /// The first address of the region.
+    pub base: u16,
+
+    /// The number of words in the region.
+    pub size: u16,
+}
+
+impl Region {
+    /// Get the addresses of the region.
+    pub fn range(&self) -> Range<usize> {
+        self.base as usize..self.base as usize + self.size as usize
+    }
+}
+
+/// A builder assigning addresses to named regions of memory.
+///
+/// Regions without a fixed base are placed right after the region
+/// declared before them, or at address 0 if there is none.
+///
+/// ```text
+/// let layout = LayoutBuilder::new()
+///     .region("code", 80)
+///     .region_at("const", 3000, 2)
+///     .region("const.msg", 16)    // at 3002
+///     .build()?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LayoutBuilder {
+    /// Declared regions, with `None` bases not assigned yet.
+    regions: Vec<(String, Option<u16>, u16)>,
+}
+
+impl LayoutBuilder {
+    /// Create a new builder with no regions.
+    pub fn new() -> Self {
+        LayoutBuilder {
+            regions: Vec::new(),
+        }
+    }
+
+    /// Declare a region placed right after the previous one.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the region.
+    /// * `size` - The number of words in the region.
+    pub fn region(mut self, name: &str, size: u16) -> Self {
+        self.regions.push((name.to_string(), None, size));
+        self
+    }
+
+    /// Declare a region at a fixed base.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the region.
+    /// * `base` - The first address of the region.
+    /// * `size` - The number of words in the region.
+    pub fn region_at(mut self, name: &str, base: u16, size: u16) -> Self {
+        self.regions.push((name.to_string(), Some(base), size));
+        self
+    }
+
+    /// Assign addresses to all regions.
+    ///
+    /// # Returns
+    /// * [`Ok(Layout)`] - The addresses are assigned.
+    /// * [`Err(LayoutError)`] - Some regions collide or do not fit in the memory.
+    pub fn build(self) -> Result<Layout, LayoutError> {
+        let mut regions: Vec<Region> = Vec::with_capacity(self.regions.len());
+        let mut next: usize = 0;
+        for (name, base, size) in self.regions {
+            if regions.iter().any(|r| r.name == name) {
+                return Err(LayoutError::DuplicateName(name));
+            }
+            let start = base.map_or(next, |b| b as usize);
+            let range = start..start + size as usize;
+            if range.end > Mem::SIZE {
+                return Err(LayoutError::OutOfMemory { name, range });
+            }
+            if let Some(other) = regions
+                .iter()
+                .find(|r| r.range().start < range.end && range.start < r.range().end)
+            {
+                return Err(LayoutError::Overlap {
+                    first: other.name.clone(),
+                    first_range: other.range(),
+                    second: name,
+                    second_range: range,
+                });
+            }
+            next = range.end;
+            regions.push(Region {
+                name,
+                base: start as u16,
+                size,
+            });
+        }
+        Ok(Layout { regions })
+    }
+}
+
+/// Addresses assigned to named regions of memory.
+///
+/// See [`LayoutBuilder`] for how to create one.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Layout {
+    /// The regions in declaration order.
+    regions: Vec<Region>,
+}
+
+impl Layout {
+    /// Get a region by name.
+    pub fn get(&self, name: &str) -> Option<&Region> {
+        self.regions.iter().find(|r| r.name == name)
+    }
+
+    /// Get the first address of a region.
+    ///
+    /// # Panics
+    /// Panics if no region is named `name`.
+    pub fn addr(&self, name: &str) -> u16 {
+        match self.get(name) {
+            Some(region) => region.base,
+            None => panic!("no region named {:?} in layout", name),
+        }
+    }
+
+    /// Get the region containing an address.
+    pub fn region_of(&self, addr: u16) -> Option<&Region> {
+        self.regions
+            .iter()
+            .find(|r| r.range().contains(&(addr as usize)))
+    }
+
+    /// Check that regions are at the addresses expected of them, e.g.
+    /// by code written against fixed addresses.
+    ///
+    /// # Arguments
+    /// * `expected` - The names of the regions and their expected first addresses.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - All regions are where they are expected.
+    /// * [`Err(LayoutError::Misplaced)`] - The first region that is not, with the
+    ///   region found at its expected address.
+    pub fn check_addrs(&self, expected: &[(&str, u16)]) -> Result<(), LayoutError> {
+        for &(name, addr) in expected {
+            if self.get(name).map(|r| r.base) != Some(addr) {
+                return Err(LayoutError::Misplaced {
+                    name: name.to_string(),
+                    expected: addr,
+                    occupant: self.region_of(addr).map(|r| r.name.clone()),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Iterate over the regions in declaration order.
+    pub fn regions(&self) -> impl Iterator<Item = &Region> {
+        self.regions.iter()
+    }
+
+    /// Get a table mapping region names to their first addresses.
+    pub fn symbols(&self) -> SymbolTable {
+        self.regions
+            .iter()
+            .map(|r| (r.name.clone(), r.base))
+            .collect()
+    }
+}
+
+impl VM {
+    /// Register symbol names, replacing the ones registered before.
+    ///
+    /// The names annotate disassembly, see [`VM::disassemble()`].
+    ///
+    /// # Arguments
+    /// * `table` - The table mapping names to addresses, e.g. from [`Layout::symbols()`].
+    pub fn load_symbols(&mut self, table: SymbolTable) {
+        self.symbols = table;
+    }
+
+    /// Get the name of an address.
+    ///
+    /// If several names share the address, the least one is returned.
+    pub fn symbol_at(&self, addr: u16) -> Option<&str> {
+        self.symbols
+            .iter()
+            .filter(|(_, &a)| a == addr)
+            .map(|(name, _)| name.as_str())
+            .min()
+    }
+
+    /// Get the address of a name.
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.symbols.get(name).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Layout {
+        LayoutBuilder::new()
+            .region("code", 80)
+            .region_at("const", 3000, 2)
+            .region("const.msg", 16)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn regions_are_placed_in_order() {
+        let layout = sample();
+        assert_eq!(layout.addr("code"), 0);
+        assert_eq!(layout.addr("const"), 3000);
+        assert_eq!(layout.addr("const.msg"), 3002);
+        assert_eq!(layout.region_of(3010).unwrap().name, "const.msg");
+        assert_eq!(layout.region_of(80), None);
+    }
+
+    #[test]
+    fn colliding_regions_are_reported() {
+        let err = LayoutBuilder::new()
+            .region("code", 80)
+            .region_at("data", 40, 10)
+            .build();
+        assert_eq!(
+            err,
+            Err(LayoutError::Overlap {
+                first: "code".to_string(),
+                first_range: 0..80,
+                second: "data".to_string(),
+                second_range: 40..50,
+            })
+        );
+
+        let err = LayoutBuilder::new()
+            .region("code", 1)
+            .region("code", 1)
+            .build();
+        assert_eq!(err, Err(LayoutError::DuplicateName("code".to_string())));
+
+        let err = LayoutBuilder::new()
+            .region_at("tail", Mem::SIZE as u16 - 1, 2)
+            .build();
+        assert_eq!(
+            err,
+            Err(LayoutError::OutOfMemory {
+                name: "tail".to_string(),
+                range: Mem::SIZE - 1..Mem::SIZE + 1,
+            })
+        );
+    }
+
+    #[test]
+    fn misplaced_regions_are_reported() {
+        let layout = sample();
+        assert_eq!(
+            layout.check_addrs(&[("code", 0), ("const.msg", 3002)]),
+            Ok(())
+        );
+        assert_eq!(
+            layout.check_addrs(&[("code", 0), ("const.msg", 3001)]),
+            Err(LayoutError::Misplaced {
+                name: "const.msg".to_string(),
+                expected: 3001,
+                occupant: Some("const".to_string()),
+            })
+        );
+        assert_eq!(
+            layout.check_addrs(&[("stack", 100)]),
+            Err(LayoutError::Misplaced {
+                name: "stack".to_string(),
+                expected: 100,
+                occupant: None,
+            })
+        );
+    }
+}
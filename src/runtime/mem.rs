@@ -1,10 +1,18 @@
+use core::ops::Add;
 use core::ops::Index;
 use core::ops::IndexMut;
+use core::ops::Neg;
 use core::ops::Range;
 use core::ops::RangeFull;
 use core::ops::RangeInclusive;
+use core::ops::Sub;
 
+use core::fmt;
+use core::str::FromStr;
+
+use super::alphabet::Alphabet;
 use super::instr::Instruction;
+use super::vm::ErrorCode;
 
 /// A generic word in [`MixVM`] with `N` bytes in it.
 ///
@@ -19,6 +27,15 @@ use super::instr::Instruction;
 /// A byte should be able to represent a scalar no less than
 /// decimal `60`.
 ///
+/// By default each byte of a magnitude is an 8-bit binary digit,
+/// so a 5-byte magnitude holds 40 bits. With the `byte-64` feature,
+/// [`Word::from_i64()`], [`Word::to_i64()`] and
+/// [`Word::to_i64_ranged()`] treat each byte as a base-64 digit
+/// instead, as in the canonical binary MIX, so a 5-byte magnitude
+/// holds 30 bits and overflows where a real MIX does. The upper 2
+/// bits of each byte are then ignored. The challenge program relies
+/// on 8-bit bytes and does not build in this mode.
+///
 /// # Generic Parameters
 /// * `N` - The number of bytes in the word, including sign.
 /// * `P` - Whether the sign byte is always positive.
@@ -28,6 +45,22 @@ pub struct Word<const N: usize, const P: bool> {
     data: [u8; N],
 }
 
+/// The number of bits in a byte of a magnitude.
+pub(crate) const BYTE_BITS: u32 = if cfg!(feature = "byte-64") { 6 } else { 8 };
+
+/// Pack base-64 digits into a magnitude.
+///
+/// Only the last 10 digits fit in 60 bits. Returns the magnitude
+/// and whether any more significant digit is nonzero.
+fn pack_base64(digits: &[u8]) -> (i64, bool) {
+    let kept = digits.len().min(10);
+    let (dropped, kept) = digits.split_at(digits.len() - kept);
+    let magnitude = kept
+        .iter()
+        .fold(0, |acc, &b| (acc << 6) | (b & 0x3F) as i64);
+    (magnitude, dropped.iter().any(|&b| b & 0x3F != 0))
+}
+
 impl<const N: usize, const P: bool> Word<N, P> {
     /// Negative sign byte content.
     pub const NEG: u8 = 1;
@@ -56,6 +89,38 @@ impl<const N: usize, const P: bool> Word<N, P> {
         w
     }
 
+    /// Create a new word from `N` bytes behind a pointer, sign byte
+    /// first.
+    ///
+    /// Sign byte settings of `P` will be honored. Prefer
+    /// [`Word::from_slice_checked()`] unless the length check shows
+    /// up, e.g. when loading a large deck.
+    ///
+    /// # Safety
+    /// `bytes` must point to at least `N` readable bytes. It need not
+    /// be aligned.
+    pub unsafe fn from_be_bytes_unchecked(bytes: *const u8) -> Self {
+        // SAFETY: The caller guarantees `N` readable bytes, and
+        // `read_unaligned()` has no alignment requirement.
+        let data = unsafe { core::ptr::read_unaligned(bytes.cast::<[u8; N]>()) };
+        Self::from_bytes(data)
+    }
+
+    /// Create a new word from a slice of bytes, sign byte first.
+    ///
+    /// Sign byte settings of `P` will be honored. This is the
+    /// `TryFrom<&[u8]>` implementation of [`Word`].
+    ///
+    /// # Arguments
+    /// * `bytes` - Exactly `N` bytes.
+    ///
+    /// # Returns
+    /// * [`Ok(Word)`] - The word.
+    /// * [`Err(())`] - `bytes` has a wrong length.
+    pub fn from_slice_checked(bytes: &[u8]) -> Result<Self, ()> {
+        bytes.try_into()
+    }
+
     /// Create a new word from an `i64`.
     ///
     /// The function stores big-endian representation of the
@@ -71,7 +136,20 @@ impl<const N: usize, const P: bool> Word<N, P> {
     /// * [`bool`] - `true` if the given `i64` is too large, `false` otherwise.
     pub fn from_i64(value: i64) -> (Self, bool) {
         let mut word = Self::new();
-        let bytes = value.abs().to_be_bytes();
+        if cfg!(feature = "byte-64") {
+            word[0] = if !P && value < 0 {
+                Self::NEG
+            } else {
+                Self::POS
+            };
+            let mut magnitude = value.unsigned_abs();
+            for word_i in (1..N).rev() {
+                word[word_i] = (magnitude & 0x3F) as u8;
+                magnitude >>= 6;
+            }
+            return (word, magnitude != 0);
+        }
+        let bytes = value.unsigned_abs().to_be_bytes();
         // See if we have something not copied.
         // Bytes marked 'dirty' have not been copied yet.
         let overflow = N - 1 < 8 && bytes[0..8 - (N - 1)].iter().any(|&b| b != 0);
@@ -107,6 +185,35 @@ impl<const N: usize, const P: bool> Word<N, P> {
         self.data[0] == Self::POS
     }
 
+    /// Check if the word is negative.
+    ///
+    /// The complement of [`Word::is_positive()`].
+    ///
+    /// # Returns
+    /// * `true` - If the word is negative, `word[0] != Self::POS`.
+    /// * `false` - If the word is positive, `word[0] == Self::POS`.
+    pub const fn is_negative(&self) -> bool {
+        !self.is_positive()
+    }
+
+    /// Check if the magnitude of the word is zero.
+    ///
+    /// The sign is ignored, so both `+0` and `-0` are zero.
+    ///
+    /// # Returns
+    /// * `true` - If all bytes except the sign byte are zero.
+    /// * `false` - Otherwise.
+    pub const fn is_zero(&self) -> bool {
+        let mut i = 1;
+        while i < N {
+            if self.data[i] != 0 {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
     /// Get sign adjustment coefficient for the word.
     ///
     /// # Returns
@@ -120,16 +227,32 @@ impl<const N: usize, const P: bool> Word<N, P> {
         }
     }
 
+    /// Get the sign byte of the word.
+    ///
+    /// # Returns
+    /// * [`Word::POS`] - If the word is positive.
+    /// * [`u8`] - Any other value, if the word is negative.
+    pub const fn sign_byte(&self) -> u8 {
+        self.data[0]
+    }
+
+    /// Set the sign of the word.
+    ///
+    /// The word stays positive if it is always positive,
+    /// i.e. `P == true`.
+    ///
+    /// # Arguments
+    /// * `negative` - Whether the word becomes negative.
+    pub fn set_sign(&mut self, negative: bool) {
+        self.data[0] = if !P && negative { Self::NEG } else { Self::POS };
+    }
+
     /// Flip the sign of the word.
     ///
     /// This method has no effect if the word is always positive,
     /// i.e. `P == true`.
     pub fn flip_sign(&mut self) {
-        self.data[0] = if !P && self.is_positive() {
-            Self::NEG
-        } else {
-            Self::POS
-        };
+        self.set_sign(self.is_positive());
     }
 
     /// Convert the word to an `i64`.
@@ -142,6 +265,10 @@ impl<const N: usize, const P: bool> Word<N, P> {
     /// * [`bool`] - `true` if the word overflows. Higher zero bytes do not count as overflow.
     pub fn to_i64(self) -> (i64, bool) {
         let sign = self.get_sign() as i64;
+        if cfg!(feature = "byte-64") {
+            let (value, overflow) = pack_base64(&self.data[1..N]);
+            return (value * sign, overflow);
+        }
         let mut bytes: [u8; 8] = [0; 8];
         let overflow = N - 1 > 8 && self.data[8..N].iter().any(|&b| b != 0);
         // Bytes marked 'dirty' have not been copied yet.
@@ -177,6 +304,10 @@ impl<const N: usize, const P: bool> Word<N, P> {
         }
         // Find sign.
         let sign = if !sign_included { 1 } else { self.get_sign() } as i64;
+        if cfg!(feature = "byte-64") {
+            let (value, overflow) = pack_base64(data);
+            return (value * sign, overflow);
+        }
         let mut result_bytes: [u8; 8] = [0; 8];
         // Get count of bytes that is needed to copy.
         let data_bytes_nonzero_count = data.iter().filter(|&&b| b != 0).count();
@@ -191,6 +322,21 @@ impl<const N: usize, const P: bool> Word<N, P> {
 
         (value * sign, data_bytes_nonzero_count > 8)
     }
+
+    /// View all bytes of the word, including the sign byte.
+    pub const fn as_array(&self) -> &[u8; N] {
+        &self.data
+    }
+
+    /// Format all bytes of the word, including the sign byte, as
+    /// uppercase hexadecimal digits.
+    ///
+    /// This is meant for dumps. A [`FullWord`] yields 12 digits, e.g.
+    /// `"0100000A0B0C"`. Words are read back from text with
+    /// [`Word::parse_literal()`].
+    pub fn bytes_as_hex(&self) -> String {
+        self.data.iter().map(|b| format!("{:02X}", b)).collect()
+    }
 }
 
 impl<const N: usize, const P: bool> Default for Word<N, P> {
@@ -202,6 +348,126 @@ impl<const N: usize, const P: bool> Default for Word<N, P> {
     }
 }
 
+impl<const N: usize, const P: bool> TryFrom<&[u8]> for Word<N, P> {
+    type Error = ();
+
+    /// Create a new word from a slice of exactly `N` bytes.
+    ///
+    /// Sign byte settings of `P` will be honored, as in
+    /// [`Word<N, P>::from_bytes()`].
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let bytes: [u8; N] = bytes.try_into().map_err(|_| ())?;
+        Ok(Self::from_bytes(bytes))
+    }
+}
+
+impl<const N: usize> Word<N, false> {
+    /// The largest magnitude representable by the word, capped
+    /// at [`i64::MAX`].
+    const MAX_MAGNITUDE: i64 = if BYTE_BITS as usize * (N - 1) >= 63 {
+        i64::MAX
+    } else {
+        (1 << (BYTE_BITS as usize * (N - 1))) - 1
+    };
+
+    /// Create a word from an `i64`, saturating at the largest
+    /// magnitude representable by the word.
+    fn from_i64_saturating(value: i64) -> Self {
+        Self::from_i64(value.clamp(-Self::MAX_MAGNITUDE, Self::MAX_MAGNITUDE)).0
+    }
+
+    /// Add two words as values, failing on overflow.
+    ///
+    /// Like [`Word::add()`], this works on the whole words and a zero
+    /// result is always positive.
+    ///
+    /// # Returns
+    /// * [`Some(Word)`] - The sum.
+    /// * [`None`] - The magnitude of the sum does not fit in the word.
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        let sum = self.to_i64().0.checked_add(other.to_i64().0)?;
+        match Self::from_i64(sum) {
+            (word, false) => Some(word),
+            (_, true) => None,
+        }
+    }
+
+    /// Subtract two words as values, failing on overflow.
+    ///
+    /// See [`Word::checked_add()`] for the semantics.
+    pub fn checked_sub(&self, other: &Self) -> Option<Self> {
+        self.checked_add(&-*other)
+    }
+}
+
+impl<const P: bool> Word<3, P> {
+    /// Widen the word into a [`FullWord`].
+    ///
+    /// The sign is kept, and the two bytes become bytes 4 and 5 of
+    /// the full word, as index registers and `rJ` are stored and
+    /// compared. Bytes 1 to 3 are zero.
+    pub const fn to_full_word(self) -> FullWord {
+        FullWord::from_bytes([self.data[0], 0, 0, 0, self.data[1], self.data[2]])
+    }
+}
+
+impl<const N: usize> Neg for Word<N, false> {
+    type Output = Self;
+
+    /// Flip the sign of the word.
+    ///
+    /// Note that `-(+0)` is `-0`, as the sign is always flipped.
+    fn neg(mut self) -> Self::Output {
+        self.flip_sign();
+        self
+    }
+}
+
+impl<const N: usize> Add for Word<N, false> {
+    type Output = Self;
+
+    /// Add two words as values.
+    ///
+    /// This is a value operation on the whole words, unlike
+    /// [`ADD`][super::Opcode::Add] which works on a field and sets
+    /// the overflow toggle. Results are saturated at the largest
+    /// magnitude representable by the word. A zero result is
+    /// always positive.
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::from_i64_saturating(self.to_i64().0.saturating_add(rhs.to_i64().0))
+    }
+}
+
+impl<const N: usize> Sub for Word<N, false> {
+    type Output = Self;
+
+    /// Subtract two words as values.
+    ///
+    /// See [`Word::add()`] for the semantics.
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::from_i64_saturating(self.to_i64().0.saturating_sub(rhs.to_i64().0))
+    }
+}
+
+impl<const N: usize, const P: bool> PartialEq<i64> for Word<N, P> {
+    /// Compare the value of the word to an `i64`.
+    ///
+    /// The value is taken from [`Word::to_i64()`], so `+0` and `-0`
+    /// are both equal to `0`, and overflow in the word is ignored.
+    fn eq(&self, other: &i64) -> bool {
+        self.to_i64().0 == *other
+    }
+}
+
+impl<const N: usize, const P: bool> PartialOrd<i64> for Word<N, P> {
+    /// Compare the value of the word to an `i64`.
+    ///
+    /// The value is taken as for `PartialEq<i64>`.
+    fn partial_cmp(&self, other: &i64) -> Option<core::cmp::Ordering> {
+        self.to_i64().0.partial_cmp(other)
+    }
+}
+
 impl<const N: usize, const P: bool> Index<RangeInclusive<usize>> for Word<N, P> {
     type Output = [u8];
 
@@ -271,6 +537,149 @@ impl From<Instruction> for Word<6, false> {
     }
 }
 
+/// Errors returned by [`Word::parse_literal()`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WordParseError {
+    /// The literal has none of the accepted forms, e.g. it is a bare
+    /// number.
+    Unrecognized,
+
+    /// The byte form does not have exactly 5 bytes.
+    ByteCount(usize),
+
+    /// A byte of the byte form is not a decimal number below the
+    /// byte size.
+    BadByte {
+        /// The position of the byte, from 1 to 5.
+        index: usize,
+    },
+
+    /// The value of the decimal form does not fit in a word.
+    OutOfRange,
+
+    /// The quoted form does not have exactly 5 characters.
+    CharCount(usize),
+
+    /// A character of the quoted form is not in [`Alphabet`].
+    BadChar(char),
+}
+
+impl fmt::Display for WordParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WordParseError::Unrecognized => f.write_str("not a word literal"),
+            WordParseError::ByteCount(count) => write!(f, "{} bytes instead of 5", count),
+            WordParseError::BadByte { index } => write!(f, "byte {} is out of range", index),
+            WordParseError::OutOfRange => f.write_str("value does not fit in a word"),
+            WordParseError::CharCount(count) => write!(f, "{} characters instead of 5", count),
+            WordParseError::BadChar(ch) => write!(f, "character {:?} is not in the alphabet", ch),
+        }
+    }
+}
+
+impl std::error::Error for WordParseError {}
+
+impl Word<6, false> {
+    /// Parse a word literal.
+    ///
+    /// Three forms are accepted, with no surrounding whitespace:
+    ///
+    /// * Bytes: an optional sign and exactly 5 decimal bytes separated
+    ///   by `:`, e.g. `+1:2:3:4:5` or `-0:0:0:31:59`. The sign defaults
+    ///   to `+`.
+    /// * Decimal: a value between `=` as in MIXAL, e.g. `=12345=` or
+    ///   `=-0=`, converted as [`Word::from_i64()`] does, except that
+    ///   `-0` keeps its sign.
+    /// * Quoted: exactly 5 characters of [`Alphabet`] between `"`,
+    ///   e.g. `"HELLO"`, with a positive sign.
+    ///
+    /// A bare number such as `12` is rejected, as it could mean either
+    /// a value or a single byte. This is the notation of the
+    /// [`Display`] of [`FullWord`], which always writes the byte form,
+    /// and of its [`FromStr`].
+    ///
+    /// [`Display`]: fmt::Display
+    ///
+    /// # Returns
+    /// * [`Ok(FullWord)`] - The word.
+    /// * [`Err(WordParseError)`] - The literal is malformed.
+    pub fn parse_literal(s: &str) -> Result<Self, WordParseError> {
+        if let Some(text) = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            let count = text.chars().count();
+            if count != 5 {
+                return Err(WordParseError::CharCount(count));
+            }
+            let mut word = Self::new();
+            for (i, ch) in text.chars().enumerate() {
+                let alpha = Alphabet::try_from(ch).map_err(|_| WordParseError::BadChar(ch))?;
+                word[i + 1] = alpha as u8;
+            }
+            return Ok(word);
+        }
+        if let Some(text) = s.strip_prefix('=').and_then(|s| s.strip_suffix('=')) {
+            let (negative, digits) = match text.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, text.strip_prefix('+').unwrap_or(text)),
+            };
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(WordParseError::Unrecognized);
+            }
+            let magnitude: i64 = digits.parse().map_err(|_| WordParseError::OutOfRange)?;
+            let (mut word, overflow) = Self::from_i64(magnitude);
+            if overflow {
+                return Err(WordParseError::OutOfRange);
+            }
+            word.set_sign(negative);
+            return Ok(word);
+        }
+        let (negative, text) = match s.as_bytes().first() {
+            Some(b'+') => (false, &s[1..]),
+            Some(b'-') => (true, &s[1..]),
+            _ => (false, s),
+        };
+        if !text.contains(':') {
+            return Err(WordParseError::Unrecognized);
+        }
+        let bytes = text.split(':').collect::<Vec<_>>();
+        if bytes.len() != 5 {
+            return Err(WordParseError::ByteCount(bytes.len()));
+        }
+        let mut word = Self::new();
+        for (i, byte) in bytes.iter().enumerate() {
+            word[i + 1] = byte
+                .parse::<u16>()
+                .ok()
+                .filter(|b| byte.bytes().all(|c| c.is_ascii_digit()) && *b < 1 << BYTE_BITS)
+                .ok_or(WordParseError::BadByte { index: i + 1 })? as u8;
+        }
+        word.set_sign(negative);
+        Ok(word)
+    }
+}
+
+impl fmt::Display for Word<6, false> {
+    /// Format the word as a byte literal, e.g. `-0:0:0:31:59`.
+    ///
+    /// See [`Word::parse_literal()`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.is_negative() { '-' } else { '+' };
+        write!(
+            f,
+            "{}{}:{}:{}:{}:{}",
+            sign, self[1], self[2], self[3], self[4], self[5]
+        )
+    }
+}
+
+impl FromStr for Word<6, false> {
+    type Err = WordParseError;
+
+    /// Parse a word literal, see [`Word::parse_literal()`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_literal(s)
+    }
+}
+
 /// Alias for a 6-byte [`Word`] including a sign byte.
 pub type FullWord = Word<6, false>;
 
@@ -281,6 +690,24 @@ pub type HalfWord = Word<3, false>;
 /// which is always equal to [`Word::POS`].
 pub type PosHalfWord = Word<3, true>;
 
+// `Mem::as_byte_slice()` relies on words having no padding.
+const _: () = assert!(core::mem::size_of::<FullWord>() == 6);
+const _: () = assert!(core::mem::size_of::<[FullWord; Mem::SIZE]>() == Mem::SIZE * 6);
+
+/// The offset basis of the 64-bit FNV-1a hash.
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Feed bytes to a 64-bit FNV-1a hash.
+///
+/// # Arguments
+/// * `hash` - The hash so far, [`FNV_OFFSET_BASIS`] to start.
+/// * `bytes` - The bytes to feed.
+pub(crate) fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
 /// The memory area of a [`MixVM`] comprised of [`FullWord`]s.
 ///
 /// [`MixVM`]: crate::MixVM
@@ -288,6 +715,13 @@ pub type PosHalfWord = Word<3, true>;
 pub struct Mem {
     /// The memory area.
     data: [FullWord; Self::SIZE],
+
+    /// The number of writes so far, see [`Mem::generation()`].
+    generation: u64,
+
+    /// The generation of the last write to each block of
+    /// [`Mem::BLOCK_SIZE`] words.
+    stamps: [u64; Self::BLOCKS],
 }
 
 impl Mem {
@@ -300,11 +734,275 @@ impl Mem {
     pub const fn new() -> Self {
         Mem {
             data: [FullWord::new(); 4000],
+            generation: 0,
+            stamps: [0; Self::BLOCKS],
         }
     }
 
+    /// Create a new memory area with a program loaded at `start`.
+    ///
+    /// All other words are zero. Usable in `const` contexts:
+    ///
+    /// ```text
+    /// static INITIAL_MEM: Mem = Mem::new_with_program(0, &PROGRAM_WORDS);
+    /// ```
+    ///
+    /// # Arguments
+    /// * `start` - The address of the first word.
+    /// * `words` - The words to load.
+    ///
+    /// # Panics
+    /// Panics if the words do not fit in the memory, which fails the
+    /// build in `const` contexts.
+    pub const fn new_with_program(start: u16, words: &[FullWord]) -> Self {
+        let start = start as usize;
+        assert!(
+            start + words.len() <= Self::SIZE,
+            "program does not fit in the memory"
+        );
+        let mut mem = Self::new();
+        let mut i = 0;
+        while i < words.len() {
+            mem.data[start + i] = words[i];
+            mem.stamps[(start + i) / Self::BLOCK_SIZE] = 1;
+            i += 1;
+        }
+        mem.generation = 1;
+        mem
+    }
+
     /// Number of words in the memory area.
     pub const SIZE: usize = 4000;
+
+    /// Number of words in a block tracked by [`Mem::written_since()`].
+    pub const BLOCK_SIZE: usize = 40;
+
+    /// Number of blocks tracked by [`Mem::written_since()`].
+    const BLOCKS: usize = Self::SIZE / Self::BLOCK_SIZE;
+
+    /// Get the generation of the memory.
+    ///
+    /// The generation grows by one on every write, so that writes
+    /// after a point can be told by [`Mem::written_since()`]. A clone
+    /// keeps the generation of the original.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Get the blocks written after a generation.
+    ///
+    /// Every word changed since [`Mem::generation()`] returned
+    /// `generation` is in one of the returned ranges, each of
+    /// [`Mem::BLOCK_SIZE`] words. Words may be reported although they
+    /// were written with their old value. The result is meaningless
+    /// if the memory was replaced as a whole in the meantime, which
+    /// shows as a generation below `generation`.
+    ///
+    /// # Returns
+    /// * [`Vec<Range<u16>>`] - The blocks, in ascending order.
+    pub fn written_since(&self, generation: u64) -> Vec<Range<u16>> {
+        (0..Self::BLOCKS)
+            .filter(|&block| self.stamps[block] > generation)
+            .map(|block| {
+                let start = (block * Self::BLOCK_SIZE) as u16;
+                start..start + Self::BLOCK_SIZE as u16
+            })
+            .collect()
+    }
+
+    /// Overwrite the whole memory with the words of another.
+    ///
+    /// Unlike an assignment, this keeps the generation of the memory
+    /// and counts as a write of every word.
+    pub fn copy_from(&mut self, other: &Mem) {
+        self.data = other.data;
+        self.touch(0..Self::SIZE);
+    }
+
+    /// Mark the words in `range` as written.
+    fn touch(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        self.generation += 1;
+        let blocks = range.start / Self::BLOCK_SIZE..=(range.end - 1) / Self::BLOCK_SIZE;
+        self.stamps[blocks].fill(self.generation);
+    }
+
+    /// View the memory as a flat slice of bytes.
+    ///
+    /// Words are laid out in address order, each starting from its
+    /// sign byte, so that the byte `j` of the word at address `i` is
+    /// at offset `i * 6 + j`. The slice has `SIZE * 6` bytes.
+    pub fn as_byte_slice(&self) -> &[u8] {
+        // SAFETY: `Word` is `#[repr(C)]` over `[u8; N]`, so a
+        // `[FullWord; SIZE]` has the same layout as `[u8; SIZE * 6]`.
+        unsafe { core::slice::from_raw_parts(self.data.as_ptr().cast::<u8>(), Self::SIZE * 6) }
+    }
+
+    /// Mutably view the memory as a flat slice of bytes.
+    ///
+    /// See [`Mem::as_byte_slice()`] for the layout.
+    pub fn as_byte_slice_mut(&mut self) -> &mut [u8] {
+        self.touch(0..Self::SIZE);
+        // SAFETY: See `as_byte_slice()`. Any byte is a valid `u8`, and
+        // `FullWord` does not restrict the content of its sign byte.
+        unsafe {
+            core::slice::from_raw_parts_mut(self.data.as_mut_ptr().cast::<u8>(), Self::SIZE * 6)
+        }
+    }
+
+    /// View the memory as a flat slice of bytes.
+    ///
+    /// Equivalent to [`Mem::as_byte_slice()`].
+    pub fn as_flat_bytes(&self) -> &[u8] {
+        self.as_byte_slice()
+    }
+
+    /// Find all occurrences of a byte pattern in the memory.
+    ///
+    /// The pattern may start at any byte and span multiple words.
+    /// See [`Mem::as_byte_slice()`] for the layout.
+    ///
+    /// # Arguments
+    /// * `needle` - The bytes to find. An empty pattern finds nothing.
+    ///
+    /// # Returns
+    /// * [`Vec<(u16, usize)>`] - The address of the word where each occurrence
+    ///   starts and its byte offset in the word, in ascending order.
+    pub fn find_bytes(&self, needle: &[u8]) -> Vec<(u16, usize)> {
+        if needle.is_empty() {
+            return Vec::new();
+        }
+        self.as_byte_slice()
+            .windows(needle.len())
+            .enumerate()
+            .filter(|(_, window)| *window == needle)
+            .map(|(pos, _)| ((pos / 6) as u16, pos % 6))
+            .collect()
+    }
+
+    /// Find all occurrences of a byte pattern with wildcards.
+    ///
+    /// Like [`Mem::find_bytes()`], but `None` in the pattern matches
+    /// any byte.
+    ///
+    /// # Arguments
+    /// * `pattern` - The bytes to find. An empty pattern finds nothing.
+    ///
+    /// # Returns
+    /// * [`Vec<(u16, usize)>`] - The address of the word where each occurrence
+    ///   starts and its byte offset in the word, in ascending order.
+    pub fn find_pattern(&self, pattern: &[Option<u8>]) -> Vec<(u16, usize)> {
+        if pattern.is_empty() {
+            return Vec::new();
+        }
+        self.as_byte_slice()
+            .windows(pattern.len())
+            .enumerate()
+            .filter(|(_, window)| {
+                window
+                    .iter()
+                    .zip(pattern)
+                    .all(|(byte, expected)| expected.is_none_or(|e| e == *byte))
+            })
+            .map(|(pos, _)| ((pos / 6) as u16, pos % 6))
+            .collect()
+    }
+
+    /// Create a memory area from a flat slice of bytes.
+    ///
+    /// See [`Mem::as_byte_slice()`] for the layout.
+    ///
+    /// # Arguments
+    /// * `bytes` - Exactly `SIZE * 6` bytes.
+    ///
+    /// # Returns
+    /// * [`Ok(Mem)`] - The memory area.
+    /// * [`Err(())`] - `bytes` has a wrong length.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ()> {
+        if bytes.len() != Self::SIZE * 6 {
+            return Err(());
+        }
+        let mut mem = Self::new();
+        mem.as_byte_slice_mut().copy_from_slice(bytes);
+        Ok(mem)
+    }
+
+    /// Copy words from one range of the memory to another.
+    ///
+    /// The ranges may overlap, in which case the words are copied as
+    /// if through a temporary buffer. Unlike `MOVE`, overlapping does
+    /// not repeat the copied words.
+    ///
+    /// # Arguments
+    /// * `src_start` - The address of the first word to copy.
+    /// * `dst_start` - The address of the first word to overwrite.
+    /// * `count` - The number of words to copy.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The words are copied.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - Either range is out of memory.
+    pub fn copy_range(
+        &mut self,
+        src_start: u16,
+        dst_start: u16,
+        count: u16,
+    ) -> Result<(), ErrorCode> {
+        let (src, dst, count) = (src_start as usize, dst_start as usize, count as usize);
+        if src + count > Self::SIZE || dst + count > Self::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        self.data.copy_within(src..src + count, dst);
+        self.touch(dst..dst + count);
+        Ok(())
+    }
+
+    /// Compute a checksum of the words in `start..end`.
+    ///
+    /// The checksum is 64-bit FNV-1a over the bytes of the words,
+    /// laid out as in [`Mem::as_byte_slice()`]. It is the same on
+    /// every platform, and does not change within a major version.
+    ///
+    /// # Returns
+    /// * [`Ok(u64)`] - The checksum.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - The range is out of memory or reversed.
+    pub fn checksum_range(&self, start: u16, end: u16) -> Result<u64, ErrorCode> {
+        let (start, end) = (start as usize, end as usize);
+        if start > end || end > Self::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        let bytes = &self.as_byte_slice()[start * 6..end * 6];
+        Ok(fnv1a(FNV_OFFSET_BASIS, bytes))
+    }
+
+    /// Hash the content of the memory.
+    ///
+    /// The hash is 64-bit FNV-1a over [`Mem::as_byte_slice()`]. It is
+    /// the same on every platform, and does not change within a major
+    /// version.
+    pub fn content_hash(&self) -> u64 {
+        fnv1a(FNV_OFFSET_BASIS, self.as_byte_slice())
+    }
+
+    /// Swap the words at two memory locations in place.
+    ///
+    /// # Arguments
+    /// * `a` - The first location.
+    /// * `b` - The second location.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The words are swapped.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - Either location is out of memory.
+    pub fn swap_words(&mut self, a: u16, b: u16) -> Result<(), ErrorCode> {
+        if a as usize >= Self::SIZE || b as usize >= Self::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        self.data.swap(a as usize, b as usize);
+        self.touch(a as usize..a as usize + 1);
+        self.touch(b as usize..b as usize + 1);
+        Ok(())
+    }
 }
 
 impl Index<u16> for Mem {
@@ -319,6 +1017,7 @@ impl Index<u16> for Mem {
 impl IndexMut<u16> for Mem {
     /// Access the mutable word at a memory location.
     fn index_mut(&mut self, index: u16) -> &mut Self::Output {
+        self.touch(index as usize..index as usize + 1);
         &mut self.data[index as usize]
     }
 }
@@ -335,6 +1034,7 @@ impl Index<Range<usize>> for Mem {
 impl IndexMut<Range<usize>> for Mem {
     /// Access the mutable word at a range.
     fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        self.touch(index.clone());
         &mut self.data[index]
     }
 }
@@ -347,3 +1047,394 @@ impl Default for Mem {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::alphabet::{encode_words, InputPolicy};
+    use super::*;
+
+    /// The largest magnitude of a 5-byte word.
+    const WORD_MAX: i64 = (1 << (5 * BYTE_BITS)) - 1;
+
+    #[test]
+    fn overflow_boundary() {
+        let (word, overflow) = FullWord::from_i64(WORD_MAX);
+        assert!(!overflow);
+        assert_eq!(word.to_i64(), (WORD_MAX, false));
+        let (word, overflow) = FullWord::from_i64(-WORD_MAX);
+        assert!(!overflow);
+        assert_eq!(word.to_i64(), (-WORD_MAX, false));
+        assert!(FullWord::from_i64(WORD_MAX + 1).1);
+        assert!(FullWord::from_i64(-WORD_MAX - 1).1);
+    }
+
+    #[cfg(not(feature = "byte-64"))]
+    #[test]
+    fn raw_bytes_hold_40_bits() {
+        assert_eq!(WORD_MAX, 0xFF_FFFF_FFFF);
+        let (word, overflow) = FullWord::from_i64(1 << 30);
+        assert!(!overflow);
+        assert_eq!(word.as_array(), &[FullWord::POS, 0, 0x40, 0, 0, 0]);
+    }
+
+    #[cfg(feature = "byte-64")]
+    #[test]
+    fn base64_bytes_hold_30_bits() {
+        assert_eq!(WORD_MAX, 0x3FFF_FFFF);
+        assert!(FullWord::from_i64(1 << 30).1);
+        let (word, overflow) = FullWord::from_i64(0x3FFF_FFFF);
+        assert!(!overflow);
+        assert_eq!(word.as_array(), &[FullWord::POS, 63, 63, 63, 63, 63]);
+        // The upper 2 bits of each byte are ignored.
+        let mut word = FullWord::new();
+        word.set_all([FullWord::POS, 0, 0, 0, 0, 0xC1]);
+        assert_eq!(word.to_i64(), (1, false));
+    }
+
+    #[test]
+    fn written_since_reports_written_blocks() {
+        let mut mem = Mem::new();
+        assert_eq!(mem.generation(), 0);
+        mem[5] = FullWord::from_i64(1).0;
+        let generation = mem.generation();
+        assert!(mem.written_since(generation).is_empty());
+        mem[79] = FullWord::from_i64(2).0;
+        mem[3999] = FullWord::from_i64(3).0;
+        assert_eq!(mem.written_since(generation), [40..80, 3960..4000]);
+        assert_eq!(mem.written_since(0), [0..40, 40..80, 3960..4000]);
+
+        let generation = mem.generation();
+        mem[118..122].fill(FullWord::from_i64(4).0);
+        assert_eq!(mem.written_since(generation), [80..120, 120..160]);
+
+        let generation = mem.generation();
+        mem.copy_from(&Mem::new());
+        assert!(mem.generation() > generation);
+        assert_eq!(
+            mem.written_since(generation).len(),
+            Mem::SIZE / Mem::BLOCK_SIZE
+        );
+    }
+
+    #[test]
+    fn display_round_trips_through_parse() {
+        // A fixed linear congruential generator, so that failures
+        // reproduce.
+        let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+        for _ in 0..10_000 {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            let mut word = FullWord::new();
+            for i in 1..=5 {
+                word[i] = ((state >> (8 * i)) % (1 << BYTE_BITS)) as u8;
+            }
+            word.set_sign(state & 1 == 1);
+            let text = word.to_string();
+            let parsed = text.parse::<FullWord>().unwrap();
+            assert_eq!(parsed.as_array(), word.as_array(), "{}", text);
+            assert_eq!(parsed.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn decimal_and_alphabetic_literals_round_trip() {
+        const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 .,()+-*/";
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        for _ in 0..10_000 {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            let value = (state >> 16) as i64 % (WORD_MAX + 1) * if state & 1 == 1 { -1 } else { 1 };
+            let text = format!("={}=", value);
+            let word = FullWord::parse_literal(&text).unwrap();
+            assert_eq!(word.to_i64(), (value, false), "{}", text);
+
+            let chars: String = (0..5)
+                .map(|i| CHARS[(state >> (8 * i + 3)) as usize % CHARS.len()] as char)
+                .collect();
+            let word = format!("\"{}\"", chars).parse::<FullWord>().unwrap();
+            let expected = encode_words(&chars, InputPolicy::Strict).unwrap();
+            assert_eq!(word.as_array(), expected[0].as_array(), "{:?}", chars);
+        }
+    }
+
+    #[test]
+    fn accepted_literals() {
+        const NEG: u8 = FullWord::NEG;
+        const POS: u8 = FullWord::POS;
+        let table: [(&str, [u8; 6]); 9] = [
+            ("+1:2:3:4:5", [POS, 1, 2, 3, 4, 5]),
+            ("1:2:3:4:5", [POS, 1, 2, 3, 4, 5]),
+            ("-0:0:0:31:59", [NEG, 0, 0, 0, 31, 59]),
+            ("-0:0:0:0:0", [NEG, 0, 0, 0, 0, 0]),
+            ("=0=", [POS, 0, 0, 0, 0, 0]),
+            ("=+1=", [POS, 0, 0, 0, 0, 1]),
+            ("=-0=", [NEG, 0, 0, 0, 0, 0]),
+            ("\"HELLO\"", [POS, 8, 5, 13, 13, 16]),
+            ("\"  A  \"", [POS, 0, 0, 1, 0, 0]),
+        ];
+        for (text, bytes) in table {
+            let word = FullWord::parse_literal(text);
+            assert_eq!(word.map(|w| *w.as_array()), Ok(bytes), "{}", text);
+        }
+        let (expected, _) = FullWord::from_i64(-12345);
+        let word = FullWord::parse_literal("=-12345=").unwrap();
+        assert_eq!(word.as_array(), expected.as_array());
+    }
+
+    #[test]
+    fn rejected_literals() {
+        let table: [(&str, WordParseError); 14] = [
+            ("12", WordParseError::Unrecognized),
+            ("", WordParseError::Unrecognized),
+            ("=12", WordParseError::Unrecognized),
+            ("==", WordParseError::Unrecognized),
+            ("=1 2=", WordParseError::Unrecognized),
+            ("+1:2:3:4", WordParseError::ByteCount(4)),
+            ("+1:2:3:4:5:6", WordParseError::ByteCount(6)),
+            ("+1:2:x:4:5", WordParseError::BadByte { index: 3 }),
+            ("+1:2:3:4:300", WordParseError::BadByte { index: 5 }),
+            ("+1:+2:3:4:5", WordParseError::BadByte { index: 2 }),
+            (" +1:2:3:4:5", WordParseError::BadByte { index: 1 }),
+            ("=1099511627776=", WordParseError::OutOfRange),
+            ("\"HI\"", WordParseError::CharCount(2)),
+            ("\"HELL_\"", WordParseError::BadChar('_')),
+        ];
+        for (text, error) in table {
+            let word = FullWord::parse_literal(text);
+            assert_eq!(word.map(|w| *w.as_array()), Err(error), "{:?}", text);
+        }
+        assert_eq!(
+            FullWord::parse_literal("=99999999999999999999=").map(|w| *w.as_array()),
+            Err(WordParseError::OutOfRange)
+        );
+    }
+
+    #[cfg(feature = "byte-64")]
+    #[test]
+    fn base64_bytes_reject_64() {
+        assert_eq!(
+            FullWord::parse_literal("+0:0:0:0:64").map(|w| *w.as_array()),
+            Err(WordParseError::BadByte { index: 5 })
+        );
+    }
+
+    #[test]
+    fn from_slice_checked_matches_the_unchecked_read() {
+        let bytes = [1, 2, 3, 4, 5, 6, 7];
+        for start in 0..=1 {
+            let checked = FullWord::from_slice_checked(&bytes[start..start + 6]).unwrap();
+            // SAFETY: `bytes` has 6 readable bytes from `start`.
+            let unchecked = unsafe { FullWord::from_be_bytes_unchecked(bytes[start..].as_ptr()) };
+            assert_eq!(checked.as_array(), unchecked.as_array());
+        }
+        assert!(FullWord::from_slice_checked(&bytes[..5]).is_err());
+        assert!(FullWord::from_slice_checked(&bytes).is_err());
+        let word = PosHalfWord::from_slice_checked(&[1, 2, 3]).unwrap();
+        assert_eq!(word.as_array()[1..], [2, 3]);
+        assert!(word.is_positive());
+    }
+
+    #[test]
+    fn negation_flips_the_sign_of_zero() {
+        let zero = FullWord::new();
+        let negated = -zero;
+        assert!(negated.is_negative());
+        assert_eq!(negated.to_i64(), (0, false));
+        assert!((-negated).is_positive());
+
+        let (word, _) = FullWord::from_i64(12345);
+        assert_eq!((-word).to_i64(), (-12345, false));
+    }
+
+    #[test]
+    fn addition_and_subtraction_cross_the_sign() {
+        let word = |value| FullWord::from_i64(value).0;
+        assert_eq!((word(-5) + word(3)).to_i64(), (-2, false));
+        assert_eq!((word(5) + word(-8)).to_i64(), (-3, false));
+        assert_eq!((word(3) - word(5)).to_i64(), (-2, false));
+        assert_eq!((word(-3) - word(-5)).to_i64(), (2, false));
+
+        // A zero result is positive, even from negative operands.
+        let sum = word(-7) + word(7);
+        assert!(sum.is_positive());
+        assert_eq!(sum.to_i64(), (0, false));
+        assert!((-FullWord::new() + -FullWord::new()).is_positive());
+    }
+
+    #[test]
+    fn value_arithmetic_saturates() {
+        let word = |value| FullWord::from_i64(value).0;
+        assert_eq!((word(WORD_MAX) + word(1)).to_i64(), (WORD_MAX, false));
+        assert_eq!(
+            (word(-WORD_MAX) - word(WORD_MAX)).to_i64(),
+            (-WORD_MAX, false)
+        );
+        let (half, _) = HalfWord::from_i64(-1);
+        let (min, _) = HalfWord::from_i64(-((1 << (2 * BYTE_BITS)) - 1));
+        assert_eq!(
+            (min + half).to_i64(),
+            (-((1 << (2 * BYTE_BITS)) - 1), false)
+        );
+    }
+
+    #[test]
+    fn checked_arithmetic_fails_past_the_boundary() {
+        let word = |value| FullWord::from_i64(value).0;
+        let value = |word: Option<FullWord>| word.map(|word| word.to_i64().0);
+        assert_eq!(
+            value(word(WORD_MAX - 1).checked_add(&word(1))),
+            Some(WORD_MAX)
+        );
+        assert_eq!(value(word(WORD_MAX).checked_add(&word(1))), None);
+        assert_eq!(value(word(-WORD_MAX).checked_add(&word(-1))), None);
+        assert_eq!(value(word(WORD_MAX).checked_add(&word(-WORD_MAX))), Some(0));
+        assert_eq!(
+            value(word(-WORD_MAX + 1).checked_sub(&word(1))),
+            Some(-WORD_MAX)
+        );
+        assert_eq!(value(word(-WORD_MAX).checked_sub(&word(1))), None);
+        assert_eq!(value(word(WORD_MAX).checked_sub(&word(-1))), None);
+        assert_eq!(value(word(3).checked_sub(&word(5))), Some(-2));
+
+        // A zero result is positive, even from negative operands.
+        let zero = word(-7).checked_add(&word(7)).unwrap();
+        assert!(zero.is_positive());
+        assert!(word(-7).checked_sub(&word(-7)).unwrap().is_positive());
+    }
+
+    #[test]
+    fn half_words_widen_into_bytes_4_and_5() {
+        let half = HalfWord::from_bytes([HalfWord::NEG, 12, 34]);
+        let full = half.to_full_word();
+        assert_eq!(full.as_array(), &[FullWord::NEG, 0, 0, 0, 12, 34]);
+        assert_eq!(full.to_i64(), half.to_i64());
+
+        let half = HalfWord::from_bytes([HalfWord::POS, 0, 7]);
+        assert_eq!(
+            half.to_full_word().as_array(),
+            &[FullWord::POS, 0, 0, 0, 0, 7]
+        );
+
+        // -0 keeps its sign.
+        let zero = HalfWord::from_bytes([HalfWord::NEG, 0, 0]);
+        assert!(zero.to_full_word().is_negative());
+        assert_eq!(zero.to_full_word().to_i64(), (0, false));
+
+        let j = PosHalfWord::from_bytes([PosHalfWord::POS, 1, 2]);
+        assert_eq!(j.to_full_word().as_array(), &[FullWord::POS, 0, 0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn pos_half_words_stay_positive() {
+        let mut j = PosHalfWord::from_bytes([PosHalfWord::POS, 3, 4]);
+        j.set_sign(true);
+        assert!(j.is_positive());
+        assert_eq!(j.as_array(), &[PosHalfWord::POS, 3, 4]);
+        j.flip_sign();
+        assert!(j.is_positive());
+        j.set_sign(false);
+        assert_eq!(j.as_array(), &[PosHalfWord::POS, 3, 4]);
+
+        // Signed half words do change.
+        let mut half = HalfWord::from_bytes([HalfWord::POS, 3, 4]);
+        half.set_sign(true);
+        assert!(half.is_negative());
+        half.flip_sign();
+        assert!(half.is_positive());
+    }
+
+    /// Set and read back the sign of a word of every width.
+    fn sign_bytes<const N: usize, const P: bool>() -> [u8; 3] {
+        let mut word = Word::<N, P>::new();
+        let positive = word.sign_byte();
+        word.set_sign(true);
+        let negative = word.sign_byte();
+        word.set_sign(false);
+        [positive, negative, word.sign_byte()]
+    }
+
+    #[test]
+    fn sign_bytes_follow_set_sign() {
+        const POS: u8 = FullWord::POS;
+        const NEG: u8 = FullWord::NEG;
+        assert_eq!(sign_bytes::<6, false>(), [POS, NEG, POS]);
+        assert_eq!(sign_bytes::<3, false>(), [POS, NEG, POS]);
+        assert_eq!(sign_bytes::<2, false>(), [POS, NEG, POS]);
+        assert_eq!(sign_bytes::<3, true>(), [POS, POS, POS]);
+
+        // Any other sign byte is negative, and is made canonical.
+        let mut word = FullWord::from_bytes([7, 0, 0, 0, 0, 1]);
+        assert!(word.is_negative());
+        assert_eq!(word.sign_byte(), 7);
+        word.set_sign(true);
+        assert_eq!(word.sign_byte(), NEG);
+        assert_eq!(word.to_i64(), (-1, false));
+    }
+
+    #[test]
+    fn hex_bytes_are_formatted() {
+        let word = FullWord::from_bytes([FullWord::NEG, 0, 0, 0x0A, 0x0B, 0x0C]);
+        assert_eq!(word.bytes_as_hex(), "0100000A0B0C");
+
+        // Every value of every byte, the sign byte included.
+        for i in 0..6 {
+            for value in 0..=u8::MAX {
+                let mut bytes = [0x5A; 6];
+                bytes[i] = value;
+                let word = FullWord::from_bytes(bytes);
+                let hex = word.bytes_as_hex();
+                assert_eq!(hex.len(), 12);
+                assert_eq!(hex, hex.to_ascii_uppercase());
+                assert_eq!(
+                    &hex[2 * i..2 * i + 2],
+                    format!("{:02X}", word.as_array()[i])
+                );
+            }
+        }
+        let word = PosHalfWord::from_bytes([PosHalfWord::NEG, 2, 3]);
+        assert_eq!(word.bytes_as_hex(), "000203");
+    }
+
+    #[test]
+    fn byte_slice_lays_words_out_in_address_order() {
+        let mut mem = Mem::new();
+        mem[0] = FullWord::from_bytes([FullWord::NEG, 1, 2, 3, 4, 5]);
+        mem[1234] = FullWord::from_bytes([FullWord::POS, 6, 7, 8, 9, 10]);
+        let bytes = mem.as_byte_slice();
+        assert_eq!(bytes.len(), Mem::SIZE * 6);
+        assert_eq!(bytes[..6], [FullWord::NEG, 1, 2, 3, 4, 5]);
+        assert_eq!(bytes[1234 * 6..1235 * 6], [FullWord::POS, 6, 7, 8, 9, 10]);
+        assert!(bytes[6..1234 * 6].iter().all(|&b| b == 0));
+
+        let copy = Mem::from_bytes(bytes).unwrap();
+        assert_eq!(copy.as_byte_slice(), bytes);
+        assert_eq!(copy[1234].as_array(), mem[1234].as_array());
+        assert!(Mem::from_bytes(&bytes[1..]).is_err());
+        assert!(Mem::from_bytes(&[0; Mem::SIZE * 6 + 1]).is_err());
+
+        mem.as_byte_slice_mut()[3999 * 6 + 5] = 42;
+        assert_eq!(mem[3999].to_i64(), (42, false));
+    }
+
+    #[test]
+    fn byte_patterns_are_found_at_any_offset() {
+        let mut mem = Mem::new();
+        mem[10] = FullWord::from_bytes([FullWord::POS, 0, 0, 0, 0xAB, 0xCD]);
+        mem[11] = FullWord::from_bytes([FullWord::NEG, 0xEF, 0, 0, 0, 0]);
+        mem[20] = FullWord::from_bytes([FullWord::POS, 0xAB, 0xCD, 0xEF, 0, 0]);
+        assert_eq!(mem.find_bytes(&[0xAB, 0xCD]), [(10, 4), (20, 1)]);
+        // Spanning the sign byte of the next word.
+        assert_eq!(mem.find_bytes(&[0xCD, FullWord::NEG, 0xEF]), [(10, 5)]);
+        assert_eq!(mem.find_bytes(&[0xCD, 0xEF]), [(20, 2)]);
+        assert!(mem.find_bytes(&[]).is_empty());
+        assert!(mem.find_bytes(&[0xAB, 0xEF]).is_empty());
+        let zeros = mem.find_bytes(&[0; 6]);
+        assert_eq!(zeros.first(), Some(&(0, 0)));
+        assert!(!zeros.contains(&(10, 0)));
+
+        assert_eq!(mem.find_pattern(&[Some(0xAB), None, Some(0xEF)]), [(20, 1)]);
+        assert_eq!(mem.find_pattern(&[Some(0xCD), None, Some(0xEF)]), [(10, 5)]);
+    }
+}
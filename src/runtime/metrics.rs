@@ -0,0 +1,579 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use super::*;
+
+/// Why a run stopped.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HaltReason {
+    /// The machine ran `HLT`.
+    Instruction,
+
+    /// The machine encountered an error.
+    Error(ErrorCode),
+
+    /// The run reached its step limit.
+    Timeout,
+}
+
+impl HaltReason {
+    /// Get the label value used in metrics.
+    pub const fn label(self) -> &'static str {
+        match self {
+            HaltReason::Instruction => "instruction",
+            HaltReason::Error(_) => "error",
+            HaltReason::Timeout => "timeout",
+        }
+    }
+}
+
+/// Metrics collected from a single run.
+///
+/// See [`VM::run_with_metrics()`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RunOutcome {
+    /// The number of instructions executed.
+    pub instructions: u64,
+
+    /// The number of cycles spent, in `u` as timed by Knuth.
+    pub cycles: u64,
+
+    /// The number of blocks transferred by `IN` and `OUT`, by unit.
+    pub io_blocks: [u64; IO_UNITS],
+
+    /// The transfer statistics, by unit.
+    pub devices: [DeviceStats; IO_UNITS],
+
+    /// Why the run stopped.
+    pub halt_reason: HaltReason,
+
+    /// The wall-clock time of the run.
+    pub duration: Duration,
+}
+
+/// Execution counts by opcode.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Profile {
+    /// The number of executions for each [`Opcode`].
+    pub counts: [u64; 64],
+}
+
+impl Profile {
+    /// Create a new profile with no execution.
+    pub const fn new() -> Self {
+        Profile { counts: [0; 64] }
+    }
+
+    /// Record an execution of an opcode.
+    pub fn record(&mut self, opcode: Opcode) {
+        self.counts[opcode as usize] += 1;
+    }
+}
+
+impl Default for Profile {
+    /// Create a new profile with no execution.
+    ///
+    /// Equivalent to [`Profile::new()`].
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Instruction {
+    /// Get the execution time of the instruction, in `u`.
+    ///
+    /// Times follow Knuth, TAOCP Vol. 1, 1.3.1. IO instructions are
+    /// counted without the time spent waiting for devices.
+    pub const fn cycles(&self) -> u64 {
+        match self.opcode {
+            Opcode::Nop => 1,
+            Opcode::Add | Opcode::Sub if self.field == 7 => 4,
+            Opcode::Add | Opcode::Sub => 2,
+            Opcode::Mul if self.field == 7 => 9,
+            Opcode::Mul => 10,
+            Opcode::Div if self.field == 7 => 11,
+            Opcode::Div => 12,
+            Opcode::Special => 10,
+            Opcode::Shift => 2,
+            Opcode::Move => 1 + 2 * self.field as u64,
+            Opcode::Jbus | Opcode::Ioc | Opcode::In | Opcode::Out | Opcode::Jred => 1,
+            Opcode::Jmp
+            | Opcode::JA
+            | Opcode::J1
+            | Opcode::J2
+            | Opcode::J3
+            | Opcode::J4
+            | Opcode::J5
+            | Opcode::J6
+            | Opcode::JX => 1,
+            Opcode::ModifyA
+            | Opcode::Modify1
+            | Opcode::Modify2
+            | Opcode::Modify3
+            | Opcode::Modify4
+            | Opcode::Modify5
+            | Opcode::Modify6
+            | Opcode::ModifyX => 1,
+            Opcode::CmpA | Opcode::CmpX if self.field == 7 => 4,
+            // Loads, stores and comparisons.
+            _ => 2,
+        }
+    }
+}
+
+/// Totals of [`RunOutcome`]s over multiple runs.
+///
+/// ```text
+/// let mut acc = MetricsAccumulator::new();
+/// for program in &programs {
+///     let outcome = vm.run_with_metrics(program, max_steps, None)?;
+///     acc.add(&outcome, None);
+/// }
+/// print!("{}", acc.render());
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct MetricsAccumulator {
+    /// The number of runs added.
+    pub runs: u64,
+
+    /// The number of instructions executed.
+    pub instructions: u64,
+
+    /// The number of cycles spent.
+    pub cycles: u64,
+
+    /// The number of blocks transferred, by unit.
+    pub io_blocks: [u64; IO_UNITS],
+
+    /// The transfer statistics, by unit.
+    pub devices: [DeviceStats; IO_UNITS],
+
+    /// The number of runs by [`HaltReason::label()`].
+    pub halts: BTreeMap<&'static str, u64>,
+
+    /// The number of runs by the [`Debug`] form of their [`ErrorCode`].
+    pub errors: BTreeMap<String, u64>,
+
+    /// The total wall-clock time.
+    pub duration: Duration,
+
+    /// The execution counts by opcode, if any profile is added.
+    pub profile: Option<Profile>,
+}
+
+impl MetricsAccumulator {
+    /// Create a new accumulator with no run.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a run.
+    ///
+    /// # Arguments
+    /// * `outcome` - The metrics of the run.
+    /// * `profile` - The execution counts of the run, if collected.
+    pub fn add(&mut self, outcome: &RunOutcome, profile: Option<&Profile>) {
+        self.runs += 1;
+        self.instructions += outcome.instructions;
+        self.cycles += outcome.cycles;
+        for (total, blocks) in self.io_blocks.iter_mut().zip(outcome.io_blocks) {
+            *total += blocks;
+        }
+        for (total, stats) in self.devices.iter_mut().zip(outcome.devices) {
+            total.blocks_read += stats.blocks_read;
+            total.words_read += stats.words_read;
+            total.blocks_written += stats.blocks_written;
+            total.words_written += stats.words_written;
+            total.controls += stats.controls;
+            total.busy_cycles += stats.busy_cycles;
+        }
+        *self.halts.entry(outcome.halt_reason.label()).or_default() += 1;
+        if let HaltReason::Error(err) = outcome.halt_reason {
+            *self.errors.entry(format!("{:?}", err)).or_default() += 1;
+        }
+        self.duration += outcome.duration;
+        if let Some(profile) = profile {
+            let total = self.profile.get_or_insert_with(Profile::new);
+            for (total, count) in total.counts.iter_mut().zip(profile.counts) {
+                *total += count;
+            }
+        }
+    }
+
+    /// Render the totals in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        };
+
+        metric(
+            "mix_runs_total",
+            "counter",
+            "Runs of the machine.",
+            &[(String::new(), self.runs.to_string())],
+        );
+        metric(
+            "mix_instructions_total",
+            "counter",
+            "Instructions executed.",
+            &[(String::new(), self.instructions.to_string())],
+        );
+        metric(
+            "mix_cycles_total",
+            "counter",
+            "Cycles spent, in units of u.",
+            &[(String::new(), self.cycles.to_string())],
+        );
+        let io_blocks: Vec<_> = self
+            .io_blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, &blocks)| blocks != 0)
+            .map(|(unit, blocks)| (labels(&[("unit", &unit.to_string())]), blocks.to_string()))
+            .collect();
+        metric(
+            "mix_io_blocks_total",
+            "counter",
+            "Blocks transferred by IN and OUT.",
+            &io_blocks,
+        );
+        let device_samples = |value: fn(&DeviceStats) -> u64| -> Vec<_> {
+            self.devices
+                .iter()
+                .enumerate()
+                .filter(|(_, stats)| value(stats) != 0)
+                .map(|(unit, stats)| {
+                    (
+                        labels(&[("unit", &unit.to_string())]),
+                        value(stats).to_string(),
+                    )
+                })
+                .collect()
+        };
+        let words: Vec<_> = self
+            .devices
+            .iter()
+            .enumerate()
+            .flat_map(|(unit, stats)| {
+                [("read", stats.words_read), ("write", stats.words_written)]
+                    .into_iter()
+                    .filter(|&(_, words)| words != 0)
+                    .map(move |(direction, words)| {
+                        (
+                            labels(&[("unit", &unit.to_string()), ("direction", direction)]),
+                            words.to_string(),
+                        )
+                    })
+            })
+            .collect();
+        metric(
+            "mix_device_words_total",
+            "counter",
+            "Words transferred by IN and OUT.",
+            &words,
+        );
+        metric(
+            "mix_device_controls_total",
+            "counter",
+            "IOC commands issued.",
+            &device_samples(|stats| stats.controls),
+        );
+        metric(
+            "mix_device_busy_cycles_total",
+            "counter",
+            "Cycles units were kept busy by transfers.",
+            &device_samples(|stats| stats.busy_cycles),
+        );
+        let halts: Vec<_> = self
+            .halts
+            .iter()
+            .map(|(reason, count)| (labels(&[("reason", reason)]), count.to_string()))
+            .collect();
+        metric("mix_halts_total", "counter", "Runs by halt reason.", &halts);
+        let errors: Vec<_> = self
+            .errors
+            .iter()
+            .map(|(code, count)| (labels(&[("code", code)]), count.to_string()))
+            .collect();
+        metric(
+            "mix_errors_total",
+            "counter",
+            "Runs stopped by an error, by error code.",
+            &errors,
+        );
+        metric(
+            "mix_run_duration_seconds",
+            "gauge",
+            "Wall-clock time of the runs.",
+            &[(String::new(), self.duration.as_secs_f64().to_string())],
+        );
+        if let Some(profile) = &self.profile {
+            let opcodes: Vec<_> = profile
+                .counts
+                .iter()
+                .enumerate()
+                .filter(|(_, &count)| count != 0)
+                .map(|(opcode, count)| {
+                    (
+                        labels(&[("opcode", &opcode.to_string())]),
+                        count.to_string(),
+                    )
+                })
+                .collect();
+            metric(
+                "mix_opcode_executions_total",
+                "counter",
+                "Instructions executed, by opcode.",
+                &opcodes,
+            );
+        }
+        out
+    }
+}
+
+/// Render the metrics of a single run in the Prometheus text
+/// exposition format.
+///
+/// # Arguments
+/// * `outcome` - The metrics of the run.
+/// * `profile` - The execution counts of the run, if collected.
+pub fn render_prometheus(outcome: &RunOutcome, profile: Option<&Profile>) -> String {
+    let mut acc = MetricsAccumulator::new();
+    acc.add(outcome, profile);
+    acc.render()
+}
+
+/// Format a set of labels, escaping their values.
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let pairs: Vec<_> = pairs
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+impl VM {
+    /// Load a program and run it like
+    /// [`VM::load_and_run_with_timeout()`], collecting metrics.
+    ///
+    /// # Arguments
+    /// * `program` - The program to run.
+    /// * `max_steps` - The maximum number of instructions to run.
+    /// * `profile` - If given, execution counts are added to it.
+    ///
+    /// # Returns
+    /// * [`Ok(RunOutcome)`] - The metrics of the run, whether it halted or not.
+    /// * [`Err(RunError::ProgramLoadError)`] - The program cannot be loaded.
+    pub fn run_with_metrics(
+        &mut self,
+        program: &MixProgram,
+        max_steps: u64,
+        mut profile: Option<&mut Profile>,
+    ) -> Result<RunOutcome, RunError> {
+        program.install(self)?;
+
+        let start = Instant::now();
+        let mut outcome = RunOutcome {
+            instructions: 0,
+            cycles: 0,
+            io_blocks: [0; IO_UNITS],
+            devices: Default::default(),
+            halt_reason: HaltReason::Instruction,
+            duration: Duration::ZERO,
+        };
+        let result = self.helper_run_with_timeout(max_steps, |vm| {
+            let instr = vm.current_instruction().ok();
+            let steps = vm.step_count();
+            vm.step()?;
+            // The instruction is fetched successfully if it steps. It
+            // may be run more than once if a wait is skipped.
+            if let Some(instr) = instr {
+                let count = vm.step_count() - steps;
+                outcome.instructions += count;
+                outcome.cycles += instr.cycles() * count;
+                if matches!(instr.opcode, Opcode::In | Opcode::Out) {
+                    outcome.io_blocks[instr.field as usize] += 1;
+                }
+                if let Some(profile) = profile.as_deref_mut() {
+                    for _ in 0..count {
+                        profile.record(instr.opcode);
+                    }
+                }
+            }
+            Ok(())
+        });
+        outcome.halt_reason = match result {
+            Ok(()) => HaltReason::Instruction,
+            Err(RunError::Timeout(_)) => HaltReason::Timeout,
+            Err(RunError::ExecutionError(err)) => HaltReason::Error(err),
+            Err(err) => return Err(err),
+        };
+        for (unit, stats) in outcome.devices.iter_mut().enumerate() {
+            *stats = self.device_stats(unit);
+        }
+        outcome.duration = start.elapsed();
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `instrs` from address 0 on a fresh machine.
+    fn metrics_of(instrs: &[Instruction], max_steps: u64) -> (RunOutcome, Profile) {
+        let mut mix = VM::new();
+        let mut profile = Profile::new();
+        let outcome = mix
+            .run_with_metrics(&MixProgram::from(instrs), max_steps, Some(&mut profile))
+            .unwrap();
+        (outcome, profile)
+    }
+
+    #[test]
+    fn runs_are_measured() {
+        // ENTA 5; HLT
+        let (outcome, profile) = metrics_of(
+            &[
+                Instruction::new(5, 2, 0, Opcode::ModifyA),
+                Instruction::new(0, 2, 0, Opcode::Special),
+            ],
+            10,
+        );
+        assert_eq!(outcome.halt_reason, HaltReason::Instruction);
+        assert_eq!(outcome.instructions, 2);
+        assert_eq!(outcome.cycles, 11);
+        assert_eq!(profile.counts[Opcode::ModifyA as usize], 1);
+        assert_eq!(profile.counts[Opcode::Special as usize], 1);
+
+        // JMP 0
+        let (outcome, profile) = metrics_of(&[Instruction::new(0, 0, 0, Opcode::Jmp)], 10);
+        assert_eq!(outcome.halt_reason, HaltReason::Timeout);
+        assert_eq!(outcome.instructions, 10);
+        assert_eq!(profile.counts[Opcode::Jmp as usize], 10);
+
+        // LDA 0,7
+        let (outcome, _) = metrics_of(&[Instruction::new(0, 5, 7, Opcode::LdA)], 10);
+        assert_eq!(
+            outcome.halt_reason,
+            HaltReason::Error(ErrorCode::InvalidIndex)
+        );
+        assert_eq!(outcome.instructions, 0);
+    }
+
+    #[test]
+    fn metrics_are_rendered_in_the_exposition_format() {
+        let mut outcome = RunOutcome {
+            instructions: 12,
+            cycles: 30,
+            io_blocks: [0; IO_UNITS],
+            devices: Default::default(),
+            halt_reason: HaltReason::Error(ErrorCode::UnknownDevice),
+            duration: Duration::from_millis(1500),
+        };
+        outcome.io_blocks[18] = 2;
+        outcome.devices[18].words_written = 48;
+        let expected = "\
+# HELP mix_runs_total Runs of the machine.
+# TYPE mix_runs_total counter
+mix_runs_total 1
+# HELP mix_instructions_total Instructions executed.
+# TYPE mix_instructions_total counter
+mix_instructions_total 12
+# HELP mix_cycles_total Cycles spent, in units of u.
+# TYPE mix_cycles_total counter
+mix_cycles_total 30
+# HELP mix_io_blocks_total Blocks transferred by IN and OUT.
+# TYPE mix_io_blocks_total counter
+mix_io_blocks_total{unit=\"18\"} 2
+# HELP mix_device_words_total Words transferred by IN and OUT.
+# TYPE mix_device_words_total counter
+mix_device_words_total{unit=\"18\",direction=\"write\"} 48
+# HELP mix_device_controls_total IOC commands issued.
+# TYPE mix_device_controls_total counter
+# HELP mix_device_busy_cycles_total Cycles units were kept busy by transfers.
+# TYPE mix_device_busy_cycles_total counter
+# HELP mix_halts_total Runs by halt reason.
+# TYPE mix_halts_total counter
+mix_halts_total{reason=\"error\"} 1
+# HELP mix_errors_total Runs stopped by an error, by error code.
+# TYPE mix_errors_total counter
+mix_errors_total{code=\"UnknownDevice\"} 1
+# HELP mix_run_duration_seconds Wall-clock time of the runs.
+# TYPE mix_run_duration_seconds gauge
+mix_run_duration_seconds 1.5
+";
+        assert_eq!(render_prometheus(&outcome, None), expected);
+    }
+
+    #[test]
+    fn opcode_counts_are_rendered_with_a_profile() {
+        let (outcome, profile) = metrics_of(&[Instruction::new(0, 2, 0, Opcode::Special)], 10);
+        let text = render_prometheus(&outcome, Some(&profile));
+        assert!(text.contains("# TYPE mix_opcode_executions_total counter\n"));
+        assert!(text.contains("mix_opcode_executions_total{opcode=\"5\"} 1\n"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(
+            labels(&[("code", "a\\b\"c\nd"), ("unit", "1")]),
+            r#"{code="a\\b\"c\nd",unit="1"}"#
+        );
+        assert_eq!(labels(&[("code", "")]), r#"{code=""}"#);
+    }
+
+    #[test]
+    fn tape_transfers_are_counted() {
+        let program = [
+            // ENT1 10
+            Instruction::new(10, 2, 0, Opcode::Modify1),
+            // OUT 1000(0); DEC1 1; J1P 1
+            Instruction::new(1000, 0, 0, Opcode::Out),
+            Instruction::new(1, 1, 0, Opcode::Modify1),
+            Instruction::new(1, 2, 0, Opcode::J1),
+            // IOC 0(0); IN 2000(0); JBUS *(0); HLT
+            Instruction::new(0, 0, 0, Opcode::Ioc),
+            Instruction::new(2000, 0, 0, Opcode::In),
+            Instruction::new(6, 0, 0, Opcode::Jbus),
+            Instruction::new(0, 2, 0, Opcode::Special),
+        ];
+        let mut mix = VM::new();
+        mix.attach_device(0, Box::new(TapeDevice::new().with_bandwidth(1)));
+        let outcome = mix
+            .run_with_metrics(&MixProgram::from(&program[..]), 10_000, None)
+            .unwrap();
+        assert_eq!(outcome.halt_reason, HaltReason::Instruction);
+
+        let expected = DeviceStats {
+            blocks_read: 1,
+            words_read: STORAGE_BLOCK_SIZE as u64,
+            blocks_written: 10,
+            words_written: 10 * STORAGE_BLOCK_SIZE as u64,
+            controls: 1,
+            busy_cycles: 11 * STORAGE_BLOCK_SIZE as u64,
+        };
+        assert_eq!(mix.device_stats(0), expected);
+        assert_eq!(outcome.devices[0], expected);
+        assert_eq!(outcome.io_blocks[0], 11);
+        // The rewind waits for the writes, and the machine for the read.
+        assert!(mix.cycle_count() >= 11 * STORAGE_BLOCK_SIZE as u64);
+        assert_eq!(mix.device_stats(1), DeviceStats::default());
+        assert_eq!(mix.device_stats(IO_UNITS), DeviceStats::default());
+
+        mix.reset();
+        assert_eq!(mix.device_stats(0), DeviceStats::default());
+    }
+}
@@ -0,0 +1,53 @@
+use super::FullWord;
+
+/// A device mapped into the address space of a [`MixVM`].
+///
+/// Loads and stores hitting a mapped address range are routed to
+/// the device instead of the memory. This is an alternative to
+/// `IN` and `OUT` for simple devices that expose a handful of
+/// registers.
+///
+/// See [`VM::map_device()`] for how to map a device.
+///
+/// [`MixVM`]: crate::MixVM
+/// [`VM::map_device()`]: super::VM::map_device
+pub trait MmioDevice {
+    /// Load a word from the device.
+    ///
+    /// # Arguments
+    /// * `offset` - The offset of the accessed address from the start of the mapped range.
+    fn load(&mut self, offset: u16) -> FullWord;
+
+    /// Store a word into the device.
+    ///
+    /// Every store instruction calls this method once. Partial-field
+    /// stores, e.g. `STA M(4:5)`, do not load from the device first:
+    /// the bytes outside the field are zero, and the sign is positive
+    /// unless the field includes it.
+    ///
+    /// # Arguments
+    /// * `offset` - The offset of the accessed address from the start of the mapped range.
+    /// * `word` - The word to store.
+    fn store(&mut self, offset: u16, word: FullWord);
+}
+
+/// A sample memory-mapped counter occupying one word.
+///
+/// Loading from the device yields the current count. Storing a
+/// word into the device adds its value to the count, so that
+/// `STA` of `+1` increments the counter.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CounterMmioDevice {
+    /// The current count.
+    pub count: i64,
+}
+
+impl MmioDevice for CounterMmioDevice {
+    fn load(&mut self, _: u16) -> FullWord {
+        FullWord::from_i64(self.count).0
+    }
+
+    fn store(&mut self, _: u16, word: FullWord) {
+        self.count += word.to_i64().0;
+    }
+}
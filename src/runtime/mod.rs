@@ -12,3 +12,70 @@ pub use vm::*;
 
 mod io;
 pub use io::*;
+
+mod devices;
+pub use devices::*;
+
+mod disasm;
+
+mod mmio;
+pub use mmio::*;
+
+mod program;
+pub use program::*;
+
+pub mod formats;
+
+mod coverage;
+pub use coverage::*;
+
+mod assert;
+
+mod layout;
+pub use layout::*;
+
+mod console;
+pub use console::*;
+
+pub mod metrics;
+
+pub mod float;
+
+pub mod analysis;
+
+pub mod asm;
+
+mod extensions;
+pub use extensions::*;
+
+mod debug;
+
+mod events;
+pub use events::*;
+
+mod sealed;
+pub use sealed::*;
+
+mod storage;
+pub use storage::*;
+
+mod txn;
+pub use txn::*;
+
+mod time;
+pub use time::*;
+
+pub mod examples;
+
+pub mod checkpoint;
+
+pub mod conformance;
+
+pub mod scheduler;
+
+pub mod semantics;
+
+pub mod patch;
+
+#[cfg(test)]
+pub(crate) mod fuzz;
@@ -0,0 +1,133 @@
+//! Byte pattern search and patching of memory.
+//!
+//! Memory images are patched the way binaries are: find the bytes of
+//! some code, with wildcards for the bytes that vary, and overwrite
+//! the words where they start. [`VM::find_and_patch()`] does both at
+//! once; [`VM::find_patch_sites()`] and [`VM::write_patch()`] split
+//! the choice of sites from the writes.
+
+use super::*;
+
+/// Errors returned by patches.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PatchError {
+    /// The pattern matches no word.
+    NoMatch,
+
+    /// The replacement does not fit in memory at an address.
+    OutOfRange {
+        /// The address of the replacement.
+        addr: u16,
+    },
+
+    /// The replacement covers a protected address, see [`VM::protect()`].
+    WriteProtected {
+        /// The protected address.
+        addr: u16,
+    },
+}
+
+impl VM {
+    /// Find the words where a byte pattern starts.
+    ///
+    /// The pattern is matched against the memory as a flat slice of
+    /// bytes, see [`Mem::find_pattern()`], so it may span multiple
+    /// words. Only matches starting at the sign byte of a word are
+    /// returned.
+    ///
+    /// # Arguments
+    /// * `pattern` - The bytes to find. `None` matches any byte.
+    ///
+    /// # Returns
+    /// * [`Vec<u16>`] - The addresses of the matches, in ascending order.
+    pub fn find_patch_sites(&self, pattern: &[Option<u8>]) -> Vec<u16> {
+        self.mem
+            .find_pattern(pattern)
+            .into_iter()
+            .filter(|&(_, offset)| offset == 0)
+            .map(|(addr, _)| addr)
+            .collect()
+    }
+
+    /// Write a replacement at each of the given addresses.
+    ///
+    /// Either all replacements are written, or none if any does not
+    /// fit in memory or covers a protected address. The words go
+    /// straight to memory, bypassing memory-mapped devices. If the
+    /// event log is enabled, each replacement is logged as an
+    /// [`Event::MemoryWrite`].
+    ///
+    /// # Arguments
+    /// * `sites` - The addresses, e.g. from [`VM::find_patch_sites()`].
+    /// * `replacement` - The words to write at each address.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The replacements are written.
+    /// * [`Err(PatchError::OutOfRange)`] - A replacement does not fit in memory.
+    /// * [`Err(PatchError::WriteProtected)`] - A replacement covers a protected address.
+    pub fn write_patch(
+        &mut self,
+        sites: &[u16],
+        replacement: &[FullWord],
+    ) -> Result<(), PatchError> {
+        if replacement.is_empty() {
+            return Ok(());
+        }
+        for &addr in sites {
+            if addr as usize + replacement.len() > Mem::SIZE {
+                return Err(PatchError::OutOfRange { addr });
+            }
+            let end = addr + replacement.len() as u16;
+            if let Some(addr) = (addr..end).find(|&a| self.is_protected(a)) {
+                return Err(PatchError::WriteProtected { addr });
+            }
+        }
+        for &addr in sites {
+            let end = addr + replacement.len() as u16;
+            for (a, word) in (addr..end).zip(replacement) {
+                self.mem[a] = *word;
+            }
+            self.helper_log(Event::MemoryWrite {
+                range: addr..=end - 1,
+            });
+        }
+        Ok(())
+    }
+
+    /// Find the words where a byte pattern starts and write a
+    /// replacement at each of them.
+    ///
+    /// See [`VM::find_patch_sites()`] and [`VM::write_patch()`]. To
+    /// patch only some of the matches, call them in turn.
+    ///
+    /// ```text
+    /// // Never take a `JANZ`, whatever its address and index.
+    /// let nop = FullWord::from(Instruction::new(0, 0, 0, Opcode::Nop));
+    /// let sites = mix.find_and_patch(&[None, None, None, None, Some(4), Some(40)], &[nop])?;
+    /// ```
+    ///
+    /// # Returns
+    /// * [`Ok(Vec<u16>)`] - The patched addresses, in ascending order.
+    /// * [`Err(PatchError::NoMatch)`] - The pattern matches no word.
+    /// * [`Err(PatchError)`] - Nothing is written, see [`VM::write_patch()`].
+    pub fn find_and_patch(
+        &mut self,
+        pattern: &[Option<u8>],
+        replacement: &[FullWord],
+    ) -> Result<Vec<u16>, PatchError> {
+        let sites = self.find_patch_sites(pattern);
+        if sites.is_empty() {
+            return Err(PatchError::NoMatch);
+        }
+        self.write_patch(&sites, replacement)?;
+        Ok(sites)
+    }
+
+    /// Replace the instruction at an address.
+    ///
+    /// Unlike [`VM::apply_patch()`], a protected address is refused.
+    /// See [`VM::write_patch()`].
+    pub fn patch_instruction(&mut self, addr: u16, instr: Instruction) -> Result<(), PatchError> {
+        self.write_patch(&[addr], &[FullWord::from(instr)])
+    }
+}
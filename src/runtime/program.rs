@@ -0,0 +1,169 @@
+use super::*;
+
+/// A program to be loaded into a [`MixVM`].
+///
+/// [`MixVM`]: crate::MixVM
+#[derive(Clone, Debug, Default)]
+pub struct MixProgram {
+    /// The address where the first word is loaded.
+    pub start: u16,
+
+    /// The address of the first instruction to run.
+    pub entry: u16,
+
+    /// The words of the program.
+    pub words: Vec<FullWord>,
+}
+
+impl MixProgram {
+    /// Create a new program.
+    ///
+    /// # Arguments
+    /// * `start` - The address where the first word is loaded.
+    /// * `entry` - The address of the first instruction to run.
+    /// * `words` - The words of the program.
+    pub fn new(start: u16, entry: u16, words: Vec<FullWord>) -> Self {
+        MixProgram {
+            start,
+            entry,
+            words,
+        }
+    }
+
+    /// Load the program into a machine and set its entry point.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The program is installed.
+    /// * [`Err(RunError::ProgramLoadError)`] - The program does not fit in the memory.
+    pub fn install(&self, vm: &mut VM) -> Result<(), RunError> {
+        vm.load_program(self)?;
+        vm.set_entry(self.entry);
+        Ok(())
+    }
+}
+
+impl From<&[Instruction]> for MixProgram {
+    /// Create a program from instructions, loaded at and entered
+    /// from address 0.
+    fn from(instrs: &[Instruction]) -> Self {
+        MixProgram::new(0, 0, instrs.iter().map(|&i| FullWord::from(i)).collect())
+    }
+}
+
+/// Errors returned by [`VM::load_and_run_with_timeout()`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RunError {
+    /// The machine encountered an error and is now halted.
+    ExecutionError(ErrorCode),
+
+    /// The program did not halt in the given number of steps.
+    Timeout(u64),
+
+    /// The program does not fit in the memory or its entry
+    /// point is out of memory.
+    ProgramLoadError,
+}
+
+impl VM {
+    /// Load a program into the memory.
+    ///
+    /// Only the words of the program are written.
+    /// The rest of the memory is left untouched.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The program is loaded.
+    /// * [`Err(RunError::ProgramLoadError)`] - The program does not fit in the memory.
+    pub fn load_program(&mut self, program: &MixProgram) -> Result<(), RunError> {
+        let start = program.start as usize;
+        let end = start + program.words.len();
+        if end > Mem::SIZE || program.entry as usize >= Mem::SIZE {
+            return Err(RunError::ProgramLoadError);
+        }
+        self.mem[start..end].clone_from_slice(&program.words);
+        Ok(())
+    }
+
+    /// Load a program and run it until it halts, running at most
+    /// `max_steps` instructions.
+    ///
+    /// The program is [`MixProgram::install()`]ed, and the registers
+    /// are [`VM::reset()`] before running. The memory
+    /// outside the program and the IO devices are left untouched, so
+    /// that data and devices can be set up in advance. All IO devices
+    /// are flushed before returning, even on timeouts.
+    ///
+    /// # Arguments
+    /// * `program` - The program to run.
+    /// * `max_steps` - The maximum number of instructions to run.
+    ///
+    /// # Returns
+    /// * [`Ok(VMStateSnapshot)`] - The state of the machine after it halted.
+    /// * [`Err(RunError::ExecutionError)`] - The machine encountered an error and is now halted.
+    /// * [`Err(RunError::Timeout)`] - The program ran for `max_steps` instructions without halting.
+    ///   The machine is left running in its final state.
+    /// * [`Err(RunError::ProgramLoadError)`] - The program cannot be loaded.
+    pub fn load_and_run_with_timeout(
+        &mut self,
+        program: &MixProgram,
+        max_steps: u64,
+    ) -> Result<VMStateSnapshot, RunError> {
+        program.install(self)?;
+        self.run_with_timeout(max_steps)
+    }
+
+    /// Run the installed program until it halts, running at most
+    /// `max_steps` instructions.
+    ///
+    /// The registers are [`VM::reset()`] and the machine starts at
+    /// [`VM::entry()`], or at [`VM::reset_vector()`] if none is set.
+    /// The memory is not written, so that it can be checked right
+    /// before the run. All IO devices are flushed before returning,
+    /// even on timeouts.
+    ///
+    /// # Arguments
+    /// * `max_steps` - The maximum number of instructions to run.
+    ///
+    /// # Returns
+    /// * [`Ok(VMStateSnapshot)`] - The state of the machine after it halted.
+    /// * [`Err(RunError::ExecutionError)`] - The machine encountered an error and is now halted.
+    /// * [`Err(RunError::Timeout)`] - The program ran for `max_steps` instructions without halting.
+    ///   The machine is left running in its final state.
+    pub fn run_with_timeout(&mut self, max_steps: u64) -> Result<VMStateSnapshot, RunError> {
+        self.helper_run_with_timeout(max_steps, VM::step)?;
+        Ok(self.snapshot())
+    }
+
+    /// Run the installed program as [`VM::run_with_timeout()`] does,
+    /// stepping with `step`.
+    ///
+    /// `step` must run one [`VM::step()`], so that callers may look at
+    /// the machine around each step.
+    pub(crate) fn helper_run_with_timeout(
+        &mut self,
+        max_steps: u64,
+        mut step: impl FnMut(&mut VM) -> Result<(), ErrorCode>,
+    ) -> Result<(), RunError> {
+        self.reset();
+        if let Some(entry) = self.entry() {
+            self.pc = entry;
+        }
+        self.restart();
+
+        // Count steps by the machine, which may skip idle ones.
+        self.step_horizon = Some(max_steps);
+        let result = loop {
+            if self.halted {
+                break Ok(());
+            }
+            if self.step_count() >= max_steps {
+                break Err(RunError::Timeout(self.step_count()));
+            }
+            if let Err(err) = step(self) {
+                break Err(RunError::ExecutionError(err));
+            }
+        };
+        self.step_horizon = None;
+        let flushed = self.flush_devices().map_err(RunError::ExecutionError);
+        result.and(flushed)
+    }
+}
@@ -0,0 +1,269 @@
+//! Running several machines side by side.
+
+use super::*;
+
+/// A machine held by a [`Scheduler`].
+struct Slot {
+    /// The machine.
+    vm: VM,
+
+    /// The cycles run under the scheduler.
+    cycles: u64,
+
+    /// The cycles run past the end of the last quantum.
+    overrun: u64,
+
+    /// The error that halted the machine, if any.
+    error: Option<ErrorCode>,
+}
+
+/// A cooperative scheduler stepping machines round-robin.
+///
+/// In each round, every running machine runs for a quantum of
+/// cycles. An instruction is never cut short, so a machine may run
+/// past its quantum; the excess is taken from its next quantum, so
+/// that over time every machine gets the same number of cycles.
+///
+/// Machines are added ready to run, e.g. by
+/// [`MixProgram::install()`] followed by [`VM::restart()`]. Each one
+/// keeps its own devices.
+///
+/// ```text
+/// let mut scheduler = Scheduler::new(100);
+/// scheduler.add(left);
+/// scheduler.add(right);
+/// scheduler.run(10_000);
+/// println!("{:?}", scheduler.cycles(0));
+/// ```
+pub struct Scheduler {
+    /// The machines, in the order they were added.
+    slots: Vec<Slot>,
+
+    /// The number of cycles each machine runs per round.
+    quantum: u64,
+}
+
+impl Scheduler {
+    /// Create a new scheduler with no machine.
+    ///
+    /// # Arguments
+    /// * `quantum` - The number of cycles each machine runs per round.
+    ///   A quantum of 0 is taken as 1.
+    pub fn new(quantum: u64) -> Self {
+        Scheduler {
+            slots: Vec::new(),
+            quantum: quantum.max(1),
+        }
+    }
+
+    /// Add a machine.
+    ///
+    /// # Returns
+    /// * [`usize`] - The index of the machine.
+    pub fn add(&mut self, vm: VM) -> usize {
+        self.slots.push(Slot {
+            vm,
+            cycles: 0,
+            overrun: 0,
+            error: None,
+        });
+        self.slots.len() - 1
+    }
+
+    /// Get the number of machines.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Check if there is no machine.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Get a machine.
+    pub fn machine(&self, index: usize) -> Option<&VM> {
+        self.slots.get(index).map(|slot| &slot.vm)
+    }
+
+    /// Get a machine mutably.
+    pub fn machine_mut(&mut self, index: usize) -> Option<&mut VM> {
+        self.slots.get_mut(index).map(|slot| &mut slot.vm)
+    }
+
+    /// Take the machines back, in the order they were added.
+    pub fn into_machines(self) -> Vec<VM> {
+        self.slots.into_iter().map(|slot| slot.vm).collect()
+    }
+
+    /// Get the number of cycles a machine has run under the scheduler.
+    pub fn cycles(&self, index: usize) -> Option<u64> {
+        self.slots.get(index).map(|slot| slot.cycles)
+    }
+
+    /// Get the error that halted a machine.
+    ///
+    /// # Returns
+    /// * [`Some(ErrorCode)`] - The machine halted on this error.
+    /// * [`None`] - The machine is running, halted normally, or does not exist.
+    pub fn error(&self, index: usize) -> Option<ErrorCode> {
+        self.slots.get(index).and_then(|slot| slot.error)
+    }
+
+    /// Check if every machine has halted.
+    pub fn all_halted(&self) -> bool {
+        self.slots.iter().all(|slot| slot.vm.halted)
+    }
+
+    /// Run one round, giving each running machine its quantum.
+    ///
+    /// # Returns
+    /// * `true` - Some machine is still running.
+    /// * `false` - Every machine has halted.
+    pub fn run_round(&mut self) -> bool {
+        for slot in self.slots.iter_mut().filter(|slot| !slot.vm.halted) {
+            if slot.overrun >= self.quantum {
+                // A whole quantum ahead: let the others catch up.
+                slot.overrun -= self.quantum;
+                continue;
+            }
+            let budget = self.quantum - slot.overrun;
+            let start = slot.vm.cycle_count();
+            let used = loop {
+                if let Err(err) = slot.vm.step() {
+                    slot.error = Some(err);
+                }
+                let used = slot.vm.cycle_count() - start;
+                if slot.vm.halted || used >= budget {
+                    break used;
+                }
+            };
+            slot.cycles += used;
+            slot.overrun = used.saturating_sub(budget);
+        }
+        !self.all_halted()
+    }
+
+    /// Run rounds until every machine halts.
+    ///
+    /// # Arguments
+    /// * `max_rounds` - The number of rounds after which to stop.
+    ///
+    /// # Returns
+    /// * [`u64`] - The number of rounds run.
+    pub fn run(&mut self, max_rounds: u64) -> u64 {
+        let mut rounds = 0;
+        while rounds < max_rounds && !self.all_halted() {
+            self.run_round();
+            rounds += 1;
+        }
+        rounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A machine ready to run `instrs` from address 0.
+    fn machine(instrs: &[Instruction]) -> VM {
+        let mut mix = VM::new();
+        MixProgram::from(instrs).install(&mut mix).unwrap();
+        mix.restart();
+        mix
+    }
+
+    /// `JMP 0`, one cycle per instruction.
+    fn cheap_loop() -> VM {
+        machine(&[Instruction::new(0, 0, 0, Opcode::Jmp)])
+    }
+
+    /// `MUL 100; JMP 0`, ten cycles then one.
+    fn costly_loop() -> VM {
+        machine(&[
+            Instruction::new(100, 5, 0, Opcode::Mul),
+            Instruction::new(0, 0, 0, Opcode::Jmp),
+        ])
+    }
+
+    #[test]
+    fn machines_of_different_costs_get_the_same_cycles() {
+        let mut scheduler = Scheduler::new(10);
+        scheduler.add(cheap_loop());
+        scheduler.add(costly_loop());
+        assert_eq!(scheduler.run(100), 100);
+        // Each machine is at most one instruction ahead of its share.
+        for index in 0..2 {
+            let cycles = scheduler.cycles(index).unwrap();
+            assert!((1000..=1010).contains(&cycles), "{}: {}", index, cycles);
+        }
+        assert!(!scheduler.all_halted());
+    }
+
+    #[test]
+    fn overruns_are_taken_from_the_next_quanta() {
+        let mut scheduler = Scheduler::new(4);
+        scheduler.add(costly_loop());
+        // The `MUL` overruns the first quantum by 6 cycles...
+        assert!(scheduler.run_round());
+        assert_eq!(scheduler.cycles(0), Some(10));
+        // ...so the machine sits out the second one...
+        assert!(scheduler.run_round());
+        assert_eq!(scheduler.cycles(0), Some(10));
+        // ...and runs for the remaining 2 cycles in the third, running
+        // the `JMP` and another `MUL`.
+        assert!(scheduler.run_round());
+        assert_eq!(scheduler.cycles(0), Some(21));
+        assert_eq!(scheduler.machine(0).unwrap().pc, 1);
+    }
+
+    #[test]
+    fn runs_stop_once_every_machine_halts() {
+        let mut scheduler = Scheduler::new(5);
+        // ENTA 1; HLT
+        scheduler.add(machine(&[
+            Instruction::new(1, 2, 0, Opcode::ModifyA),
+            Instruction::new(0, 2, 0, Opcode::Special),
+        ]));
+        // MUL 100; HLT
+        scheduler.add(machine(&[
+            Instruction::new(100, 5, 0, Opcode::Mul),
+            Instruction::new(0, 2, 0, Opcode::Special),
+        ]));
+        // The first machine halts in the first round, the second one
+        // sits out the second round to make up for its `MUL` and halts
+        // in the third.
+        assert_eq!(scheduler.run(1000), 3);
+        assert!(scheduler.all_halted());
+        assert!(!scheduler.run_round());
+        assert_eq!(scheduler.run(1000), 0);
+        assert_eq!(scheduler.cycles(0), Some(11));
+        assert_eq!(scheduler.cycles(1), Some(20));
+    }
+
+    #[test]
+    fn errors_and_cycles_are_reported_by_machine() {
+        let mut scheduler = Scheduler::new(10);
+        // ENTA 1; LDA 4000
+        let faulting = scheduler.add(machine(&[
+            Instruction::new(1, 2, 0, Opcode::ModifyA),
+            Instruction::new(4000, 5, 0, Opcode::LdA),
+        ]));
+        // HLT
+        let halting = scheduler.add(machine(&[Instruction::new(0, 2, 0, Opcode::Special)]));
+        let running = scheduler.add(cheap_loop());
+        assert_eq!(scheduler.len(), 3);
+
+        scheduler.run(3);
+        assert_eq!(scheduler.error(faulting), Some(ErrorCode::InvalidAddress));
+        assert!(scheduler.machine(faulting).unwrap().halted);
+        // The faulting `LDA` is counted.
+        assert_eq!(scheduler.cycles(faulting), Some(3));
+        assert_eq!(scheduler.error(halting), None);
+        assert_eq!(scheduler.cycles(halting), Some(10));
+        assert_eq!(scheduler.error(running), None);
+        assert_eq!(scheduler.cycles(running), Some(30));
+        assert_eq!(scheduler.error(3), None);
+        assert_eq!(scheduler.cycles(3), None);
+        assert!(scheduler.machine(3).is_none());
+    }
+}
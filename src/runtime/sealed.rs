@@ -0,0 +1,175 @@
+use super::*;
+
+/// A source of the bytes served by a [`SealedKeyDevice`].
+pub type KeySource = Box<dyn FnOnce() -> Result<Vec<u8>, ()>>;
+
+/// A read-only device serving a block of secret words exactly once.
+///
+/// The words are not produced until the first `IN` from the device,
+/// so they never sit in [`Mem`] before the program asks for them.
+/// Every later read fails, as does the first one if the source of
+/// the words fails.
+///
+/// ```text
+/// let key = SealedKeyDevice::from_env(4, "CHALLENGE_KEY");
+/// mix.attach_device(16, Box::new(key));
+/// ```
+pub struct SealedKeyDevice {
+    /// The number of words in the block.
+    words: usize,
+
+    /// The source of the bytes, or `None` once consumed.
+    source: Option<KeySource>,
+}
+
+impl SealedKeyDevice {
+    /// Create a new device reading its bytes from a closure.
+    ///
+    /// The closure is called on the first read, and must return
+    /// exactly `words * 6` bytes, one [`FullWord`] per 6 bytes.
+    ///
+    /// # Arguments
+    /// * `words` - The number of words in the block.
+    /// * `source` - The source of the bytes.
+    pub fn new(words: usize, source: impl FnOnce() -> Result<Vec<u8>, ()> + 'static) -> Self {
+        SealedKeyDevice {
+            words,
+            source: Some(Box::new(source)),
+        }
+    }
+
+    /// Create a new device serving the given bytes.
+    ///
+    /// # Arguments
+    /// * `words` - The number of words in the block.
+    /// * `bytes` - The bytes of the words, 6 bytes per word.
+    pub fn from_bytes(words: usize, bytes: Vec<u8>) -> Self {
+        Self::new(words, move || Ok(bytes))
+    }
+
+    /// Create a new device serving words from an environment variable.
+    ///
+    /// The variable holds the words as literals of
+    /// [`Word::parse_literal()`] separated by blanks, e.g.
+    /// `+1:2:3:4:5 =-12=`, and is read when the device is created.
+    ///
+    /// # Arguments
+    /// * `words` - The number of words in the block.
+    /// * `name` - The name of the variable.
+    pub fn from_env(words: usize, name: &str) -> Self {
+        let value = std::env::var(name).map_err(|_| ());
+        Self::new(words, move || {
+            value?
+                .split_whitespace()
+                .map(|literal| FullWord::parse_literal(literal).map_err(|_| ()))
+                .map(|word| word.map(|word| *word.as_array()))
+                .collect::<Result<Vec<_>, _>>()
+                .map(|words| words.concat())
+        })
+    }
+
+    /// Create a new device serving bytes from a file.
+    ///
+    /// The file is read when the device is created. Reading stops
+    /// past `words * 6` bytes, making the device fail.
+    ///
+    /// # Arguments
+    /// * `words` - The number of words in the block.
+    /// * `path` - The path of the file.
+    pub fn from_file(words: usize, path: impl AsRef<std::path::Path>) -> Self {
+        let data = std::fs::File::open(path)
+            .and_then(|file| formats::common::read_limited(file, words * 6))
+            .map_err(|_| ());
+        Self::new(words, move || data)
+    }
+
+    /// Check if the words have been served or lost.
+    pub fn is_consumed(&self) -> bool {
+        self.source.is_none()
+    }
+}
+
+/// Secret bytes, zeroed when dropped on any way out of a read.
+struct ZeroOnDrop(Vec<u8>);
+
+impl Drop for ZeroOnDrop {
+    fn drop(&mut self) {
+        self.0.fill(0);
+    }
+}
+
+impl IODevice for SealedKeyDevice {
+    fn read(&mut self, buffer: &mut [FullWord]) -> Result<(), ()> {
+        if buffer.len() != self.words {
+            return Err(());
+        }
+        let source = self.source.take().ok_or(())?;
+        let bytes = ZeroOnDrop(source()?);
+        if bytes.0.len() != self.words * 6 {
+            return Err(());
+        }
+        for (word, chunk) in buffer.iter_mut().zip(bytes.0.chunks_exact(6)) {
+            *word = FullWord::try_from(chunk).map_err(|_| ())?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, _: &[FullWord]) -> Result<(), usize> {
+        Err(0)
+    }
+
+    fn control(&mut self, _: i16) -> Result<(), ()> {
+        Err(())
+    }
+
+    fn is_busy(&self) -> Result<bool, ()> {
+        Ok(false)
+    }
+
+    fn is_ready(&self) -> Result<bool, ()> {
+        Ok(!self.is_consumed())
+    }
+
+    fn get_block_size(&self) -> usize {
+        self.words
+    }
+
+    fn name(&self) -> &str {
+        "sealed key"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_are_served_once() {
+        let mut key = SealedKeyDevice::from_bytes(2, vec![0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 2]);
+        assert!(key.is_ready().unwrap());
+        let mut buffer = [FullWord::new(); 2];
+        assert_eq!(key.read(&mut buffer), Ok(()));
+        assert_eq!(buffer[0].to_i64().0, 1);
+        assert_eq!(buffer[1].to_i64().0, -2);
+        assert!(key.is_consumed());
+        assert_eq!(key.read(&mut buffer), Err(()));
+    }
+
+    #[test]
+    fn failed_reads_consume_the_words() {
+        let mut buffer = [FullWord::new(); 2];
+        let mut key = SealedKeyDevice::from_bytes(2, vec![0; 6]);
+        assert_eq!(key.read(&mut buffer), Err(()));
+        assert!(key.is_consumed());
+        assert!(!key.is_ready().unwrap());
+
+        let mut key = SealedKeyDevice::new(2, || Err(()));
+        assert_eq!(key.read(&mut buffer), Err(()));
+        assert!(key.is_consumed());
+
+        // A block of the wrong size leaves the words in place.
+        let mut key = SealedKeyDevice::from_bytes(2, vec![0; 12]);
+        assert_eq!(key.read(&mut buffer[..1]), Err(()));
+        assert!(!key.is_consumed());
+    }
+}
@@ -0,0 +1,654 @@
+//! The choices this machine makes where MIX leaves the behavior open.
+//!
+//! Knuth does not define every corner of the instruction set, e.g.
+//! loading more than two bytes into an index register. Each choice
+//! made here is an entry of the [`SemanticsReport`] returned by
+//! [`Semantics::current()`], with the settings of [`VM`] that change
+//! it. Every entry is backed by a check named after its identifier,
+//! run by [`check_semantics()`] and by a test of the same name.
+//!
+//! The code making a choice cites its entry in a comment, as in "the
+//! `div-by-zero` entry". The registry tests fail if an entry is not
+//! cited, if a citation names no entry, or if a comment calls some
+//! behavior undefined without citing an entry.
+//!
+//! ```text
+//! for entry in Semantics::current().entries() {
+//!     println!("{}: {}", entry.id, entry.description);
+//! }
+//! ```
+//!
+//! [`VM`]: crate::VM
+
+use std::fmt;
+
+use super::*;
+
+/// A setting of [`VM`] that changes some documented choice.
+///
+/// [`VM`]: crate::VM
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Setting {
+    /// [`VM::strict_index`].
+    ///
+    /// [`VM::strict_index`]: crate::VM::strict_index
+    StrictIndex,
+
+    /// [`VM::overflow_policy`].
+    ///
+    /// [`VM::overflow_policy`]: crate::VM::overflow_policy
+    OverflowPolicy,
+
+    /// [`VM::index_overflow_mode`].
+    ///
+    /// [`VM::index_overflow_mode`]: crate::VM::index_overflow_mode
+    IndexOverflowMode,
+
+    /// [`VM::num_char_radix`].
+    ///
+    /// [`VM::num_char_radix`]: crate::VM::num_char_radix
+    NumCharRadix,
+
+    /// [`VM::report_end_of_input`].
+    ///
+    /// [`VM::report_end_of_input`]: crate::VM::report_end_of_input
+    ReportEndOfInput,
+}
+
+impl Setting {
+    /// All settings.
+    pub const ALL: [Setting; 5] = [
+        Setting::StrictIndex,
+        Setting::OverflowPolicy,
+        Setting::IndexOverflowMode,
+        Setting::NumCharRadix,
+        Setting::ReportEndOfInput,
+    ];
+}
+
+/// A documented choice.
+#[derive(Clone, Copy, Debug)]
+pub struct SemanticsEntry {
+    /// The identifier, e.g. `ld-index-wide`.
+    pub id: &'static str,
+
+    /// What this machine does.
+    pub description: &'static str,
+
+    /// The settings that change the behavior.
+    pub affected_by: &'static [Setting],
+
+    /// Check that the machine behaves as described.
+    check: fn() -> bool,
+}
+
+impl SemanticsEntry {
+    /// Check that the machine behaves as described.
+    pub fn holds(&self) -> bool {
+        (self.check)()
+    }
+}
+
+/// The documented choices of this machine.
+///
+/// See [`Semantics::current()`].
+#[derive(Debug)]
+pub struct SemanticsReport {
+    /// The entries, by instruction.
+    entries: &'static [SemanticsEntry],
+}
+
+impl SemanticsReport {
+    /// Get all entries.
+    pub fn entries(&self) -> &'static [SemanticsEntry] {
+        self.entries
+    }
+
+    /// Get the entry with an identifier.
+    pub fn get(&self, id: &str) -> Option<&'static SemanticsEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    /// Get the entries changed by a setting.
+    pub fn affected_by(&self, setting: Setting) -> impl Iterator<Item = &'static SemanticsEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.affected_by.contains(&setting))
+    }
+}
+
+/// The registry of documented choices.
+pub struct Semantics;
+
+impl Semantics {
+    /// Get the choices of this build.
+    pub fn current() -> &'static SemanticsReport {
+        &REPORT
+    }
+}
+
+/// The report of this build.
+static REPORT: SemanticsReport = SemanticsReport { entries: &ENTRIES };
+
+/// The entries of [`REPORT`].
+static ENTRIES: [SemanticsEntry; 12] = [
+    SemanticsEntry {
+        id: "div-by-zero",
+        description: "DIV by zero sets the overflow toggle and clears the magnitudes of rA and rX.",
+        affected_by: &[],
+        check: div_by_zero,
+    },
+    SemanticsEntry {
+        id: "div-quotient-overflow",
+        description: "DIV with a quotient too large sets the overflow toggle and keeps the \
+                      lowest bytes of the quotient in rA and of the remainder in rX.",
+        affected_by: &[],
+        check: div_quotient_overflow,
+    },
+    SemanticsEntry {
+        id: "char-high-digits",
+        description: "CHAR converts the lowest ten digits of rA and drops the others.",
+        affected_by: &[Setting::NumCharRadix],
+        check: char_high_digits,
+    },
+    SemanticsEntry {
+        id: "num-char-radix",
+        description: "NUM and CHAR fail with ErrorCode::Generic if the radix is not in 2..=10.",
+        affected_by: &[Setting::NumCharRadix],
+        check: num_char_radix,
+    },
+    SemanticsEntry {
+        id: "arith-overflow",
+        description: "A result too large for its register is wrapped, saturated or \
+                      rejected with ErrorCode::ArithmeticOverflow, leaving the register \
+                      untouched. NUM wraps without setting the overflow toggle.",
+        affected_by: &[Setting::OverflowPolicy],
+        check: arith_overflow,
+    },
+    SemanticsEntry {
+        id: "ld-index-wide",
+        description: "LD1-6 and LD1-6N keep the sign and bytes 4 and 5 of the field. \
+                      Nonzero bytes 1 to 3 set the overflow toggle, or fail with \
+                      ErrorCode::IndexRangeViolation if index values are strict.",
+        affected_by: &[Setting::StrictIndex],
+        check: ld_index_wide,
+    },
+    SemanticsEntry {
+        id: "index-overflow",
+        description: "INC1-6, DEC1-6 and the rI1 update of MOVE follow the overflow \
+                      policy when the result does not fit, unless the index overflow mode \
+                      wraps, saturates or fails with ErrorCode::IndexOverflow.",
+        affected_by: &[
+            Setting::IndexOverflowMode,
+            Setting::OverflowPolicy,
+            Setting::StrictIndex,
+        ],
+        check: index_overflow,
+    },
+    SemanticsEntry {
+        id: "move-past-end",
+        description: "MOVE past the end of memory fails with ErrorCode::InvalidAddress \
+                      after moving the words that fit, leaving rI1 untouched.",
+        affected_by: &[],
+        check: move_past_end,
+    },
+    SemanticsEntry {
+        id: "pc-past-end",
+        description: "An instruction in the last word of memory that does not jump leaves pc \
+                      at Mem::SIZE, and the next step fails with ErrorCode::InvalidAddress. \
+                      pc never wraps to 0.",
+        affected_by: &[],
+        check: pc_past_end,
+    },
+    SemanticsEntry {
+        id: "shift-sign",
+        description: "SLA, SRA, SLAX and SRAX move whole bytes of the magnitudes and keep \
+                      the signs. Bytes shifted past the registers are lost.",
+        affected_by: &[],
+        check: shift_sign,
+    },
+    SemanticsEntry {
+        id: "in-end-of-input",
+        description: "IN from a device at its end of input fails with ErrorCode::IOError, \
+                      or ErrorCode::EndOfInput if reported.",
+        affected_by: &[Setting::ReportEndOfInput],
+        check: in_end_of_input,
+    },
+    SemanticsEntry {
+        id: "ioc-barrier",
+        description: "IOC M(63) waits until no unit is busy with a transfer and flushes \
+                      every device. Unit 63 never has a device.",
+        affected_by: &[],
+        check: ioc_barrier,
+    },
+];
+
+/// Run an instruction on a fresh machine.
+///
+/// The memory at address 100 holds `operand`.
+fn run(
+    instr: Instruction,
+    operand: FullWord,
+    setup: impl FnOnce(&mut VM),
+) -> (VM, Result<(), ErrorCode>) {
+    let mut mix = VM::new();
+    mix.reset();
+    mix.mem[0] = FullWord::from(instr);
+    mix.mem[100] = operand;
+    setup(&mut mix);
+    mix.halted = false;
+    let result = mix.step();
+    (mix, result)
+}
+
+/// The largest magnitude of a word.
+fn max_magnitude<const N: usize>() -> Word<N, false> {
+    Word::from_i64(i64::MAX).0
+}
+
+fn div_by_zero() -> bool {
+    let instr = Instruction::new(100, 5, 0, Opcode::Div);
+    let (mix, result) = run(instr, FullWord::new(), |mix| {
+        mix.set_x(7);
+    });
+    result.is_ok() && mix.overflow && mix.r_a.to_i64().0 == 0 && mix.r_x.to_i64().0 == 0
+}
+
+fn div_quotient_overflow() -> bool {
+    let instr = Instruction::new(100, 5, 0, Opcode::Div);
+    let (mix, result) = run(instr, FullWord::from_i64(1).0, |mix| {
+        mix.set_a(1);
+        mix.set_x(5);
+    });
+    result.is_ok() && mix.overflow && mix.r_a.to_i64().0 == 5 && mix.r_x.to_i64().0 == 0
+}
+
+fn char_high_digits() -> bool {
+    let instr = Instruction::new(0, 1, 0, Opcode::Special);
+    let (mix, result) = run(instr, FullWord::new(), |mix| {
+        mix.num_char_radix = 2;
+        mix.set_a(0b100_0000_0001);
+    });
+    result.is_ok() && mix.r_a[1..=5] == [30; 5] && mix.r_x[1..=4] == [30; 4] && mix.r_x[5] == 31
+}
+
+fn num_char_radix() -> bool {
+    [0, 1].iter().all(|&field| {
+        let instr = Instruction::new(0, field, 0, Opcode::Special);
+        let (_, result) = run(instr, FullWord::new(), |mix| mix.num_char_radix = 11);
+        result == Err(ErrorCode::Generic)
+    })
+}
+
+fn arith_overflow() -> bool {
+    let instr = Instruction::new(100, 5, 0, Opcode::Add);
+    let max = max_magnitude::<6>();
+    let (trapped, result) = run(instr, FullWord::from_i64(1).0, |mix| {
+        mix.overflow_policy = OverflowPolicy::Trap;
+        mix.r_a = max;
+    });
+    let trap_holds =
+        result == Err(ErrorCode::ArithmeticOverflow) && trapped.r_a.as_array() == max.as_array();
+    let (saturated, result) = run(instr, FullWord::from_i64(1).0, |mix| {
+        mix.overflow_policy = OverflowPolicy::Saturate;
+        mix.r_a = max;
+    });
+    let saturate_holds =
+        result.is_ok() && saturated.overflow && saturated.r_a.as_array() == max.as_array();
+    trap_holds && saturate_holds
+}
+
+fn ld_index_wide() -> bool {
+    let instr = Instruction::new(100, 5, 0, Opcode::Ld1);
+    let operand = FullWord::from_bytes([FullWord::NEG, 1, 0, 0, 2, 3]);
+    let (loose, result) = run(instr, operand, |_| ());
+    let loose_holds =
+        result.is_ok() && loose.overflow && loose.r_in[1].as_array() == &[HalfWord::NEG, 2, 3];
+    let (strict, result) = run(instr, operand, |mix| mix.strict_index = true);
+    let strict_holds =
+        result == Err(ErrorCode::IndexRangeViolation) && strict.r_in[1].to_i64().0 == 0;
+    loose_holds && strict_holds
+}
+
+fn index_overflow() -> bool {
+    let instr = Instruction::new(1, 0, 0, Opcode::Modify1);
+    let max = max_magnitude::<3>();
+    let (trapped, result) = run(instr, FullWord::new(), |mix| {
+        mix.index_overflow_mode = IndexOverflowMode::Trap;
+        mix.r_in[1] = max;
+    });
+    let trap_holds =
+        result == Err(ErrorCode::IndexOverflow) && trapped.r_in[1].as_array() == max.as_array();
+    let (wrapped, result) = run(instr, FullWord::new(), |mix| mix.r_in[1] = max);
+    let wrap_holds = result.is_ok() && wrapped.overflow && wrapped.r_in[1].to_i64().0 == 0;
+    let (saturated, result) = run(instr, FullWord::new(), |mix| {
+        mix.overflow_policy = OverflowPolicy::Saturate;
+        mix.r_in[1] = max;
+    });
+    let policy_holds = result.is_ok() && saturated.r_in[1].as_array() == max.as_array();
+    trap_holds && wrap_holds && policy_holds
+}
+
+fn move_past_end() -> bool {
+    let last = Mem::SIZE as i64 - 1;
+    let instr = Instruction::new(100, 2, 0, Opcode::Move);
+    let (mix, result) = run(instr, FullWord::from_i64(42).0, |mix| {
+        mix.r_in[1] = HalfWord::from_i64(last).0;
+    });
+    result == Err(ErrorCode::InvalidAddress)
+        && mix.mem[last as u16].to_i64().0 == 42
+        && mix.r_in[1].to_i64().0 == last
+}
+
+fn pc_past_end() -> bool {
+    let last = Mem::SIZE as u16 - 1;
+    let mut mix = VM::new();
+    mix.reset();
+    mix.mem[last] = FullWord::from(Instruction::new(0, 0, 0, Opcode::Nop));
+    mix.pc = last;
+    mix.halted = false;
+    let ran = mix.step().is_ok() && mix.pc as usize == Mem::SIZE;
+    ran && mix.step() == Err(ErrorCode::InvalidAddress) && mix.pc as usize == Mem::SIZE
+}
+
+fn shift_sign() -> bool {
+    let instr = Instruction::new(1, 0, 0, Opcode::Shift);
+    let (shifted, result) = run(instr, FullWord::new(), |mix| {
+        mix.set_a(-5);
+    });
+    let shift_holds = result.is_ok() && shifted.r_a.as_array() == &[FullWord::NEG, 0, 0, 0, 5, 0];
+    let instr = Instruction::new(5, 0, 0, Opcode::Shift);
+    let (cleared, result) = run(instr, FullWord::new(), |mix| {
+        mix.set_a(-5);
+    });
+    let clear_holds = result.is_ok() && cleared.r_a.as_array() == &[FullWord::NEG, 0, 0, 0, 0, 0];
+    shift_holds && clear_holds
+}
+
+fn in_end_of_input() -> bool {
+    let instr = Instruction::new(100, 0, 0, Opcode::In);
+    [(false, ErrorCode::IOError), (true, ErrorCode::EndOfInput)]
+        .iter()
+        .all(|&(report, expected)| {
+            let (_, result) = run(instr, FullWord::new(), |mix| {
+                mix.attach_device(0, Box::new(TapeDevice::new()));
+                mix.report_end_of_input = report;
+            });
+            result == Err(expected)
+        })
+}
+
+fn ioc_barrier() -> bool {
+    let mut mix = VM::new();
+    mix.reset();
+    mix.attach_device(0, Box::new(TapeDevice::new().with_bandwidth(1)));
+    mix.attach_device(1, Box::new(TapeDevice::new()));
+    mix.mem[0] = FullWord::from(Instruction::new(100, 0, 0, Opcode::Out));
+    mix.mem[1] = FullWord::from(Instruction::new(100, 1, 0, Opcode::Out));
+    mix.mem[2] = FullWord::from(Instruction::new(0, IOC_BARRIER_UNIT, 0, Opcode::Ioc));
+    mix.mem[3] = FullWord::from(Instruction::new(10, 0, 0, Opcode::Jbus));
+    mix.halted = false;
+    let ran = (0..4).all(|_| mix.step().is_ok());
+    ran && mix.pc == 4 && mix.cycle_count() >= STORAGE_BLOCK_SIZE as u64
+}
+
+/// A problem with the registry.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Violation {
+    /// The machine does not behave as an entry describes.
+    Failed(&'static str),
+
+    /// Two entries have the same identifier.
+    Duplicate(&'static str),
+
+    /// A setting changes no entry.
+    Unregistered(Setting),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Failed(id) => write!(f, "{}: the machine does not behave as described", id),
+            Violation::Duplicate(id) => write!(f, "{}: registered twice", id),
+            Violation::Unregistered(setting) => write!(f, "{:?}: changes no entry", setting),
+        }
+    }
+}
+
+/// Check the registry against the machine.
+///
+/// Every entry must hold and have its own identifier, and every
+/// [`Setting`] must change some entry, so that a setting added for a
+/// new choice cannot go unregistered.
+///
+/// # Returns
+/// * [`Vec<Violation>`] - The problems found, in registry order.
+pub fn check_semantics() -> Vec<Violation> {
+    let report = Semantics::current();
+    let mut found = Vec::new();
+    for (i, entry) in report.entries().iter().enumerate() {
+        if report.entries()[..i].iter().any(|e| e.id == entry.id) {
+            found.push(Violation::Duplicate(entry.id));
+        }
+        if !entry.holds() {
+            found.push(Violation::Failed(entry.id));
+        }
+    }
+    for setting in Setting::ALL {
+        if report.affected_by(setting).next().is_none() {
+            found.push(Violation::Unregistered(setting));
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    /// The registered identifiers, each tested below.
+    const TESTED: [&str; 12] = [
+        "div-by-zero",
+        "div-quotient-overflow",
+        "char-high-digits",
+        "num-char-radix",
+        "arith-overflow",
+        "ld-index-wide",
+        "index-overflow",
+        "move-past-end",
+        "pc-past-end",
+        "shift-sign",
+        "in-end-of-input",
+        "ioc-barrier",
+    ];
+
+    fn holds(id: &str) -> bool {
+        let entry = Semantics::current().get(id);
+        assert!(entry.is_some(), "{} is not registered", id);
+        entry.is_some_and(|entry| entry.holds())
+    }
+
+    /// Collect the sources of the machine, except this module.
+    fn sources(dir: &Path, found: &mut Vec<(PathBuf, String)>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                sources(&path, found);
+            } else if path.extension().is_some_and(|ext| ext == "rs")
+                && !path.ends_with("semantics.rs")
+            {
+                let text = fs::read_to_string(&path).unwrap();
+                found.push((path, text));
+            }
+        }
+    }
+
+    /// Get the blocks of consecutive comment lines of the machine,
+    /// with their files.
+    fn comment_blocks() -> Vec<(PathBuf, Vec<String>)> {
+        let mut files = Vec::new();
+        sources(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("src/runtime"),
+            &mut files,
+        );
+        let mut blocks = Vec::new();
+        for (path, text) in files {
+            let mut block = Vec::new();
+            for line in text.lines() {
+                let line = line.trim_start();
+                if line.starts_with("//") {
+                    block.push(line.to_string());
+                } else if !block.is_empty() {
+                    blocks.push((path.clone(), core::mem::take(&mut block)));
+                }
+            }
+            if !block.is_empty() {
+                blocks.push((path, block));
+            }
+        }
+        blocks
+    }
+
+    /// Get the identifiers cited in a comment line, as in "the
+    /// `div-by-zero` entry".
+    fn citations(line: &str) -> Vec<&str> {
+        line.match_indices("` entr")
+            .filter_map(|(end, _)| {
+                let start = line[..end].rfind('`')? + 1;
+                let id = &line[start..end];
+                id.bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+                    .then_some(id)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_entry_is_tested() {
+        let mut ids = Semantics::current()
+            .entries()
+            .iter()
+            .map(|entry| entry.id)
+            .collect::<Vec<_>>();
+        let mut tested = TESTED.to_vec();
+        ids.sort_unstable();
+        tested.sort_unstable();
+        assert_eq!(ids, tested);
+    }
+
+    #[test]
+    fn registry_is_consistent() {
+        assert_eq!(check_semantics(), []);
+    }
+
+    #[test]
+    fn every_entry_is_cited() {
+        let cited = comment_blocks()
+            .iter()
+            .flat_map(|(_, block)| block.iter().flat_map(|line| citations(line)))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        for entry in Semantics::current().entries() {
+            assert!(
+                cited.iter().any(|id| id == entry.id),
+                "{} is not cited",
+                entry.id
+            );
+        }
+    }
+
+    #[test]
+    fn every_citation_is_registered() {
+        for (path, block) in comment_blocks() {
+            for id in block.iter().flat_map(|line| citations(line)) {
+                assert!(
+                    Semantics::current().get(id).is_some(),
+                    "{} cites {}, which is not registered",
+                    path.display(),
+                    id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn undefined_behavior_is_registered() {
+        for (path, block) in comment_blocks() {
+            let text = block.join(" ");
+            let undefined = text
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .any(|word| word == "undefined");
+            if undefined {
+                assert!(
+                    block.iter().any(|line| !citations(line).is_empty()),
+                    "{} calls some behavior undefined without citing an entry: {}",
+                    path.display(),
+                    text
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn div_by_zero() {
+        assert!(holds("div-by-zero"));
+    }
+
+    #[test]
+    fn div_quotient_overflow() {
+        assert!(holds("div-quotient-overflow"));
+    }
+
+    #[test]
+    fn char_high_digits() {
+        assert!(holds("char-high-digits"));
+    }
+
+    #[test]
+    fn num_char_radix() {
+        assert!(holds("num-char-radix"));
+    }
+
+    #[test]
+    fn arith_overflow() {
+        assert!(holds("arith-overflow"));
+    }
+
+    #[test]
+    fn ld_index_wide() {
+        assert!(holds("ld-index-wide"));
+    }
+
+    #[test]
+    fn index_overflow() {
+        assert!(holds("index-overflow"));
+    }
+
+    #[test]
+    fn move_past_end() {
+        assert!(holds("move-past-end"));
+    }
+
+    #[test]
+    fn pc_past_end() {
+        assert!(holds("pc-past-end"));
+    }
+
+    #[test]
+    fn shift_sign() {
+        assert!(holds("shift-sign"));
+    }
+
+    #[test]
+    fn in_end_of_input() {
+        assert!(holds("in-end-of-input"));
+    }
+
+    #[test]
+    fn ioc_barrier() {
+        assert!(holds("ioc-barrier"));
+    }
+}
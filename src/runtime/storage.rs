@@ -0,0 +1,563 @@
+use core::ops::RangeInclusive;
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::formats::common::read_limited;
+use super::formats::scratch::*;
+use super::*;
+
+/// The unit numbers of the tape units.
+pub const TAPE_UNITS: RangeInclusive<usize> = 0..=7;
+
+/// The unit numbers of the disk units.
+pub const DISK_UNITS: RangeInclusive<usize> = 8..=15;
+
+/// The number of words in a block of a tape or disk unit.
+pub const STORAGE_BLOCK_SIZE: usize = 100;
+
+/// A random-access disk unit.
+///
+/// `IN` and `OUT` transfer the block at the current position, which
+/// is set by [`IODevice::seek()`] and does not move by itself.
+#[derive(Clone, Debug)]
+pub struct DiskDevice {
+    /// The content of the disk, block after block.
+    data: Vec<FullWord>,
+
+    /// The current block.
+    block: u64,
+
+    /// The transfer rate, in words per cycle.
+    bandwidth: Option<u64>,
+}
+
+impl DiskDevice {
+    /// Create a new disk of zero words.
+    ///
+    /// # Arguments
+    /// * `blocks` - The number of blocks on the disk.
+    pub fn new(blocks: usize) -> Self {
+        DiskDevice {
+            data: vec![FullWord::new(); blocks * STORAGE_BLOCK_SIZE],
+            block: 0,
+            bandwidth: None,
+        }
+    }
+
+    /// Set the transfer rate, in words per cycle.
+    ///
+    /// See [`IODevice::bandwidth()`].
+    pub fn with_bandwidth(mut self, words_per_cycle: u64) -> Self {
+        self.bandwidth = Some(words_per_cycle);
+        self
+    }
+
+    /// Get the number of blocks on the disk.
+    pub fn blocks(&self) -> u64 {
+        (self.data.len() / STORAGE_BLOCK_SIZE) as u64
+    }
+
+    /// Get the current block.
+    pub fn position(&self) -> u64 {
+        self.block
+    }
+
+    /// Get the words of the current block.
+    fn current(&self) -> &[FullWord] {
+        let start = self.block as usize * STORAGE_BLOCK_SIZE;
+        &self.data[start..start + STORAGE_BLOCK_SIZE]
+    }
+
+    /// Get the words of the current block, mutably.
+    fn current_mut(&mut self) -> &mut [FullWord] {
+        let start = self.block as usize * STORAGE_BLOCK_SIZE;
+        &mut self.data[start..start + STORAGE_BLOCK_SIZE]
+    }
+}
+
+impl IODevice for DiskDevice {
+    fn read(&mut self, buffer: &mut [FullWord]) -> Result<(), ()> {
+        self.peek(buffer)?;
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[FullWord]) -> Result<(), usize> {
+        if data.len() != self.get_block_size() {
+            return Err(0);
+        }
+        self.current_mut().copy_from_slice(data);
+        Ok(())
+    }
+
+    fn peek(&self, buffer: &mut [FullWord]) -> Result<usize, ()> {
+        if buffer.len() != self.get_block_size() {
+            return Err(());
+        }
+        buffer.copy_from_slice(self.current());
+        Ok(buffer.len())
+    }
+
+    fn control(&mut self, _: i16) -> Result<(), ()> {
+        Err(())
+    }
+
+    fn is_busy(&self) -> Result<bool, ()> {
+        Ok(false)
+    }
+
+    fn is_ready(&self) -> Result<bool, ()> {
+        Ok(true)
+    }
+
+    fn get_block_size(&self) -> usize {
+        STORAGE_BLOCK_SIZE
+    }
+
+    fn name(&self) -> &str {
+        "disk"
+    }
+
+    fn seek(&mut self, block: u64) -> Result<(), ()> {
+        if block >= self.blocks() {
+            return Err(());
+        }
+        self.block = block;
+        Ok(())
+    }
+
+    fn bandwidth(&self) -> Option<u64> {
+        self.bandwidth
+    }
+}
+
+/// A sequential tape unit.
+///
+/// `IN` and `OUT` transfer the block at the current position and move
+/// past it. Writing at the end of the tape extends it. As in Knuth,
+/// `IOC 0` rewinds the tape, and `IOC M` skips `M` blocks forward or
+/// `-M` blocks backward.
+#[derive(Clone, Debug, Default)]
+pub struct TapeDevice {
+    /// The content of the tape, block after block.
+    data: Vec<FullWord>,
+
+    /// The current block.
+    block: u64,
+
+    /// The transfer rate, in words per cycle.
+    bandwidth: Option<u64>,
+}
+
+impl TapeDevice {
+    /// Create a new empty tape.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the transfer rate, in words per cycle.
+    ///
+    /// See [`IODevice::bandwidth()`].
+    pub fn with_bandwidth(mut self, words_per_cycle: u64) -> Self {
+        self.bandwidth = Some(words_per_cycle);
+        self
+    }
+
+    /// Get the number of blocks on the tape.
+    pub fn blocks(&self) -> u64 {
+        (self.data.len() / STORAGE_BLOCK_SIZE) as u64
+    }
+
+    /// Get the current block.
+    pub fn position(&self) -> u64 {
+        self.block
+    }
+}
+
+impl IODevice for TapeDevice {
+    fn read(&mut self, buffer: &mut [FullWord]) -> Result<(), ()> {
+        self.peek(buffer)?;
+        self.block += 1;
+        Ok(())
+    }
+
+    fn peek(&self, buffer: &mut [FullWord]) -> Result<usize, ()> {
+        if buffer.len() != self.get_block_size() || self.block >= self.blocks() {
+            return Err(());
+        }
+        let start = self.block as usize * STORAGE_BLOCK_SIZE;
+        buffer.copy_from_slice(&self.data[start..start + STORAGE_BLOCK_SIZE]);
+        Ok(buffer.len())
+    }
+
+    fn write(&mut self, data: &[FullWord]) -> Result<(), usize> {
+        if data.len() != self.get_block_size() || self.block > self.blocks() {
+            return Err(0);
+        }
+        let start = self.block as usize * STORAGE_BLOCK_SIZE;
+        if self.block == self.blocks() {
+            self.data.extend_from_slice(data);
+        } else {
+            self.data[start..start + STORAGE_BLOCK_SIZE].copy_from_slice(data);
+        }
+        self.block += 1;
+        Ok(())
+    }
+
+    fn control(&mut self, command: i16) -> Result<(), ()> {
+        match command {
+            0 => {
+                self.block = 0;
+                Ok(())
+            }
+            1.. => self.seek(self.block + command as u64),
+            _ => {
+                self.block = self.block.saturating_sub(command.unsigned_abs() as u64);
+                Ok(())
+            }
+        }
+    }
+
+    fn is_busy(&self) -> Result<bool, ()> {
+        Ok(false)
+    }
+
+    fn is_ready(&self) -> Result<bool, ()> {
+        Ok(true)
+    }
+
+    fn get_block_size(&self) -> usize {
+        STORAGE_BLOCK_SIZE
+    }
+
+    fn name(&self) -> &str {
+        "tape"
+    }
+
+    fn reset(&mut self) {
+        self.block = 0;
+    }
+
+    fn at_eof(&self) -> bool {
+        self.block >= self.blocks()
+    }
+
+    /// Skip forward to a block.
+    ///
+    /// A tape cannot seek backward, nor past its end.
+    fn seek(&mut self, block: u64) -> Result<(), ()> {
+        if block < self.block || block > self.blocks() {
+            return Err(());
+        }
+        self.block = block;
+        Ok(())
+    }
+
+    fn bandwidth(&self) -> Option<u64> {
+        self.bandwidth
+    }
+}
+
+/// A store of a few blocks kept in a file across runs.
+///
+/// `IOC M` selects slot `M`, in `0..64`. `OUT` writes the block of
+/// the selected slot to the file, and `IN` reads it back. Slots never
+/// written read as zero words, as does every slot if the file does
+/// not exist. Each transfer holds a lock on a companion file named
+/// after the file with `.lock` appended, so that several machines
+/// may share it. An `OUT` writes the whole store to a temporary file
+/// and renames it over the file, so that the file is never left half
+/// written. See [`formats::scratch`] for the format.
+///
+/// A failed transfer only fails the instruction. Its cause, e.g. the
+/// slot at which the file is truncated, is kept as
+/// [`ScratchStoreDevice::last_error()`].
+///
+/// ```text
+/// mix.attach_device(16, Box::new(ScratchStoreDevice::new("stage.mixs")));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ScratchStoreDevice {
+    /// The path of the file.
+    path: PathBuf,
+
+    /// The selected slot.
+    slot: u8,
+
+    /// The cause of the last failed transfer.
+    last_error: Option<ScratchError>,
+}
+
+impl ScratchStoreDevice {
+    /// Create a new store kept in a file, with slot 0 selected.
+    ///
+    /// The file is not accessed until the first transfer.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        ScratchStoreDevice {
+            path: path.into(),
+            slot: 0,
+            last_error: None,
+        }
+    }
+
+    /// Get the selected slot.
+    pub fn slot(&self) -> u8 {
+        self.slot
+    }
+
+    /// Get the cause of the last failed transfer.
+    pub fn last_error(&self) -> Option<ScratchError> {
+        self.last_error
+    }
+
+    /// Get the path of the file with `suffix` appended.
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut path = OsString::from(self.path.as_os_str());
+        path.push(suffix);
+        path.into()
+    }
+
+    /// Open the lock file, creating it if needed.
+    ///
+    /// The file itself is replaced by each store, so it cannot be
+    /// locked itself.
+    fn lock_file(&self) -> Result<File, ScratchError> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.sibling(".lock"))
+            .map_err(|err| ScratchError::Io(err.kind()))
+    }
+
+    /// Read all slots from the file, which must be locked.
+    fn read_slots(path: &Path) -> Result<ScratchSlots, ScratchError> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(empty_slots()),
+            Err(err) => return Err(ScratchError::Io(err.kind())),
+        };
+        let data =
+            read_limited(file, SCRATCH_MAX_LEN).map_err(|err| ScratchError::Io(err.kind()))?;
+        if data.is_empty() {
+            return Ok(empty_slots());
+        }
+        read_scratch(&data)
+    }
+
+    /// Read all slots from the file.
+    ///
+    /// # Returns
+    /// * [`Ok(ScratchSlots)`] - The slots, all empty if the file does not exist or is empty.
+    /// * [`Err(ScratchError)`] - The file cannot be read or is malformed.
+    pub fn load(&self) -> Result<ScratchSlots, ScratchError> {
+        let lock = self.lock_file()?;
+        lock.lock_shared()
+            .map_err(|err| ScratchError::Io(err.kind()))?;
+        Self::read_slots(&self.path)
+    }
+
+    /// Write the selected slot to the file, keeping the others.
+    fn store(&self, words: &[FullWord]) -> Result<(), ScratchError> {
+        let io_error = |err: std::io::Error| ScratchError::Io(err.kind());
+        let lock = self.lock_file()?;
+        lock.lock().map_err(io_error)?;
+        let mut slots = Self::read_slots(&self.path)?;
+        slots[self.slot as usize] = Some(words.to_vec());
+        let temp = self.sibling(".tmp");
+        let mut file = File::create(&temp).map_err(io_error)?;
+        file.write_all(&write_scratch(&slots)).map_err(io_error)?;
+        file.sync_all().map_err(io_error)?;
+        std::fs::rename(&temp, &self.path).map_err(io_error)
+    }
+}
+
+impl IODevice for ScratchStoreDevice {
+    fn read(&mut self, buffer: &mut [FullWord]) -> Result<(), ()> {
+        if buffer.len() != self.get_block_size() {
+            return Err(());
+        }
+        let slots = self.load().map_err(|err| {
+            self.last_error = Some(err);
+        })?;
+        match &slots[self.slot as usize] {
+            Some(words) => buffer.copy_from_slice(words),
+            None => buffer.fill(FullWord::new()),
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[FullWord]) -> Result<(), usize> {
+        if data.len() != self.get_block_size() {
+            return Err(0);
+        }
+        self.store(data).map_err(|err| {
+            self.last_error = Some(err);
+            0
+        })
+    }
+
+    fn control(&mut self, command: i16) -> Result<(), ()> {
+        match u8::try_from(command) {
+            Ok(slot) if (slot as usize) < SCRATCH_SLOTS => {
+                self.slot = slot;
+                Ok(())
+            }
+            _ => Err(()),
+        }
+    }
+
+    fn is_busy(&self) -> Result<bool, ()> {
+        Ok(false)
+    }
+
+    fn is_ready(&self) -> Result<bool, ()> {
+        Ok(true)
+    }
+
+    fn get_block_size(&self) -> usize {
+        SCRATCH_BLOCK_SIZE
+    }
+
+    fn name(&self) -> &str {
+        "scratch store"
+    }
+
+    fn reset(&mut self) {
+        self.slot = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::formats::common::FormatError;
+    use super::*;
+
+    /// Get a fresh path for a scratch store in the temporary directory.
+    fn scratch_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "modern_legacy-{}-{}.mixs",
+            std::process::id(),
+            name
+        ));
+        remove_store(&path);
+        path
+    }
+
+    /// Remove a scratch store and its lock file.
+    fn remove_store(path: &Path) {
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(ScratchStoreDevice::new(path).sibling(".lock"));
+    }
+
+    /// Run `IOC slot(16)` and then `op 100(16)` on a fresh machine with
+    /// a scratch store on unit 16.
+    fn run_transfer(
+        path: &Path,
+        slot: i16,
+        op: Opcode,
+        block: &[FullWord],
+    ) -> (VM, Result<(), ErrorCode>) {
+        let mut mix = VM::new();
+        mix.mem[0] = FullWord::from(Instruction::new(slot, 16, 0, Opcode::Ioc));
+        mix.mem[1] = FullWord::from(Instruction::new(100, 16, 0, op));
+        mix.mem[2] = FullWord::from(Instruction::new(0, 2, 0, Opcode::Special));
+        for (i, word) in block.iter().enumerate() {
+            mix.mem[100 + i as u16] = *word;
+        }
+        mix.attach_device(16, std::boxed::Box::new(ScratchStoreDevice::new(path)));
+        mix.reset();
+        mix.restart();
+        let result = mix.run_until_halt();
+        (mix, result)
+    }
+
+    fn block(seed: i64) -> Vec<FullWord> {
+        (0..SCRATCH_BLOCK_SIZE as i64)
+            .map(|i| FullWord::from_i64(seed * 1000 + i).0)
+            .collect()
+    }
+
+    fn words(mix: &VM) -> Vec<i64> {
+        (0..SCRATCH_BLOCK_SIZE as u16)
+            .map(|i| mix.memory()[100 + i].to_i64().0)
+            .collect()
+    }
+
+    #[test]
+    fn machines_share_the_store_across_runs() {
+        let path = scratch_path("share");
+        let (_, result) = run_transfer(&path, 3, Opcode::Out, &block(1));
+        assert_eq!(result, Ok(()));
+        let (_, result) = run_transfer(&path, 5, Opcode::Out, &block(2));
+        assert_eq!(result, Ok(()));
+
+        let (mix, result) = run_transfer(&path, 3, Opcode::In, &[]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            words(&mix),
+            (1000..1000 + SCRATCH_BLOCK_SIZE as i64).collect::<Vec<_>>()
+        );
+        let (mix, _) = run_transfer(&path, 5, Opcode::In, &[]);
+        assert_eq!(words(&mix)[0], 2000);
+        let (mix, _) = run_transfer(&path, 4, Opcode::In, &[]);
+        assert!(words(&mix).iter().all(|&w| w == 0));
+
+        // The store is replaced as a whole, without a leftover.
+        assert!(!ScratchStoreDevice::new(&path).sibling(".tmp").exists());
+        remove_store(&path);
+    }
+
+    #[test]
+    fn missing_store_reads_as_empty() {
+        let path = scratch_path("missing");
+        let (mix, result) = run_transfer(&path, 0, Opcode::In, &[]);
+        assert_eq!(result, Ok(()));
+        assert!(words(&mix).iter().all(|&w| w == 0));
+        assert!(!path.exists());
+        remove_store(&path);
+    }
+
+    #[test]
+    fn corrupt_store_is_reported() {
+        let path = scratch_path("corrupt");
+        let mut slots = empty_slots();
+        slots[2] = Some(block(2));
+        slots[7] = Some(block(7));
+        let data = write_scratch(&slots);
+        let entry = 1 + SCRATCH_BLOCK_SIZE * 6;
+        let store = ScratchStoreDevice::new(&path);
+
+        // Cut inside the second slot, between the slots, and in the
+        // header.
+        for (len, err) in [
+            (9 + entry + 10, ScratchError::Truncated { slot: Some(7) }),
+            (9 + entry, ScratchError::Truncated { slot: None }),
+            (6, ScratchError::Format(FormatError::UnexpectedEof)),
+        ] {
+            std::fs::write(&path, &data[..len]).unwrap();
+            assert_eq!(store.load().unwrap_err(), err, "cut at {}", len);
+        }
+        let mut bad = data.clone();
+        bad[0] = b'X';
+        std::fs::write(&path, &bad).unwrap();
+        assert_eq!(
+            store.load().unwrap_err(),
+            ScratchError::Format(FormatError::BadMagic)
+        );
+
+        // A transfer on a corrupt store fails and leaves it as is.
+        std::fs::write(&path, &data[..9 + entry]).unwrap();
+        let mut device = ScratchStoreDevice::new(&path);
+        assert_eq!(device.write(&block(3)), Err(0));
+        assert_eq!(
+            device.last_error(),
+            Some(ScratchError::Truncated { slot: None })
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), &data[..9 + entry]);
+        let (_, result) = run_transfer(&path, 2, Opcode::In, &[]);
+        assert!(result.is_err());
+        remove_store(&path);
+    }
+}
@@ -0,0 +1,282 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::*;
+
+/// A source of time for devices.
+///
+/// Devices that depend on time take a source at construction
+/// instead of reading a clock themselves, so that the same device
+/// may run on simulated cycles, on the host clock, or on a clock
+/// advanced by hand.
+pub trait TimeSource {
+    /// Get the current time, in units of the source.
+    ///
+    /// The time never goes backward, except across a reset of the
+    /// source.
+    fn now_units(&self) -> u64;
+}
+
+/// The cycles run by a [`MixVM`], in cycles.
+///
+/// See [`VM::cycle_time()`]. The time is updated after each
+/// instruction, and goes back to 0 with [`VM::reset()`].
+///
+/// [`MixVM`]: crate::MixVM
+#[derive(Clone, Debug, Default)]
+pub struct CycleTime {
+    /// The cycles published by the machine.
+    cycles: Arc<AtomicU64>,
+}
+
+impl CycleTime {
+    /// Publish the cycle count of the machine.
+    pub(crate) fn publish(&self, cycles: u64) {
+        self.cycles.store(cycles, Ordering::Relaxed);
+    }
+}
+
+impl TimeSource for CycleTime {
+    fn now_units(&self) -> u64 {
+        self.cycles.load(Ordering::Relaxed)
+    }
+}
+
+/// The host clock, in microseconds since the source was created.
+#[derive(Clone, Copy, Debug)]
+pub struct WallClock {
+    /// The time of creation.
+    start: Instant,
+}
+
+impl WallClock {
+    /// Create a new clock starting at 0.
+    pub fn new() -> Self {
+        WallClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for WallClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeSource for WallClock {
+    fn now_units(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+}
+
+/// A clock advanced by hand.
+///
+/// Clones share the same time, so that a test may keep one and give
+/// another to a device.
+///
+/// ```text
+/// let time = ManualTime::new();
+/// mix.attach_device(19, Box::new(ClockDevice::new(time.clone())));
+/// time.advance(100);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ManualTime {
+    /// The current time.
+    units: Arc<AtomicU64>,
+}
+
+impl ManualTime {
+    /// Create a new clock at 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the clock forward.
+    pub fn advance(&self, units: u64) {
+        self.units.fetch_add(units, Ordering::SeqCst);
+    }
+
+    /// Set the clock.
+    pub fn set(&self, units: u64) {
+        self.units.store(units, Ordering::SeqCst);
+    }
+}
+
+impl TimeSource for ManualTime {
+    fn now_units(&self) -> u64 {
+        self.units.load(Ordering::SeqCst)
+    }
+}
+
+/// A clock unit with an alarm.
+///
+/// A one-word `IN` reads the time of the source, keeping as many of
+/// its lowest bytes as fit in a word. `IOC M` sets the alarm `M`
+/// units ahead, and the unit is busy until then, so that a program
+/// may wait for it with `JBUS` or `JRED`. `IOC 0` clears the alarm.
+#[derive(Clone, Debug)]
+pub struct ClockDevice<T: TimeSource> {
+    /// The source of time.
+    source: T,
+
+    /// The time of the alarm, if set.
+    alarm: Option<u64>,
+}
+
+impl<T: TimeSource> ClockDevice<T> {
+    /// Create a new clock unit with no alarm.
+    pub fn new(source: T) -> Self {
+        ClockDevice {
+            source,
+            alarm: None,
+        }
+    }
+
+    /// Get the source of time.
+    pub fn source(&self) -> &T {
+        &self.source
+    }
+}
+
+impl<T: TimeSource> IODevice for ClockDevice<T> {
+    fn read(&mut self, buffer: &mut [FullWord]) -> Result<(), ()> {
+        if buffer.len() != self.get_block_size() {
+            return Err(());
+        }
+        let now = self.source.now_units() & i64::MAX as u64;
+        buffer[0] = FullWord::from_i64(now as i64).0;
+        Ok(())
+    }
+
+    fn write(&mut self, _: &[FullWord]) -> Result<(), usize> {
+        Err(0)
+    }
+
+    fn control(&mut self, command: i16) -> Result<(), ()> {
+        match command {
+            0 => self.alarm = None,
+            1.. => self.alarm = Some(self.source.now_units() + command as u64),
+            _ => return Err(()),
+        }
+        Ok(())
+    }
+
+    fn is_busy(&self) -> Result<bool, ()> {
+        Ok(self
+            .alarm
+            .is_some_and(|alarm| self.source.now_units() < alarm))
+    }
+
+    fn is_ready(&self) -> Result<bool, ()> {
+        self.is_busy().map(|busy| !busy)
+    }
+
+    fn get_block_size(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> &str {
+        "clock"
+    }
+
+    fn reset(&mut self) {
+        self.alarm = None;
+    }
+}
+
+impl VM {
+    /// Get the cycles run by the machine as a [`TimeSource`].
+    ///
+    /// The machine only publishes its cycles once this is called, so
+    /// that machines without a clock pay nothing for it.
+    pub fn cycle_time(&mut self) -> CycleTime {
+        let cycles = self.cycle_count();
+        let time = self.cycle_time.get_or_insert_with(CycleTime::default);
+        time.publish(cycles);
+        time.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Load `IOC 5(19)`, then wait for the clock with `JBUS *(19)` and
+    /// halt.
+    fn load_waiting_program(mix: &mut VM, clock: std::boxed::Box<dyn IODevice>) {
+        mix.mem[0] = FullWord::from(Instruction::new(5, 19, 0, Opcode::Ioc));
+        mix.mem[1] = FullWord::from(Instruction::new(1, 19, 0, Opcode::Jbus));
+        mix.mem[2] = FullWord::from(Instruction::new(0, 2, 0, Opcode::Special));
+        mix.attach_device(19, clock);
+        mix.reset();
+        mix.halted = false;
+    }
+
+    #[test]
+    fn alarm_goes_from_busy_to_ready() {
+        let time = ManualTime::new();
+        let mut clock = ClockDevice::new(time.clone());
+        assert_eq!(clock.is_busy(), Ok(false));
+
+        time.set(100);
+        clock.control(10).unwrap();
+        assert_eq!((clock.is_busy(), clock.is_ready()), (Ok(true), Ok(false)));
+        time.advance(9);
+        assert_eq!(clock.is_busy(), Ok(true));
+        time.advance(1);
+        assert_eq!((clock.is_busy(), clock.is_ready()), (Ok(false), Ok(true)));
+
+        // IOC 0 and resets clear the alarm.
+        clock.control(10).unwrap();
+        clock.control(0).unwrap();
+        assert_eq!(clock.is_busy(), Ok(false));
+        clock.control(10).unwrap();
+        clock.reset();
+        assert_eq!(clock.is_busy(), Ok(false));
+        assert_eq!(clock.control(-1), Err(()));
+    }
+
+    #[test]
+    fn clock_reads_the_time() {
+        let time = ManualTime::new();
+        let mut clock = ClockDevice::new(time.clone());
+        time.set(1234);
+        let mut buffer = [FullWord::new()];
+        clock.read(&mut buffer).unwrap();
+        assert_eq!(buffer[0].to_i64().0, 1234);
+        assert_eq!(clock.read(&mut [FullWord::new(); 2]), Err(()));
+    }
+
+    #[test]
+    fn program_waits_for_the_alarm() {
+        let time = ManualTime::new();
+        let mut mix = VM::new();
+        load_waiting_program(
+            &mut mix,
+            std::boxed::Box::new(ClockDevice::new(time.clone())),
+        );
+        for _ in 0..50 {
+            mix.step().unwrap();
+        }
+        assert_eq!(mix.pc, 1);
+        time.advance(4);
+        mix.step().unwrap();
+        assert_eq!(mix.pc, 1);
+        time.advance(1);
+        mix.step().unwrap();
+        assert_eq!(mix.pc, 2);
+        mix.step().unwrap();
+        assert!(mix.halted);
+    }
+
+    #[test]
+    fn alarm_rings_after_cycles_of_the_machine() {
+        let mut mix = VM::new();
+        let time = mix.cycle_time();
+        load_waiting_program(&mut mix, std::boxed::Box::new(ClockDevice::new(time)));
+        mix.run_until_halt().unwrap();
+        assert!(mix.cycle_count() >= 5);
+    }
+}
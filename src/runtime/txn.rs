@@ -0,0 +1,326 @@
+use core::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::*;
+
+/// Writes to the memory of a [`MixVM`], buffered and applied all at
+/// once.
+///
+/// No instruction sees a transaction half applied. See
+/// [`VM::with_mem_transaction()`] and [`TxnHandle::submit()`].
+///
+/// [`MixVM`]: crate::MixVM
+#[derive(Clone, Debug, Default)]
+pub struct MemTxn {
+    /// The writes, in the order they were made.
+    writes: Vec<(u16, FullWord)>,
+
+    /// The lowest and the highest address written.
+    bounds: Option<(u16, u16)>,
+}
+
+impl MemTxn {
+    /// Create a new transaction with no write.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a word.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The write is buffered.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - `addr` is out of memory.
+    pub fn write(&mut self, addr: u16, word: FullWord) -> Result<(), ErrorCode> {
+        if addr as usize >= Mem::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        self.writes.push((addr, word));
+        self.bounds = Some(match self.bounds {
+            Some((low, high)) => (low.min(addr), high.max(addr)),
+            None => (addr, addr),
+        });
+        Ok(())
+    }
+
+    /// Write consecutive words.
+    ///
+    /// Nothing is buffered unless all words fit in memory.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The writes are buffered.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - The words do not fit in memory.
+    pub fn write_slice(&mut self, start: u16, words: &[FullWord]) -> Result<(), ErrorCode> {
+        if start as usize + words.len() > Mem::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        for (addr, word) in (start..).zip(words) {
+            self.write(addr, *word)?;
+        }
+        Ok(())
+    }
+
+    /// Get the word last written to an address.
+    ///
+    /// # Returns
+    /// * [`Some(FullWord)`] - The word to be written.
+    /// * [`None`] - The transaction does not write `addr`.
+    pub fn get(&self, addr: u16) -> Option<FullWord> {
+        self.writes
+            .iter()
+            .rev()
+            .find(|(a, _)| *a == addr)
+            .map(|(_, word)| *word)
+    }
+
+    /// Get the number of writes.
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    /// Check if there is no write.
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Get the range of addresses the transaction touches.
+    ///
+    /// Addresses in the range are not necessarily all written.
+    pub fn touched(&self) -> Option<RangeInclusive<u16>> {
+        self.bounds.map(|(low, high)| low..=high)
+    }
+
+    /// Apply the writes to a memory.
+    fn apply(&self, mem: &mut Mem) {
+        for &(addr, word) in &self.writes {
+            mem[addr] = word;
+        }
+    }
+}
+
+/// Transactions waiting for a [`MixVM`] to reach an instruction
+/// boundary.
+///
+/// [`MixVM`]: crate::MixVM
+#[derive(Debug, Default)]
+struct TxnQueue {
+    /// Whether `txns` may be non-empty.
+    pending: AtomicBool,
+
+    /// The transactions, oldest first.
+    txns: Mutex<Vec<MemTxn>>,
+}
+
+/// A handle to submit memory transactions to a [`MixVM`], which may
+/// be sent to other threads.
+///
+/// See [`VM::txn_handle()`].
+///
+/// [`MixVM`]: crate::MixVM
+#[derive(Clone, Debug, Default)]
+pub struct TxnHandle {
+    /// The queue shared with the machine.
+    queue: Arc<TxnQueue>,
+}
+
+impl TxnHandle {
+    /// Submit a transaction.
+    ///
+    /// The machine applies it before its next instruction, or at the
+    /// latest with the next [`VM::apply_pending_transactions()`].
+    /// Transactions are applied in the order they are submitted.
+    pub fn submit(&self, txn: MemTxn) {
+        if txn.is_empty() {
+            return;
+        }
+        let mut txns = self.queue.txns.lock().unwrap_or_else(|e| e.into_inner());
+        txns.push(txn);
+        self.queue.pending.store(true, Ordering::SeqCst);
+    }
+
+    /// Check if some transaction is waiting to be applied.
+    pub fn is_pending(&self) -> bool {
+        self.queue.pending.load(Ordering::SeqCst)
+    }
+
+    /// Take the waiting transactions.
+    fn take(&self) -> Vec<MemTxn> {
+        let mut txns = self.queue.txns.lock().unwrap_or_else(|e| e.into_inner());
+        self.queue.pending.store(false, Ordering::SeqCst);
+        core::mem::take(&mut *txns)
+    }
+}
+
+impl VM {
+    /// Write memory in one transaction.
+    ///
+    /// The writes made by `f` are applied together once it returns,
+    /// after any transaction submitted through [`VM::txn_handle()`].
+    /// They go straight to memory, bypassing memory-mapped devices.
+    /// If the event log is enabled, the touched range is logged as a
+    /// single [`Event::MemoryWrite`].
+    ///
+    /// ```text
+    /// mix.with_mem_transaction(|txn| txn.write_slice(1000, &words))?;
+    /// ```
+    pub fn with_mem_transaction<R>(&mut self, f: impl FnOnce(&mut MemTxn) -> R) -> R {
+        let mut txn = MemTxn::new();
+        let result = f(&mut txn);
+        self.apply_pending_transactions();
+        self.helper_apply_transaction(&txn);
+        result
+    }
+
+    /// Get a handle to submit transactions from other threads.
+    ///
+    /// Submitted transactions are applied at instruction boundaries,
+    /// see [`TxnHandle::submit()`].
+    pub fn txn_handle(&self) -> TxnHandle {
+        self.txns.clone()
+    }
+
+    /// Apply the transactions submitted through [`VM::txn_handle()`].
+    ///
+    /// [`VM::step()`] calls this before each instruction, so a halted
+    /// machine only needs it to see the writes.
+    ///
+    /// # Returns
+    /// * [`usize`] - The number of transactions applied.
+    pub fn apply_pending_transactions(&mut self) -> usize {
+        if !self.txns.is_pending() {
+            return 0;
+        }
+        let txns = self.txns.take();
+        for txn in &txns {
+            self.helper_apply_transaction(txn);
+        }
+        txns.len()
+    }
+
+    /// Apply a transaction and log its touched range.
+    fn helper_apply_transaction(&mut self, txn: &MemTxn) {
+        txn.apply(&mut self.mem);
+        if let Some(range) = txn.touched() {
+            self.helper_log(Event::MemoryWrite { range });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Words 1000 to 1019, each holding `value`.
+    fn block(value: i64) -> MemTxn {
+        let mut txn = MemTxn::new();
+        txn.write_slice(1000, &[FullWord::from_i64(value).0; 20])
+            .unwrap();
+        txn
+    }
+
+    /// Check that the 20 words from `start` hold the same value.
+    fn assert_uniform(mix: &VM, start: u16) {
+        let first = mix.mem[start].to_i64().0;
+        for addr in start..start + 20 {
+            assert_eq!(mix.mem[addr].to_i64().0, first, "torn write at {}", addr);
+        }
+    }
+
+    #[test]
+    fn writes_are_buffered_until_the_end() {
+        let mut mix = VM::new();
+        mix.reset();
+        mix.enable_event_log();
+        let result = mix.with_mem_transaction(|txn| {
+            txn.write(1005, FullWord::from_i64(1).0)?;
+            txn.write(1001, FullWord::from_i64(2).0)?;
+            txn.write(1005, FullWord::from_i64(3).0)?;
+            assert_eq!(txn.get(1005).map(|word| word.to_i64().0), Some(3));
+            assert!(txn.get(1002).is_none());
+            assert_eq!(txn.len(), 3);
+            assert_eq!(txn.touched(), Some(1001..=1005));
+            // A slice past the end is not buffered at all.
+            assert_eq!(
+                txn.write_slice(Mem::SIZE as u16 - 1, &[FullWord::new(); 2]),
+                Err(ErrorCode::InvalidAddress)
+            );
+            assert_eq!(
+                txn.write(Mem::SIZE as u16, FullWord::new()),
+                Err(ErrorCode::InvalidAddress)
+            );
+            assert_eq!(txn.len(), 3);
+            Ok::<_, ErrorCode>(())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.mem[1005].to_i64().0, 3);
+        assert_eq!(mix.mem[1001].to_i64().0, 2);
+        let events = mix.take_events();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            &events[0].event,
+            Event::MemoryWrite { range } if *range == (1001..=1005)
+        ));
+
+        // An empty transaction logs nothing.
+        mix.with_mem_transaction(|_| ());
+        assert!(mix.take_events().is_empty());
+    }
+
+    #[test]
+    fn submitted_writes_land_between_steps() {
+        // NOP; NOP
+        let mut mix = VM::new();
+        mix.reset();
+        mix.mem[0] = FullWord::from(Instruction::new(0, 0, 0, Opcode::Nop));
+        mix.mem[1] = FullWord::from(Instruction::new(0, 0, 0, Opcode::Nop));
+        mix.halted = false;
+        let handle = mix.txn_handle();
+        mix.step().unwrap();
+
+        std::thread::spawn(move || {
+            handle.submit(block(1));
+            handle.submit(MemTxn::new());
+            handle.submit(block(2));
+        })
+        .join()
+        .unwrap();
+        assert!(mix.txn_handle().is_pending());
+        assert_eq!(mix.mem[1000].to_i64().0, 0);
+
+        mix.step().unwrap();
+        assert!(!mix.txn_handle().is_pending());
+        assert_eq!(mix.mem[1000].to_i64().0, 2);
+        assert_uniform(&mix, 1000);
+        assert_eq!(mix.apply_pending_transactions(), 0);
+    }
+
+    #[test]
+    fn submitted_writes_are_never_torn() {
+        // ENT1 2000; MOVE 1000(20); JMP 0
+        let mut mix = VM::new();
+        mix.reset();
+        mix.mem[0] = FullWord::from(Instruction::new(2000, 2, 0, Opcode::Modify1));
+        mix.mem[1] = FullWord::from(Instruction::new(1000, 20, 0, Opcode::Move));
+        mix.mem[2] = FullWord::from(Instruction::new(0, 0, 0, Opcode::Jmp));
+        mix.halted = false;
+
+        let handle = mix.txn_handle();
+        let writer = std::thread::spawn(move || {
+            for value in 1..=200 {
+                handle.submit(block(value));
+                std::thread::yield_now();
+            }
+        });
+        // The hook: neither the words written nor their copy made by
+        // the program are ever seen half updated.
+        while !writer.is_finished() {
+            mix.step().unwrap();
+            assert_uniform(&mix, 1000);
+            assert_uniform(&mix, 2000);
+        }
+        writer.join().unwrap();
+        mix.step().unwrap();
+        assert_eq!(mix.mem[1000].to_i64().0, 200);
+        assert_uniform(&mix, 1000);
+    }
+}
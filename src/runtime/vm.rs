@@ -1,4 +1,6 @@
 use core::cmp::Ordering;
+use core::ops::Range;
+use std::collections::BTreeSet;
 
 use super::*;
 
@@ -15,8 +17,26 @@ pub enum ErrorCode {
     InvalidAddress,
 
     /// An invalid `F` part is found in current instruction.
+    #[deprecated(note = "use `InvalidFieldRange` or `InvalidFieldMode` instead")]
     InvalidField,
 
+    /// The `F` part of current instruction is not a valid field
+    /// `(L:R)`, i.e. `L > R` or `R > 5`.
+    InvalidFieldRange {
+        /// The `F` part.
+        field: u8,
+    },
+
+    /// The `F` part of current instruction selects no operation
+    /// of its opcode.
+    InvalidFieldMode {
+        /// The opcode.
+        opcode: Opcode,
+
+        /// The `F` part.
+        field: u8,
+    },
+
     /// An invalid `I` part is found in current instruction.
     InvalidIndex,
 
@@ -26,8 +46,43 @@ pub enum ErrorCode {
     /// An error is issued by an IO device.
     IOError,
 
+    /// An IO device reports a block size different from that of its
+    /// previous transfer.
+    ///
+    /// This indicates a misbehaving device rather than a failed
+    /// transfer. A device put in place of another starts afresh.
+    IOBlockSizeMismatch,
+
+    /// An input device has no more data.
+    ///
+    /// Only reported if [`VM::report_end_of_input`] is set, otherwise
+    /// [`ErrorCode::IOError`] is reported instead.
+    EndOfInput,
+
     /// The machine is not running and must be [`MixVM::restart()`]ed.
     Halted,
+
+    /// An index register overflows while [`VM::index_overflow_mode`]
+    /// is [`IndexOverflowMode::Trap`].
+    IndexOverflow,
+
+    /// A value outside the range of an index register is placed
+    /// into one while [`VM::strict_index`] is set.
+    IndexRangeViolation,
+
+    /// An arithmetic result overflows while [`VM::overflow_policy`]
+    /// is [`OverflowPolicy::Trap`].
+    ArithmeticOverflow,
+
+    /// The machine ran as many steps as its limit allows.
+    ///
+    /// See [`VM::set_step_limit()`].
+    StepLimitExceeded,
+
+    /// An instruction writes to a protected address.
+    ///
+    /// See [`VM::protect()`].
+    WriteProtected,
 }
 
 /// Values of the comparison indicator in [`MixVM`].
@@ -56,6 +111,152 @@ impl Default for CompIndicator {
     }
 }
 
+/// Behaviors of `INC1-6`, `DEC1-6` and the `rI1` update of `MOVE`
+/// when the result does not fit in an index register.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum IndexOverflowMode {
+    /// Follow [`VM::overflow_policy`], as for `rA` and `rX`.
+    #[default]
+    Policy,
+
+    /// Keep the lowest bytes of the magnitude, i.e. the magnitude
+    /// is taken modulo the register capacity. The sign is kept.
+    Wrap,
+
+    /// Clamp the result to the largest magnitude of the register.
+    Saturate,
+
+    /// Fail with [`ErrorCode::IndexOverflow`], leaving the register
+    /// untouched.
+    Trap,
+}
+
+/// Behaviors of `ADD`, `SUB`, `INC`, `DEC`, the `rI1` update of
+/// `MOVE` and `NUM` when the result does not fit in a register.
+///
+/// Index registers follow [`VM::index_overflow_mode`] first, which
+/// defaults to this policy.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum OverflowPolicy {
+    /// Keep the lowest 5 bytes of the magnitude and the sign, as in
+    /// Knuth's MIX.
+    #[default]
+    WrapLow,
+
+    /// Clamp the result to the largest magnitude of the register.
+    Saturate,
+
+    /// Fail with [`ErrorCode::ArithmeticOverflow`], leaving the
+    /// register untouched.
+    Trap,
+}
+
+/// Registers of a [`MixVM`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RegisterId {
+    /// The register `rA`.
+    A,
+
+    /// The register `rI1`.
+    I1,
+
+    /// The register `rI2`.
+    I2,
+
+    /// The register `rI3`.
+    I3,
+
+    /// The register `rI4`.
+    I4,
+
+    /// The register `rI5`.
+    I5,
+
+    /// The register `rI6`.
+    I6,
+
+    /// The register `rX`.
+    X,
+
+    /// The register `rJ`.
+    J,
+}
+
+/// A copy of the registers and the memory of a [`MixVM`].
+///
+/// IO devices are not included.
+#[derive(Clone, Debug)]
+pub struct VMStateSnapshot {
+    /// The register `rA`.
+    pub r_a: FullWord,
+
+    /// The register `rX`.
+    pub r_x: FullWord,
+
+    /// The registers `rIn`. See [`VM::r_in`].
+    pub r_in: [HalfWord; 7],
+
+    /// The register `rJ`.
+    pub r_j: PosHalfWord,
+
+    /// The comparison indicator.
+    pub comp: CompIndicator,
+
+    /// The overflow toggle.
+    pub overflow: bool,
+
+    /// The machine running state.
+    pub halted: bool,
+
+    /// The instruction pointer.
+    pub pc: u16,
+
+    /// The memory.
+    pub mem: Mem,
+}
+
+/// A copy of the register file of a [`MixVM`].
+///
+/// Unlike [`VMStateSnapshot`], the memory, `pc` and the running state
+/// are not included, which makes it cheap to take after every step.
+#[derive(Clone, Copy, Debug)]
+pub struct RegisterSet {
+    /// The register `rA`.
+    pub a: FullWord,
+
+    /// The register `rX`.
+    pub x: FullWord,
+
+    /// The registers `rIn`. See [`VM::r_in`].
+    pub i: [HalfWord; 7],
+
+    /// The register `rJ`.
+    pub j: PosHalfWord,
+
+    /// The comparison indicator.
+    pub comp: CompIndicator,
+
+    /// The overflow toggle.
+    pub overflow: bool,
+}
+
+impl PartialEq for RegisterSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.a.as_array() == other.a.as_array()
+            && self.x.as_array() == other.x.as_array()
+            && self
+                .i
+                .iter()
+                .zip(other.i.iter())
+                .all(|(l, r)| l.as_array() == r.as_array())
+            && self.j.as_array() == other.j.as_array()
+            && self.comp == other.comp
+            && self.overflow == other.overflow
+    }
+}
+
+impl Eq for RegisterSet {}
+
 /// The state of a MIX machine.
 #[repr(C)]
 pub struct VM {
@@ -84,14 +285,154 @@ pub struct VM {
     /// The instruction pointer.
     pub pc: u16,
 
-    /// IO devices.
-    pub io_devices: [Option<std::boxed::Box<dyn io::IODevice>>; 21],
+    /// The radix of digits converted by `NUM` and `CHAR`.
+    ///
+    /// Must be in `2..=10`, so that every digit has a character
+    /// code in `30..=39`. `NUM` reads the digit of a byte as its
+    /// value modulo 10, then modulo the radix. Defaults to
+    /// [`VM::DEFAULT_NUM_CHAR_RADIX`].
+    pub num_char_radix: u8,
+
+    /// Whether `IN` uses [`IODevice::read_nonblocking()`].
+    ///
+    /// If set, `IN` on a device without data available finishes
+    /// immediately and leaves the memory untouched. The program
+    /// should wait with `JBUS` and retry.
+    pub non_blocking_io: bool,
+
+    /// Whether a failed `IN` on a device at its end of input fails
+    /// with [`ErrorCode::EndOfInput`] instead of [`ErrorCode::IOError`].
+    ///
+    /// See [`IODevice::at_eof()`]. Defaults to `false`.
+    pub report_end_of_input: bool,
+
+    /// The behavior of arithmetic overflows.
+    ///
+    /// The overflow toggle is set on overflows in
+    /// [`OverflowPolicy::WrapLow`] and [`OverflowPolicy::Saturate`],
+    /// except by `NUM`, which wraps silently as in Knuth.
+    pub overflow_policy: OverflowPolicy,
+
+    /// The behavior of index register overflows in `INC1-6`,
+    /// `DEC1-6` and `MOVE`.
+    ///
+    /// Defaults to [`IndexOverflowMode::Policy`]. The overflow toggle
+    /// is set on overflows that do not trap.
+    pub index_overflow_mode: IndexOverflowMode,
+
+    /// Whether values outside the range of an index register are
+    /// rejected.
+    ///
+    /// If set, `LD1-6`, `LD1-6N`, `INC1-6`, `DEC1-6`, `ENT1-6`,
+    /// `ENN1-6` and `MOVE` fail with [`ErrorCode::IndexRangeViolation`]
+    /// instead of truncating a value into an index register, leaving
+    /// the register untouched. Otherwise the value is truncated and
+    /// the overflow toggle is set.
+    ///
+    /// This takes precedence over [`VM::index_overflow_mode`].
+    pub strict_index: bool,
+
+    /// The extensions enabled.
+    ///
+    /// Defaults to [`ExtensionSet::available()`]. Extensions not
+    /// compiled into this build stay disabled even if set here.
+    pub extensions: ExtensionSet,
+
+    /// Whether [`VM::restart()`] jumps to the entry point.
+    ///
+    /// If set, restarting a machine that halted without an error
+    /// continues at [`VM::entry()`] instead of after the `HLT`.
+    /// Has no effect if no entry point is set.
+    pub restart_at_entry: bool,
+
+    /// Whether to skip idle polling of units kept busy by transfers.
+    ///
+    /// If set, a `JBUS` jumping to itself while its unit is busy only
+    /// because of [`IODevice::bandwidth()`] is not run again until the
+    /// transfer ends. The cycles and steps of the skipped iterations
+    /// are counted as if they had run, and the registers and `pc` are
+    /// the same, so only the time taken by the host changes. A
+    /// breakpoint on the `JBUS` disables the skipping.
+    pub fast_forward_waits: bool,
+
+    /// IO devices, see [`VM::attach_device()`].
+    io_devices: [Option<std::boxed::Box<dyn io::IODevice>>; IO_UNITS],
 
     /// The memory.
+    ///
+    /// Prefer [`VM::memory()`] and [`VM::memory_mut()`], which will
+    /// stay when the representation of the memory changes.
     pub mem: Mem,
+
+    /// Memory-mapped devices and their address ranges.
+    mmio_devices: Vec<(Range<u16>, std::boxed::Box<dyn MmioDevice>)>,
+
+    /// Protected address ranges, see [`VM::protect()`].
+    protected: Vec<Range<u16>>,
+
+    /// Executed addresses, if tracked.
+    coverage: Option<std::boxed::Box<CoverageMap>>,
+
+    /// The STOP switch of the console.
+    stop: StopHandle,
+
+    /// Transactions submitted from other threads, see
+    /// [`VM::txn_handle()`].
+    pub(crate) txns: TxnHandle,
+
+    /// The number of steps run since the last [`VM::restart()`].
+    pub(crate) step_count: u64,
+
+    /// The maximum number of steps between restarts, if limited.
+    step_limit: Option<u64>,
+
+    /// The number of steps a caller is about to run up to, which
+    /// skipped waits must not pass.
+    pub(crate) step_horizon: Option<u64>,
+
+    /// The entry point, if set.
+    entry: Option<u16>,
+
+    /// The address [`VM::reset()`] sets `pc` to.
+    reset_vector: u16,
+
+    /// Whether the machine halted on an error.
+    pub(crate) faulted: bool,
+
+    /// The number of cycles run since the last [`VM::reset()`].
+    cycles: u64,
+
+    /// The published cycles, see [`VM::cycle_time()`].
+    pub(crate) cycle_time: Option<CycleTime>,
+
+    /// Recorded events, if logged.
+    events: Option<Vec<EventRecord>>,
+
+    /// Symbol names, see [`VM::load_symbols()`].
+    pub(crate) symbols: SymbolTable,
+
+    /// Breakpoints, see [`VM::add_breakpoint()`].
+    pub(crate) breakpoints: BTreeSet<u16>,
+
+    /// Transfer statistics by unit.
+    device_stats: [DeviceStats; IO_UNITS],
+
+    /// The cycle each unit stays busy until, by unit.
+    busy_until: [u64; IO_UNITS],
+
+    /// The number of times the device of each unit was replaced, by
+    /// unit.
+    device_generations: [u64; IO_UNITS],
+
+    /// The generation of the device of each unit at its last transfer
+    /// and its block size, by unit.
+    block_sizes: [Option<(u64, usize)>; IO_UNITS],
 }
 
 impl VM {
+    /// The default radix of `NUM` and `CHAR`, as in the conventional MIX.
+    pub const DEFAULT_NUM_CHAR_RADIX: u8 = 10;
+
     /// Create a new MIX machine.
     pub fn new() -> Self {
         VM {
@@ -103,30 +444,328 @@ impl VM {
             overflow: false,
             halted: true,
             pc: 0,
+            num_char_radix: Self::DEFAULT_NUM_CHAR_RADIX,
+            non_blocking_io: false,
+            report_end_of_input: false,
+            overflow_policy: Default::default(),
+            index_overflow_mode: Default::default(),
+            strict_index: false,
+            extensions: ExtensionSet::available(),
+            restart_at_entry: false,
+            fast_forward_waits: false,
             io_devices: Default::default(),
             mem: Mem::new(),
+            mmio_devices: Vec::new(),
+            protected: Vec::new(),
+            coverage: None,
+            stop: StopHandle::default(),
+            txns: TxnHandle::default(),
+            step_count: 0,
+            step_limit: None,
+            step_horizon: None,
+            entry: None,
+            reset_vector: 0,
+            faulted: false,
+            cycles: 0,
+            cycle_time: None,
+            events: None,
+            symbols: SymbolTable::new(),
+            breakpoints: BTreeSet::new(),
+            device_stats: Default::default(),
+            busy_until: Default::default(),
+            device_generations: Default::default(),
+            block_sizes: Default::default(),
+        }
+    }
+
+    /// Set the entry point.
+    ///
+    /// See [`VM::restart_at_entry`].
+    pub fn set_entry(&mut self, pc: u16) {
+        self.entry = Some(pc);
+    }
+
+    /// Get the entry point, if set.
+    pub fn entry(&self) -> Option<u16> {
+        self.entry
+    }
+
+    /// Set the address [`VM::reset()`] sets `pc` to.
+    ///
+    /// Defaults to 0.
+    pub fn set_reset_vector(&mut self, pc: u16) {
+        self.reset_vector = pc;
+    }
+
+    /// Get the address [`VM::reset()`] sets `pc` to.
+    pub fn reset_vector(&self) -> u16 {
+        self.reset_vector
+    }
+
+    /// Limit the number of steps between restarts.
+    ///
+    /// Equivalent to [`VM::set_step_limit()`], for chaining.
+    pub fn with_step_limit(&mut self, max_steps: u64) -> &mut Self {
+        self.set_step_limit(max_steps);
+        self
+    }
+
+    /// Limit the number of steps between restarts.
+    ///
+    /// Once `max_steps` instructions are run after the last
+    /// [`VM::restart()`], [`VM::step()`] halts the machine with
+    /// [`ErrorCode::StepLimitExceeded`] instead of running the next one.
+    pub fn set_step_limit(&mut self, max_steps: u64) {
+        self.step_limit = Some(max_steps);
+    }
+
+    /// Remove the step limit.
+    pub fn clear_step_limit(&mut self) {
+        self.step_limit = None;
+    }
+
+    /// Get the number of steps run since the last [`VM::restart()`].
+    pub fn step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    /// Map a device into the address space.
+    ///
+    /// Loads and stores to addresses in `range` are routed to
+    /// `device` instead of the memory. The range may extend beyond
+    /// [`Mem::SIZE`].
+    ///
+    /// # Arguments
+    /// * `range` - The address range to map.
+    /// * `device` - The device to map.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The device is mapped.
+    /// * [`Err(ErrorCode::InvalidAddress)`] - `range` is empty or overlaps with a mapped device.
+    pub fn map_device(
+        &mut self,
+        range: Range<u16>,
+        device: std::boxed::Box<dyn MmioDevice>,
+    ) -> Result<(), ErrorCode> {
+        let overlapping = self
+            .mmio_devices
+            .iter()
+            .any(|(r, _)| r.start < range.end && range.start < r.end);
+        if range.is_empty() || overlapping {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        self.mmio_devices.push((range, device));
+        Ok(())
+    }
+
+    /// Protect a range of addresses from writes.
+    ///
+    /// Instructions writing to a protected address, i.e. stores,
+    /// `MOVE` and `IN`, fail with [`ErrorCode::WriteProtected`]. This
+    /// covers memory-mapped devices too. Writes of the host, e.g.
+    /// through [`VM::memory_mut()`], are not affected. Protection is
+    /// kept across resets.
+    pub fn protect(&mut self, range: Range<u16>) {
+        if !range.is_empty() {
+            self.protected.push(range);
         }
     }
 
+    /// Remove the protection of a range of addresses.
+    ///
+    /// Other protected addresses stay protected.
+    pub fn unprotect(&mut self, range: Range<u16>) {
+        self.protected = self
+            .protected
+            .iter()
+            .flat_map(|r| {
+                [
+                    r.start..r.end.min(range.start),
+                    r.start.max(range.end)..r.end,
+                ]
+            })
+            .filter(|r| !r.is_empty())
+            .collect();
+    }
+
+    /// Check if an address is protected from writes.
+    pub fn is_protected(&self, addr: u16) -> bool {
+        self.protected.iter().any(|r| r.contains(&addr))
+    }
+
+    /// Iterate over the plugged IO devices.
+    ///
+    /// # Returns
+    /// * [`Iterator`] - Unit numbers and references of the present devices.
+    pub fn devices(&self) -> impl Iterator<Item = (usize, &dyn IODevice)> {
+        self.io_devices
+            .iter()
+            .enumerate()
+            .filter_map(|(unit, dev)| Some((unit, dev.as_deref()?)))
+    }
+
+    /// Iterate mutably over the plugged IO devices.
+    ///
+    /// This is useful for configuring all devices at once, e.g.
+    /// issuing a `control` command to each of them.
+    ///
+    /// # Returns
+    /// * [`Iterator`] - Unit numbers and mutable references of the present devices.
+    pub fn devices_mut(&mut self) -> impl Iterator<Item = (usize, &mut dyn IODevice)> {
+        self.io_devices
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(unit, dev)| {
+                let dev: &mut dyn IODevice = dev.as_deref_mut()?;
+                Some((unit, dev))
+            })
+    }
+
+    /// Get the number of plugged IO devices.
+    pub fn io_device_count(&self) -> usize {
+        self.devices().count()
+    }
+
+    /// Get the unit numbers of the plugged IO devices, in order.
+    pub fn attached_device_units(&self) -> Vec<usize> {
+        self.devices().map(|(unit, _)| unit).collect()
+    }
+
+    /// Check if a device is plugged into a unit.
+    ///
+    /// Units out of range have no device.
+    pub fn is_device_attached(&self, unit: usize) -> bool {
+        matches!(self.io_devices.get(unit), Some(Some(_)))
+    }
+
+    /// Plug a device into a unit, replacing the device on it.
+    ///
+    /// The new device may have another block size than the one it
+    /// replaces.
+    ///
+    /// # Returns
+    /// * [`Some(Box<dyn IODevice>)`] - The replaced device.
+    /// * [`None`] - No device was on the unit.
+    ///
+    /// # Panics
+    /// Panics if `unit` is not below [`IO_UNITS`].
+    pub fn attach_device(
+        &mut self,
+        unit: usize,
+        device: std::boxed::Box<dyn IODevice>,
+    ) -> Option<std::boxed::Box<dyn IODevice>> {
+        self.device_generations[unit] += 1;
+        self.io_devices[unit].replace(device)
+    }
+
+    /// Unplug the device of a unit.
+    ///
+    /// Units out of range have no device.
+    ///
+    /// # Returns
+    /// * [`Some(Box<dyn IODevice>)`] - The unplugged device.
+    /// * [`None`] - No device was on the unit.
+    pub fn detach_device(&mut self, unit: usize) -> Option<std::boxed::Box<dyn IODevice>> {
+        let device = self.io_devices.get_mut(unit)?.take()?;
+        self.device_generations[unit] += 1;
+        Some(device)
+    }
+
+    /// Get the device of a unit.
+    ///
+    /// Units out of range have no device.
+    pub fn device(&self, unit: usize) -> Option<&dyn IODevice> {
+        self.io_devices.get(unit)?.as_deref()
+    }
+
+    /// Get the device of a unit mutably.
+    ///
+    /// Units out of range have no device.
+    pub fn device_mut(&mut self, unit: usize) -> Option<&mut dyn IODevice> {
+        let dev: &mut dyn IODevice = self.io_devices.get_mut(unit)?.as_deref_mut()?;
+        Some(dev)
+    }
+
+    /// Get the memory.
+    pub fn memory(&self) -> &Mem {
+        &self.mem
+    }
+
+    /// Get the memory mutably.
+    pub fn memory_mut(&mut self) -> &mut Mem {
+        &mut self.mem
+    }
+
     /// Reset the machine.
     ///
     /// This method resets the machine to its initial state,
-    /// clearing the registers.
+    /// clearing the registers, the cycle count and the device
+    /// statistics. `pc` is set to [`VM::reset_vector()`].
     pub fn reset(&mut self) {
         self.r_a = Default::default();
         self.r_x = Default::default();
         self.r_in = Default::default();
         self.r_j = Default::default();
-        self.pc = 0;
+        self.pc = self.reset_vector;
         self.overflow = false;
         self.comp = Default::default();
+        self.cycles = 0;
+        self.device_stats = Default::default();
+        self.busy_until = Default::default();
+        if let Some(time) = self.cycle_time.as_ref() {
+            time.publish(0);
+        }
+    }
+
+    /// Get the transfer statistics of a unit since the last
+    /// [`VM::reset()`].
+    ///
+    /// Units out of range have no transfer.
+    pub fn device_stats(&self, unit: usize) -> DeviceStats {
+        self.device_stats.get(unit).copied().unwrap_or_default()
+    }
+
+    /// Reset all IO devices.
+    ///
+    /// This method calls [`IODevice::reset()`] on every plugged
+    /// device. The registers and the memory are left untouched.
+    pub fn reset_devices(&mut self) {
+        for (_, dev) in self.devices_mut() {
+            dev.reset();
+        }
+    }
+
+    /// Clear the memory.
+    ///
+    /// The registers and the IO devices are left untouched.
+    pub fn reset_memory(&mut self) {
+        self.mem.copy_from(&Mem::new());
+    }
+
+    /// Reset the registers, the memory and the IO devices.
+    ///
+    /// Equivalent to [`VM::reset()`], [`VM::reset_memory()`] and
+    /// [`VM::reset_devices()`] in turn. The devices stay plugged.
+    pub fn warm_reset(&mut self) {
+        self.reset();
+        self.reset_memory();
+        self.reset_devices();
     }
 
     /// Restart the machine.
     ///
-    /// This function un-halts the machine.
+    /// This function un-halts the machine and clears its step count.
+    /// If [`VM::restart_at_entry`] is set and the machine halted
+    /// without an error, `pc` is set to [`VM::entry()`].
     pub fn restart(&mut self) {
+        if self.restart_at_entry && self.halted && !self.faulted {
+            if let Some(entry) = self.entry {
+                self.pc = entry;
+            }
+        }
         self.halted = false;
+        self.faulted = false;
+        self.step_count = 0;
     }
 
     /// Run the next instruction of the machine.
@@ -135,100 +774,98 @@ impl VM {
     /// * [`Ok(())`] - The machine successfully completed its operation.
     /// * [`Err(ErrorCode)`] - The machine encountered an error and is now halted.
     pub fn step(&mut self) -> Result<(), ErrorCode> {
+        self.helper_begin_step()?;
+
+        // Fetch the instruction.
+        let instr = self.helper_fetch().inspect_err(|&err| {
+            self.helper_halt_on_error(err);
+        })?;
+        self.helper_run_fetched(instr)
+    }
+
+    /// Run an instruction as if it had been fetched from `pc`.
+    ///
+    /// This is [`VM::step()`] without the fetch: the word at `pc` is
+    /// neither read nor written, and `pc` moves on unless the
+    /// instruction jumps.
+    ///
+    /// # Arguments
+    /// * `instr` - The instruction to run.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The machine successfully completed its operation.
+    /// * [`Err(ErrorCode)`] - The machine encountered an error and is now halted.
+    pub fn execute(&mut self, instr: Instruction) -> Result<(), ErrorCode> {
+        self.helper_begin_step()?;
+        self.helper_run_fetched(instr)
+    }
+
+    /// Get ready to run an instruction, checking the step limit.
+    fn helper_begin_step(&mut self) -> Result<(), ErrorCode> {
+        self.apply_pending_transactions();
+
         if self.halted {
             return Err(ErrorCode::Halted);
         }
 
-        // Fetch the instruction.
-        let instr: Instruction = self.mem[self.pc].try_into().map_err(|_| {
-            self.halt();
-            ErrorCode::IllegalInstruction
-        })?;
+        if self
+            .step_limit
+            .is_some_and(|limit| self.step_count >= limit)
+        {
+            self.helper_halt_on_error(ErrorCode::StepLimitExceeded);
+            return Err(ErrorCode::StepLimitExceeded);
+        }
+        Ok(())
+    }
+
+    /// Run an instruction fetched from `pc`.
+    fn helper_run_fetched(&mut self, instr: Instruction) -> Result<(), ErrorCode> {
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.set(self.pc);
+        }
 
-        self.pc += 1;
+        let pc = self.pc;
+        let overflow = self.overflow;
+        // The fetch fails past the end of memory and of mapped ranges,
+        // which end before `u16::MAX`, so `pc + 1` never wraps to 0.
+        // The next fetch then fails unless the instruction jumps, the
+        // `pc-past-end` entry.
+        self.pc = pc + 1;
+        self.step_count += 1;
 
         // Run the instruction.
-        match instr.opcode {
-            Opcode::Nop => self.handle_instr_nop(&instr),
-
-            Opcode::Add => self.handle_instr_add_sub(&instr),
-            Opcode::Sub => self.handle_instr_add_sub(&instr),
-            Opcode::Mul => self.handle_instr_mul(&instr),
-            Opcode::Div => self.handle_instr_div(&instr),
-
-            Opcode::Special => self.handle_instr_special(&instr),
-            Opcode::Shift => self.handle_instr_shift(&instr),
-            Opcode::Move => self.handle_instr_move(&instr),
-
-            Opcode::LdA => self.handle_instr_load_6b(&instr),
-            Opcode::Ld1 => self.handle_instr_load_3b(&instr),
-            Opcode::Ld2 => self.handle_instr_load_3b(&instr),
-            Opcode::Ld3 => self.handle_instr_load_3b(&instr),
-            Opcode::Ld4 => self.handle_instr_load_3b(&instr),
-            Opcode::Ld5 => self.handle_instr_load_3b(&instr),
-            Opcode::Ld6 => self.handle_instr_load_3b(&instr),
-            Opcode::LdX => self.handle_instr_load_6b(&instr),
-
-            Opcode::LdAN => self.handle_instr_load_neg_6b(&instr),
-            Opcode::Ld1N => self.handle_instr_load_neg_3b(&instr),
-            Opcode::Ld2N => self.handle_instr_load_neg_3b(&instr),
-            Opcode::Ld3N => self.handle_instr_load_neg_3b(&instr),
-            Opcode::Ld4N => self.handle_instr_load_neg_3b(&instr),
-            Opcode::Ld5N => self.handle_instr_load_neg_3b(&instr),
-            Opcode::Ld6N => self.handle_instr_load_neg_3b(&instr),
-            Opcode::LdXN => self.handle_instr_load_neg_6b(&instr),
-
-            Opcode::StA => self.handle_instr_store_6b(&instr),
-            Opcode::St1 => self.handle_instr_store_3b(&instr),
-            Opcode::St2 => self.handle_instr_store_3b(&instr),
-            Opcode::St3 => self.handle_instr_store_3b(&instr),
-            Opcode::St4 => self.handle_instr_store_3b(&instr),
-            Opcode::St5 => self.handle_instr_store_3b(&instr),
-            Opcode::St6 => self.handle_instr_store_3b(&instr),
-            Opcode::StX => self.handle_instr_store_6b(&instr),
-            Opcode::StJ => self.handle_instr_store_j(&instr),
-            Opcode::StZ => self.handle_instr_store_zero(&instr),
-
-            Opcode::Jbus => self.handle_instr_jbus_jred(&instr),
-            Opcode::Ioc => self.handle_instr_ioc(&instr),
-            Opcode::In => self.handle_instr_in_out(&instr),
-            Opcode::Out => self.handle_instr_in_out(&instr),
-            Opcode::Jred => self.handle_instr_jbus_jred(&instr),
-            Opcode::Jmp => self.handle_instr_jmp(&instr),
-
-            Opcode::JA => self.handle_instr_jmp_reg_6b(&instr),
-            Opcode::J1 => self.handle_instr_jmp_reg_3b(&instr),
-            Opcode::J2 => self.handle_instr_jmp_reg_3b(&instr),
-            Opcode::J3 => self.handle_instr_jmp_reg_3b(&instr),
-            Opcode::J4 => self.handle_instr_jmp_reg_3b(&instr),
-            Opcode::J5 => self.handle_instr_jmp_reg_3b(&instr),
-            Opcode::J6 => self.handle_instr_jmp_reg_3b(&instr),
-            Opcode::JX => self.handle_instr_jmp_reg_6b(&instr),
-
-            Opcode::ModifyA => self.handle_instr_modify_6b(&instr),
-            Opcode::Modify1 => self.handle_instr_modify_3b(&instr),
-            Opcode::Modify2 => self.handle_instr_modify_3b(&instr),
-            Opcode::Modify3 => self.handle_instr_modify_3b(&instr),
-            Opcode::Modify4 => self.handle_instr_modify_3b(&instr),
-            Opcode::Modify5 => self.handle_instr_modify_3b(&instr),
-            Opcode::Modify6 => self.handle_instr_modify_3b(&instr),
-            Opcode::ModifyX => self.handle_instr_modify_6b(&instr),
-
-            Opcode::CmpA => self.handle_instr_cmp_6b(&instr),
-            Opcode::Cmp1 => self.handle_instr_cmp_3b(&instr),
-            Opcode::Cmp2 => self.handle_instr_cmp_3b(&instr),
-            Opcode::Cmp3 => self.handle_instr_cmp_3b(&instr),
-            Opcode::Cmp4 => self.handle_instr_cmp_3b(&instr),
-            Opcode::Cmp5 => self.handle_instr_cmp_3b(&instr),
-            Opcode::Cmp6 => self.handle_instr_cmp_3b(&instr),
-            Opcode::CmpX => self.handle_instr_cmp_6b(&instr),
-        }
-        .map_err(|err| {
-            self.halt();
-            err
-        })?;
+        let result = self.helper_execute(&instr);
+        if !overflow && self.overflow {
+            self.helper_log(Event::Overflow { pc });
+        }
+        // Events are stamped with the cycle the instruction started.
+        match result {
+            Ok(()) if self.halted => self.helper_log(Event::Halt {
+                reason: metrics::HaltReason::Instruction,
+            }),
+            Ok(()) => {}
+            Err(err) => self.helper_halt_on_error(err),
+        }
+        self.cycles += instr.cycles();
+        if self.fast_forward_waits
+            && result.is_ok()
+            && instr.opcode == Opcode::Jbus
+            && self.pc == pc
+        {
+            self.helper_fast_forward_wait(&instr);
+        }
+        if let Some(time) = self.cycle_time.as_ref() {
+            time.publish(self.cycles);
+        }
+        result
+    }
 
-        Ok(())
+    /// Get the number of cycles run since the last [`VM::reset()`].
+    ///
+    /// Each instruction takes [`Instruction::cycles()`], whether it
+    /// succeeds or not.
+    pub fn cycle_count(&self) -> u64 {
+        self.cycles
     }
 
     /// Halt the machine.
@@ -236,855 +873,1645 @@ impl VM {
         self.halted = true;
     }
 
-    /// Get indexed address.
-    fn helper_get_eff_addr(&self, addr: i16, index: u8) -> Result<u16, ErrorCode> {
-        // Direct or indirect addressing.
-        // r_in[0] is always zero.
-        if !(0..=6).contains(&index) {
-            // We have been provided a bad index.
-            return Err(ErrorCode::InvalidIndex);
+    /// Run the machine until it halts.
+    ///
+    /// All IO devices are flushed before returning, whether the
+    /// machine halted normally or not.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The machine halted normally.
+    /// * [`Err(ErrorCode)`] - The machine encountered an error and is now halted,
+    ///   or a device failed to flush.
+    pub fn run_until_halt(&mut self) -> Result<(), ErrorCode> {
+        let result = loop {
+            if self.halted {
+                break Ok(());
+            }
+            if let Err(err) = self.step() {
+                break Err(err);
+            }
+        };
+        let flushed = self.flush_devices();
+        result.and(flushed)
+    }
+
+    /// Run the machine until it halts, STOP is pressed or a
+    /// breakpoint is reached.
+    ///
+    /// STOP is checked before each instruction, see
+    /// [`VM::stop_handle()`]. Breakpoints are checked before each
+    /// instruction but the first, so that a run resumed from a
+    /// breakpoint moves on, see [`VM::add_breakpoint()`]. All IO
+    /// devices are flushed before returning, as in
+    /// [`VM::run_until_halt()`].
+    ///
+    /// # Returns
+    /// * [`Ok(StepEvent)`] - The reason to return.
+    /// * [`Err(ErrorCode)`] - The machine encountered an error and is now halted,
+    ///   or a device failed to flush.
+    pub fn run(&mut self) -> Result<StepEvent, ErrorCode> {
+        let mut first = true;
+        let result = loop {
+            if self.halted {
+                break Ok(StepEvent::Halted);
+            }
+            if self.stop.is_stopped() {
+                break Ok(StepEvent::OperatorStop);
+            }
+            if !first && self.breakpoints.contains(&self.pc) {
+                break Ok(StepEvent::Breakpoint(self.pc));
+            }
+            first = false;
+            if let Err(err) = self.step() {
+                break Err(err);
+            }
+        };
+        let flushed = self.flush_devices();
+        result.and_then(|event| flushed.map(|_| event))
+    }
+
+    /// Run a subroutine until it returns.
+    ///
+    /// `rJ` is set to `return_addr` as if by a `JMP` from the
+    /// instruction before it, and the machine runs from `entry` until
+    /// it reaches `return_addr`. The instruction there is not run.
+    /// Nothing needs to be planted at `return_addr`. All IO devices
+    /// are flushed before returning, as in [`VM::run_until_halt()`].
+    ///
+    /// The machine is restarted as by [`VM::restart()`], so each call
+    /// may run as many steps as [`VM::set_step_limit()`] allows.
+    ///
+    /// ```text
+    /// mix.set_a(12);
+    /// mix.run_subprogram(SQUARE, 3999)?;
+    /// assert_eq!(mix.r_a.to_i64().0, 144);
+    /// ```
+    ///
+    /// # Arguments
+    /// * `entry` - The address of the subroutine.
+    /// * `return_addr` - The address the subroutine returns to.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The subroutine returned.
+    /// * [`Err(ErrorCode::Halted)`] - The machine halted before the subroutine returned.
+    /// * [`Err(ErrorCode)`] - The machine encountered an error and is now halted,
+    ///   or a device failed to flush.
+    pub fn run_subprogram(&mut self, entry: u16, return_addr: u16) -> Result<(), ErrorCode> {
+        self.r_j = PosHalfWord::from_i64(return_addr as i64).0;
+        self.pc = entry;
+        self.halted = false;
+        self.faulted = false;
+        self.step_count = 0;
+        let result = loop {
+            if let Err(err) = self.step() {
+                break Err(err);
+            }
+            // A `HLT` right before `return_addr` halts the subroutine.
+            if self.halted {
+                break Err(ErrorCode::Halted);
+            }
+            if self.pc == return_addr {
+                break Ok(());
+            }
+        };
+        let flushed = self.flush_devices();
+        result.and(flushed)
+    }
+
+    /// Get a handle to the STOP switch.
+    ///
+    /// The handle may be sent to another thread to stop
+    /// [`VM::run()`] at the next instruction boundary.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Flush all IO devices.
+    ///
+    /// Every device is flushed even if some of them fail.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - All devices are flushed.
+    /// * [`Err(ErrorCode::IOError)`] - Some device failed to flush.
+    pub fn flush_devices(&mut self) -> Result<(), ErrorCode> {
+        let mut result = Ok(());
+        for (_, dev) in self.devices_mut() {
+            if dev.flush().is_err() {
+                result = Err(ErrorCode::IOError);
+            }
         }
-        let reg = self.r_in[index as usize];
-        let reg_val = reg.to_i64().0;
-        (reg_val + addr as i64)
-            .try_into()
-            .map_err(|_| ErrorCode::InvalidAddress)
+        result
     }
 
-    /// Get indexed address. May panic or return negative value.
-    fn helper_get_eff_addr_unchecked(&self, addr: i16, index: u8) -> i16 {
-        let reg = self.r_in[index as usize];
-        let reg_val = reg.to_i64().0;
-        reg_val as i16 + addr
+    /// Start recording which addresses are executed.
+    ///
+    /// Every instruction fetched by [`VM::step()`] marks its address
+    /// in [`VM::execution_coverage()`]. Calling this method again
+    /// keeps the addresses already recorded.
+    pub fn enable_coverage_tracking(&mut self) {
+        if self.coverage.is_none() {
+            self.coverage = Some(std::boxed::Box::default());
+        }
     }
 
-    /// Do actual jump.
-    fn helper_do_jump(&mut self, location: u16, save_r_j: bool) {
-        if save_r_j {
-            let pc = self.pc.to_be_bytes();
-            self.r_j[1..=2].copy_from_slice(&pc);
+    /// Stop recording executed addresses, discarding the record.
+    pub fn disable_coverage_tracking(&mut self) {
+        self.coverage = None;
+    }
+
+    /// Get the addresses executed since tracking was enabled.
+    ///
+    /// # Returns
+    /// * [`Some(&CoverageMap)`] - The executed addresses.
+    /// * [`None`] - Tracking is not enabled.
+    pub fn execution_coverage(&self) -> Option<&CoverageMap> {
+        self.coverage.as_deref()
+    }
+
+    /// Start recording significant events.
+    ///
+    /// IO transfers, overflows and halts caused by [`VM::step()`] are
+    /// appended to a log, which is taken with [`VM::take_events()`].
+    /// Calling this method again keeps the events already recorded.
+    pub fn enable_event_log(&mut self) {
+        if self.events.is_none() {
+            self.events = Some(Vec::new());
         }
-        // Do jump.
-        self.pc = location;
     }
 
-    /// Get IO device.
-    fn helper_get_io_device(&self, dev_id: usize) -> Result<&dyn IODevice, ErrorCode> {
-        let dev = self
-            .io_devices
-            .get(dev_id)
-            .ok_or(ErrorCode::InvalidField)?
-            .as_ref()
-            .ok_or(ErrorCode::UnknownDevice)?
-            .as_ref();
-        Ok(dev)
+    /// Stop recording events, discarding the log.
+    pub fn disable_event_log(&mut self) {
+        self.events = None;
     }
 
-    /// Get IO device.
-    fn helper_get_io_device_mut(
-        &mut self,
-        dev_id: usize,
-    ) -> Result<&mut std::boxed::Box<dyn io::IODevice>, ErrorCode> {
-        let dev = self
-            .io_devices
-            .get_mut(dev_id)
-            .ok_or(ErrorCode::InvalidField)?
-            .as_mut()
-            .ok_or(ErrorCode::UnknownDevice)?;
-        Ok(dev)
+    /// Take the events recorded since the last call.
+    ///
+    /// # Returns
+    /// * [`Vec<EventRecord>`] - The events in the order they happened.
+    ///   Empty if the log is not enabled.
+    pub fn take_events(&mut self) -> Vec<EventRecord> {
+        self.events.as_mut().map(std::mem::take).unwrap_or_default()
     }
 
-    /// Handler for `NOP`.
-    fn handle_instr_nop(&mut self, _: &Instruction) -> Result<(), ErrorCode> {
-        // Do nothing.
-        Ok(())
+    /// Set the register `rA` to a value.
+    ///
+    /// Only the lowest 5 bytes of the magnitude are kept.
+    ///
+    /// # Returns
+    /// * [`bool`] - `true` if the value does not fit, `false` otherwise.
+    pub fn set_a(&mut self, value: i64) -> bool {
+        let (word, overflow) = FullWord::from_i64(value);
+        self.r_a = word;
+        overflow
     }
 
-    /// Handler for `LDA` and `LDX`.
-    fn handle_instr_load_6b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain everything.
-        let (field, sign_copy_needed) = instr.field.to_range_inclusive_signless();
-        let mem_cell = &self.mem[self.helper_get_eff_addr(instr.addr, instr.index)?];
-        let reg = match instr.opcode {
-            Opcode::LdA => &mut self.r_a,
-            Opcode::LdX => &mut self.r_x,
-            _ => unreachable!(),
-        };
-        // Zero reg before copying. Handle 'understood' positive sign too.
-        reg.set_all([FullWord::POS, 0, 0, 0, 0, 0]);
-        // Copy bytes shifted right.
-        for (reg_cursor, mem_cursor) in (1..=5).rev().zip(field.rev()) {
-            reg[reg_cursor] = mem_cell[mem_cursor];
+    /// Set the register `rX` to a value.
+    ///
+    /// Only the lowest 5 bytes of the magnitude are kept.
+    ///
+    /// # Returns
+    /// * [`bool`] - `true` if the value does not fit, `false` otherwise.
+    pub fn set_x(&mut self, value: i64) -> bool {
+        let (word, overflow) = FullWord::from_i64(value);
+        self.r_x = word;
+        overflow
+    }
+
+    /// Set the register `rIn` to a value.
+    ///
+    /// Only the lowest 2 bytes of the magnitude are kept.
+    ///
+    /// # Arguments
+    /// * `n` - The index of the register, in `1..=6`.
+    /// * `value` - The value to set.
+    ///
+    /// # Returns
+    /// * [`Ok(bool)`] - `true` if the value does not fit, `false` otherwise.
+    /// * [`Err(ErrorCode::InvalidIndex)`] - `n` is not in `1..=6`.
+    pub fn set_index(&mut self, n: usize, value: i64) -> Result<bool, ErrorCode> {
+        if !(1..=6).contains(&n) {
+            return Err(ErrorCode::InvalidIndex);
         }
-        // Copy sign byte if needed.
-        if sign_copy_needed {
-            reg[0] = mem_cell[0];
+        let (word, overflow) = HalfWord::from_i64(value);
+        self.r_in[n] = word;
+        Ok(overflow)
+    }
+
+    /// Set the register `rJ` to a value.
+    ///
+    /// Only the lowest 2 bytes of the magnitude are kept, and the
+    /// sign is always positive.
+    ///
+    /// # Returns
+    /// * [`bool`] - `true` if the value is negative or does not fit, `false` otherwise.
+    pub fn set_j(&mut self, value: i64) -> bool {
+        let (word, overflow) = PosHalfWord::from_i64(value);
+        self.r_j = word;
+        overflow || value < 0
+    }
+
+    /// Take a copy of the register file.
+    pub fn registers(&self) -> RegisterSet {
+        RegisterSet {
+            a: self.r_a,
+            x: self.r_x,
+            i: self.r_in,
+            j: self.r_j,
+            comp: self.comp,
+            overflow: self.overflow,
         }
-        Ok(())
     }
 
-    /// Handler for `LDAN` and `LDXN`.
-    fn handle_instr_load_neg_6b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain everything.
-        let (field, sign_copy_needed) = instr.field.to_range_inclusive_signless();
-        let mem_cell = &self.mem[self.helper_get_eff_addr(instr.addr, instr.index)?];
-        let reg = match instr.opcode {
-            Opcode::LdAN => &mut self.r_a,
-            Opcode::LdXN => &mut self.r_x,
-            _ => unreachable!(),
+    /// Restore the register file from a copy.
+    ///
+    /// `pc`, the memory and the running state are not affected.
+    pub fn set_registers(&mut self, regs: &RegisterSet) {
+        self.r_a = regs.a;
+        self.r_x = regs.x;
+        self.r_in = regs.i;
+        self.r_j = regs.j;
+        self.comp = regs.comp;
+        self.overflow = regs.overflow;
+    }
+
+    /// Load a register from memory as if `LDr addr(field)` were
+    /// executed.
+    ///
+    /// The program counter is not changed, and the machine is not
+    /// halted on errors.
+    ///
+    /// # Arguments
+    /// * `reg` - The register to load.
+    /// * `addr` - The address to load from.
+    /// * `field` - The field `F` to load.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The register is loaded.
+    /// * [`Err(ErrorCode::IllegalInstruction)`] - `reg` is [`RegisterId::J`], which cannot be loaded.
+    /// * [`Err(ErrorCode)`] - The load fails as the instruction would.
+    pub fn load_register_from_memory(
+        &mut self,
+        reg: RegisterId,
+        addr: u16,
+        field: u8,
+    ) -> Result<(), ErrorCode> {
+        let opcode = match reg {
+            RegisterId::A => Opcode::LdA,
+            RegisterId::I1 => Opcode::Ld1,
+            RegisterId::I2 => Opcode::Ld2,
+            RegisterId::I3 => Opcode::Ld3,
+            RegisterId::I4 => Opcode::Ld4,
+            RegisterId::I5 => Opcode::Ld5,
+            RegisterId::I6 => Opcode::Ld6,
+            RegisterId::X => Opcode::LdX,
+            RegisterId::J => return Err(ErrorCode::IllegalInstruction),
         };
-        // Zero reg before copying. Handle 'understood' negative sign.
-        reg.set_all([FullWord::POS, 0, 0, 0, 0, 0]);
-        // Copy bytes shifted right.
-        for (reg_cursor, mem_cursor) in (1..=5).rev().zip(field.rev()) {
-            reg[reg_cursor] = mem_cell[mem_cursor];
+        self.helper_execute_synthetic(addr, field, opcode)
+    }
+
+    /// Compare a register with a memory cell as if `CMPr addr(field)`
+    /// were executed, leaving the machine untouched.
+    ///
+    /// Not even the comparison indicator is set, so this may be used
+    /// in assertions and debugger conditions. The memory is read
+    /// directly, bypassing memory-mapped devices.
+    ///
+    /// # Arguments
+    /// * `reg` - The register to compare.
+    /// * `addr` - The address to compare with.
+    /// * `field` - The field `F` to compare.
+    ///
+    /// # Returns
+    /// * [`Ok(CompIndicator)`] - The indicator `CMPr` would set.
+    /// * [`Err(ErrorCode::IllegalInstruction)`] - `reg` is [`RegisterId::J`], which cannot be compared.
+    /// * [`Err(ErrorCode)`] - The comparison fails as the instruction would.
+    pub fn compare_registers_with_memory(
+        &self,
+        reg: RegisterId,
+        addr: u16,
+        field: u8,
+    ) -> Result<CompIndicator, ErrorCode> {
+        let (opcode, value) = match reg {
+            RegisterId::A => (Opcode::CmpA, self.r_a),
+            RegisterId::I1 => (Opcode::Cmp1, self.r_in[1].to_full_word()),
+            RegisterId::I2 => (Opcode::Cmp2, self.r_in[2].to_full_word()),
+            RegisterId::I3 => (Opcode::Cmp3, self.r_in[3].to_full_word()),
+            RegisterId::I4 => (Opcode::Cmp4, self.r_in[4].to_full_word()),
+            RegisterId::I5 => (Opcode::Cmp5, self.r_in[5].to_full_word()),
+            RegisterId::I6 => (Opcode::Cmp6, self.r_in[6].to_full_word()),
+            RegisterId::X => (Opcode::CmpX, self.r_x),
+            RegisterId::J => return Err(ErrorCode::IllegalInstruction),
+        };
+        let instr = Instruction::new(0, field, 0, opcode);
+        self.helper_check_extension(&instr)?;
+        if !instr.is_ieee754() {
+            self.helper_check_field_range(field)?;
         }
-        // Copy negated sign byte if needed.
-        if sign_copy_needed {
-            reg[0] = mem_cell[0];
-            reg.flip_sign();
+        if addr as usize >= Mem::SIZE {
+            return Err(ErrorCode::InvalidAddress);
         }
-        Ok(())
+        Ok(Self::helper_compare(&value, &self.mem[addr], field))
     }
 
-    /// Handler for `LD1-6`.
+    /// Store a register into memory as if `STr addr(field)` were
+    /// executed.
     ///
-    /// Note that this instruction only sets the first sign, 4th
-    /// and 5th bits of the original memory location. This prevents
-    /// the said 'undefined behavior' from happening.
-    fn handle_instr_load_3b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain everything.
-        let (field, sign_copy_needed) = instr.field.to_range_inclusive_signless();
-        let mem_cell = &self.mem[self.helper_get_eff_addr(instr.addr, instr.index)?];
-        let reg = match instr.opcode {
-            Opcode::Ld1 => &mut self.r_in[1],
-            Opcode::Ld2 => &mut self.r_in[2],
-            Opcode::Ld3 => &mut self.r_in[3],
-            Opcode::Ld4 => &mut self.r_in[4],
-            Opcode::Ld5 => &mut self.r_in[5],
-            Opcode::Ld6 => &mut self.r_in[6],
-            _ => unreachable!(),
+    /// The program counter is not changed, and the machine is not
+    /// halted on errors.
+    ///
+    /// # Arguments
+    /// * `reg` - The register to store.
+    /// * `addr` - The address to store into.
+    /// * `field` - The field `F` to store.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The register is stored.
+    /// * [`Err(ErrorCode)`] - The store fails as the instruction would.
+    pub fn store_register_to_memory(
+        &mut self,
+        reg: RegisterId,
+        addr: u16,
+        field: u8,
+    ) -> Result<(), ErrorCode> {
+        let opcode = match reg {
+            RegisterId::A => Opcode::StA,
+            RegisterId::I1 => Opcode::St1,
+            RegisterId::I2 => Opcode::St2,
+            RegisterId::I3 => Opcode::St3,
+            RegisterId::I4 => Opcode::St4,
+            RegisterId::I5 => Opcode::St5,
+            RegisterId::I6 => Opcode::St6,
+            RegisterId::X => Opcode::StX,
+            RegisterId::J => Opcode::StJ,
         };
-        // We need to care about only the 4th, 5th and the sign byte.
-        // So we make a temporary word and fill back the reg only the
-        // 4th, 5th and the sign byte. Handle 'understood' positive sign.
-        let mut temp = FullWord::from_bytes([FullWord::POS, 0, 0, 0, 0, 0]);
-        // Copy bytes shifted right.
-        for (reg_cursor, mem_cursor) in (1..=5).rev().zip(field.rev()) {
-            temp[reg_cursor] = mem_cell[mem_cursor];
+        self.helper_execute_synthetic(addr, field, opcode)
+    }
+
+    /// Take a snapshot of the registers and the memory.
+    pub fn snapshot(&self) -> VMStateSnapshot {
+        VMStateSnapshot {
+            r_a: self.r_a,
+            r_x: self.r_x,
+            r_in: self.r_in,
+            r_j: self.r_j,
+            comp: self.comp,
+            overflow: self.overflow,
+            halted: self.halted,
+            pc: self.pc,
+            mem: self.mem.clone(),
         }
-        // Copy sign byte if needed.
-        if sign_copy_needed {
-            temp[0] = mem_cell[0];
+    }
+
+    /// Hash the registers, `pc`, the running state and the memory.
+    ///
+    /// The hash is 64-bit FNV-1a over, in order:
+    ///
+    /// * the bytes of `rA`, `rX`, `rI1` to `rI6` and `rJ`, each from
+    ///   its sign byte;
+    /// * one byte each for the comparison indicator (`0` equal, `1`
+    ///   less, `2` greater, `3` unordered), the overflow toggle and
+    ///   the running state;
+    /// * `pc` and [`Mem::content_hash()`], in big-endian.
+    ///
+    /// IO devices, counters and instrumentation such as coverage and
+    /// the event log are left out, so that observing a run does not
+    /// change its hash. The hash is the same on every platform, and
+    /// does not change within a major version.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = FNV_OFFSET_BASIS;
+        hash = fnv1a(hash, self.r_a.as_array());
+        hash = fnv1a(hash, self.r_x.as_array());
+        for r in &self.r_in[1..] {
+            hash = fnv1a(hash, r.as_array());
         }
-        // Fill back the reg.
-        reg[0] = temp[0];
-        reg[1] = temp[4];
-        reg[2] = temp[5];
-        Ok(())
+        hash = fnv1a(hash, self.r_j.as_array());
+        let comp = match self.comp {
+            CompIndicator::Equal => 0,
+            CompIndicator::Less => 1,
+            CompIndicator::Greater => 2,
+            CompIndicator::Unordered => 3,
+        };
+        hash = fnv1a(hash, &[comp, self.overflow as u8, self.halted as u8]);
+        hash = fnv1a(hash, &self.pc.to_be_bytes());
+        fnv1a(hash, &self.mem.content_hash().to_be_bytes())
     }
 
-    /// Handler for `LD1-6N`.
+    /// Check if two machines are in the same state.
     ///
-    /// Note that this instruction only sets the first sign, 4th
-    /// and 5th bits of the original memory location. This prevents
-    /// the said 'undefined behavior' from happening.
-    fn handle_instr_load_neg_3b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain everything.
-        let (field, sign_copy_needed) = instr.field.to_range_inclusive_signless();
-        let memory_cell = &self.mem[self.helper_get_eff_addr(instr.addr, instr.index)?];
-        let reg = match instr.opcode {
-            Opcode::Ld1N => &mut self.r_in[1],
-            Opcode::Ld2N => &mut self.r_in[2],
-            Opcode::Ld3N => &mut self.r_in[3],
-            Opcode::Ld4N => &mut self.r_in[4],
-            Opcode::Ld5N => &mut self.r_in[5],
-            Opcode::Ld6N => &mut self.r_in[6],
-            _ => unreachable!(),
+    /// The parts hashed by [`VM::state_hash()`] are compared: the
+    /// registers, `pc`, the running state and the memory.
+    pub fn state_eq(&self, other: &VM) -> bool {
+        self.registers() == other.registers()
+            && self.pc == other.pc
+            && self.halted == other.halted
+            && self.mem.as_byte_slice() == other.mem.as_byte_slice()
+    }
+
+    /// Check that the words in `start..start + len` have not been
+    /// changed, as a guard before running a program.
+    ///
+    /// # Arguments
+    /// * `expected_checksum` - The [`Mem::checksum_range()`] of the words.
+    /// * `start` - The address of the first word.
+    /// * `len` - The number of words.
+    ///
+    /// # Returns
+    /// * `true` - The words have the expected checksum.
+    /// * `false` - The checksum differs, or the range is out of memory.
+    pub fn verify_program_integrity(&self, expected_checksum: u64, start: u16, len: u16) -> bool {
+        let Ok(end) = u16::try_from(start as usize + len as usize) else {
+            return false;
         };
-        // We need to care about only the 4th, 5th and the sign byte.
-        // So we make a temporary word and fill back the reg only the
-        // 4th, 5th and the sign byte. Handle 'understood' positive sign.
-        let mut temp = FullWord::from_bytes([FullWord::POS, 0, 0, 0, 0, 0]);
-        // Copy bytes shifted right.
-        for (reg_cursor, memory_cell_cursor) in (1..=5).rev().zip(field.rev()) {
-            temp[reg_cursor] = memory_cell[memory_cell_cursor];
+        self.mem.checksum_range(start, end) == Ok(expected_checksum)
+    }
+
+    /// Record an event if the log is enabled.
+    pub(crate) fn helper_log(&mut self, event: Event) {
+        if let Some(events) = self.events.as_mut() {
+            events.push(EventRecord {
+                cycle: self.cycles,
+                event,
+            });
         }
-        // Copy negated sign byte if needed.
-        if sign_copy_needed {
-            temp[0] = memory_cell[0];
-            temp.flip_sign();
+    }
+
+    /// Pack an arithmetic result into a register, following `policy`.
+    ///
+    /// This is the `arith-overflow` entry of
+    /// [`semantics::Semantics::current()`].
+    ///
+    /// # Returns
+    /// * [`Ok(Word)`] - The word to store. The overflow toggle is set if the result does not fit.
+    /// * [`Err(ErrorCode::ArithmeticOverflow)`] - The result does not fit and overflows trap.
+    fn helper_pack_arith_result<const N: usize>(
+        &mut self,
+        value: i64,
+        policy: OverflowPolicy,
+    ) -> Result<Word<N, false>, ErrorCode> {
+        let (word, overflow) = Word::<N, false>::from_i64(value);
+        if !overflow {
+            return Ok(word);
         }
-        // Fill back the reg.
-        reg[0] = temp[0];
-        reg[1] = temp[4];
-        reg[2] = temp[5];
-        Ok(())
+        let word = match policy {
+            OverflowPolicy::WrapLow => word,
+            OverflowPolicy::Saturate => {
+                let max = (1i64 << ((N - 1) as u32 * BYTE_BITS)) - 1;
+                Word::from_i64(value.clamp(-max, max)).0
+            }
+            OverflowPolicy::Trap => return Err(ErrorCode::ArithmeticOverflow),
+        };
+        self.overflow = true;
+        Ok(word)
     }
 
-    /// Handler for `JMP` and variants.
-    fn handle_instr_jmp(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        let target_addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
-        // Match jump conditions.
-        let should_jump = match instr.field {
-            0 | 1 => true,
-            2 => self.overflow,
-            3 => !self.overflow,
-            4 => self.comp == CompIndicator::Less,
-            5 => self.comp == CompIndicator::Equal,
-            6 => self.comp == CompIndicator::Greater,
-            7 => self.comp != CompIndicator::Less,
-            8 => self.comp != CompIndicator::Equal,
-            9 => self.comp != CompIndicator::Greater,
-            10 => self.comp != CompIndicator::Unordered,
-            11 => self.comp == CompIndicator::Unordered,
-            _ => return Err(ErrorCode::InvalidField),
+    /// Pack a result into an index register, following
+    /// [`VM::strict_index`], then [`VM::index_overflow_mode`].
+    ///
+    /// This is the `index-overflow` entry of
+    /// [`semantics::Semantics::current()`].
+    ///
+    /// # Returns
+    /// * [`Ok(HalfWord)`] - The word to store. The overflow toggle is set if the result does not fit.
+    /// * [`Err(ErrorCode)`] - The result does not fit and overflows are rejected.
+    fn helper_pack_index_result(&mut self, value: i64) -> Result<HalfWord, ErrorCode> {
+        let fits = !HalfWord::from_i64(value).1;
+        let policy = match self.index_overflow_mode {
+            _ if fits => OverflowPolicy::WrapLow,
+            _ if self.strict_index => return Err(ErrorCode::IndexRangeViolation),
+            IndexOverflowMode::Policy => self.overflow_policy,
+            IndexOverflowMode::Wrap => OverflowPolicy::WrapLow,
+            IndexOverflowMode::Saturate => OverflowPolicy::Saturate,
+            IndexOverflowMode::Trap => return Err(ErrorCode::IndexOverflow),
         };
-        // Clear overflow flag.
-        if instr.field == 2 || instr.field == 3 {
-            self.overflow = false;
-        }
-        if should_jump {
-            self.helper_do_jump(target_addr, instr.field != 1);
+        self.helper_pack_arith_result(value, policy)
+    }
+
+    /// Handle a value that does not fit in an index register.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The value should be truncated. The overflow toggle is set.
+    /// * [`Err(ErrorCode::IndexRangeViolation)`] - [`VM::strict_index`] is set.
+    fn helper_index_range_violation(&mut self) -> Result<(), ErrorCode> {
+        if self.strict_index {
+            return Err(ErrorCode::IndexRangeViolation);
         }
+        self.overflow = true;
         Ok(())
     }
 
-    /// Handler for `CHAR`, `NUM` and `HLT`, and various extensions.
-    fn handle_instr_special(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        if instr.field == 0 {
-            // NUM instruction
-            let a_content = &self.r_a[1..=5];
-            let x_content = &self.r_x[1..=5];
-            let mut result: i64 = 0;
-            // For each byte, we extract its 1st position,
-            // and push it to `result`.
-            for &byte in a_content.iter().chain(x_content) {
-                let digit = byte % 10;
-                result = result * 10 + digit as i64;
+    /// Halt the machine on an error, logging [`Event::Halt`].
+    fn helper_halt_on_error(&mut self, err: ErrorCode) {
+        self.halt();
+        self.faulted = true;
+        self.helper_log(Event::Halt {
+            reason: metrics::HaltReason::Error(err),
+        });
+    }
+
+    /// Run a decoded instruction.
+    ///
+    /// The machine is not halted on errors.
+    fn helper_execute(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        self.helper_check_extension(instr)?;
+
+        match instr.opcode {
+            Opcode::Nop => self.handle_instr_nop(instr),
+
+            Opcode::Add => self.handle_instr_add_sub(instr),
+            Opcode::Sub => self.handle_instr_add_sub(instr),
+            Opcode::Mul => self.handle_instr_mul(instr),
+            Opcode::Div => self.handle_instr_div(instr),
+
+            Opcode::Special => self.handle_instr_special(instr),
+            Opcode::Shift => self.handle_instr_shift(instr),
+            Opcode::Move => self.handle_instr_move(instr),
+
+            Opcode::LdA => self.handle_instr_load_6b(instr),
+            Opcode::Ld1 => self.handle_instr_load_3b(instr),
+            Opcode::Ld2 => self.handle_instr_load_3b(instr),
+            Opcode::Ld3 => self.handle_instr_load_3b(instr),
+            Opcode::Ld4 => self.handle_instr_load_3b(instr),
+            Opcode::Ld5 => self.handle_instr_load_3b(instr),
+            Opcode::Ld6 => self.handle_instr_load_3b(instr),
+            Opcode::LdX => self.handle_instr_load_6b(instr),
+
+            Opcode::LdAN => self.handle_instr_load_neg_6b(instr),
+            Opcode::Ld1N => self.handle_instr_load_neg_3b(instr),
+            Opcode::Ld2N => self.handle_instr_load_neg_3b(instr),
+            Opcode::Ld3N => self.handle_instr_load_neg_3b(instr),
+            Opcode::Ld4N => self.handle_instr_load_neg_3b(instr),
+            Opcode::Ld5N => self.handle_instr_load_neg_3b(instr),
+            Opcode::Ld6N => self.handle_instr_load_neg_3b(instr),
+            Opcode::LdXN => self.handle_instr_load_neg_6b(instr),
+
+            Opcode::StA => self.handle_instr_store_6b(instr),
+            Opcode::St1 => self.handle_instr_store_3b(instr),
+            Opcode::St2 => self.handle_instr_store_3b(instr),
+            Opcode::St3 => self.handle_instr_store_3b(instr),
+            Opcode::St4 => self.handle_instr_store_3b(instr),
+            Opcode::St5 => self.handle_instr_store_3b(instr),
+            Opcode::St6 => self.handle_instr_store_3b(instr),
+            Opcode::StX => self.handle_instr_store_6b(instr),
+            Opcode::StJ => self.handle_instr_store_j(instr),
+            Opcode::StZ => self.handle_instr_store_zero(instr),
+
+            Opcode::Jbus => self.handle_instr_jbus_jred(instr),
+            Opcode::Ioc => self.handle_instr_ioc(instr),
+            Opcode::In => self.handle_instr_in_out(instr),
+            Opcode::Out => self.handle_instr_in_out(instr),
+            Opcode::Jred => self.handle_instr_jbus_jred(instr),
+            Opcode::Jmp => self.handle_instr_jmp(instr),
+
+            Opcode::JA => self.handle_instr_jmp_reg_6b(instr),
+            Opcode::J1 => self.handle_instr_jmp_reg_3b(instr),
+            Opcode::J2 => self.handle_instr_jmp_reg_3b(instr),
+            Opcode::J3 => self.handle_instr_jmp_reg_3b(instr),
+            Opcode::J4 => self.handle_instr_jmp_reg_3b(instr),
+            Opcode::J5 => self.handle_instr_jmp_reg_3b(instr),
+            Opcode::J6 => self.handle_instr_jmp_reg_3b(instr),
+            Opcode::JX => self.handle_instr_jmp_reg_6b(instr),
+
+            Opcode::ModifyA => self.handle_instr_modify_6b(instr),
+            Opcode::Modify1 => self.handle_instr_modify_3b(instr),
+            Opcode::Modify2 => self.handle_instr_modify_3b(instr),
+            Opcode::Modify3 => self.handle_instr_modify_3b(instr),
+            Opcode::Modify4 => self.handle_instr_modify_3b(instr),
+            Opcode::Modify5 => self.handle_instr_modify_3b(instr),
+            Opcode::Modify6 => self.handle_instr_modify_3b(instr),
+            Opcode::ModifyX => self.handle_instr_modify_6b(instr),
+
+            Opcode::CmpA => self.handle_instr_cmp_6b(instr),
+            Opcode::Cmp1 => self.handle_instr_cmp_3b(instr),
+            Opcode::Cmp2 => self.handle_instr_cmp_3b(instr),
+            Opcode::Cmp3 => self.handle_instr_cmp_3b(instr),
+            Opcode::Cmp4 => self.handle_instr_cmp_3b(instr),
+            Opcode::Cmp5 => self.handle_instr_cmp_3b(instr),
+            Opcode::Cmp6 => self.handle_instr_cmp_3b(instr),
+            Opcode::CmpX => self.handle_instr_cmp_6b(instr),
+        }
+    }
+
+    /// Reject instructions of extensions not compiled in or not
+    /// enabled in [`VM::extensions`].
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - The instruction is not of a disabled extension.
+    /// * [`Err(ErrorCode::InvalidFieldMode)`] - It is, and its opcode has field modes.
+    /// * [`Err(ErrorCode::InvalidFieldRange)`] - It is, and its opcode takes a field.
+    fn helper_check_extension(&self, instr: &Instruction) -> Result<(), ErrorCode> {
+        let disabled = instr
+            .extension()
+            .is_some_and(|ext| !self.extensions.contains(ext));
+        if !instr.is_disabled_extension() && !disabled {
+            return Ok(());
+        }
+        Err(if instr.opcode.has_field_modes() {
+            ErrorCode::InvalidFieldMode {
+                opcode: instr.opcode,
+                field: instr.field,
             }
-            // Rebuild a word of 4 bytes.
-            let result_word = FullWord::from_i64(result).0;
-            // We do not modify the sign byte.
-            self.r_a[1..=5].copy_from_slice(&result_word[1..=5]);
-            Ok(())
-        } else if instr.field == 1 {
-            // CHAR instruction
-            // Obtain original number.
-            let mut source = self.r_a.to_i64().0.abs();
-            // Extract each digit.
-            for reg_i in (0..10).rev() {
-                if reg_i >= 5 {
-                    self.r_x[reg_i - 5 + 1] = (source % 10 + 30) as u8;
-                } else {
-                    self.r_a[reg_i + 1] = (source % 10 + 30) as u8;
-                }
-                source /= 10;
+        } else {
+            ErrorCode::InvalidFieldRange { field: instr.field }
+        })
+    }
+
+    /// Compare a register with a memory cell as `CMPr` does.
+    ///
+    /// The fields `(L:R)` are compared as signed numbers, so `+0`
+    /// and `-0` are equal. If `field` is 7, the words are compared
+    /// as `binary32` floats instead, as in `F32CMPA`.
+    fn helper_compare(reg: &FullWord, target: &FullWord, field: u8) -> CompIndicator {
+        if field == 7 {
+            let target_value = float::from_word(target);
+            let reg_value = float::from_word(reg);
+            if reg_value.is_nan() || target_value.is_nan() {
+                return CompIndicator::Unordered;
             }
+            return match reg_value.total_cmp(&target_value) {
+                Ordering::Less => CompIndicator::Less,
+                Ordering::Equal => CompIndicator::Equal,
+                Ordering::Greater => CompIndicator::Greater,
+            };
+        }
+        let target_value = target.to_i64_ranged(field.to_range_inclusive()).0;
+        let reg_value = reg.to_i64_ranged(field.to_range_inclusive()).0;
+        match reg_value.cmp(&target_value) {
+            Ordering::Equal => CompIndicator::Equal,
+            Ordering::Less => CompIndicator::Less,
+            Ordering::Greater => CompIndicator::Greater,
+        }
+    }
+
+    /// Run an unindexed instruction that is not in memory.
+    fn helper_execute_synthetic(
+        &mut self,
+        addr: u16,
+        field: u8,
+        opcode: Opcode,
+    ) -> Result<(), ErrorCode> {
+        let addr = i16::try_from(addr).map_err(|_| ErrorCode::InvalidAddress)?;
+        self.helper_execute(&Instruction::new(addr, field, 0, opcode))
+    }
+
+    /// Get indexed address.
+    fn helper_get_eff_addr(&self, addr: i16, index: u8) -> Result<u16, ErrorCode> {
+        // Direct or indirect addressing.
+        // r_in[0] is always zero.
+        if !(0..=6).contains(&index) {
+            // We have been provided a bad index.
+            return Err(ErrorCode::InvalidIndex);
+        }
+        let reg = self.r_in[index as usize];
+        let reg_val = reg.to_i64().0;
+        (reg_val + addr as i64)
+            .try_into()
+            .map_err(|_| ErrorCode::InvalidAddress)
+    }
+
+    /// Get indexed address. May panic or return negative value.
+    fn helper_get_eff_addr_unchecked(&self, addr: i16, index: u8) -> i16 {
+        let reg = self.r_in[index as usize];
+        let reg_val = reg.to_i64().0;
+        reg_val as i16 + addr
+    }
+
+    /// Do actual jump.
+    fn helper_do_jump(&mut self, location: u16, save_r_j: bool) {
+        if save_r_j {
+            let pc = self.pc.to_be_bytes();
+            self.r_j[1..=2].copy_from_slice(&pc);
+        }
+        // Do jump.
+        self.pc = location;
+    }
+
+    /// Check if an address is mapped to a device.
+    fn helper_is_mapped(&self, addr: u16) -> bool {
+        self.mmio_devices.iter().any(|(r, _)| r.contains(&addr))
+    }
+
+    /// Check if an address can be accessed.
+    fn helper_check_addr(&self, addr: u16) -> Result<(), ErrorCode> {
+        if self.helper_is_mapped(addr) || (addr as usize) < Mem::SIZE {
             Ok(())
-        } else if instr.field == 2 {
-            // HLT instruction
-            // Making it just like NOP if we restart the
-            // machine later.
-            self.halted = true;
-            Ok(())
-        } else if instr.field >= 3 && instr.field <= 8 {
-            if instr.field == 3 {
-                // F32CVTF322I4B
-                let reg = &mut self.r_a;
-                let orig_value = f32::from_be_bytes([reg[2], reg[3], reg[4], reg[5]]);
-                reg.set_all([FullWord::POS, 0, 0, 0, 0, 0]);
-                reg[0] = if orig_value.is_sign_positive() {
-                    FullWord::POS
-                } else {
-                    FullWord::NEG
-                };
-                if !orig_value.is_finite()
-                    || orig_value > i32::MAX as f32
-                    || orig_value < i32::MIN as f32
-                {
-                    self.overflow = true
-                }
-                let result = orig_value.abs() as u32;
-                reg[2..=5].copy_from_slice(&result.to_be_bytes());
-                Ok(())
-            } else if instr.field == 4 {
-                // F32CVTF322I2B
-                let reg = &mut self.r_a;
-                let orig_value = f32::from_be_bytes([reg[2], reg[3], reg[4], reg[5]]);
-                reg.set_all([FullWord::POS, 0, 0, 0, 0, 0]);
-                reg[0] = if orig_value.is_sign_positive() {
-                    FullWord::POS
-                } else {
-                    FullWord::NEG
-                };
-                if !orig_value.is_finite()
-                    || orig_value > i16::MAX as f32
-                    || orig_value < i16::MIN as f32
-                {
-                    self.overflow = true
-                }
-                let result = orig_value.abs() as u16;
-                reg[4..=5].copy_from_slice(&result.to_be_bytes());
-                Ok(())
-            } else if instr.field == 5 {
-                // F32CVTF322I1B
-                let reg = &mut self.r_a;
-                let orig_value = f32::from_be_bytes([reg[2], reg[3], reg[4], reg[5]]);
-                reg.set_all([FullWord::POS, 0, 0, 0, 0, 0]);
-                reg[0] = if orig_value.is_sign_positive() {
-                    FullWord::POS
-                } else {
-                    FullWord::NEG
-                };
-                if !orig_value.is_finite()
-                    || orig_value > i8::MAX as f32
-                    || orig_value < i8::MIN as f32
-                {
-                    self.overflow = true
-                }
-                let result = orig_value.abs() as u8;
-                reg[5..=5].copy_from_slice(&result.to_be_bytes());
-                Ok(())
-            } else {
-                let reg = &mut self.r_a;
-                let new_value = match instr.field {
-                    // F32CVTI4B2F32
-                    6 => Ok(u32::from_be_bytes([reg[2], reg[3], reg[4], reg[5]]) as f32),
-                    // F32CVTI2B2F32
-                    7 => Ok(u16::from_be_bytes([reg[4], reg[5]]) as f32),
-                    // F32CVTI1B2F32
-                    8 => Ok(u8::from_be_bytes([reg[5]]) as f32),
-                    _ => unreachable!(),
-                }?;
-                reg.set_all([FullWord::POS, 0, 0, 0, 0, 0]);
-                reg[2..=5].copy_from_slice(&new_value.to_be_bytes());
-                Ok(())
-            }
-        } else if instr.field >= 9 && instr.field <= 12 {
-            if instr.field == 9 {
-                // NOT
-                let reg = &mut self.r_a;
-                reg.flip_sign();
-                for i in 1..=5 {
-                    reg[i] = !reg[i];
-                }
-                Ok(())
-            } else {
-                let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
-                let reg = &mut self.r_a;
-                let mem_cell = &mut self.mem[addr];
-                let map_fn = match instr.field {
-                    // AND
-                    10 => |a: u8, b: u8| a & b,
-                    // OR
-                    11 => |a: u8, b: u8| a | b,
-                    // XOR
-                    12 => |a: u8, b: u8| a ^ b,
-                    _ => unreachable!(),
-                };
-                for (r, m) in reg[..].iter_mut().zip(mem_cell[..].iter()) {
-                    *r = map_fn(*r, *m);
-                }
-                Ok(())
-            }
         } else {
-            Err(ErrorCode::InvalidField)
+            Err(ErrorCode::InvalidAddress)
         }
     }
 
-    /// Handler for `STZ`.
-    fn handle_instr_store_zero(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain everything.
-        let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
-        let field = instr.field.to_range_inclusive();
-        let mem_cell = &mut self.mem[addr];
-        // Zero the memory cell.
-        for i in field {
-            if i == 0 {
-                // Deal with signs.
-                mem_cell[0] = FullWord::POS;
-            } else {
-                mem_cell[i] = 0;
-            }
+    /// Fetch the instruction at `pc` from memory or a memory-mapped
+    /// device.
+    fn helper_fetch(&mut self) -> Result<Instruction, ErrorCode> {
+        let word = self.helper_load(self.pc)?;
+        Instruction::try_from(word).map_err(|_| ErrorCode::IllegalInstruction)
+    }
+
+    /// Load a word from memory or a memory-mapped device.
+    ///
+    /// All memory reads of instructions must go through this method.
+    fn helper_load(&mut self, addr: u16) -> Result<FullWord, ErrorCode> {
+        if let Some((range, dev)) = self
+            .mmio_devices
+            .iter_mut()
+            .find(|(r, _)| r.contains(&addr))
+        {
+            return Ok(dev.load(addr - range.start));
+        }
+        if addr as usize >= Mem::SIZE {
+            return Err(ErrorCode::InvalidAddress);
+        }
+        Ok(self.mem[addr])
+    }
+
+    /// Check if instructions may write to `addr`.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - `addr` is not protected.
+    /// * [`Err(ErrorCode::WriteProtected)`] - Otherwise.
+    fn helper_check_writable(&self, addr: u16) -> Result<(), ErrorCode> {
+        if self.is_protected(addr) {
+            return Err(ErrorCode::WriteProtected);
         }
         Ok(())
     }
 
-    /// Handler for `MOVE`.
-    fn handle_instr_move(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain from address.
-        let from_addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
-        // Obtain to address.
-        let to_addr = u16::from_be_bytes([self.r_in[1][1], self.r_in[1][2]]);
-        let num_words = instr.field;
-        // Move each word.
-        for i in 0..num_words {
-            let orig_mem = self.mem[from_addr + i as u16];
-            self.mem[to_addr + i as u16].clone_from(&orig_mem);
+    /// Store a word into memory or a memory-mapped device.
+    ///
+    /// All memory writes of instructions must go through this method.
+    fn helper_store(&mut self, addr: u16, word: FullWord) -> Result<(), ErrorCode> {
+        self.helper_check_writable(addr)?;
+        if let Some((range, dev)) = self
+            .mmio_devices
+            .iter_mut()
+            .find(|(r, _)| r.contains(&addr))
+        {
+            dev.store(addr - range.start, word);
+            return Ok(());
         }
-        let new_r_i1_val = self.r_in[1].to_i64().0 + num_words as i64;
-        let (new_r_i1, overflow) = HalfWord::from_i64(new_r_i1_val);
-        self.r_in[1][..].copy_from_slice(&new_r_i1[..]);
-        if overflow {
-            self.overflow = overflow;
+        if addr as usize >= Mem::SIZE {
+            return Err(ErrorCode::InvalidAddress);
         }
+        self.mem[addr] = word;
         Ok(())
     }
 
-    /// Handler for `STA` and `STX`.
-    fn handle_instr_store_6b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain everything.
+    /// Store the field `(L:R)` of `instr` from a register.
+    ///
+    /// As in Knuth, bytes `max(L, 1)` to `R` of the memory cell are
+    /// replaced by as many of the rightmost bytes of the register.
+    /// The sign is copied only if `L == 0`, and the rest of the cell
+    /// is left unchanged. So `(0:0)` changes the sign alone, as no
+    /// byte remains in the field once the sign is taken out. Shorter
+    /// registers must be padded with zero bytes on the left. All
+    /// stores must go through this method.
+    ///
+    /// # Arguments
+    /// * `instr` - The store instruction.
+    /// * `reg` - The content of the register, padded to a [`FullWord`].
+    fn helper_store_field(&mut self, instr: &Instruction, reg: FullWord) -> Result<(), ErrorCode> {
+        self.helper_check_field_range(instr.field)?;
         let (field, sign_copy_needed) = instr.field.to_range_inclusive_signless();
         let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
-        let mem_cell = &mut self.mem[addr];
-        let reg = match instr.opcode {
-            Opcode::StA => &self.r_a,
-            Opcode::StX => &self.r_x,
-            _ => unreachable!(),
+        // Devices are not read, see `MmioDevice::store()`.
+        let mut mem_cell = if self.helper_is_mapped(addr) {
+            FullWord::new()
+        } else {
+            self.helper_load(addr)?
         };
-        // Copy bytes shifted right.
+        // Copy bytes shifted right, pairing the last byte of the
+        // field with the last byte of the register.
         for (reg_cursor, mem_cursor) in (1..=5).rev().zip(field.rev()) {
             mem_cell[mem_cursor] = reg[reg_cursor];
         }
         if sign_copy_needed {
-            // Copy sign bit.
-            mem_cell[0] = reg[0];
+            mem_cell.set_sign(reg.is_negative());
         }
-        Ok(())
+        self.helper_store(addr, mem_cell)
     }
 
-    /// Handler for `ST1-6`.
-    fn handle_instr_store_3b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain everything.
+    /// Load the field `(L:R)` of `instr` as a register value.
+    ///
+    /// As in Knuth, bytes `max(L, 1)` to `R` of the memory cell become
+    /// the rightmost bytes of the value, and the other bytes are zero.
+    /// The sign is copied if `L == 0`, and is understood positive
+    /// otherwise. So `(0:0)` loads `+0` or `-0` with the sign of the
+    /// cell, and `(4:4)` loads byte 4 alone as a positive value. All
+    /// loads must go through this method.
+    ///
+    /// # Arguments
+    /// * `instr` - The load instruction.
+    fn helper_load_field(&mut self, instr: &Instruction) -> Result<FullWord, ErrorCode> {
+        self.helper_check_field_range(instr.field)?;
         let (field, sign_copy_needed) = instr.field.to_range_inclusive_signless();
         let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
-        let mem_cell = &mut self.mem[addr];
-        let reg = match instr.opcode {
-            Opcode::St1 => &self.r_in[1],
-            Opcode::St2 => &self.r_in[2],
-            Opcode::St3 => &self.r_in[3],
-            Opcode::St4 => &self.r_in[4],
-            Opcode::St5 => &self.r_in[5],
-            Opcode::St6 => &self.r_in[6],
-            _ => unreachable!(),
-        };
-        let padded_reg = [reg[0], 0, 0, 0, reg[1], reg[2]];
+        let mem_cell = self.helper_load(addr)?;
+        let mut value = FullWord::new();
         // Copy bytes shifted right.
         for (reg_cursor, mem_cursor) in (1..=5).rev().zip(field.rev()) {
-            mem_cell[mem_cursor] = padded_reg[reg_cursor];
+            value[reg_cursor] = mem_cell[mem_cursor];
         }
         if sign_copy_needed {
-            // Copy sign bit.
-            mem_cell[0] = padded_reg[0];
+            value.set_sign(mem_cell.is_negative());
         }
-        Ok(())
+        Ok(value)
     }
 
-    /// Handler for `STJ`.
-    fn handle_instr_store_j(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain everything.
-        let (field, sign_copy_needed) = instr.field.to_range_inclusive_signless();
-        let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
-        let mem_cell = &mut self.mem[addr];
-        let reg = &self.r_j;
-        let padded_reg = [reg[0], 0, 0, 0, reg[1], reg[2]];
-        // Copy bytes shifted right.
-        for (reg_cursor, mem_cursor) in (1..=5).rev().zip(field.rev()) {
-            mem_cell[mem_cursor] = padded_reg[reg_cursor];
+    /// Fit a loaded value into an index register.
+    ///
+    /// Knuth leaves loading more than two bytes into an index register
+    /// undefined. This machine keeps the sign and bytes 4 and 5 of the
+    /// value. If any of bytes 1 to 3 is nonzero, the value does not
+    /// fit, which is an error under [`VM::strict_index`] and sets the
+    /// overflow toggle otherwise. This is the `ld-index-wide` entry of
+    /// [`semantics::Semantics::current()`].
+    ///
+    /// # Returns
+    /// * [`Ok(HalfWord)`] - The value kept.
+    /// * [`Err(ErrorCode::IndexRangeViolation)`] - The value does not fit under [`VM::strict_index`].
+    fn helper_fit_index(&mut self, value: FullWord) -> Result<HalfWord, ErrorCode> {
+        if value[1..=3].iter().any(|&b| b != 0) {
+            self.helper_index_range_violation()?;
         }
-        if sign_copy_needed {
-            // Copy sign bit.
-            mem_cell[0] = padded_reg[0];
+        Ok(HalfWord::from_bytes([
+            value.sign_byte(),
+            value[4],
+            value[5],
+        ]))
+    }
+
+    /// Check if `field` is a valid field `(L:R)`.
+    ///
+    /// # Returns
+    /// * [`Ok(())`] - `L <= R <= 5`.
+    /// * [`Err(ErrorCode::InvalidFieldRange)`] - Otherwise.
+    fn helper_check_field_range(&self, field: u8) -> Result<(), ErrorCode> {
+        let (l, r) = (field / 8, field % 8);
+        if l > r || r > 5 {
+            return Err(ErrorCode::InvalidFieldRange { field });
         }
         Ok(())
     }
 
-    /// Handler for `INCA`, `DECA`, `ENTA`, `ENNA`, `INCX`,
-    /// `DECX`, `ENTX` and `ENNX`.
-    fn handle_instr_modify_6b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
-        let reg = match instr.opcode {
-            Opcode::ModifyA => &mut self.r_a,
-            Opcode::ModifyX => &mut self.r_x,
-            _ => unreachable!(),
-        };
+    /// Get IO device.
+    fn helper_get_io_device(&self, dev_id: usize) -> Result<&dyn IODevice, ErrorCode> {
+        let dev = self
+            .io_devices
+            .get(dev_id)
+            .ok_or(ErrorCode::UnknownDevice)?
+            .as_ref()
+            .ok_or(ErrorCode::UnknownDevice)?
+            .as_ref();
+        Ok(dev)
+    }
 
-        if instr.field == 0 || instr.field == 1 {
-            // INCx and DECx
-            // Add or subtract one.
-            let addr = addr as i64;
-            let offset = if instr.field == 0 { addr } else { -addr };
-            let value = reg.to_i64().0;
-            // Convert back modified value.
-            let (new_word, overflow) = FullWord::from_i64(value + offset);
-            reg.clone_from(&new_word);
-            if overflow {
-                self.overflow = overflow;
+    /// Wait until a unit is no longer kept busy by a transfer.
+    ///
+    /// The wait is added to the cycle count.
+    fn helper_wait_device(&mut self, dev_id: usize) {
+        self.cycles = self.cycles.max(self.busy_until[dev_id]);
+    }
+
+    /// Get the block size of a unit, checking that it has not changed
+    /// since the last transfer of the same device.
+    ///
+    /// A device is the same until [`VM::attach_device()`] or
+    /// [`VM::detach_device()`] replaces it.
+    ///
+    /// # Returns
+    /// * [`Ok(usize)`] - The block size.
+    /// * [`Err(ErrorCode::UnknownDevice)`] - No device is on the unit.
+    /// * [`Err(ErrorCode::IOBlockSizeMismatch)`] - The block size has changed.
+    fn helper_check_block_size(&mut self, dev_id: usize) -> Result<usize, ErrorCode> {
+        let dev = self
+            .io_devices
+            .get(dev_id)
+            .ok_or(ErrorCode::UnknownDevice)?
+            .as_deref()
+            .ok_or(ErrorCode::UnknownDevice)?;
+        let generation = self.device_generations[dev_id];
+        let size = dev.get_block_size();
+        match self.block_sizes[dev_id] {
+            Some((last_generation, last)) if last_generation == generation && last != size => {
+                Err(ErrorCode::IOBlockSizeMismatch)
             }
-            Ok(())
-        } else if instr.field == 2 || instr.field == 3 {
-            // ENTx and ENNx
-            let new_word = FullWord::from_i64(addr as i64).0;
-            // Copy new word into reg.
-            reg.clone_from(&new_word);
-            if instr.field == 3 {
-                reg.flip_sign();
+            _ => {
+                self.block_sizes[dev_id] = Some((generation, size));
+                Ok(size)
             }
-            Ok(())
-        } else {
-            Err(ErrorCode::InvalidField)
         }
     }
 
-    /// Handler for `INC1-6`, `DEC1-6`, `ENT1-6`, `ENN1-6`.
-    fn handle_instr_modify_3b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        let addr = self.helper_get_eff_addr_unchecked(instr.addr, instr.index);
-        let reg = match instr.opcode {
-            Opcode::Modify1 => &mut self.r_in[1],
-            Opcode::Modify2 => &mut self.r_in[2],
-            Opcode::Modify3 => &mut self.r_in[3],
-            Opcode::Modify4 => &mut self.r_in[4],
-            Opcode::Modify5 => &mut self.r_in[5],
-            Opcode::Modify6 => &mut self.r_in[6],
-            _ => unreachable!(),
-        };
-
-        if instr.field == 0 || instr.field == 1 {
-            // INCx and DECx
-            // Add or subtract one.
-            let addr = addr as i64;
-            let offset = if instr.field == 0 { addr } else { -addr };
-            let value = reg.to_i64().0;
-            // Convert back modified value.
-            let (new_word, overflow) = HalfWord::from_i64(value + offset);
-            reg.clone_from(&new_word);
-            if overflow {
-                self.overflow = overflow;
-            }
-            Ok(())
-        } else if instr.field == 2 || instr.field == 3 {
-            // ENTx and ENNx
-            let new_word = HalfWord::from_i64(addr as i64).0;
-            // Copy new word into reg.
-            reg.clone_from(&new_word);
-            if instr.field == 3 {
-                reg.flip_sign();
-            }
-            Ok(())
-        } else {
-            Err(ErrorCode::InvalidField)
-        }
+    /// Wait until no unit is kept busy by a transfer, then flush all
+    /// devices.
+    ///
+    /// See [`IOC_BARRIER_UNIT`].
+    fn helper_drain_devices(&mut self) -> Result<(), ErrorCode> {
+        let end = self.busy_until.iter().copied().max().unwrap_or(0);
+        self.cycles = self.cycles.max(end);
+        self.flush_devices()
     }
 
-    /// Handler for `ADD` and `SUB`.
-    /// `F32ADD` and `F32SUB` are passed through if enabled.
-    fn handle_instr_add_sub(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain V from memory.
-        let target_mem = &self.mem[self.helper_get_eff_addr(instr.addr, instr.index)?];
+    /// Skip the iterations of a `JBUS` to itself waiting for a
+    /// transfer to end.
+    ///
+    /// Each iteration would jump back to the `JBUS` with the same
+    /// effect, so only the cycle and step counts change. The step
+    /// limit and the step horizon are honored.
+    fn helper_fast_forward_wait(&mut self, instr: &Instruction) {
+        let dev_id = instr.field as usize;
+        if self.breakpoints.contains(&self.pc) {
+            return;
+        }
+        // A device busy by itself may become ready at any time.
+        match self.helper_get_io_device(dev_id) {
+            Ok(dev) if dev.is_busy() == Ok(false) => {}
+            _ => return,
+        }
+        let cost = instr.cycles().max(1);
+        let mut iterations = self.busy_until[dev_id]
+            .saturating_sub(self.cycles)
+            .div_ceil(cost);
+        if let Some(limit) = self.step_limit.into_iter().chain(self.step_horizon).min() {
+            iterations = iterations.min(limit.saturating_sub(self.step_count));
+        }
+        self.step_count += iterations;
+        self.cycles += iterations * cost;
+    }
 
-        if instr.field == 7 {
-            // F32ADD, F32SUB
-            let target_value =
-                f32::from_be_bytes([target_mem[2], target_mem[3], target_mem[4], target_mem[5]]);
-            let orig_value =
-                f32::from_be_bytes([self.r_a[2], self.r_a[3], self.r_a[4], self.r_a[5]]);
-            let new_value = match instr.opcode {
-                Opcode::Add => orig_value + target_value,
-                Opcode::Sub => orig_value - target_value,
-                _ => unreachable!(),
-            };
-            let new_bytes = new_value.to_be_bytes();
-            let sign = if new_value.is_sign_positive() {
-                FullWord::POS
-            } else {
-                FullWord::NEG
-            };
-            self.r_a.set_all([
-                sign,
-                0,
-                new_bytes[0],
-                new_bytes[1],
-                new_bytes[2],
-                new_bytes[3],
-            ]);
-            if !new_value.is_finite() {
-                self.overflow = true;
-            }
-        } else {
-            let orig_value = self.r_a.to_i64().0;
-            let target_value = target_mem.to_i64_ranged(instr.field.to_range_inclusive()).0;
-            // Calculate and pack new value.
-            let new_value = match instr.opcode {
-                Opcode::Add => orig_value + target_value,
-                Opcode::Sub => orig_value - target_value,
-                _ => unreachable!(),
-            };
-            let (new_word, overflow) = FullWord::from_i64(new_value);
-            // Set new value.
-            self.r_a.clone_from(&new_word);
-            if overflow {
-                self.overflow = overflow;
-            }
+    /// Keep a unit busy for a transfer of `words` words.
+    ///
+    /// See [`IODevice::bandwidth()`].
+    fn helper_start_transfer(&mut self, dev_id: usize, words: usize) {
+        let bandwidth = match self.helper_get_io_device(dev_id) {
+            Ok(dev) => dev.bandwidth(),
+            Err(_) => None,
+        };
+        if let Some(bandwidth) = bandwidth.filter(|&b| b > 0) {
+            let duration = (words as u64).div_ceil(bandwidth);
+            self.busy_until[dev_id] = self.cycles + duration;
+            self.device_stats[dev_id].busy_cycles += duration;
         }
+    }
+
+    /// Get IO device.
+    fn helper_get_io_device_mut(
+        &mut self,
+        dev_id: usize,
+    ) -> Result<&mut std::boxed::Box<dyn io::IODevice>, ErrorCode> {
+        let dev = self
+            .io_devices
+            .get_mut(dev_id)
+            .ok_or(ErrorCode::UnknownDevice)?
+            .as_mut()
+            .ok_or(ErrorCode::UnknownDevice)?;
+        Ok(dev)
+    }
 
+    /// Handler for `NOP`.
+    fn handle_instr_nop(&mut self, _: &Instruction) -> Result<(), ErrorCode> {
+        // Do nothing.
         Ok(())
     }
 
-    /// Handler for `MUL` and `F32MUL`.
-    fn handle_instr_mul(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain V from memory.
-        let target_mem = &self.mem[self.helper_get_eff_addr(instr.addr, instr.index)?];
-        if instr.field == 7 {
-            // F32MUL
-            let target_value =
-                f32::from_be_bytes([target_mem[2], target_mem[3], target_mem[4], target_mem[5]]);
-            let orig_value =
-                f32::from_be_bytes([self.r_a[2], self.r_a[3], self.r_a[4], self.r_a[5]]);
-            let new_value = orig_value * target_value;
-            let new_bytes = new_value.to_be_bytes();
-            let sign = if new_value.is_sign_positive() {
-                FullWord::POS
-            } else {
-                FullWord::NEG
-            };
-            self.r_a.set_all([
-                sign,
-                0,
-                new_bytes[0],
-                new_bytes[1],
-                new_bytes[2],
-                new_bytes[3],
-            ]);
-            if !new_value.is_finite() {
-                self.overflow = true;
-            }
-        } else {
-            let orig_value = self.r_a.to_i64().0;
-            let target_value = target_mem.to_i64_ranged(instr.field.to_range_inclusive()).0;
-            // Copy value into registers.
-            let new_val = orig_value as i128 * target_value as i128;
-            let new_val_bytes = new_val.abs().to_be_bytes();
-            let mut new_val_bytes_dirty = new_val_bytes.map(|b| b != 0);
-            for (reg_i, byte_i) in (1..6).rev().zip((0..11).rev()) {
-                self.r_a[reg_i] = new_val_bytes[byte_i];
-                new_val_bytes_dirty[byte_i] = false;
-            }
-            for (reg_i, byte_i) in (1..6).rev().zip((11..16).rev()) {
-                self.r_x[reg_i] = new_val_bytes[byte_i];
-                new_val_bytes_dirty[byte_i] = false;
-            }
-            // Treat sign.
-            let new_sign = if new_val < 0 {
-                FullWord::NEG
-            } else {
-                FullWord::POS
-            };
-            self.r_a[0] = new_sign;
-            self.r_x[0] = new_sign;
-            let overflow = new_val_bytes_dirty.iter().any(|b| *b);
-            if overflow {
-                self.overflow = overflow;
-            }
+    /// Handler for `LDA` and `LDX`.
+    fn handle_instr_load_6b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        let value = self.helper_load_field(instr)?;
+        match instr.opcode {
+            Opcode::LdA => self.r_a = value,
+            Opcode::LdX => self.r_x = value,
+            _ => unreachable!(),
         }
         Ok(())
     }
 
-    /// Handler for `DIV` and `F32DIV`.
-    fn handle_instr_div(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        let target_mem = &self.mem[self.helper_get_eff_addr(instr.addr, instr.index)?];
-        if instr.field == 7 {
-            // F32DIV
-            let target_value =
-                f32::from_be_bytes([target_mem[2], target_mem[3], target_mem[4], target_mem[5]]);
-            let orig_value =
-                f32::from_be_bytes([self.r_a[2], self.r_a[3], self.r_a[4], self.r_a[5]]);
-            let new_value = orig_value / target_value;
-            let new_bytes = new_value.to_be_bytes();
-            let sign = if new_value.is_sign_positive() {
-                FullWord::POS
-            } else {
-                FullWord::NEG
-            };
-            self.r_a.set_all([
-                sign,
-                0,
-                new_bytes[0],
-                new_bytes[1],
-                new_bytes[2],
-                new_bytes[3],
-            ]);
-            if !new_value.is_finite() {
-                self.overflow = true;
-            }
-        } else {
-            let target_value = target_mem.to_i64_ranged(instr.field.to_range_inclusive()).0 as i128;
-            let orig_value = i128::from_be_bytes([
-                0,
-                0,
-                0,
-                0,
-                0,
-                0,
-                self.r_a[1],
-                self.r_a[2],
-                self.r_a[3],
-                self.r_a[4],
-                self.r_a[5],
-                self.r_x[1],
-                self.r_x[2],
-                self.r_x[3],
-                self.r_x[4],
-                self.r_x[5],
-            ]) * self.r_a.get_sign() as i128;
-            // Calculate results.
-            let quotient: i64 = orig_value
-                .checked_div(target_value)
-                .unwrap_or_else(|| {
-                    self.overflow = true;
-                    0
-                })
-                .abs()
-                .try_into()
-                .map_err(|_| {
-                    self.overflow = true;
-                })
-                .unwrap_or(0);
-            let remainder: i64 = orig_value
-                .checked_rem(target_value)
-                .unwrap_or_else(|| {
-                    self.overflow = true;
-                    0
-                })
-                .abs()
-                .try_into()
-                .map_err(|_| {
-                    self.overflow = true;
-                })
-                .unwrap_or(0);
-            // Copy results into registers.
-            let (new_a, overflow_a) = FullWord::from_i64(quotient);
-            let (new_x, overflow_x) = FullWord::from_i64(remainder);
-            self.r_x[0] = self.r_a[0];
-            self.r_a[0] = if orig_value.signum() == target_value.signum() {
-                FullWord::POS
-            } else {
-                FullWord::NEG
-            };
-            self.r_a[1..=5].copy_from_slice(&new_a[1..=5]);
-            self.r_x[1..=5].copy_from_slice(&new_x[1..=5]);
-            if overflow_a || overflow_x {
-                self.overflow = true;
-            }
+    /// Handler for `LDAN` and `LDXN`.
+    fn handle_instr_load_neg_6b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        let mut value = self.helper_load_field(instr)?;
+        // The opposite sign is loaded, even if understood.
+        value.flip_sign();
+        match instr.opcode {
+            Opcode::LdAN => self.r_a = value,
+            Opcode::LdXN => self.r_x = value,
+            _ => unreachable!(),
         }
         Ok(())
     }
 
-    /// Handler for `CMPA` and `CMPX`, `F32CMPA` and `F32CMPX`.
-    fn handle_instr_cmp_6b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain CONTENT(M).
-        let target_mem = &self.mem[self.helper_get_eff_addr(instr.addr, instr.index)?];
-        let reg = match instr.opcode {
-            Opcode::CmpA => &self.r_a,
-            Opcode::CmpX => &self.r_x,
+    /// Handler for `LD1-6`.
+    ///
+    /// See [`VM::helper_fit_index()`] for fields wider than an index
+    /// register.
+    fn handle_instr_load_3b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        let value = self.helper_load_field(instr)?;
+        let value = self.helper_fit_index(value)?;
+        match instr.opcode {
+            Opcode::Ld1 => self.r_in[1] = value,
+            Opcode::Ld2 => self.r_in[2] = value,
+            Opcode::Ld3 => self.r_in[3] = value,
+            Opcode::Ld4 => self.r_in[4] = value,
+            Opcode::Ld5 => self.r_in[5] = value,
+            Opcode::Ld6 => self.r_in[6] = value,
             _ => unreachable!(),
-        };
-        if instr.field == 7 {
-            // F32CMPA and F32CMPX
-            let target_value =
-                f32::from_be_bytes([target_mem[2], target_mem[3], target_mem[4], target_mem[5]]);
-            let reg_value = f32::from_be_bytes([reg[2], reg[3], reg[4], reg[5]]);
-            self.comp = if reg_value.is_nan() || target_value.is_nan() {
-                CompIndicator::Unordered
-            } else {
-                match reg_value.total_cmp(&target_value) {
-                    Ordering::Less => CompIndicator::Less,
-                    Ordering::Equal => CompIndicator::Equal,
-                    Ordering::Greater => CompIndicator::Greater,
-                }
-            };
-        } else {
-            let target_value = target_mem.to_i64_ranged(instr.field.to_range_inclusive()).0;
-            let reg_value = reg.to_i64_ranged(instr.field.to_range_inclusive()).0;
-            // Calculate and set flags.
-            self.comp = match reg_value.cmp(&target_value) {
-                Ordering::Equal => CompIndicator::Equal,
-                Ordering::Less => CompIndicator::Less,
-                Ordering::Greater => CompIndicator::Greater,
-            };
         }
         Ok(())
     }
 
-    /// Handler for `CMP1-6`.
-    fn handle_instr_cmp_3b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Obtain CONTENT(M).
-        let target_mem = &self.mem[self.helper_get_eff_addr(instr.addr, instr.index)?];
-        let target_value = target_mem.to_i64_ranged(instr.field.to_range_inclusive()).0;
-        let reg = match instr.opcode {
-            Opcode::Cmp1 => &self.r_in[1],
-            Opcode::Cmp2 => &self.r_in[2],
-            Opcode::Cmp3 => &self.r_in[3],
-            Opcode::Cmp4 => &self.r_in[4],
-            Opcode::Cmp5 => &self.r_in[5],
-            Opcode::Cmp6 => &self.r_in[6],
+    /// Handler for `LD1-6N`.
+    ///
+    /// See [`VM::helper_fit_index()`] for fields wider than an index
+    /// register.
+    fn handle_instr_load_neg_3b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        let mut value = self.helper_load_field(instr)?;
+        // The opposite sign is loaded, even if understood.
+        value.flip_sign();
+        let value = self.helper_fit_index(value)?;
+        match instr.opcode {
+            Opcode::Ld1N => self.r_in[1] = value,
+            Opcode::Ld2N => self.r_in[2] = value,
+            Opcode::Ld3N => self.r_in[3] = value,
+            Opcode::Ld4N => self.r_in[4] = value,
+            Opcode::Ld5N => self.r_in[5] = value,
+            Opcode::Ld6N => self.r_in[6] = value,
             _ => unreachable!(),
-        };
-        let padded_reg = FullWord::from_bytes([reg[0], 0, 0, 0, reg[1], reg[2]]);
-        let reg_value = padded_reg.to_i64_ranged(instr.field.to_range_inclusive()).0;
-        // Calculate and set flags.
-        self.comp =
-            if (reg_value == target_value) || (reg_value.abs() == 0 && target_value.abs() == 0) {
-                // +0 and -0 are equal.
-                CompIndicator::Equal
-            } else if reg_value > target_value {
-                CompIndicator::Greater
-            } else {
-                CompIndicator::Less
-            };
+        }
         Ok(())
     }
 
-    /// Handler for `JA` and `JX`.
-    fn handle_instr_jmp_reg_6b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+    /// Handler for `JMP` and variants.
+    fn handle_instr_jmp(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
         let target_addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
-        let reg = match instr.opcode {
-            Opcode::JA => &self.r_a,
-            Opcode::JX => &self.r_x,
-            _ => unreachable!(),
-        };
-        let reg_value = reg.to_i64().0;
-        let reg_value_sign = reg_value.signum();
-        let is_odd = reg_value & 1 != 0;
+        // Match jump conditions.
         let should_jump = match instr.field {
-            0 => reg_value_sign == -1,
-            1 => reg_value_sign == 0,
-            2 => reg_value_sign == 1,
-            3 => reg_value_sign != -1,
-            4 => reg_value_sign != 0,
-            5 => reg_value_sign != 1,
-            6 => !is_odd,
-            7 => is_odd,
-            _ => return Err(ErrorCode::InvalidField),
+            0 | 1 => true,
+            2 => self.overflow,
+            3 => !self.overflow,
+            4 => self.comp == CompIndicator::Less,
+            5 => self.comp == CompIndicator::Equal,
+            6 => self.comp == CompIndicator::Greater,
+            7 => self.comp != CompIndicator::Less,
+            8 => self.comp != CompIndicator::Equal,
+            9 => self.comp != CompIndicator::Greater,
+            10 => self.comp != CompIndicator::Unordered,
+            11 => self.comp == CompIndicator::Unordered,
+            _ => {
+                return Err(ErrorCode::InvalidFieldMode {
+                    opcode: instr.opcode,
+                    field: instr.field,
+                })
+            }
         };
+        // Clear overflow flag.
+        if instr.field == 2 || instr.field == 3 {
+            self.overflow = false;
+        }
         if should_jump {
-            self.helper_do_jump(target_addr, true);
+            self.helper_do_jump(target_addr, instr.field != 1);
         }
         Ok(())
     }
 
-    /// Handler for `J1-6`.
-    fn handle_instr_jmp_reg_3b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        let target_addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
-        let reg = match instr.opcode {
-            Opcode::J1 => &self.r_in[1],
-            Opcode::J2 => &self.r_in[2],
-            Opcode::J3 => &self.r_in[3],
-            Opcode::J4 => &self.r_in[4],
-            Opcode::J5 => &self.r_in[5],
-            Opcode::J6 => &self.r_in[6],
-            _ => unreachable!(),
-        };
+    /// Handler for `CHAR`, `NUM` and `HLT`, and various extensions.
+    fn handle_instr_special(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        // Other radixes are the `num-char-radix` entry.
+        if instr.field <= 1 && !(2..=10).contains(&self.num_char_radix) {
+            return Err(ErrorCode::Generic);
+        }
+        let radix = self.num_char_radix as i64;
+        if instr.field == 0 {
+            // NUM instruction
+            let a_content = &self.r_a[1..=5];
+            let x_content = &self.r_x[1..=5];
+            let mut result: i64 = 0;
+            // For each byte, we extract its 1st position,
+            // and push it to `result`.
+            for &byte in a_content.iter().chain(x_content) {
+                let digit = byte as i64 % 10 % radix;
+                result = result * radix + digit;
+            }
+            // Rebuild a word of 5 bytes. Knuth keeps the value modulo
+            // b^5 without setting the overflow toggle.
+            let result_word = match self.overflow_policy {
+                OverflowPolicy::WrapLow => FullWord::from_i64(result).0,
+                policy => self.helper_pack_arith_result(result, policy)?,
+            };
+            // We do not modify the sign byte.
+            self.r_a[1..=5].copy_from_slice(&result_word[1..=5]);
+            Ok(())
+        } else if instr.field == 1 {
+            // CHAR instruction
+            // Obtain original number. Digits past the tenth are dropped,
+            // the `char-high-digits` entry.
+            let mut source = self.r_a.to_i64().0.abs();
+            // Extract each digit.
+            for reg_i in (0..10).rev() {
+                if reg_i >= 5 {
+                    self.r_x[reg_i - 5 + 1] = (source % radix + 30) as u8;
+                } else {
+                    self.r_a[reg_i + 1] = (source % radix + 30) as u8;
+                }
+                source /= radix;
+            }
+            Ok(())
+        } else if instr.field == 2 {
+            // HLT instruction
+            // Making it just like NOP if we restart the
+            // machine later.
+            self.halted = true;
+            // Do not lose any buffered output.
+            self.flush_devices()
+        } else if instr.field >= 3 && instr.field <= 8 {
+            if instr.field == 3 {
+                // F32CVTF322I4B
+                let reg = &mut self.r_a;
+                let orig_value = float::from_word(reg);
+                reg.set_all([FullWord::POS, 0, 0, 0, 0, 0]);
+                reg.set_sign(orig_value.is_sign_negative());
+                if !orig_value.is_finite()
+                    || orig_value > i32::MAX as f32
+                    || orig_value < i32::MIN as f32
+                {
+                    self.overflow = true
+                }
+                let result = float::to_magnitude_saturating(orig_value, u32::MAX);
+                reg[2..=5].copy_from_slice(&result.to_be_bytes());
+                Ok(())
+            } else if instr.field == 4 {
+                // F32CVTF322I2B
+                let reg = &mut self.r_a;
+                let orig_value = float::from_word(reg);
+                reg.set_all([FullWord::POS, 0, 0, 0, 0, 0]);
+                reg.set_sign(orig_value.is_sign_negative());
+                if !orig_value.is_finite()
+                    || orig_value > i16::MAX as f32
+                    || orig_value < i16::MIN as f32
+                {
+                    self.overflow = true
+                }
+                let result = float::to_magnitude_saturating(orig_value, u16::MAX as u32) as u16;
+                reg[4..=5].copy_from_slice(&result.to_be_bytes());
+                Ok(())
+            } else if instr.field == 5 {
+                // F32CVTF322I1B
+                let reg = &mut self.r_a;
+                let orig_value = float::from_word(reg);
+                reg.set_all([FullWord::POS, 0, 0, 0, 0, 0]);
+                reg.set_sign(orig_value.is_sign_negative());
+                if !orig_value.is_finite()
+                    || orig_value > i8::MAX as f32
+                    || orig_value < i8::MIN as f32
+                {
+                    self.overflow = true
+                }
+                let result = float::to_magnitude_saturating(orig_value, u8::MAX as u32) as u8;
+                reg[5..=5].copy_from_slice(&result.to_be_bytes());
+                Ok(())
+            } else {
+                let reg = &mut self.r_a;
+                let new_value = match instr.field {
+                    // F32CVTI4B2F32
+                    6 => Ok(u32::from_be_bytes([reg[2], reg[3], reg[4], reg[5]]) as f32),
+                    // F32CVTI2B2F32
+                    7 => Ok(u16::from_be_bytes([reg[4], reg[5]]) as f32),
+                    // F32CVTI1B2F32
+                    8 => Ok(u8::from_be_bytes([reg[5]]) as f32),
+                    _ => unreachable!(),
+                }?;
+                *reg = float::to_word(new_value);
+                Ok(())
+            }
+        } else if instr.field == 15 {
+            // IDCAP
+            // A machine without extensions does not know this either.
+            let extensions = self.extensions.intersection(ExtensionSet::available());
+            if extensions.is_empty() {
+                return Err(ErrorCode::IllegalInstruction);
+            }
+            self.r_a = FullWord::from_i64(extensions.bits() as i64).0;
+            self.r_x = FullWord::from_i64(BYTE_BITS as i64).0;
+            Ok(())
+        } else if instr.field >= 9 && instr.field <= 14 {
+            if instr.field == 9 {
+                // NOT
+                let reg = &mut self.r_a;
+                reg.flip_sign();
+                for i in 1..=5 {
+                    reg[i] = !reg[i];
+                }
+                Ok(())
+            } else if instr.field == 13 || instr.field == 14 {
+                let magnitude = self.r_a.to_i64().0.unsigned_abs();
+                let count = if instr.field == 13 {
+                    // POPCNT
+                    magnitude.count_ones()
+                } else {
+                    // CLZ
+                    magnitude.leading_zeros() - (64 - 5 * BYTE_BITS)
+                };
+                self.r_a = FullWord::from_i64(count as i64).0;
+                Ok(())
+            } else {
+                let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
+                let mem_cell = self.helper_load(addr)?;
+                let reg = &mut self.r_a;
+                let map_fn = match instr.field {
+                    // AND
+                    10 => |a: u8, b: u8| a & b,
+                    // OR
+                    11 => |a: u8, b: u8| a | b,
+                    // XOR
+                    12 => |a: u8, b: u8| a ^ b,
+                    _ => unreachable!(),
+                };
+                for (r, m) in reg[..].iter_mut().zip(mem_cell[..].iter()) {
+                    *r = map_fn(*r, *m);
+                }
+                Ok(())
+            }
+        } else {
+            Err(ErrorCode::InvalidFieldMode {
+                opcode: instr.opcode,
+                field: instr.field,
+            })
+        }
+    }
+
+    /// Handler for `STZ`.
+    fn handle_instr_store_zero(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        self.helper_store_field(instr, FullWord::new())
+    }
+
+    /// Handler for `MOVE`.
+    fn handle_instr_move(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        // Obtain from address.
+        let from_addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
+        // Obtain to address from rI1, which fits whatever the byte
+        // size once it is known not to be negative.
+        let to_addr =
+            u16::try_from(self.r_in[1].to_i64().0).map_err(|_| ErrorCode::InvalidAddress)?;
+        let num_words = instr.field;
+        let new_r_i1_val = self.r_in[1].to_i64().0 + num_words as i64;
+        let new_r_i1 = self.helper_pack_index_result(new_r_i1_val)?;
+        // Move each word. Running past the end of memory is the
+        // `move-past-end` entry.
+        for i in 0..num_words as u16 {
+            let from = from_addr.checked_add(i).ok_or(ErrorCode::InvalidAddress)?;
+            let to = to_addr.checked_add(i).ok_or(ErrorCode::InvalidAddress)?;
+            let orig_mem = self.helper_load(from)?;
+            self.helper_store(to, orig_mem)?;
+        }
+        self.r_in[1][..].copy_from_slice(&new_r_i1[..]);
+        Ok(())
+    }
+
+    /// Handler for `STA` and `STX`.
+    fn handle_instr_store_6b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        let reg = match instr.opcode {
+            Opcode::StA => self.r_a,
+            Opcode::StX => self.r_x,
+            _ => unreachable!(),
+        };
+        self.helper_store_field(instr, reg)
+    }
+
+    /// Handler for `ST1-6`.
+    fn handle_instr_store_3b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        let reg = match instr.opcode {
+            Opcode::St1 => &self.r_in[1],
+            Opcode::St2 => &self.r_in[2],
+            Opcode::St3 => &self.r_in[3],
+            Opcode::St4 => &self.r_in[4],
+            Opcode::St5 => &self.r_in[5],
+            Opcode::St6 => &self.r_in[6],
+            _ => unreachable!(),
+        };
+        self.helper_store_field(instr, reg.to_full_word())
+    }
+
+    /// Handler for `STJ`.
+    fn handle_instr_store_j(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        if instr.field % 8 > 2 {
+            return Err(ErrorCode::InvalidFieldRange { field: instr.field });
+        }
+        let reg = &self.r_j;
+        self.helper_store_field(instr, reg.to_full_word())
+    }
+
+    /// Handler for `INCA`, `DECA`, `ENTA`, `ENNA`, `INCX`,
+    /// `DECX`, `ENTX` and `ENNX`.
+    fn handle_instr_modify_6b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
+
+        if instr.field == 0 || instr.field == 1 {
+            // INCx and DECx
+            // Add or subtract one.
+            let addr = addr as i64;
+            let offset = if instr.field == 0 { addr } else { -addr };
+            let value = match instr.opcode {
+                Opcode::ModifyA => self.r_a.to_i64().0,
+                Opcode::ModifyX => self.r_x.to_i64().0,
+                _ => unreachable!(),
+            };
+            // Convert back modified value.
+            let new_word = self.helper_pack_arith_result(value + offset, self.overflow_policy)?;
+            match instr.opcode {
+                Opcode::ModifyA => self.r_a = new_word,
+                Opcode::ModifyX => self.r_x = new_word,
+                _ => unreachable!(),
+            }
+            Ok(())
+        } else if instr.field == 2 || instr.field == 3 {
+            let reg = match instr.opcode {
+                Opcode::ModifyA => &mut self.r_a,
+                Opcode::ModifyX => &mut self.r_x,
+                _ => unreachable!(),
+            };
+            // ENTx and ENNx
+            let new_word = FullWord::from_i64(addr as i64).0;
+            // Copy new word into reg.
+            reg.clone_from(&new_word);
+            if instr.field == 3 {
+                reg.flip_sign();
+            }
+            Ok(())
+        } else {
+            Err(ErrorCode::InvalidFieldMode {
+                opcode: instr.opcode,
+                field: instr.field,
+            })
+        }
+    }
+
+    /// Handler for `INC1-6`, `DEC1-6`, `ENT1-6`, `ENN1-6`.
+    fn handle_instr_modify_3b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        if !(0..=6).contains(&instr.index) {
+            return Err(ErrorCode::InvalidIndex);
+        }
+        // Indexed in full width, so that a wide index is not truncated.
+        let addr = self.r_in[instr.index as usize].to_i64().0 + instr.addr as i64;
+        let strict_index = self.strict_index;
+        let reg_index = match instr.opcode {
+            Opcode::Modify1 => 1,
+            Opcode::Modify2 => 2,
+            Opcode::Modify3 => 3,
+            Opcode::Modify4 => 4,
+            Opcode::Modify5 => 5,
+            Opcode::Modify6 => 6,
+            _ => unreachable!(),
+        };
+
+        if instr.field == 0 || instr.field == 1 {
+            // INCx and DECx
+            // Add or subtract one.
+            let offset = if instr.field == 0 { addr } else { -addr };
+            let value = self.r_in[reg_index].to_i64().0 + offset;
+            // Convert back modified value.
+            self.r_in[reg_index] = self.helper_pack_index_result(value)?;
+            Ok(())
+        } else if instr.field == 2 || instr.field == 3 {
+            let reg = &mut self.r_in[reg_index];
+            // ENTx and ENNx
+            let (new_word, overflow) = HalfWord::from_i64(addr);
+            if overflow && strict_index {
+                return Err(ErrorCode::IndexRangeViolation);
+            }
+            // Copy new word into reg.
+            reg.clone_from(&new_word);
+            if instr.field == 3 {
+                reg.flip_sign();
+            }
+            if overflow {
+                self.overflow = true;
+            }
+            Ok(())
+        } else {
+            Err(ErrorCode::InvalidFieldMode {
+                opcode: instr.opcode,
+                field: instr.field,
+            })
+        }
+    }
+
+    /// Handler for `ADD` and `SUB`.
+    /// `F32ADD` and `F32SUB` are passed through if enabled.
+    fn handle_instr_add_sub(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        if instr.field != 7 {
+            self.helper_check_field_range(instr.field)?;
+        }
+        // Obtain V from memory.
+        let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
+        let target_mem = self.helper_load(addr)?;
+
+        if instr.field == 7 {
+            // F32ADD, F32SUB
+            let target_value = float::from_word(&target_mem);
+            let orig_value = float::from_word(&self.r_a);
+            let new_value = match instr.opcode {
+                Opcode::Add => orig_value + target_value,
+                Opcode::Sub => orig_value - target_value,
+                _ => unreachable!(),
+            };
+            self.r_a = float::to_word(new_value);
+            if !new_value.is_finite() {
+                self.overflow = true;
+            }
+        } else {
+            let orig_value = self.r_a.to_i64().0;
+            let target_value = target_mem.to_i64_ranged(instr.field.to_range_inclusive()).0;
+            // Calculate and pack new value.
+            let new_value = match instr.opcode {
+                Opcode::Add => orig_value + target_value,
+                Opcode::Sub => orig_value - target_value,
+                _ => unreachable!(),
+            };
+            let new_word = self.helper_pack_arith_result(new_value, self.overflow_policy)?;
+            // Set new value.
+            self.r_a.clone_from(&new_word);
+        }
+
+        Ok(())
+    }
+
+    /// Handler for `MUL` and `F32MUL`.
+    fn handle_instr_mul(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        if instr.field != 7 {
+            self.helper_check_field_range(instr.field)?;
+        }
+        // Obtain V from memory.
+        let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
+        let target_mem = self.helper_load(addr)?;
+        if instr.field == 7 {
+            // F32MUL
+            let target_value = float::from_word(&target_mem);
+            let orig_value = float::from_word(&self.r_a);
+            let new_value = orig_value * target_value;
+            self.r_a = float::to_word(new_value);
+            if !new_value.is_finite() {
+                self.overflow = true;
+            }
+        } else {
+            let orig_value = self.r_a.to_i64().0;
+            let target_value = target_mem.to_i64_ranged(instr.field.to_range_inclusive()).0;
+            // Copy value into registers.
+            let new_val = orig_value as i128 * target_value as i128;
+            let magnitude = new_val.unsigned_abs();
+            let (new_a, overflow) = FullWord::from_i64((magnitude >> (5 * BYTE_BITS)) as i64);
+            let (new_x, _) = FullWord::from_i64((magnitude & ((1 << (5 * BYTE_BITS)) - 1)) as i64);
+            self.r_a[1..=5].copy_from_slice(&new_a[1..=5]);
+            self.r_x[1..=5].copy_from_slice(&new_x[1..=5]);
+            // Treat sign.
+            self.r_a.set_sign(new_val < 0);
+            self.r_x.set_sign(new_val < 0);
+            if overflow {
+                self.overflow = overflow;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handler for `DIV` and `F32DIV`.
+    fn handle_instr_div(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        if instr.field != 7 {
+            self.helper_check_field_range(instr.field)?;
+        }
+        let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
+        let target_mem = self.helper_load(addr)?;
+        if instr.field == 7 {
+            // F32DIV
+            let target_value = float::from_word(&target_mem);
+            let orig_value = float::from_word(&self.r_a);
+            let new_value = orig_value / target_value;
+            self.r_a = float::to_word(new_value);
+            if !new_value.is_finite() {
+                self.overflow = true;
+            }
+        } else {
+            let target_value = target_mem.to_i64_ranged(instr.field.to_range_inclusive()).0 as i128;
+            let high = self.r_a.to_i64().0.unsigned_abs() as i128;
+            let low = self.r_x.to_i64().0.unsigned_abs() as i128;
+            let orig_value = ((high << (5 * BYTE_BITS)) | low) * self.r_a.get_sign() as i128;
+            // Calculate results. A zero divisor is the `div-by-zero` entry
+            // and a quotient too large the `div-quotient-overflow` entry.
+            let quotient: i64 = orig_value
+                .checked_div(target_value)
+                .unwrap_or_else(|| {
+                    self.overflow = true;
+                    0
+                })
+                .abs()
+                .try_into()
+                .map_err(|_| {
+                    self.overflow = true;
+                })
+                .unwrap_or(0);
+            let remainder: i64 = orig_value
+                .checked_rem(target_value)
+                .unwrap_or_else(|| {
+                    self.overflow = true;
+                    0
+                })
+                .abs()
+                .try_into()
+                .map_err(|_| {
+                    self.overflow = true;
+                })
+                .unwrap_or(0);
+            // Copy results into registers.
+            let (new_a, overflow_a) = FullWord::from_i64(quotient);
+            let (new_x, overflow_x) = FullWord::from_i64(remainder);
+            self.r_x.set_sign(self.r_a.is_negative());
+            self.r_a
+                .set_sign(orig_value.signum() != target_value.signum());
+            self.r_a[1..=5].copy_from_slice(&new_a[1..=5]);
+            self.r_x[1..=5].copy_from_slice(&new_x[1..=5]);
+            if overflow_a || overflow_x {
+                self.overflow = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handler for `CMPA` and `CMPX`, `F32CMPA` and `F32CMPX`.
+    fn handle_instr_cmp_6b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        if instr.field != 7 {
+            self.helper_check_field_range(instr.field)?;
+        }
+        // Obtain CONTENT(M).
+        let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
+        let target_mem = self.helper_load(addr)?;
+        let reg = match instr.opcode {
+            Opcode::CmpA => &self.r_a,
+            Opcode::CmpX => &self.r_x,
+            _ => unreachable!(),
+        };
+        // Field 7 selects F32CMPA and F32CMPX.
+        self.comp = Self::helper_compare(reg, &target_mem, instr.field);
+        Ok(())
+    }
+
+    /// Handler for `CMP1-6`.
+    fn handle_instr_cmp_3b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        self.helper_check_field_range(instr.field)?;
+        // Obtain CONTENT(M).
+        let addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
+        let target_mem = self.helper_load(addr)?;
+        let reg = match instr.opcode {
+            Opcode::Cmp1 => &self.r_in[1],
+            Opcode::Cmp2 => &self.r_in[2],
+            Opcode::Cmp3 => &self.r_in[3],
+            Opcode::Cmp4 => &self.r_in[4],
+            Opcode::Cmp5 => &self.r_in[5],
+            Opcode::Cmp6 => &self.r_in[6],
+            _ => unreachable!(),
+        };
+        self.comp = Self::helper_compare(&reg.to_full_word(), &target_mem, instr.field);
+        Ok(())
+    }
+
+    /// Handler for `JA` and `JX`.
+    fn handle_instr_jmp_reg_6b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        let target_addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
+        let reg = match instr.opcode {
+            Opcode::JA => &self.r_a,
+            Opcode::JX => &self.r_x,
+            _ => unreachable!(),
+        };
+        let reg_value = reg.to_i64().0;
+        let reg_value_sign = reg_value.signum();
+        let is_odd = reg_value & 1 != 0;
+        let should_jump = match instr.field {
+            0 => reg_value_sign == -1,
+            1 => reg_value_sign == 0,
+            2 => reg_value_sign == 1,
+            3 => reg_value_sign != -1,
+            4 => reg_value_sign != 0,
+            5 => reg_value_sign != 1,
+            6 => !is_odd,
+            7 => is_odd,
+            _ => {
+                return Err(ErrorCode::InvalidFieldMode {
+                    opcode: instr.opcode,
+                    field: instr.field,
+                })
+            }
+        };
+        if should_jump {
+            self.helper_do_jump(target_addr, true);
+        }
+        Ok(())
+    }
+
+    /// Handler for `J1-6`.
+    fn handle_instr_jmp_reg_3b(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        let target_addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
+        let reg = match instr.opcode {
+            Opcode::J1 => &self.r_in[1],
+            Opcode::J2 => &self.r_in[2],
+            Opcode::J3 => &self.r_in[3],
+            Opcode::J4 => &self.r_in[4],
+            Opcode::J5 => &self.r_in[5],
+            Opcode::J6 => &self.r_in[6],
+            _ => unreachable!(),
+        };
         let reg_value_sign = reg.to_i64().0.signum();
         let should_jump = match instr.field {
             0 => reg_value_sign == -1,
@@ -1093,187 +2520,2005 @@ impl VM {
             3 => reg_value_sign != -1,
             4 => reg_value_sign != 0,
             5 => reg_value_sign != 1,
-            _ => return Err(ErrorCode::InvalidField),
+            _ => {
+                return Err(ErrorCode::InvalidFieldMode {
+                    opcode: instr.opcode,
+                    field: instr.field,
+                })
+            }
+        };
+        if should_jump {
+            self.helper_do_jump(target_addr, true);
+        }
+        Ok(())
+    }
+
+    /// Handler for `SLA`, `SRA`, `SLAX`, `SRAX`, `SLC`, `SRC`, `SLB` and `SRB`.
+    fn handle_instr_shift(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        let count = self.helper_get_eff_addr(instr.addr, instr.index)?;
+        if instr.field == 0 || instr.field == 1 {
+            // SLA and SRA.
+            // Spread the bytes of the magnitude. The sign is kept, the
+            // `shift-sign` entry.
+            let a = &self.r_a;
+            let orig_value = u64::from_be_bytes([0, 0, 0, a[1], a[2], a[3], a[4], a[5]]);
+            // Shift the value in bits (count * 8, count is in bytes).
+            // Bytes shifted past the register are lost.
+            let bits = count as u32 * 8;
+            let shifted_value = match instr.field {
+                0 => orig_value.checked_shl(bits).unwrap_or(0),
+                1 => orig_value.checked_shr(bits).unwrap_or(0),
+                _ => unreachable!(),
+            };
+            // Store back.
+            self.r_a[1..=5].copy_from_slice(&shifted_value.to_be_bytes()[3..=7]);
+        } else if (instr.field == 2 || instr.field == 3) || (instr.field == 6 || instr.field == 7) {
+            // SLAX, SRAX, SLB and SRB.
+            // Pack both magnitudes, `BYTE_BITS` bits per byte, so that
+            // SLB and SRB never leave a byte out of range.
+            let byte_mask = (1u128 << BYTE_BITS) - 1;
+            let orig_value = self.r_a[1..=5]
+                .iter()
+                .chain(&self.r_x[1..=5])
+                .fold(0, |acc, &b| (acc << BYTE_BITS) | (b as u128 & byte_mask));
+            // Shift. Bits shifted past the registers are lost.
+            let bits = match instr.field {
+                2 | 3 => count as u32 * BYTE_BITS,
+                _ => count as u32,
+            };
+            let mut shifted_value = match instr.field {
+                2 | 6 => orig_value.checked_shl(bits).unwrap_or(0),
+                3 | 7 => orig_value.checked_shr(bits).unwrap_or(0),
+                _ => unreachable!(),
+            };
+            // Store back, least significant byte first.
+            for reg_i in (1..=10).rev() {
+                let byte = (shifted_value & byte_mask) as u8;
+                if reg_i > 5 {
+                    self.r_x[reg_i - 5] = byte;
+                } else {
+                    self.r_a[reg_i] = byte;
+                }
+                shifted_value >>= BYTE_BITS;
+            }
+        } else if instr.field == 4 || instr.field == 5 {
+            // SLC and SRC.
+            // Spread out bytes.
+            let orig_bytes = [
+                self.r_a[1],
+                self.r_a[2],
+                self.r_a[3],
+                self.r_a[4],
+                self.r_a[5],
+                self.r_x[1],
+                self.r_x[2],
+                self.r_x[3],
+                self.r_x[4],
+                self.r_x[5],
+            ];
+            // Zero the registers.
+            self.r_a[1..=5].copy_from_slice(&[0; 5]);
+            self.r_x[1..=5].copy_from_slice(&[0; 5]);
+            // Create cyclic iterator.
+            let mut orig_bytes_iter = orig_bytes.iter().cycle();
+            // Get shift count.
+            let offset = if instr.field == 4 {
+                // SLC.
+                count % 10
+            } else {
+                // SRC.
+                10 - count % 10
+            };
+            for _ in 0..offset {
+                // Advance the iterator by `offset` steps,
+                // to simulate shifting.
+                // The iterator is infinite so we don't worry about
+                // panics.
+                orig_bytes_iter.next().or_else(|| unreachable!());
+            }
+            // Write back.
+            for (reg_i, &digit) in (0..10).zip(orig_bytes_iter) {
+                if reg_i >= 5 {
+                    self.r_x[reg_i - 5 + 1] = digit;
+                } else {
+                    self.r_a[reg_i + 1] = digit;
+                }
+            }
+        } else {
+            return Err(ErrorCode::InvalidFieldMode {
+                opcode: instr.opcode,
+                field: instr.field,
+            });
+        }
+        Ok(())
+    }
+
+    /// Handler for `JBUS` and `JRED`.
+    fn handle_instr_jbus_jred(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        // Get device ID.
+        let dev_id: usize = instr.field as usize;
+        // Get device reference.
+        let dev = self.helper_get_io_device(dev_id)?;
+        // Transfers keep a device busy too.
+        let transferring = self.cycles < self.busy_until[dev_id];
+        // Call appropriate callbacks.
+        let should_jump = match instr.opcode {
+            Opcode::Jbus => dev.is_busy().map_err(|_| ErrorCode::IOError)? || transferring,
+            Opcode::Jred => dev.is_ready().map_err(|_| ErrorCode::IOError)? && !transferring,
+            _ => unreachable!(),
         };
         if should_jump {
-            self.helper_do_jump(target_addr, true);
+            // Do jump.
+            let jump_addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
+            self.helper_do_jump(jump_addr, true);
+        }
+        Ok(())
+    }
+
+    /// Handler for `IOC`.
+    fn handle_instr_ioc(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        // Get command.
+        let command = self.helper_get_eff_addr_unchecked(instr.addr, instr.index);
+        // Get device ID.
+        let dev_id: usize = instr.field as usize;
+        // The `ioc-barrier` entry.
+        if instr.field == IOC_BARRIER_UNIT {
+            return self.helper_drain_devices();
+        }
+        let r_x = self.r_x.to_i64().0;
+        // Get device reference.
+        self.helper_get_io_device(dev_id)?;
+        self.helper_wait_device(dev_id);
+        self.device_stats[dev_id].controls += 1;
+        let dev = self.helper_get_io_device_mut(dev_id)?;
+        // Disks position to block rX on `IOC 0`. Devices that cannot
+        // seek get the command as any other.
+        if DISK_UNITS.contains(&dev_id) && command == 0 {
+            if let Ok(block) = u64::try_from(r_x) {
+                if dev.seek(block).is_ok() {
+                    return Ok(());
+                }
+            }
+        }
+        // Call appropriate callbacks.
+        dev.control(command).map_err(|_| ErrorCode::IOError)?;
+        Ok(())
+    }
+
+    /// Handler for `IN` and `OUT`.
+    fn handle_instr_in_out(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
+        let addr_start = self.helper_get_eff_addr(instr.addr, instr.index)?;
+        // Get device ID.
+        let dev_id: usize = instr.field as usize;
+        let dev_blk_size = self.helper_check_block_size(dev_id)?;
+        // Check every address in the block.
+        let addrs = (0..dev_blk_size)
+            .map(|i| {
+                let addr = u16::try_from(i)
+                    .ok()
+                    .and_then(|i| addr_start.checked_add(i))
+                    .ok_or(ErrorCode::InvalidAddress)?;
+                self.helper_check_addr(addr)?;
+                if instr.opcode == Opcode::In {
+                    // Fail before the device gives its data away.
+                    self.helper_check_writable(addr)?;
+                }
+                Ok(addr)
+            })
+            .collect::<Result<Vec<u16>, ErrorCode>>()?;
+        self.helper_wait_device(dev_id);
+        // Call appropriate callbacks.
+        match instr.opcode {
+            Opcode::In => {
+                let mut words = vec![FullWord::new(); dev_blk_size];
+                let non_blocking = self.non_blocking_io;
+                let report_eof = self.report_end_of_input;
+                let dev = self.helper_get_io_device_mut(dev_id)?;
+                // The `in-end-of-input` entry.
+                let read_error = |at_eof: bool| {
+                    if report_eof && at_eof {
+                        ErrorCode::EndOfInput
+                    } else {
+                        ErrorCode::IOError
+                    }
+                };
+                if non_blocking {
+                    match dev.read_nonblocking(&mut words) {
+                        Ok(()) => (),
+                        Err(NonBlockingIOResult {
+                            cause: NonBlockingIOCause::WouldBlock,
+                            ..
+                        }) => return Ok(()),
+                        Err(_) => return Err(read_error(dev.at_eof())),
+                    }
+                } else if dev.read(&mut words).is_err() {
+                    return Err(read_error(dev.at_eof()));
+                }
+                for (&addr, &word) in addrs.iter().zip(&words) {
+                    self.helper_store(addr, word)?;
+                }
+                let stats = &mut self.device_stats[dev_id];
+                stats.blocks_read += 1;
+                stats.words_read += words.len() as u64;
+                self.helper_start_transfer(dev_id, words.len());
+                if self.events.is_some() {
+                    self.helper_log(Event::Io {
+                        unit: instr.field,
+                        words,
+                    });
+                }
+            }
+            Opcode::Out => {
+                // Clone words.
+                let words = addrs
+                    .into_iter()
+                    .map(|addr| self.helper_load(addr))
+                    .collect::<Result<Vec<FullWord>, ErrorCode>>()?;
+                let dev = self.helper_get_io_device_mut(dev_id)?;
+                dev.write(&words).map_err(|_| ErrorCode::IOError)?;
+                let stats = &mut self.device_stats[dev_id];
+                stats.blocks_written += 1;
+                stats.words_written += words.len() as u64;
+                self.helper_start_transfer(dev_id, words.len());
+                if self.events.is_some() {
+                    self.helper_log(Event::Io {
+                        unit: instr.field,
+                        words,
+                    });
+                }
+            }
+            _ => unreachable!(),
+        };
+        Ok(())
+    }
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run one instruction at address 0 on a fresh machine.
+    fn step(instr: Instruction, setup: impl FnOnce(&mut VM)) -> (VM, Result<(), ErrorCode>) {
+        let mut mix = VM::new();
+        mix.reset();
+        mix.mem[0] = FullWord::from(instr);
+        setup(&mut mix);
+        mix.halted = false;
+        let result = mix.step();
+        (mix, result)
+    }
+
+    fn word(value: i64) -> FullWord {
+        FullWord::from_i64(value).0
+    }
+
+    /// Check that every byte of the magnitude fits in `BYTE_BITS`.
+    fn is_valid(word: &FullWord) -> bool {
+        word[1..=5].iter().all(|&b| (b as u32) < 1 << BYTE_BITS)
+    }
+
+    /// A device whose block size is changed from outside.
+    struct ResizingDevice {
+        size: std::rc::Rc<core::cell::Cell<usize>>,
+    }
+
+    impl IODevice for ResizingDevice {
+        fn read(&mut self, buffer: &mut [FullWord]) -> Result<(), ()> {
+            buffer.fill(word(1));
+            Ok(())
+        }
+
+        fn write(&mut self, _: &[FullWord]) -> Result<(), usize> {
+            Ok(())
+        }
+
+        fn control(&mut self, _: i16) -> Result<(), ()> {
+            Ok(())
+        }
+
+        fn is_busy(&self) -> Result<bool, ()> {
+            Ok(false)
+        }
+
+        fn is_ready(&self) -> Result<bool, ()> {
+            Ok(true)
+        }
+
+        fn get_block_size(&self) -> usize {
+            self.size.get()
+        }
+    }
+
+    /// A device logging its writes and flushes.
+    struct LoggingDevice {
+        name: &'static str,
+        block_size: usize,
+        bandwidth: Option<u64>,
+        log: std::rc::Rc<core::cell::RefCell<Vec<String>>>,
+    }
+
+    impl IODevice for LoggingDevice {
+        fn read(&mut self, _: &mut [FullWord]) -> Result<(), ()> {
+            Err(())
+        }
+
+        fn write(&mut self, _: &[FullWord]) -> Result<(), usize> {
+            self.log.borrow_mut().push(format!("{} write", self.name));
+            Ok(())
+        }
+
+        fn control(&mut self, _: i16) -> Result<(), ()> {
+            Err(())
+        }
+
+        fn is_busy(&self) -> Result<bool, ()> {
+            Ok(false)
+        }
+
+        fn is_ready(&self) -> Result<bool, ()> {
+            Ok(true)
+        }
+
+        fn get_block_size(&self) -> usize {
+            self.block_size
+        }
+
+        fn flush(&mut self) -> Result<(), ()> {
+            self.log.borrow_mut().push(format!("{} flush", self.name));
+            Ok(())
+        }
+
+        fn bandwidth(&self) -> Option<u64> {
+            self.bandwidth
+        }
+    }
+
+    /// Run a program with a slow tape on unit 0 and a fast printer on
+    /// unit 18, returning the log of both.
+    fn run_tape_and_printer(program: &[Instruction]) -> (VM, Vec<String>) {
+        let log = std::rc::Rc::new(core::cell::RefCell::new(Vec::new()));
+        let mut mix = VM::new();
+        mix.reset();
+        mix.load_program(&MixProgram::from(program)).unwrap();
+        mix.attach_device(
+            0,
+            Box::new(LoggingDevice {
+                name: "tape",
+                block_size: 100,
+                bandwidth: Some(1),
+                log: log.clone(),
+            }),
+        );
+        mix.attach_device(
+            18,
+            Box::new(LoggingDevice {
+                name: "printer",
+                block_size: 24,
+                bandwidth: None,
+                log: log.clone(),
+            }),
+        );
+        mix.halted = false;
+        for _ in program {
+            mix.step().unwrap();
+        }
+        let log = log.borrow().clone();
+        (mix, log)
+    }
+
+    #[test]
+    fn units_finish_out_of_order() {
+        let (mix, log) = run_tape_and_printer(&[
+            Instruction::new(1000, 0, 0, Opcode::Out),
+            Instruction::new(2000, 18, 0, Opcode::Out),
+            Instruction::new(40, 18, 0, Opcode::Jbus),
+            Instruction::new(41, 0, 0, Opcode::Jbus),
+        ]);
+        // The blocks are handed over in instruction order, but the
+        // printer is ready while the tape started first is not.
+        assert_eq!(log, ["tape write", "printer write"]);
+        assert_eq!(mix.pc, 41);
+        assert!(mix.cycle_count() < 100);
+    }
+
+    #[test]
+    fn ioc_barrier_waits_for_all_units() {
+        let (mix, log) = run_tape_and_printer(&[
+            Instruction::new(1000, 0, 0, Opcode::Out),
+            Instruction::new(2000, 18, 0, Opcode::Out),
+            Instruction::new(0, IOC_BARRIER_UNIT, 0, Opcode::Ioc),
+            Instruction::new(41, 0, 0, Opcode::Jbus),
+            Instruction::new(2000, 18, 0, Opcode::Out),
+        ]);
+        assert_eq!(
+            log,
+            [
+                "tape write",
+                "printer write",
+                "tape flush",
+                "printer flush",
+                "printer write"
+            ]
+        );
+        assert_eq!(mix.pc, 5);
+        assert!(mix.cycle_count() >= 100);
+    }
+
+    #[test]
+    fn ioc_negative_skips_back_on_tapes() {
+        let program = [
+            Instruction::new(1000, 0, 0, Opcode::Out),
+            Instruction::new(2000, 0, 0, Opcode::Out),
+            Instruction::new(-4095, 0, 0, Opcode::Ioc),
+            Instruction::new(3000, 0, 0, Opcode::In),
+        ];
+        let mut mix = VM::new();
+        mix.reset();
+        mix.load_program(&MixProgram::from(&program[..])).unwrap();
+        mix.attach_device(0, Box::new(TapeDevice::new()));
+        mix.mem[1000] = word(1);
+        mix.mem[2000] = word(2);
+        mix.halted = false;
+        for _ in program {
+            mix.step().unwrap();
+        }
+        assert_eq!(mix.mem[3000].to_i64().0, 1);
+    }
+
+    #[test]
+    fn block_size_mismatch() {
+        let size = std::rc::Rc::new(core::cell::Cell::new(2));
+        let mut mix = VM::new();
+        mix.reset();
+        mix.mem[0] = FullWord::from(Instruction::new(100, 5, 0, Opcode::In));
+        mix.mem[1] = FullWord::from(Instruction::new(100, 5, 0, Opcode::Out));
+        mix.mem[2] = FullWord::from(Instruction::new(100, 5, 0, Opcode::In));
+        mix.attach_device(5, Box::new(ResizingDevice { size: size.clone() }));
+        mix.halted = false;
+        assert_eq!(mix.step(), Ok(()));
+        assert_eq!(mix.step(), Ok(()));
+        size.set(3);
+        assert_eq!(mix.step(), Err(ErrorCode::IOBlockSizeMismatch));
+        assert_eq!(mix.mem[102].to_i64().0, 0);
+
+        // A new device on the same unit may have another size, even
+        // at the address of the one it replaces.
+        let mut mix = VM::new();
+        mix.reset();
+        mix.mem[0] = FullWord::from(Instruction::new(100, 5, 0, Opcode::In));
+        mix.mem[1] = FullWord::from(Instruction::new(100, 5, 0, Opcode::In));
+        mix.mem[2] = FullWord::from(Instruction::new(100, 5, 0, Opcode::In));
+        mix.attach_device(5, Box::new(ResizingDevice { size: size.clone() }));
+        mix.halted = false;
+        assert_eq!(mix.step(), Ok(()));
+        drop(mix.detach_device(5));
+        let size = std::rc::Rc::new(core::cell::Cell::new(1));
+        mix.attach_device(5, Box::new(ResizingDevice { size: size.clone() }));
+        assert_eq!(mix.step(), Ok(()));
+        size.set(2);
+        assert_eq!(mix.step(), Err(ErrorCode::IOBlockSizeMismatch));
+    }
+
+    #[test]
+    fn devices_are_attached_and_detached() {
+        let mut mix = VM::new();
+        assert!(mix.attach_device(5, Box::new(TapeDevice::new())).is_none());
+        assert_eq!(mix.device(5).unwrap().name(), "tape");
+        let replaced = mix.attach_device(5, Box::new(TapeDevice::new().with_bandwidth(1)));
+        assert_eq!(replaced.unwrap().name(), "tape");
+        assert!(mix.device_mut(5).is_some());
+        assert!(mix.detach_device(5).is_some());
+        assert!(mix.detach_device(5).is_none());
+        assert!(mix.device(5).is_none());
+        assert!(mix.device(IO_UNITS).is_none());
+        assert!(mix.detach_device(IO_UNITS).is_none());
+    }
+
+    #[test]
+    fn pc_stops_past_the_end() {
+        let last = Mem::SIZE as u16 - 1;
+        let mut mix = VM::new();
+        mix.reset();
+        mix.mem[last] = FullWord::from(Instruction::new(0, 0, 0, Opcode::Nop));
+        mix.pc = last;
+        mix.halted = false;
+        assert_eq!(mix.step(), Ok(()));
+        assert_eq!(mix.pc as usize, Mem::SIZE);
+        assert_eq!(mix.step(), Err(ErrorCode::InvalidAddress));
+        assert_eq!(mix.pc as usize, Mem::SIZE);
+        assert!(mix.halted);
+
+        // A jump from the last word is fine.
+        mix.mem[last] = FullWord::from(Instruction::new(10, 0, 0, Opcode::Jmp));
+        mix.pc = last;
+        mix.restart();
+        assert_eq!(mix.step(), Ok(()));
+        assert_eq!(mix.pc, 10);
+    }
+
+    #[test]
+    fn move_targets_the_value_of_ri1() {
+        let (mix, result) = step(Instruction::new(100, 3, 0, Opcode::Move), |mix| {
+            mix.set_index(1, 1000).unwrap();
+            for i in 0..3 {
+                mix.mem[100 + i] = word(7 + i as i64);
+            }
+        });
+        assert_eq!(result, Ok(()));
+        for i in 0..3 {
+            assert_eq!(mix.mem[1000 + i].to_i64().0, 7 + i as i64);
+        }
+        assert_eq!(mix.r_in[1].to_i64().0, 1003);
+    }
+
+    #[test]
+    fn move_rejects_a_negative_ri1() {
+        let (mix, result) = step(Instruction::new(100, 3, 0, Opcode::Move), |mix| {
+            mix.set_index(1, -1000).unwrap();
+            mix.mem[100] = word(7);
+        });
+        assert_eq!(result, Err(ErrorCode::InvalidAddress));
+        assert_eq!(mix.mem[1000].to_i64().0, 0);
+        assert_eq!(mix.r_in[1].to_i64().0, -1000);
+
+        // -0 is address 0.
+        let (mix, result) = step(Instruction::new(100, 1, 0, Opcode::Move), |mix| {
+            mix.set_index(1, 0).unwrap();
+            mix.r_in[1].set_sign(true);
+            mix.mem[100] = word(7);
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.mem[0].to_i64().0, 7);
+    }
+
+    #[test]
+    fn slb_srb_keep_bytes_in_range() {
+        let (mix, result) = step(Instruction::new(1, 6, 0, Opcode::Shift), |mix| {
+            mix.r_a = word(63);
+            mix.r_x = word(1 << (5 * BYTE_BITS - 1));
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.r_a.to_i64().0, 127);
+        assert_eq!(mix.r_x.to_i64().0, 0);
+        assert!(is_valid(&mix.r_a) && is_valid(&mix.r_x));
+
+        let (mix, result) = step(Instruction::new(1, 7, 0, Opcode::Shift), |mix| {
+            mix.r_a = word(3);
+            mix.r_x = word(0);
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.r_a.to_i64().0, 1);
+        assert_eq!(mix.r_x.to_i64().0, 1 << (5 * BYTE_BITS - 1));
+        assert!(is_valid(&mix.r_a) && is_valid(&mix.r_x));
+    }
+
+    #[test]
+    fn sla_sra_keep_negative_signs() {
+        let (mix, result) = step(Instruction::new(1, 0, 0, Opcode::Shift), |mix| {
+            mix.r_a.set_all([FullWord::NEG, 0, 0, 0, 1, 2]);
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.r_a.as_array(), &[FullWord::NEG, 0, 0, 1, 2, 0]);
+
+        let (mix, result) = step(Instruction::new(2, 1, 0, Opcode::Shift), |mix| {
+            mix.r_a.set_all([FullWord::NEG, 1, 2, 3, 4, 5]);
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.r_a.as_array(), &[FullWord::NEG, 0, 0, 1, 2, 3]);
+
+        let (mix, result) = step(Instruction::new(6, 1, 0, Opcode::Shift), |mix| {
+            mix.r_a.set_all([FullWord::NEG, 1, 2, 3, 4, 5]);
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.r_a.as_array(), &[FullWord::NEG, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn slax_moves_whole_bytes() {
+        let (mix, result) = step(Instruction::new(2, 2, 0, Opcode::Shift), |mix| {
+            mix.r_a.set_all([FullWord::NEG, 0, 0, 0, 1, 2]);
+            mix.r_x.set_all([FullWord::POS, 3, 4, 5, 6, 7]);
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.r_a.as_array(), &[FullWord::NEG, 0, 1, 2, 3, 4]);
+        assert_eq!(mix.r_x.as_array(), &[FullWord::POS, 5, 6, 7, 0, 0]);
+    }
+
+    #[test]
+    fn sta_sign_only_field() {
+        for (sign, cell) in [
+            (FullWord::NEG, FullWord::POS),
+            (FullWord::POS, FullWord::NEG),
+        ] {
+            let (mix, result) = step(Instruction::new(100, 0, 0, Opcode::StA), |mix| {
+                mix.r_a.set_all([sign, 9, 8, 7, 6, 5]);
+                mix.mem[100].set_all([cell, 1, 2, 3, 4, 5]);
+            });
+            assert_eq!(result, Ok(()));
+            assert_eq!(mix.mem[100].as_array(), &[sign, 1, 2, 3, 4, 5]);
+        }
+
+        let (mix, result) = step(Instruction::new(100, 0, 0, Opcode::St1), |mix| {
+            mix.r_in[1].set_all([FullWord::NEG, 9, 8]);
+            mix.mem[100].set_all([FullWord::POS, 1, 2, 3, 4, 5]);
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.mem[100].as_array(), &[FullWord::NEG, 1, 2, 3, 4, 5]);
+    }
+
+    /// Get the cycle and the reason of each [`Event::Halt`] logged.
+    fn halts(mix: &mut VM) -> Vec<(u64, metrics::HaltReason)> {
+        mix.take_events()
+            .into_iter()
+            .filter_map(|record| match record.event {
+                Event::Halt { reason } => Some((record.cycle, reason)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn halt_event_is_stamped_when_hlt_starts() {
+        let nop = Instruction::new(0, 0, 0, Opcode::Nop);
+        let (mut mix, result) = step(nop, |mix| {
+            mix.mem[1] = FullWord::from(Instruction::new(0, 2, 0, Opcode::Special));
+            mix.enable_event_log();
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.step(), Ok(()));
+        assert!(mix.halted);
+        assert_eq!(
+            halts(&mut mix),
+            [(nop.cycles(), metrics::HaltReason::Instruction)]
+        );
+        assert!(mix.cycle_count() > nop.cycles());
+    }
+
+    #[test]
+    fn halt_event_is_stamped_when_the_failing_instruction_starts() {
+        let nop = Instruction::new(0, 0, 0, Opcode::Nop);
+        let (mut mix, result) = step(nop, |mix| {
+            mix.mem[1] = FullWord::from(Instruction::new(4000, 5, 0, Opcode::LdA));
+            mix.enable_event_log();
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.step(), Err(ErrorCode::InvalidAddress));
+        let reason = metrics::HaltReason::Error(ErrorCode::InvalidAddress);
+        assert_eq!(halts(&mut mix), [(nop.cycles(), reason)]);
+    }
+
+    #[test]
+    fn step_limit_logs_a_halt() {
+        let (mut mix, result) = step(Instruction::new(0, 0, 0, Opcode::Nop), |mix| {
+            mix.set_step_limit(1);
+            mix.enable_event_log();
+        });
+        assert_eq!(result, Ok(()));
+        let cycles = mix.cycle_count();
+        assert_eq!(mix.step(), Err(ErrorCode::StepLimitExceeded));
+        assert!(mix.halted);
+        let reason = metrics::HaltReason::Error(ErrorCode::StepLimitExceeded);
+        assert_eq!(halts(&mut mix), [(cycles, reason)]);
+    }
+
+    #[test]
+    fn fetch_failure_logs_a_halt() {
+        let mut mix = VM::new();
+        mix.reset();
+        mix.enable_event_log();
+        mix.pc = Mem::SIZE as u16;
+        mix.halted = false;
+        assert_eq!(mix.step(), Err(ErrorCode::InvalidAddress));
+        assert!(mix.halted);
+        let reason = metrics::HaltReason::Error(ErrorCode::InvalidAddress);
+        assert_eq!(halts(&mut mix), [(0, reason)]);
+    }
+
+    /// Get a register of rA, rX and rI1-6, as its sign and magnitude.
+    fn reg(mix: &VM, id: RegisterId) -> (bool, i64) {
+        let (negative, value) = match id {
+            RegisterId::A => (mix.r_a.is_negative(), mix.r_a.to_i64().0),
+            RegisterId::X => (mix.r_x.is_negative(), mix.r_x.to_i64().0),
+            _ => {
+                let i = id as usize;
+                (mix.r_in[i].is_negative(), mix.r_in[i].to_i64().0)
+            }
+        };
+        (negative, value.abs())
+    }
+
+    fn set_reg(mix: &mut VM, id: RegisterId, value: i64) {
+        match id {
+            RegisterId::A => mix.r_a = word(value),
+            RegisterId::X => mix.r_x = word(value),
+            _ => mix.r_in[id as usize] = HalfWord::from_i64(value).0,
+        }
+    }
+
+    /// Operations going one past the largest magnitude of a register,
+    /// with the register, its starting value and the operand at 100.
+    fn edges() -> Vec<(&'static str, Instruction, RegisterId, i64, i64)> {
+        use Opcode::{Add, Modify1, Modify6, ModifyA, ModifyX, Sub};
+        use RegisterId::{A, I1, I6, X};
+        let op = |addr, field, opcode| Instruction::new(addr, field, 0, opcode);
+        let w = (1 << (5 * BYTE_BITS)) - 1;
+        let i = (1 << (2 * BYTE_BITS)) - 1;
+        vec![
+            ("ADD+", op(100, 5, Add), A, w, 1),
+            ("ADD-", op(100, 5, Add), A, -w, -1),
+            ("SUB+", op(100, 5, Sub), A, w, -1),
+            ("SUB-", op(100, 5, Sub), A, -w, 1),
+            ("INCA", op(1, 0, ModifyA), A, w, 0),
+            ("DECA", op(1, 1, ModifyA), A, -w, 0),
+            ("INCX", op(1, 0, ModifyX), X, w, 0),
+            ("DECX", op(1, 1, ModifyX), X, -w, 0),
+            ("INC1", op(1, 0, Modify1), I1, i, 0),
+            ("DEC1", op(1, 1, Modify1), I1, -i, 0),
+            ("INC6", op(1, 0, Modify6), I6, i, 0),
+            ("DEC6", op(1, 1, Modify6), I6, -i, 0),
+        ]
+    }
+
+    #[test]
+    fn overflow_policies_at_the_edges() {
+        for (name, instr, id, start, operand) in edges() {
+            let negative = start < 0;
+            for policy in [
+                OverflowPolicy::WrapLow,
+                OverflowPolicy::Saturate,
+                OverflowPolicy::Trap,
+            ] {
+                let (mix, result) = step(instr, |mix| {
+                    mix.overflow_policy = policy;
+                    mix.mem[100] = word(operand);
+                    set_reg(mix, id, start);
+                });
+                let (expected, magnitude, overflow) = match policy {
+                    OverflowPolicy::WrapLow => (Ok(()), 0, true),
+                    OverflowPolicy::Saturate => (Ok(()), start.abs(), true),
+                    OverflowPolicy::Trap => {
+                        (Err(ErrorCode::ArithmeticOverflow), start.abs(), false)
+                    }
+                };
+                assert_eq!(result, expected, "{} {:?}", name, policy);
+                assert_eq!(
+                    reg(&mix, id),
+                    (negative, magnitude),
+                    "{} {:?}",
+                    name,
+                    policy
+                );
+                assert_eq!(mix.overflow, overflow, "{} {:?}", name, policy);
+            }
+        }
+    }
+
+    #[test]
+    fn results_inside_the_edges_do_not_overflow() {
+        for (name, instr, id, start, operand) in edges() {
+            let inside = start - start.signum();
+            for policy in [
+                OverflowPolicy::WrapLow,
+                OverflowPolicy::Saturate,
+                OverflowPolicy::Trap,
+            ] {
+                let (mix, result) = step(instr, |mix| {
+                    mix.overflow_policy = policy;
+                    mix.mem[100] = word(operand);
+                    set_reg(mix, id, inside);
+                });
+                assert_eq!(result, Ok(()), "{} {:?}", name, policy);
+                assert_eq!(
+                    reg(&mix, id),
+                    (start < 0, start.abs()),
+                    "{} {:?}",
+                    name,
+                    policy
+                );
+                assert!(!mix.overflow, "{} {:?}", name, policy);
+            }
+        }
+    }
+
+    #[test]
+    fn index_overflow_mode_overrides_the_policy() {
+        let inc1 = Instruction::new(1, 0, 0, Opcode::Modify1);
+        let index_max = (1 << (2 * BYTE_BITS)) - 1;
+        let table = [
+            (IndexOverflowMode::Wrap, Ok(()), 0),
+            (IndexOverflowMode::Saturate, Ok(()), index_max),
+            (
+                IndexOverflowMode::Trap,
+                Err(ErrorCode::IndexOverflow),
+                index_max,
+            ),
+        ];
+        for (mode, expected, magnitude) in table {
+            let (mix, result) = step(inc1, |mix| {
+                mix.overflow_policy = OverflowPolicy::Trap;
+                mix.index_overflow_mode = mode;
+                mix.r_in[1] = HalfWord::from_i64(index_max).0;
+            });
+            assert_eq!(result, expected, "{:?}", mode);
+            assert_eq!(reg(&mix, RegisterId::I1), (false, magnitude), "{:?}", mode);
+        }
+        let (_, result) = step(inc1, |mix| {
+            mix.strict_index = true;
+            mix.r_in[1] = HalfWord::from_i64(index_max).0;
+        });
+        assert_eq!(result, Err(ErrorCode::IndexRangeViolation));
+    }
+
+    #[test]
+    fn move_follows_the_overflow_policy() {
+        let index_max = (1 << (2 * BYTE_BITS)) - 1;
+        let table = [
+            (OverflowPolicy::WrapLow, ErrorCode::InvalidAddress),
+            (OverflowPolicy::Saturate, ErrorCode::InvalidAddress),
+            (OverflowPolicy::Trap, ErrorCode::ArithmeticOverflow),
+        ];
+        for (policy, error) in table {
+            // The target is out of memory, so only a trap stops the
+            // move before it starts.
+            let (mix, result) = step(Instruction::new(100, 1, 0, Opcode::Move), |mix| {
+                mix.overflow_policy = policy;
+                mix.r_in[1] = HalfWord::from_i64(index_max).0;
+            });
+            assert_eq!(result, Err(error), "{:?}", policy);
+            assert_eq!(mix.overflow, policy != OverflowPolicy::Trap, "{:?}", policy);
+            assert_eq!(mix.r_in[1].to_i64().0, index_max, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn num_wraps_silently() {
+        // Ten nines overflow only with 6-bit bytes.
+        let (mix, result) = step(Instruction::new(0, 0, 0, Opcode::Special), |mix| {
+            mix.r_a.set_all([FullWord::POS, 39, 39, 39, 39, 39]);
+            mix.r_x.set_all([FullWord::POS, 39, 39, 39, 39, 39]);
+        });
+        assert_eq!(result, Ok(()));
+        assert!(!mix.overflow);
+        let max = (1i64 << (5 * BYTE_BITS)) - 1;
+        assert_eq!(mix.r_a.to_i64().0, 9_999_999_999 % (max + 1));
+
+        let (mix, result) = step(Instruction::new(0, 0, 0, Opcode::Special), |mix| {
+            mix.overflow_policy = OverflowPolicy::Trap;
+            mix.r_a.set_all([FullWord::POS, 39, 39, 39, 39, 39]);
+            mix.r_x.set_all([FullWord::POS, 39, 39, 39, 39, 39]);
+        });
+        if BYTE_BITS == 6 {
+            assert_eq!(result, Err(ErrorCode::ArithmeticOverflow));
+        } else {
+            assert_eq!(result, Ok(()));
+            assert_eq!(mix.r_a.to_i64().0, 9_999_999_999);
+        }
+    }
+
+    /// A device serving the same word on every load.
+    struct RomDevice(FullWord);
+
+    impl MmioDevice for RomDevice {
+        fn load(&mut self, _: u16) -> FullWord {
+            self.0
+        }
+
+        fn store(&mut self, _: u16, _: FullWord) {}
+    }
+
+    #[test]
+    fn mmio_counter_counts_each_store_once() {
+        let program = [
+            Instruction::new(100, 5, 0, Opcode::LdA),
+            Instruction::new(2000, 5, 0, Opcode::StA),
+            Instruction::new(2000, 5, 0, Opcode::StA),
+            Instruction::new(2000, 37, 0, Opcode::StA),
+            Instruction::new(2000, 5, 0, Opcode::LdA),
+            Instruction::new(0, 2, 0, Opcode::Special),
+        ];
+        let mut mix = VM::new();
+        mix.reset();
+        let counter = Box::new(CounterMmioDevice { count: 10 });
+        mix.map_device(2000..2001, counter).unwrap();
+        mix.load_program(&MixProgram::from(&program[..])).unwrap();
+        mix.mem[100] = word(1);
+        mix.halted = false;
+        mix.run_until_halt().unwrap();
+        assert_eq!(mix.r_a.to_i64().0, 13);
+        assert_eq!(mix.mem[2000].to_i64().0, 0);
+    }
+
+    #[test]
+    fn move_across_a_mapped_boundary() {
+        let counter = || Box::new(CounterMmioDevice { count: 7 });
+        let (mix, result) = step(Instruction::new(1998, 3, 0, Opcode::Move), |mix| {
+            mix.map_device(2000..2001, counter()).unwrap();
+            mix.mem[1998] = word(11);
+            mix.mem[1999] = word(12);
+            mix.r_in[1] = HalfWord::from_i64(3000).0;
+        });
+        assert_eq!(result, Ok(()));
+        let moved: Vec<i64> = (3000..3003).map(|a| mix.mem[a].to_i64().0).collect();
+        assert_eq!(moved, [11, 12, 7]);
+        assert_eq!(mix.r_in[1].to_i64().0, 3003);
+
+        let (mut mix, result) = step(Instruction::new(100, 2, 0, Opcode::Move), |mix| {
+            mix.map_device(2000..2001, counter()).unwrap();
+            mix.mem[1] = FullWord::from(Instruction::new(2000, 5, 0, Opcode::LdA));
+            mix.mem[100] = word(5);
+            mix.mem[101] = word(2);
+            mix.r_in[1] = HalfWord::from_i64(1999).0;
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.mem[1999].to_i64().0, 5);
+        assert_eq!(mix.mem[2000].to_i64().0, 0);
+        assert_eq!(mix.r_in[1].to_i64().0, 2001);
+        assert_eq!(mix.step(), Ok(()));
+        assert_eq!(mix.r_a.to_i64().0, 9);
+    }
+
+    #[test]
+    fn instructions_are_fetched_through_the_mapping() {
+        let hlt = FullWord::from(Instruction::new(0, 2, 0, Opcode::Special));
+        for pc in [3995, 4005] {
+            let mut mix = VM::new();
+            mix.reset();
+            mix.map_device(3990..4010, Box::new(RomDevice(hlt)))
+                .unwrap();
+            mix.pc = pc;
+            mix.halted = false;
+            assert_eq!(mix.step(), Ok(()), "{}", pc);
+            assert!(mix.halted, "{}", pc);
+            assert_eq!(mix.pc, pc + 1);
         }
-        Ok(())
     }
 
-    /// Handler for `SLA`, `SRA`, `SLAX`, `SRAX`, `SLC`, `SRC`, `SLB` and `SRB`.
-    fn handle_instr_shift(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        let count = self.helper_get_eff_addr(instr.addr, instr.index)?;
-        if instr.field == 0 || instr.field == 1 {
-            // SLA and SRA.
-            // Spread original register to bytes.
-            let orig_value = u64::from_be_bytes(self.r_a.to_i64().0.to_be_bytes());
-            // Shift the value in bits (count * 8, count is in bytes).
-            let shifted_value = match instr.field {
-                0 => orig_value << (count * 8),
-                1 => orig_value >> (count * 8),
-                _ => unreachable!(),
-            };
-            // Store back.
-            self.r_a[1..=5].copy_from_slice(&shifted_value.to_be_bytes()[3..=7]);
-        } else if (instr.field == 2 || instr.field == 3) || (instr.field == 6 || instr.field == 7) {
-            // SLAX, SRAX, SLB and SRB.
-            // Spread original register to bytes.
-            let orig_a_bytes = &self.r_a[1..=5];
-            let orig_x_bytes = &self.r_x[1..=5];
-            let orig_value = u128::from_be_bytes([
-                0,
-                0,
-                0,
-                0,
-                0,
-                0,
-                orig_a_bytes[0],
-                orig_a_bytes[1],
-                orig_a_bytes[2],
-                orig_a_bytes[3],
-                orig_a_bytes[4],
-                orig_x_bytes[0],
-                orig_x_bytes[1],
-                orig_x_bytes[2],
-                orig_x_bytes[3],
-                orig_x_bytes[4],
-            ]);
-            // Shift.
-            let shifted_value = match instr.field {
-                2 => orig_value << (count * 8),
-                3 => orig_value >> (count * 8),
-                6 => orig_value << count,
-                7 => orig_value >> count,
-                _ => unreachable!(),
+    #[test]
+    fn run_with_timeout_runs_the_installed_memory() {
+        let program = MixProgram::from(
+            &[
+                Instruction::new(100, 5, 0, Opcode::LdA),
+                Instruction::new(0, 2, 0, Opcode::Special),
+            ][..],
+        );
+        let mut mix = VM::new();
+        program.install(&mut mix).unwrap();
+        mix.mem[0] = FullWord::from(Instruction::new(101, 5, 0, Opcode::LdA));
+        mix.mem[101] = word(7);
+        mix.r_a = word(1);
+        mix.run_with_timeout(10).unwrap();
+        assert_eq!(mix.r_a.to_i64().0, 7);
+        let result = mix.run_with_timeout(1).map(|_| ());
+        assert_eq!(result, Err(RunError::Timeout(1)));
+    }
+
+    /// The largest magnitude of a word.
+    const WORD_MAX: i64 = (1 << (5 * BYTE_BITS)) - 1;
+
+    #[test]
+    fn mul_splits_the_product_at_the_byte_size() {
+        // (B^5 - 1)^2 = (B^5 - 2) * B^5 + 1 for bytes of B values.
+        for (a, v, negative) in [(WORD_MAX, WORD_MAX, false), (-WORD_MAX, WORD_MAX, true)] {
+            let (mix, result) = step(Instruction::new(2000, 5, 0, Opcode::Mul), |mix| {
+                mix.r_a = word(a);
+                mix.mem[2000] = word(v);
+            });
+            assert_eq!(result, Ok(()));
+            for reg in [mix.r_a, mix.r_x] {
+                let bytes = &reg.as_array()[1..];
+                assert!(bytes.iter().all(|&b| (b as u32) < 1 << BYTE_BITS));
+                assert_eq!(reg.is_negative(), negative);
+            }
+            assert_eq!(mix.r_a.to_i64().0.abs(), WORD_MAX - 1);
+            assert_eq!(mix.r_x.to_i64().0.abs(), 1);
+            assert!(!mix.overflow);
+        }
+    }
+
+    #[test]
+    fn div_joins_the_dividend_at_the_byte_size() {
+        let (mix, result) = step(Instruction::new(2000, 5, 0, Opcode::Div), |mix| {
+            mix.r_a = word(WORD_MAX - 1);
+            mix.r_x = word(3);
+            mix.mem[2000] = word(WORD_MAX);
+        });
+        assert_eq!(result, Ok(()));
+        // (B^5 - 2) * B^5 + 3 = (B^5 - 1)^2 + 2.
+        assert_eq!(mix.r_a.to_i64().0, WORD_MAX);
+        assert_eq!(mix.r_x.to_i64().0, 2);
+        assert!(!mix.overflow);
+    }
+
+    #[test]
+    fn stj_field_must_lie_within_0_2() {
+        for (field, legal) in [
+            (2, true),
+            (10, true),
+            (0, true),
+            (18, true),
+            (5, false),
+            (3, false),
+            (11, false),
+            (27, false),
+        ] {
+            let instr = Instruction::new(2000, field, 0, Opcode::StJ);
+            let expected = if legal {
+                Ok(())
+            } else {
+                Err(ErrorCode::InvalidFieldRange { field })
             };
-            // Store back.
-            let shifted_bytes = shifted_value.to_be_bytes();
-            self.r_a[1..=5].copy_from_slice(&shifted_bytes[6..=10]);
-            self.r_x[1..=5].copy_from_slice(&shifted_bytes[11..=15]);
-        } else if instr.field == 4 || instr.field == 5 {
-            // SLC and SRC.
-            // Spread out bytes.
-            let orig_bytes = [
-                self.r_a[1],
-                self.r_a[2],
-                self.r_a[3],
-                self.r_a[4],
-                self.r_a[5],
-                self.r_x[1],
-                self.r_x[2],
-                self.r_x[3],
-                self.r_x[4],
-                self.r_x[5],
+            assert_eq!(instr.validate(), expected, "{}", field);
+            let (_, result) = step(instr, |mix| mix.r_j = PosHalfWord::from_i64(7).0);
+            assert_eq!(result, expected, "{}", field);
+        }
+    }
+
+    #[test]
+    fn num_and_char_in_base_10() {
+        // The example of Knuth, in section 1.3.1.
+        let (mix, result) = step(Instruction::new(0, 0, 0, Opcode::Special), |mix| {
+            mix.r_a.set_all([FullWord::NEG, 0, 0, 31, 32, 39]);
+            mix.r_x.set_all([FullWord::POS, 37, 57, 47, 30, 30]);
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.r_a.to_i64(), (-12_977_700, false));
+        assert_eq!(mix.r_x.as_array(), &[FullWord::POS, 37, 57, 47, 30, 30]);
+
+        let (mix, result) = step(Instruction::new(0, 1, 0, Opcode::Special), |mix| {
+            mix.r_a = word(-12_977_699);
+            mix.r_x = word(1);
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(mix.r_a.as_array(), &[FullWord::NEG, 30, 30, 31, 32, 39]);
+        assert_eq!(mix.r_x.as_array(), &[FullWord::POS, 37, 37, 36, 39, 39]);
+    }
+
+    #[test]
+    fn num_and_char_round_trip_in_other_radixes() {
+        for radix in 2..=10 {
+            let value = WORD_MAX.min(i64::from(radix).pow(10) - 1) / 3;
+            let (chars, _) = step(Instruction::new(0, 1, 0, Opcode::Special), |mix| {
+                mix.num_char_radix = radix;
+                mix.r_a = word(value);
+            });
+            for &byte in chars.r_a[1..=5].iter().chain(&chars.r_x[1..=5]) {
+                assert!((30..30 + radix).contains(&byte), "{}: {}", radix, byte);
+            }
+            let (num, result) = step(Instruction::new(0, 0, 0, Opcode::Special), |mix| {
+                mix.num_char_radix = radix;
+                mix.r_a = chars.r_a;
+                mix.r_x = chars.r_x;
+            });
+            assert_eq!(result, Ok(()));
+            assert_eq!(num.r_a.to_i64(), (value, false), "{}", radix);
+        }
+
+        for radix in [0, 1, 11, 16] {
+            for field in [0, 1] {
+                let (_, result) = step(Instruction::new(0, field, 0, Opcode::Special), |mix| {
+                    mix.num_char_radix = radix;
+                });
+                assert_eq!(result, Err(ErrorCode::Generic), "{}", radix);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_fields_are_told_apart() {
+        let range = |field| Err(ErrorCode::InvalidFieldRange { field });
+        let mode = |opcode, field| Err(ErrorCode::InvalidFieldMode { opcode, field });
+        let table = [
+            // Loads, stores, arithmetic and comparisons take a field.
+            (Opcode::LdA, 6, range(6)),
+            (Opcode::Ld1N, 25, range(25)),
+            (Opcode::StA, 6, range(6)),
+            (Opcode::StZ, 24, range(24)),
+            (Opcode::Add, 26, range(26)),
+            (Opcode::Div, 6, range(6)),
+            (Opcode::CmpA, 6, range(6)),
+            (Opcode::CmpX, 40, range(40)),
+            // Jumps, address transfers, shifts and specials select modes.
+            (Opcode::Jmp, 12, mode(Opcode::Jmp, 12)),
+            (Opcode::JA, 8, mode(Opcode::JA, 8)),
+            (Opcode::J1, 6, mode(Opcode::J1, 6)),
+            (Opcode::JX, 9, mode(Opcode::JX, 9)),
+            (Opcode::ModifyA, 4, mode(Opcode::ModifyA, 4)),
+            (Opcode::Modify1, 5, mode(Opcode::Modify1, 5)),
+            (Opcode::Shift, 8, mode(Opcode::Shift, 8)),
+            (Opcode::Special, 16, mode(Opcode::Special, 16)),
+        ];
+        for (opcode, field, expected) in table {
+            let instr = Instruction::new(100, field, 0, opcode);
+            assert_eq!(instr.validate(), expected, "{:?} {}", opcode, field);
+            let (_, result) = step(instr, |_| {});
+            assert_eq!(result, expected, "{:?} {}", opcode, field);
+        }
+    }
+
+    /// Install a program with a logging printer on unit 18.
+    fn machine_with_printer(
+        program: &[Instruction],
+    ) -> (VM, std::rc::Rc<core::cell::RefCell<Vec<String>>>) {
+        let log = std::rc::Rc::new(core::cell::RefCell::new(Vec::new()));
+        let mut mix = VM::new();
+        MixProgram::from(program).install(&mut mix).unwrap();
+        mix.attach_device(
+            18,
+            Box::new(LoggingDevice {
+                name: "printer",
+                block_size: 24,
+                bandwidth: None,
+                log: log.clone(),
+            }),
+        );
+        (mix, log)
+    }
+
+    #[test]
+    fn hlt_flushes_output_without_ioc() {
+        let (mut mix, log) = machine_with_printer(&[
+            Instruction::new(2000, 18, 0, Opcode::Out),
+            Instruction::new(0, 2, 0, Opcode::Special),
+        ]);
+        mix.reset();
+        mix.restart();
+        mix.step().unwrap();
+        assert_eq!(*log.borrow(), ["printer write"]);
+        // The flush comes from HLT itself, not from the end of a run.
+        mix.step().unwrap();
+        assert!(mix.halted);
+        assert_eq!(*log.borrow(), ["printer write", "printer flush"]);
+    }
+
+    #[test]
+    fn runs_flush_output_on_every_exit() {
+        // Killed by the step limit.
+        let (mut mix, log) = machine_with_printer(&[
+            Instruction::new(2000, 18, 0, Opcode::Out),
+            Instruction::new(1, 0, 0, Opcode::Jmp),
+        ]);
+        let result = mix.run_with_timeout(50).map(|_| ());
+        assert_eq!(result, Err(RunError::Timeout(50)));
+        assert_eq!(*log.borrow(), ["printer write", "printer flush"]);
+
+        // Stopped by a failing instruction.
+        let (mut mix, log) = machine_with_printer(&[
+            Instruction::new(2000, 18, 0, Opcode::Out),
+            Instruction::new(2000, 6, 0, Opcode::LdA),
+        ]);
+        mix.reset();
+        mix.restart();
+        assert_eq!(
+            mix.run_until_halt(),
+            Err(ErrorCode::InvalidFieldRange { field: 6 })
+        );
+        assert_eq!(*log.borrow(), ["printer write", "printer flush"]);
+    }
+
+    #[test]
+    fn inc1_past_the_range_in_each_mode() {
+        let index_max = (1 << (2 * BYTE_BITS)) - 1;
+        // INC1 5 from the largest positive value, and DEC1 5 from the
+        // largest negative value, in each mode.
+        for (field, start, negative) in [(0, index_max, false), (1, -index_max, true)] {
+            let table = [
+                (IndexOverflowMode::Wrap, Ok(()), 4, true),
+                (IndexOverflowMode::Saturate, Ok(()), index_max, true),
+                (
+                    IndexOverflowMode::Trap,
+                    Err(ErrorCode::IndexOverflow),
+                    index_max,
+                    false,
+                ),
             ];
-            // Zero the registers.
-            self.r_a[1..=5].copy_from_slice(&[0; 5]);
-            self.r_x[1..=5].copy_from_slice(&[0; 5]);
-            // Create cyclic iterator.
-            let mut orig_bytes_iter = orig_bytes.iter().cycle();
-            // Get shift count.
-            let offset = if instr.field == 4 {
-                // SLC.
-                count % 10
+            for (mode, expected, magnitude, overflow) in table {
+                let (mix, result) = step(Instruction::new(5, field, 0, Opcode::Modify1), |mix| {
+                    mix.index_overflow_mode = mode;
+                    mix.r_in[1] = HalfWord::from_i64(start).0;
+                });
+                assert_eq!(result, expected, "{:?}", mode);
+                assert_eq!(
+                    reg(&mix, RegisterId::I1),
+                    (negative, magnitude),
+                    "{:?}",
+                    mode
+                );
+                assert_eq!(mix.overflow, overflow, "{:?}", mode);
+            }
+        }
+
+        // Results in range are exact in every mode.
+        for mode in [
+            IndexOverflowMode::Wrap,
+            IndexOverflowMode::Saturate,
+            IndexOverflowMode::Trap,
+        ] {
+            let (mix, result) = step(Instruction::new(5, 0, 0, Opcode::Modify1), |mix| {
+                mix.index_overflow_mode = mode;
+                mix.r_in[1] = HalfWord::from_i64(index_max - 5).0;
+            });
+            assert_eq!(result, Ok(()));
+            assert_eq!(reg(&mix, RegisterId::I1), (false, index_max));
+            assert!(!mix.overflow);
+        }
+    }
+
+    #[test]
+    fn field_7_follows_the_ieee754_feature() {
+        let enabled = cfg!(feature = "x-ieee754");
+        for (opcode, expected) in [
+            (Opcode::Add, 5.0),
+            (Opcode::Sub, 1.0),
+            (Opcode::Mul, 6.0),
+            (Opcode::Div, 1.5),
+        ] {
+            let (mix, result) = step(Instruction::new(2000, 7, 0, opcode), |mix| {
+                mix.r_a = FullWord::from_f32(3.0);
+                mix.mem[2000] = FullWord::from_f32(2.0);
+            });
+            if enabled {
+                assert_eq!(result, Ok(()), "{:?}", opcode);
+                assert_eq!(mix.r_a.to_f32(), expected, "{:?}", opcode);
             } else {
-                // SRC.
-                10 - count % 10
-            };
-            for _ in 0..offset {
-                // Advance the iterator by `offset` steps,
-                // to simulate shifting.
-                // The iterator is infinite so we don't worry about
-                // panics.
-                orig_bytes_iter.next().or_else(|| unreachable!());
+                assert_eq!(result, Err(ErrorCode::InvalidFieldRange { field: 7 }));
+                assert_eq!(mix.r_a.to_f32(), 3.0, "{:?}", opcode);
             }
-            // Write back.
-            for (reg_i, &digit) in (0..10).zip(orig_bytes_iter) {
-                if reg_i >= 5 {
-                    self.r_x[reg_i - 5 + 1] = digit;
-                } else {
-                    self.r_a[reg_i + 1] = digit;
+        }
+
+        // Only a float comparison can be unordered.
+        for opcode in [Opcode::CmpA, Opcode::CmpX] {
+            let (mix, result) = step(Instruction::new(2000, 7, 0, opcode), |mix| {
+                mix.r_a = FullWord::from_f32(1.0);
+                mix.r_x = FullWord::from_f32(1.0);
+                mix.mem[2000] = FullWord::from_f32(f32::NAN);
+            });
+            if enabled {
+                assert_eq!(result, Ok(()), "{:?}", opcode);
+                assert_eq!(mix.comp, CompIndicator::Unordered, "{:?}", opcode);
+            } else {
+                assert_eq!(result, Err(ErrorCode::InvalidFieldRange { field: 7 }));
+                assert_eq!(mix.comp, CompIndicator::Equal, "{:?}", opcode);
+            }
+        }
+
+        for (opcode, field) in [
+            (Opcode::Special, 3),
+            (Opcode::Special, 8),
+            (Opcode::Jmp, 10),
+            (Opcode::Jmp, 11),
+        ] {
+            let instr = Instruction::new(100, field, 0, opcode);
+            let expected = if enabled {
+                Ok(())
+            } else {
+                Err(ErrorCode::InvalidFieldMode { opcode, field })
+            };
+            assert_eq!(instr.validate(), expected, "{:?} {}", opcode, field);
+            let (_, result) = step(instr, |_| {});
+            assert_eq!(result, expected, "{:?} {}", opcode, field);
+        }
+    }
+
+    #[cfg(feature = "x-ieee754")]
+    #[test]
+    fn float_arithmetic_matches_the_host() {
+        let values = [1.0f32, -2.5, 3.0e-5, 1.0e20, -7.125, 0.1, 12_345.678];
+        for &a in &values {
+            for &b in &values {
+                for (opcode, host) in [
+                    (Opcode::Add, a + b),
+                    (Opcode::Sub, a - b),
+                    (Opcode::Mul, a * b),
+                    (Opcode::Div, a / b),
+                ] {
+                    let (mix, result) = step(Instruction::new(2000, 7, 0, opcode), |mix| {
+                        mix.r_a = FullWord::from_f32(a);
+                        mix.mem[2000] = FullWord::from_f32(b);
+                    });
+                    assert_eq!(result, Ok(()));
+                    assert_eq!(
+                        mix.r_a.to_f32().to_bits(),
+                        host.to_bits(),
+                        "{:?} {} {}",
+                        opcode,
+                        a,
+                        b
+                    );
+                    assert_eq!(mix.r_a.is_negative(), host.is_sign_negative());
                 }
             }
-        } else {
-            return Err(ErrorCode::InvalidField);
         }
-        Ok(())
     }
 
-    /// Handler for `JBUS` and `JRED`.
-    fn handle_instr_jbus_jred(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Get device ID.
-        let dev_id: usize = instr.field as usize;
-        // Get device reference.
-        let dev = self.helper_get_io_device(dev_id)?;
-        // Call appropriate callbacks.
-        let should_jump = match instr.opcode {
-            Opcode::Jbus => dev.is_busy().map_err(|_| ErrorCode::IOError)?,
-            Opcode::Jred => dev.is_ready().map_err(|_| ErrorCode::IOError)?,
-            _ => unreachable!(),
+    #[cfg(feature = "x-ieee754")]
+    #[test]
+    fn float_edge_cases_are_pinned() {
+        let run = |opcode, a: f32, b: f32| {
+            let (mix, result) = step(Instruction::new(2000, 7, 0, opcode), |mix| {
+                mix.r_a = FullWord::from_f32(a);
+                mix.mem[2000] = FullWord::from_f32(b);
+            });
+            assert_eq!(result, Ok(()));
+            mix.r_a
         };
-        if should_jump {
-            // Do jump.
-            let jump_addr = self.helper_get_eff_addr(instr.addr, instr.index)?;
-            self.helper_do_jump(jump_addr, true);
+        // Invalid operations give the canonical NaN.
+        for (opcode, a, b) in [
+            (Opcode::Div, 0.0, 0.0),
+            (Opcode::Sub, f32::INFINITY, f32::INFINITY),
+            (Opcode::Mul, -0.0, f32::NEG_INFINITY),
+            (Opcode::Add, f32::NAN, 1.0),
+        ] {
+            let word = run(opcode, a, b);
+            assert_eq!(
+                word.to_f32().to_bits(),
+                float::CANONICAL_NAN,
+                "{:?}",
+                opcode
+            );
+            assert!(word.is_positive());
+        }
+        // Signed zeros keep their sign, in the sign byte too.
+        for (opcode, a, b, negative) in [
+            (Opcode::Add, -0.0, -0.0, true),
+            (Opcode::Add, -0.0, 0.0, false),
+            (Opcode::Mul, 0.0, -1.0, true),
+            (Opcode::Sub, 0.0, 0.0, false),
+            (Opcode::Div, -1.0, f32::INFINITY, true),
+        ] {
+            let word = run(opcode, a, b);
+            assert_eq!(word.to_f32(), 0.0, "{:?}", opcode);
+            assert_eq!(word.to_f32().is_sign_negative(), negative, "{:?}", opcode);
+            assert_eq!(word.is_negative(), negative, "{:?}", opcode);
         }
-        Ok(())
     }
 
-    /// Handler for `IOC`.
-    fn handle_instr_ioc(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Get command.
-        let command = self.helper_get_eff_addr_unchecked(instr.addr, instr.index);
-        // Get device ID.
-        let dev_id: usize = instr.field as usize;
-        // Get device reference.
-        let dev = self.helper_get_io_device_mut(dev_id)?;
-        // Call appropriate callbacks.
-        dev.control(command).map_err(|_| ErrorCode::IOError)?;
-        Ok(())
+    #[cfg(feature = "x-ieee754")]
+    #[test]
+    fn float_conversions_saturate() {
+        let convert = |field, value: f32| {
+            let (mix, result) = step(Instruction::new(0, field, 0, Opcode::Special), |mix| {
+                mix.r_a = FullWord::from_f32(value);
+            });
+            assert_eq!(result, Ok(()));
+            // The result takes 8-bit bytes even with 6-bit bytes.
+            let magnitude = u32::from_be_bytes([mix.r_a[2], mix.r_a[3], mix.r_a[4], mix.r_a[5]]);
+            let sign = if mix.r_a.is_negative() { -1 } else { 1 };
+            (sign * magnitude as i64, mix.overflow)
+        };
+        assert_eq!(convert(3, -3.7), (-3, false));
+        assert_eq!(convert(3, 1.0e12), (u32::MAX as i64, true));
+        assert_eq!(convert(4, 1.0e6), (u16::MAX as i64, true));
+        assert_eq!(convert(4, -1.0e6), (-(u16::MAX as i64), true));
+        // Overflows are flagged outside the signed range, before
+        // truncation.
+        assert_eq!(convert(5, 126.9), (126, false));
+        assert_eq!(convert(5, 127.5), (127, true));
+        assert_eq!(convert(5, 300.0), (u8::MAX as i64, true));
+        assert_eq!(convert(5, f32::NAN), (0, true));
+        assert_eq!(convert(5, f32::NEG_INFINITY), (-(u8::MAX as i64), true));
     }
 
-    /// Handler for `IN` and `OUT`.
-    fn handle_instr_in_out(&mut self, instr: &Instruction) -> Result<(), ErrorCode> {
-        // Check starting address.
-        let addr_start = self.helper_get_eff_addr(instr.addr, instr.index)?;
-        if !(0..Mem::SIZE as u16).contains(&addr_start) {
-            return Err(ErrorCode::InvalidAddress);
+    #[test]
+    fn registers_are_seeded_from_integers() {
+        let index_max = (1 << (2 * BYTE_BITS)) - 1;
+        let mut mix = VM::new();
+        assert!(!mix.set_a(-WORD_MAX));
+        assert!(!mix.set_x(12345));
+        assert_eq!(mix.set_index(1, -index_max), Ok(false));
+        assert_eq!(mix.set_index(6, 300), Ok(false));
+        assert!(!mix.set_j(index_max));
+        assert_eq!(reg(&mix, RegisterId::A), (true, WORD_MAX));
+        assert_eq!(reg(&mix, RegisterId::X), (false, 12345));
+        assert_eq!(reg(&mix, RegisterId::I1), (true, index_max));
+        assert_eq!(reg(&mix, RegisterId::I6), (false, 300));
+        assert_eq!(mix.r_j.to_i64(), (index_max, false));
+
+        // Overflowing values keep their lowest bytes.
+        assert!(mix.set_a(WORD_MAX + 3));
+        assert_eq!(reg(&mix, RegisterId::A), (false, 2));
+        assert!(mix.set_x(-(WORD_MAX + 1)));
+        assert_eq!(reg(&mix, RegisterId::X), (true, 0));
+        assert_eq!(mix.set_index(2, index_max + 6), Ok(true));
+        assert_eq!(reg(&mix, RegisterId::I2), (false, 5));
+        assert!(mix.set_j(index_max + 1));
+        assert_eq!(mix.r_j.to_i64(), (0, false));
+
+        // rJ cannot be negative.
+        assert!(mix.set_j(-7));
+        assert_eq!(mix.r_j.to_i64(), (7, false));
+    }
+
+    #[test]
+    fn setters_take_the_most_negative_integer() {
+        // The magnitude 2^63 overflows every register, leaving zeros.
+        let mut mix = VM::new();
+        assert!(mix.set_a(i64::MIN));
+        assert_eq!(reg(&mix, RegisterId::A), (true, 0));
+        assert!(mix.set_x(i64::MIN));
+        assert_eq!(reg(&mix, RegisterId::X), (true, 0));
+        assert_eq!(mix.set_index(3, i64::MIN), Ok(true));
+        assert_eq!(reg(&mix, RegisterId::I3), (true, 0));
+        assert!(mix.set_j(i64::MIN));
+        assert_eq!(mix.r_j.to_i64(), (0, false));
+        assert_eq!(FullWord::from_i64(i64::MIN + 1).0.to_i64().0, -WORD_MAX);
+    }
+
+    #[test]
+    fn set_index_rejects_other_registers() {
+        let mut mix = VM::new();
+        for n in [0, 7, usize::MAX] {
+            assert_eq!(mix.set_index(n, 1), Err(ErrorCode::InvalidIndex), "{}", n);
         }
-        // Get device ID.
-        let dev_id: usize = instr.field as usize;
-        // Get device reference.
-        let dev = self
-            .io_devices
-            .get_mut(dev_id)
-            .ok_or(ErrorCode::InvalidField)?
-            .as_mut()
-            .ok_or(ErrorCode::UnknownDevice)?;
-        let dev_blk_size = dev.get_block_size();
-        // Check ending address.
-        let addr_end = addr_start + dev_blk_size as u16;
-        if !(0..Mem::SIZE as u16).contains(&addr_end) {
-            return Err(ErrorCode::InvalidAddress);
+        assert!(mix.r_in.iter().all(|reg| reg.to_i64() == (0, false)));
+    }
+
+    #[test]
+    fn index_registers_hold_two_bytes_on_every_path() {
+        let index_max: i64 = (1 << (2 * BYTE_BITS)) - 1;
+        // Each path with the value of rI1 it should produce, run once
+        // at the boundary and once past it.
+        let table = [
+            ("LD1", Opcode::Ld1, 5, 0, index_max),
+            ("LD1 negative", Opcode::Ld1, 5, 0, -index_max),
+            ("LD1N", Opcode::Ld1N, 5, 0, -index_max),
+            ("INC1", Opcode::Modify1, 0, 0, index_max),
+            ("DEC1", Opcode::Modify1, 1, 0, -index_max),
+            ("ENT1", Opcode::Modify1, 2, 2, index_max),
+            ("ENN1", Opcode::Modify1, 3, 2, -index_max),
+        ];
+        for (name, opcode, field, index, expected) in table {
+            for past in [0, 1] {
+                for strict in [false, true] {
+                    let target = expected + expected.signum() * past;
+                    let instr = match opcode {
+                        Opcode::Modify1 if field <= 1 => Instruction::new(1, field, 0, opcode),
+                        Opcode::Modify1 => Instruction::new(past as i16, field, index, opcode),
+                        _ => Instruction::new(2000, field, index, opcode),
+                    };
+                    let (mix, result) = step(instr, |mix| {
+                        mix.strict_index = strict;
+                        let loaded = if opcode == Opcode::Ld1N {
+                            -target
+                        } else {
+                            target
+                        };
+                        mix.mem[2000] = word(loaded);
+                        // INC1 1 and DEC1 1 step onto the target.
+                        let start = target - target.signum();
+                        mix.r_in[1] = HalfWord::from_i64(start).0;
+                        mix.r_in[2] = HalfWord::from_i64(index_max).0;
+                    });
+                    let what = format!("{} past={} strict={}", name, past, strict);
+                    if past == 0 {
+                        assert_eq!(result, Ok(()), "{}", what);
+                        assert_eq!(mix.r_in[1].to_i64().0, expected, "{}", what);
+                        assert!(!mix.overflow, "{}", what);
+                    } else if strict {
+                        assert_eq!(result, Err(ErrorCode::IndexRangeViolation), "{}", what);
+                    } else {
+                        assert_eq!(result, Ok(()), "{}", what);
+                        assert!(mix.overflow, "{}", what);
+                    }
+                }
+            }
         }
-        // Call appropriate callbacks.
-        match instr.opcode {
-            Opcode::In => {
-                let slice = &mut self.mem[addr_start as usize..addr_end as usize];
-                dev.read(slice).map_err(|_| ErrorCode::IOError)?;
+
+        // MOVE adds the word count to rI1. Its target is then out of
+        // memory, so only a violation stops the move before it starts.
+        for strict in [false, true] {
+            for (count, violation) in [(0, false), (1, true)] {
+                let (mix, result) = step(Instruction::new(100, count, 0, Opcode::Move), |mix| {
+                    mix.strict_index = strict;
+                    mix.r_in[1] = HalfWord::from_i64(index_max).0;
+                });
+                let expected = match (count, violation && strict) {
+                    (0, _) => Ok(()),
+                    (_, true) => Err(ErrorCode::IndexRangeViolation),
+                    _ => Err(ErrorCode::InvalidAddress),
+                };
+                assert_eq!(result, expected, "{} {}", strict, count);
+                assert_eq!(mix.overflow, violation && !strict, "{} {}", strict, count);
             }
-            Opcode::Out => {
-                // Clone words.
-                let words = &self.mem[addr_start as usize..addr_end as usize];
-                dev.write(words).map_err(|_| ErrorCode::IOError)?;
+        }
+    }
+
+    #[cfg(feature = "x-binarith")]
+    #[test]
+    fn popcnt_and_clz_count_bits_of_the_magnitude() {
+        let ones = ((1u16 << BYTE_BITS) - 1) as u8;
+        let bits = 5 * BYTE_BITS as i64;
+        let table = [
+            ([FullWord::POS, 0, 0, 0, 0, 0], 0, bits),
+            ([FullWord::NEG, 0, 0, 0, 0, 0], 0, bits),
+            ([FullWord::POS, ones, ones, ones, ones, ones], bits, 0),
+            ([FullWord::NEG, ones, ones, ones, ones, ones], bits, 0),
+            ([FullWord::POS, 0, 0, 0, 0, 1], 1, bits - 1),
+            ([FullWord::POS, 1, 0, 0, 0, 0], 1, BYTE_BITS as i64 - 1),
+            (
+                [FullWord::NEG, 0, 0b101, 0, 0b11, 0],
+                4,
+                2 * BYTE_BITS as i64 - 3,
+            ),
+        ];
+        for (bytes, popcnt, clz) in table {
+            for (field, expected) in [(13, popcnt), (14, clz)] {
+                let (mix, result) = step(Instruction::new(0, field, 0, Opcode::Special), |mix| {
+                    mix.r_a.set_all(bytes);
+                    mix.r_x = word(77);
+                });
+                assert_eq!(result, Ok(()));
+                assert_eq!(mix.r_a.to_i64(), (expected, false), "{} {:?}", field, bytes);
+                assert!(mix.r_a.is_positive());
+                assert_eq!(mix.r_x.to_i64().0, 77);
             }
-            _ => unreachable!(),
-        };
-        Ok(())
+        }
     }
-}
 
-impl Default for VM {
-    fn default() -> Self {
-        Self::new()
+    #[cfg(not(feature = "x-binarith"))]
+    #[test]
+    fn popcnt_and_clz_need_the_binarith_feature() {
+        for field in [13, 14] {
+            let (_, result) = step(Instruction::new(0, field, 0, Opcode::Special), |_| {});
+            assert_eq!(
+                result,
+                Err(ErrorCode::InvalidFieldMode {
+                    opcode: Opcode::Special,
+                    field
+                })
+            );
+        }
+        // The older bit operations are always there.
+        let (_, result) = step(Instruction::new(0, 9, 0, Opcode::Special), |_| {});
+        assert_eq!(result, Ok(()));
+    }
+
+    /// The memory cell of the examples of Knuth, in section 1.3.1.
+    const CELL: [u8; 6] = [FullWord::NEG, 1, 2, 3, 4, 5];
+
+    #[test]
+    fn sta_follows_the_examples_of_knuth() {
+        let table = [
+            (5, [FullWord::POS, 6, 7, 8, 9, 0]),
+            (13, [FullWord::NEG, 6, 7, 8, 9, 0]),
+            (45, [FullWord::NEG, 1, 2, 3, 4, 0]),
+            (18, [FullWord::NEG, 1, 0, 3, 4, 5]),
+            (19, [FullWord::NEG, 1, 9, 0, 4, 5]),
+            (1, [FullWord::POS, 0, 2, 3, 4, 5]),
+        ];
+        for (field, expected) in table {
+            let (mix, result) = step(Instruction::new(2000, field, 0, Opcode::StA), |mix| {
+                mix.mem[2000].set_all(CELL);
+                mix.r_a.set_all([FullWord::POS, 6, 7, 8, 9, 0]);
+            });
+            assert_eq!(result, Ok(()));
+            assert_eq!(
+                mix.mem[2000].as_array(),
+                &expected,
+                "({}:{})",
+                field / 8,
+                field % 8
+            );
+        }
+    }
+
+    #[test]
+    fn stores_of_every_field() {
+        let a = [FullWord::POS, 6, 7, 8, 9, 10];
+        let x = [FullWord::NEG, 11, 12, 13, 14, 15];
+        let i2 = [FullWord::NEG, 0, 0, 0, 16, 17];
+        let j = [FullWord::POS, 0, 0, 0, 18, 19];
+        let zero = [FullWord::POS, 0, 0, 0, 0, 0];
+        for (opcode, reg, max) in [
+            (Opcode::StA, a, 5),
+            (Opcode::StX, x, 5),
+            (Opcode::St2, i2, 5),
+            (Opcode::StJ, j, 2),
+            (Opcode::StZ, zero, 5),
+        ] {
+            for r in 0..=max {
+                for l in 0..=r {
+                    // Bytes L to R of the cell take the rightmost bytes
+                    // of the register, the sign only if L is 0.
+                    let mut expected = CELL;
+                    for (offset, pos) in (l.max(1)..=r).rev().enumerate() {
+                        expected[pos] = reg[5 - offset];
+                    }
+                    if l == 0 {
+                        expected[0] = reg[0];
+                    }
+                    let field = (8 * l + r) as u8;
+                    let (mix, result) = step(Instruction::new(2000, field, 0, opcode), |mix| {
+                        mix.mem[2000].set_all(CELL);
+                        mix.r_a.set_all(a);
+                        mix.r_x.set_all(x);
+                        mix.r_in[2].set_all([i2[0], i2[4], i2[5]]);
+                        mix.r_j.set_all([j[0], j[4], j[5]]);
+                    });
+                    assert_eq!(result, Ok(()), "{:?} ({}:{})", opcode, l, r);
+                    assert_eq!(
+                        mix.mem[2000].as_array(),
+                        &expected,
+                        "{:?} ({}:{})",
+                        opcode,
+                        l,
+                        r
+                    );
+                }
+            }
+        }
+    }
+
+    /// The cell `- 80 3 5 4` of the load examples of Knuth, with `80`
+    /// in two 6-bit bytes.
+    const LOAD_CELL: [u8; 6] = [FullWord::NEG, 1, 16, 3, 5, 4];
+
+    #[test]
+    fn lda_follows_the_examples_of_knuth() {
+        let table = [
+            (5, [FullWord::NEG, 1, 16, 3, 5, 4]),
+            (13, [FullWord::POS, 1, 16, 3, 5, 4]),
+            (29, [FullWord::POS, 0, 0, 3, 5, 4]),
+            (3, [FullWord::NEG, 0, 0, 1, 16, 3]),
+            (36, [FullWord::POS, 0, 0, 0, 0, 5]),
+            (0, [FullWord::NEG, 0, 0, 0, 0, 0]),
+            (9, [FullWord::POS, 0, 0, 0, 0, 1]),
+        ];
+        for (field, expected) in table {
+            let (mix, result) = step(Instruction::new(2000, field, 0, Opcode::LdA), |mix| {
+                mix.mem[2000].set_all(LOAD_CELL);
+                mix.r_a = word(-12345);
+            });
+            assert_eq!(result, Ok(()));
+            assert_eq!(
+                mix.r_a.as_array(),
+                &expected,
+                "({}:{})",
+                field / 8,
+                field % 8
+            );
+        }
+    }
+
+    #[test]
+    fn loads_of_every_field() {
+        for (opcode, negate) in [
+            (Opcode::LdA, false),
+            (Opcode::LdX, false),
+            (Opcode::LdAN, true),
+            (Opcode::Ld3, false),
+            (Opcode::Ld3N, true),
+        ] {
+            for r in 0..=5 {
+                for l in 0..=r {
+                    // Bytes L to R of the cell become the rightmost
+                    // bytes, with the sign of the cell only if L is 0.
+                    let mut expected = [FullWord::POS, 0, 0, 0, 0, 0];
+                    for (offset, pos) in (l.max(1)..=r).rev().enumerate() {
+                        expected[5 - offset] = LOAD_CELL[pos];
+                    }
+                    if l == 0 {
+                        expected[0] = LOAD_CELL[0];
+                    }
+                    if negate {
+                        let mut negated = FullWord::from_bytes(expected);
+                        negated.flip_sign();
+                        expected = *negated.as_array();
+                    }
+                    let field = (8 * l + r) as u8;
+                    let (mix, result) = step(Instruction::new(2000, field, 0, opcode), |mix| {
+                        mix.mem[2000].set_all(LOAD_CELL);
+                        mix.r_a = word(-12345);
+                        mix.r_x = word(-12345);
+                        mix.r_in[3] = HalfWord::from_i64(-123).0;
+                    });
+                    let what = format!("{:?} ({}:{})", opcode, l, r);
+                    assert_eq!(result, Ok(()), "{}", what);
+                    match opcode {
+                        Opcode::LdA | Opcode::LdAN => {
+                            assert_eq!(mix.r_a.as_array(), &expected, "{}", what)
+                        }
+                        Opcode::LdX => assert_eq!(mix.r_x.as_array(), &expected, "{}", what),
+                        _ => {
+                            // Only the sign and bytes 4 and 5 are kept,
+                            // with an overflow if more was loaded.
+                            let kept = [expected[0], expected[4], expected[5]];
+                            assert_eq!(mix.r_in[3].as_array(), &kept, "{}", what);
+                            let wide = expected[1..=3].iter().any(|&b| b != 0);
+                            assert_eq!(mix.overflow, wide, "{}", what);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// A card reader with a finite deck, failing once it is empty or
+    /// on the card `jam`.
+    struct Deck {
+        cards: std::collections::VecDeque<i64>,
+        jam: Option<i64>,
+    }
+
+    impl IODevice for Deck {
+        fn read(&mut self, buffer: &mut [FullWord]) -> Result<(), ()> {
+            match self.cards.front() {
+                Some(&card) if Some(card) != self.jam => {
+                    buffer.fill(word(card));
+                    self.cards.pop_front();
+                    Ok(())
+                }
+                _ => Err(()),
+            }
+        }
+
+        fn write(&mut self, _: &[FullWord]) -> Result<(), usize> {
+            Err(0)
+        }
+
+        fn control(&mut self, _: i16) -> Result<(), ()> {
+            Err(())
+        }
+
+        fn is_busy(&self) -> Result<bool, ()> {
+            Ok(false)
+        }
+
+        fn is_ready(&self) -> Result<bool, ()> {
+            Ok(true)
+        }
+
+        fn get_block_size(&self) -> usize {
+            16
+        }
+
+        fn at_eof(&self) -> bool {
+            self.cards.is_empty()
+        }
+    }
+
+    /// Sum the first word of every card until the reader fails.
+    fn read_deck(cards: &[i64], jam: Option<i64>, report_end_of_input: bool) -> (VM, ErrorCode) {
+        let program = [
+            Instruction::new(1000, 16, 0, Opcode::In),
+            Instruction::new(1, 16, 0, Opcode::Jbus),
+            Instruction::new(1000, 5, 0, Opcode::Add),
+            Instruction::new(1, 0, 0, Opcode::Modify1),
+            Instruction::new(0, 0, 0, Opcode::Jmp),
+        ];
+        let mut mix = VM::new();
+        MixProgram::from(&program[..]).install(&mut mix).unwrap();
+        mix.attach_device(
+            16,
+            Box::new(Deck {
+                cards: cards.iter().copied().collect(),
+                jam,
+            }),
+        );
+        mix.report_end_of_input = report_end_of_input;
+        mix.reset();
+        mix.restart();
+        let err = mix.run_until_halt().unwrap_err();
+        (mix, err)
+    }
+
+    #[test]
+    fn end_of_the_deck_is_reported() {
+        let (mix, err) = read_deck(&[3, 4, 5], None, true);
+        assert_eq!(err, ErrorCode::EndOfInput);
+        assert_eq!(mix.r_in[1].to_i64().0, 3);
+        assert_eq!(mix.r_a.to_i64().0, 12);
+        // The faulting IN has been fetched.
+        assert_eq!(mix.pc, 1);
+
+        let (mix, err) = read_deck(&[], None, true);
+        assert_eq!(err, ErrorCode::EndOfInput);
+        assert_eq!(mix.r_in[1].to_i64().0, 0);
+    }
+
+    #[test]
+    fn other_read_failures_are_io_errors() {
+        // Not reported unless asked for.
+        let (mix, err) = read_deck(&[3, 4, 5], None, false);
+        assert_eq!(err, ErrorCode::IOError);
+        assert_eq!(mix.r_in[1].to_i64().0, 3);
+
+        // A jam is not the end of the deck.
+        let (mix, err) = read_deck(&[3, 4, 5], Some(4), true);
+        assert_eq!(err, ErrorCode::IOError);
+        assert_eq!(mix.r_in[1].to_i64().0, 1);
+    }
+
+    #[test]
+    fn register_set_round_trips_through_a_step() {
+        // ADD 100; JMP 0
+        let mut mix = VM::new();
+        mix.reset();
+        mix.mem[0] = FullWord::from(Instruction::new(100, 5, 0, Opcode::Add));
+        mix.mem[1] = FullWord::from(Instruction::new(0, 0, 0, Opcode::Jmp));
+        mix.mem[100] = word(5);
+        mix.r_a = word(WORD_MAX);
+        mix.r_x = word(-3);
+        mix.set_index(2, -7).unwrap();
+        mix.comp = CompIndicator::Less;
+        mix.halted = false;
+        let before = mix.registers();
+
+        mix.step().unwrap();
+        let added = mix.registers();
+        assert_ne!(added, before);
+        assert!(added.overflow);
+        assert_eq!(added.a.as_array(), word(4).as_array());
+        assert_eq!(added.x.as_array(), before.x.as_array());
+        assert_eq!(added.i[2].as_array(), before.i[2].as_array());
+        assert_eq!(added.comp, CompIndicator::Less);
+
+        // Only the register file goes back.
+        mix.set_registers(&before);
+        assert_eq!(mix.registers(), before);
+        assert_eq!(mix.pc, 1);
+        assert_eq!(mix.mem[100].to_i64().0, 5);
+
+        // The jump sets rJ and nothing else.
+        mix.step().unwrap();
+        let jumped = mix.registers();
+        assert_eq!(jumped.j.to_i64().0, 2);
+        mix.set_registers(&RegisterSet {
+            j: before.j,
+            ..jumped
+        });
+        assert_eq!(mix.registers(), before);
+
+        // Stepping again from the restored registers repeats the step.
+        mix.step().unwrap();
+        assert_eq!(mix.pc, 1);
+        assert_eq!(mix.registers(), added);
+    }
+
+    /// A program writing 1000 blocks to a tape on unit 0, waiting out
+    /// each one with `JBUS *`, for about 100,000 cycles in all.
+    fn polling_machine(fast_forward_waits: bool) -> VM {
+        let program = [
+            Instruction::new(1000, 2, 0, Opcode::Modify1),
+            Instruction::new(1000, 0, 0, Opcode::Out),
+            Instruction::new(2, 0, 0, Opcode::Jbus),
+            Instruction::new(1, 1, 0, Opcode::Modify1),
+            Instruction::new(1, 2, 0, Opcode::J1),
+            Instruction::new(0, 2, 0, Opcode::Special),
+        ];
+        let mut mix = VM::new();
+        MixProgram::from(&program[..]).install(&mut mix).unwrap();
+        mix.attach_device(
+            0,
+            Box::new(LoggingDevice {
+                name: "tape",
+                block_size: 100,
+                bandwidth: Some(1),
+                log: Default::default(),
+            }),
+        );
+        mix.fast_forward_waits = fast_forward_waits;
+        mix.reset();
+        mix.restart();
+        mix
+    }
+
+    /// Check that two machines are in the same architectural state.
+    fn assert_same_state(fast: &VM, slow: &VM) {
+        assert_eq!(fast.pc, slow.pc);
+        assert_eq!(fast.halted, slow.halted);
+        assert_eq!(fast.registers(), slow.registers());
+        assert_eq!(fast.cycle_count(), slow.cycle_count());
+        assert_eq!(fast.step_count(), slow.step_count());
+        assert_eq!(fast.device_stats(0), slow.device_stats(0));
+    }
+
+    #[test]
+    fn skipped_waits_match_the_slow_path() {
+        let mut machines = [polling_machine(true), polling_machine(false)];
+        let mut host_steps = [0u64; 2];
+        for (mix, host_steps) in machines.iter_mut().zip(host_steps.iter_mut()) {
+            while !mix.halted {
+                mix.step().unwrap();
+                *host_steps += 1;
+            }
+        }
+        let [fast, slow] = &machines;
+        assert_same_state(fast, slow);
+        assert!(slow.cycle_count() >= 100_000);
+        assert_eq!(slow.step_count(), host_steps[1]);
+        // Each wait of 100 cycles is down to the JBUS skipping it and
+        // the one falling through.
+        assert_eq!(host_steps[0], 1 + 1000 * 5 + 1);
+        assert!(host_steps[1] > 20 * host_steps[0]);
+    }
+
+    #[test]
+    fn skipped_waits_stop_at_the_step_limit() {
+        for max_steps in [1, 2, 3, 50, 5_000, 99_999] {
+            let mut fast = polling_machine(true);
+            let mut slow = polling_machine(false);
+            let fast_result = fast.run_with_timeout(max_steps);
+            let slow_result = slow.run_with_timeout(max_steps);
+            assert!(
+                matches!(fast_result, Err(RunError::Timeout(n)) if n == max_steps),
+                "{}: {:?}",
+                max_steps,
+                fast_result.err()
+            );
+            assert!(matches!(slow_result, Err(RunError::Timeout(n)) if n == max_steps));
+            assert_same_state(&fast, &slow);
+        }
+
+        // A breakpoint on the JBUS keeps every iteration.
+        let mut mix = polling_machine(true);
+        mix.add_breakpoint(2);
+        mix.step().unwrap();
+        mix.step().unwrap();
+        mix.step().unwrap();
+        assert_eq!(mix.step_count(), 3);
+        assert_eq!(mix.pc, 2);
+    }
+
+    #[test]
+    fn subprograms_return_to_the_given_address() {
+        let mut mix = VM::new();
+        mix.reset();
+        // STJ 102; ENTA 7; JMP *
+        mix.mem[100] = FullWord::from(Instruction::new(102, 2, 0, Opcode::StJ));
+        mix.mem[101] = FullWord::from(Instruction::new(7, 2, 0, Opcode::ModifyA));
+        mix.mem[102] = FullWord::from(Instruction::new(0, 0, 0, Opcode::Jmp));
+        assert_eq!(mix.run_subprogram(100, 3000), Ok(()));
+        assert_eq!(reg(&mix, RegisterId::A), (false, 7));
+        assert_eq!(mix.pc, 3000);
+        assert_eq!(mix.step_count(), 3);
+        assert!(!mix.halted);
+    }
+
+    #[test]
+    fn subprograms_report_halts_and_errors() {
+        let mut mix = VM::new();
+        mix.reset();
+        // A `HLT` right before the return address is not a return.
+        mix.mem[200] = FullWord::from(Instruction::new(0, 2, 0, Opcode::Special));
+        assert_eq!(mix.run_subprogram(200, 201), Err(ErrorCode::Halted));
+        assert!(mix.halted);
+        assert!(!mix.faulted);
+
+        // LDA 4000
+        mix.mem[300] = FullWord::from(Instruction::new(4000, 5, 0, Opcode::LdA));
+        assert_eq!(
+            mix.run_subprogram(300, 3000),
+            Err(ErrorCode::InvalidAddress)
+        );
+        assert!(mix.halted);
+        assert!(mix.faulted);
+        assert_eq!(mix.pc, 301);
+    }
+
+    #[test]
+    fn subprograms_get_the_whole_step_limit() {
+        let mut mix = VM::new();
+        mix.reset();
+        mix.set_step_limit(5);
+        // STJ 102; ENTA 7; JMP *
+        mix.mem[100] = FullWord::from(Instruction::new(102, 2, 0, Opcode::StJ));
+        mix.mem[101] = FullWord::from(Instruction::new(7, 2, 0, Opcode::ModifyA));
+        mix.mem[102] = FullWord::from(Instruction::new(0, 0, 0, Opcode::Jmp));
+        assert_eq!(mix.run_subprogram(100, 3000), Ok(()));
+        assert_eq!(mix.run_subprogram(100, 3000), Ok(()));
+
+        // JMP 400
+        mix.mem[400] = FullWord::from(Instruction::new(400, 0, 0, Opcode::Jmp));
+        assert_eq!(
+            mix.run_subprogram(400, 3000),
+            Err(ErrorCode::StepLimitExceeded)
+        );
+        assert_eq!(mix.step_count(), 5);
     }
 }
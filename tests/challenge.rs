@@ -0,0 +1,135 @@
+//! Runs of the challenge binary with scripted answers.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const FLAG: &str = "D3CTF(TECH-EV0LVE,EMBR@C3-PR0GR3SS)";
+const WELCOME: &str = "EXPL0RE 1960S' PAST 1N 4 PRESENT W0RLD";
+const RIGHT: &str = "NOW MARCH BEYOND, AND REVIVE THE LEGACY.";
+const WRONG: &str = "THAT IS NOT CORRECT. TRY AGAIN :D";
+
+/// The key the cipher text of the challenge was made with, as word
+/// literals.
+const KEY: &str = "+61:122:25:196:82 +225:8:159:107:39 +92:179:64:214:142 +145:47:168:115:11";
+
+fn challenge(args: &[&str], input: &str) -> Output {
+    challenge_with_env(args, &[], input)
+}
+
+fn challenge_with_env(args: &[&str], env: &[(&str, &str)], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_modern_legacy"))
+        .args(args)
+        .env_remove("CHALLENGE_KEY")
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn right_answer() {
+    let output = challenge(&[], &format!("{}\n", FLAG));
+    assert!(output.status.success());
+    assert!(stdout(&output).contains(RIGHT));
+}
+
+#[test]
+fn lowercase_answer_is_accepted() {
+    let output = challenge(&[], &format!("  {}\n", FLAG.to_ascii_lowercase()));
+    assert!(output.status.success());
+    assert!(stdout(&output).contains(RIGHT));
+}
+
+#[test]
+fn short_answer_is_rejected() {
+    let output = challenge(&[], "D3CTF(SHORT)\n");
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("the answer must be 35 characters long, not 12"));
+    assert!(!stdout(&output).contains(RIGHT));
+    assert!(!stdout(&output).contains(WRONG));
+}
+
+#[test]
+fn foreign_character_is_rejected() {
+    let output = challenge(&[], "D3CTF(TECH-EV0LVE_EMBR@C3-PR0GR3SS)\n");
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("character '_' at position 18 is not in the MIX alphabet"));
+}
+
+#[test]
+fn key_is_read_from_the_environment() {
+    let input = format!("{}\n", FLAG);
+    let output = challenge_with_env(&[], &[("CHALLENGE_KEY", KEY)], &input);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains(RIGHT));
+
+    let other = KEY.replace("+61:", "+62:");
+    let output = challenge_with_env(&[], &[("CHALLENGE_KEY", &other)], &input);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains(WRONG));
+
+    // A malformed key stops the machine at its `IN`.
+    let output = challenge_with_env(&[], &[("CHALLENGE_KEY", "+61:122")], &input);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Machine stopped abnormally"));
+}
+
+#[test]
+fn raw_mode_keeps_input_as_is() {
+    let output = challenge(&["--raw"], FLAG);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains(RIGHT));
+
+    // Lowercase letters are not in the alphabet and are not fixed up.
+    let output = challenge(&["--raw"], &FLAG.to_ascii_lowercase());
+    assert!(output.status.success());
+    assert!(stdout(&output).contains(WRONG));
+}
+
+#[test]
+fn banner_is_printed_before_reading_the_answer() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_modern_legacy"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let (lines, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if lines.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+
+    // The answer is held back until the banner shows up.
+    let first = rx.recv_timeout(Duration::from_secs(10));
+    let banner = first.as_deref().is_ok_and(|line| line.starts_with(WELCOME));
+    stdin.write_all(format!("{}\n", FLAG).as_bytes()).unwrap();
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+    assert!(banner, "no banner before the answer: {:?}", first);
+}
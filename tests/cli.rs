@@ -0,0 +1,348 @@
+//! Runs of the `mixlegacy` front end on the fixtures.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::OnceLock;
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn temp_path(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    path.to_string_lossy().into_owned()
+}
+
+fn mixlegacy(args: &[&str], input: &str) -> Output {
+    run(env!("CARGO_BIN_EXE_mixlegacy"), args, input)
+}
+
+/// Build `mixlegacy` with `byte-64` toggled, once, and get its path.
+fn mixlegacy_of_other_byte_size() -> &'static PathBuf {
+    static PATH: OnceLock<PathBuf> = OnceLock::new();
+    PATH.get_or_init(|| {
+        let target_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("other-byte-size");
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let mut build = Command::new(cargo);
+        build
+            .args(["build", "-q", "--bin", "mixlegacy", "--manifest-path"])
+            .arg(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"))
+            .arg("--target-dir")
+            .arg(&target_dir);
+        if !cfg!(feature = "byte-64") {
+            build.args(["--features", "byte-64"]);
+        }
+        assert!(build.status().unwrap().success());
+        target_dir.join("debug/mixlegacy")
+    })
+}
+
+fn run(program: impl AsRef<std::ffi::OsStr>, args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn run_assembles_mixal_sources() {
+    let output = mixlegacy(&["run", &fixture("hello.mixal")], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "HELLO FROM MIX\n");
+}
+
+#[test]
+fn run_feeds_the_input_to_the_card_reader() {
+    let input = fixture("cards.txt");
+    let output = mixlegacy(&["run", &fixture("echo.mixal"), "--input", &input], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "FIRST CARD\nSECOND CARD\n");
+}
+
+#[test]
+fn run_rejects_cards_outside_the_alphabet() {
+    let input = fixture("bad_cards.txt");
+    let output = mixlegacy(&["run", &fixture("echo.mixal"), "--input", &input], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains(&format!(
+        "{}:2: character '\\t' at offset 6 is not in the MIX alphabet",
+        input
+    )));
+}
+
+#[test]
+fn run_reports_failures() {
+    let looping = fixture("loop.mixal");
+    let output = mixlegacy(&["run", &looping, "--max-steps", "50"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("machine did not halt after 50 steps"));
+
+    let output = mixlegacy(&["run", &fixture("bad.mixal")], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("bad.mixal:2:15: undefined symbol `NOWHERE`"));
+
+    let card = temp_path("one_card.txt");
+    std::fs::write(&card, "ONLY CARD\n").unwrap();
+    let output = mixlegacy(&["run", &fixture("echo.mixal"), "--input", &card], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "ONLY CARD\n");
+    assert!(stderr(&output).contains("out of input cards"));
+}
+
+#[test]
+fn usage_errors() {
+    let hello = fixture("hello.mixal");
+    let cases: [(&[&str], &str); 7] = [
+        (&[], "missing command"),
+        (&["frobnicate"], "unknown command: frobnicate"),
+        (&["run"], "missing program"),
+        (
+            &["run", &hello, "--max-steps"],
+            "missing value for --max-steps",
+        ),
+        (
+            &["run", &hello, "--max-steps", "many"],
+            "invalid value for --max-steps: many",
+        ),
+        (
+            &["run", &hello, "--entry", "4000"],
+            "invalid address for --entry: 4000",
+        ),
+        (&["asm", &hello], "missing output image (-o)"),
+    ];
+    for (args, message) in cases {
+        let output = mixlegacy(args, "");
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        let stderr = stderr(&output);
+        assert!(
+            stderr.starts_with(&format!("error: {}\n", message)),
+            "{}",
+            stderr
+        );
+        assert!(stderr.contains("Usage: mixlegacy"), "{:?}", args);
+    }
+}
+
+#[test]
+fn help_and_version() {
+    let output = mixlegacy(&["--help"], "");
+    assert_eq!(output.status.code(), Some(0));
+    for command in ["run", "asm", "disasm", "dbg", "batch", "selfcheck"] {
+        assert!(
+            stdout(&output).contains(&format!("\n  {} ", command)),
+            "{}",
+            command
+        );
+    }
+
+    let output = mixlegacy(&["--version"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let version = format!("mixlegacy {}\n", env!("CARGO_PKG_VERSION"));
+    assert_eq!(stdout(&output), version);
+}
+
+#[test]
+fn asm_writes_an_image() {
+    let image = temp_path("hello.img");
+    let output = mixlegacy(
+        &["asm", &fixture("hello.mixal"), "-o", &image, "--listing"],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let listing = stdout(&output);
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[0], "1000  0003EA001225  START    OUT  MSG(PRINTER)");
+    assert!(lines[1].starts_with("1001  000000000205  "));
+
+    let output = mixlegacy(&["disasm", &image, "--from", "1000", "--to", "1001"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "1000  0003EA001225  OUT  1002,0(18)\n1001  000000000205  HLT  0,0\n"
+    );
+
+    let output = mixlegacy(&["run", &image, "--entry", "1000"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "HELLO FROM MIX\n");
+}
+
+#[test]
+fn asm_reports_the_error_position() {
+    let image = temp_path("bad.img");
+    let output = mixlegacy(&["asm", &fixture("bad.mixal"), "-o", &image], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("bad.mixal:2:15: undefined symbol `NOWHERE`"));
+}
+
+#[test]
+fn asm_prints_warnings() {
+    let image = temp_path("overlap.img");
+    let output = mixlegacy(&["asm", &fixture("overlap.mixal"), "-o", &image], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stderr(&output),
+        format!(
+            "{}:2:22: warning: `2(2:3)` overwrites an earlier part\n",
+            fixture("overlap.mixal")
+        )
+    );
+}
+
+#[test]
+fn asm_rejects_values_out_of_range() {
+    let image = temp_path("huge_con.img");
+    let output = mixlegacy(&["asm", &fixture("huge_con.mixal"), "-o", &image], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("huge_con.mixal:2:15: value -"));
+    assert!(stderr(&output).contains(" is out of range"));
+}
+
+#[test]
+fn asm_enforces_the_input_limits() {
+    let image = temp_path("limits.img");
+    let output = mixlegacy(&["asm", &fixture("long_line.mixal"), "-o", &image], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output)
+        .contains("long_line.mixal:2:257: line is 300 characters long, more than 256"));
+
+    let output = mixlegacy(&["asm", &fixture("deep_expr.mixal"), "-o", &image], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("deep_expr.mixal:2:15: expression has more than 64 operators"));
+
+    let output = mixlegacy(&["asm", &fixture("many_segments.mixal"), "-o", &image], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("SegmentCountExceeded { count: 257 }"));
+}
+
+#[test]
+fn run_rejects_images_with_too_many_segments() {
+    let output = mixlegacy(&["run", &fixture("many_segments.img")], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("bad memory image: SegmentCountExceeded { count: 257 }"));
+}
+
+#[test]
+fn dbg_runs_commands_from_the_standard_input() {
+    let script = "b 1001\nc\nregs\ns\ns\nm 1002 2\nd 5\nfoo\nq\nc\n";
+    let output = mixlegacy(&["dbg", &fixture("hello.mixal")], script);
+    assert_eq!(output.status.code(), Some(0));
+    let expected = "\
+HELLO FROM MIX
+breakpoint at 1001
+pc=1001 rA=+0:0:0:0:0 rX=+0:0:0:0:0 rI=[0, 0, 0, 0, 0, 0] rJ=0 CI=Equal OV=false
+1001  000000000205  HLT  0,0
+machine halted
+1002  0008050D0D10  LDAN 2053,13(1:5)
+1003  00000613100E  LD6  6,19(2:0)
+error: no breakpoint at 5
+error: unknown command: foo
+";
+    assert_eq!(stdout(&output), expected);
+}
+
+#[test]
+fn dbg_dumps_stop_at_the_end_of_memory() {
+    let script = "m 3998 18446744073709551615\nm 5 18446744073709551615\nm 3999\nq\n";
+    let output = mixlegacy(&["dbg", &fixture("hello.mixal")], script);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = stdout(&output);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2 + 3995 + 1);
+    assert!(lines[0].starts_with("3998  "));
+    assert!(lines[1].starts_with("3999  "));
+    assert!(lines[2].starts_with("0005  "));
+    assert!(lines[3996].starts_with("3999  "));
+    assert!(lines[3997].starts_with("3999  "));
+}
+
+#[test]
+fn dbg_stores_word_literals() {
+    let script = "set 1002 \"SALUT\"\nset 3000 =-12=\nset 3001 +1:2:3:4:5\nm 3000 2\n\
+                  set 3000 12\nset 3000\nset 4000 =1=\nc\nq\n";
+    let output = mixlegacy(&["dbg", &fixture("hello.mixal")], script);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = stdout(&output);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].starts_with("3000  01000000000C  "), "{}", lines[0]);
+    assert!(lines[1].starts_with("3001  000102030405  "), "{}", lines[1]);
+    assert_eq!(
+        lines[2..],
+        [
+            "error: invalid word: 12: not a word literal",
+            "error: missing word",
+            "error: invalid address: 4000",
+            "SALUT FROM MIX",
+            "machine halted",
+        ]
+    );
+}
+
+#[test]
+fn dbg_honors_the_step_limit() {
+    let looping = fixture("loop.mixal");
+    let output = mixlegacy(&["dbg", &looping, "--max-steps", "10"], "c\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "machine stopped at 2000: StepLimitExceeded\n"
+    );
+}
+
+#[test]
+fn portable_programs_print_the_same_whatever_the_byte_size() {
+    let other = mixlegacy_of_other_byte_size();
+    for (name, portable) in [("portable.mixal", true), ("byte_size.mixal", false)] {
+        let args = ["run", &fixture(name)];
+        let output = mixlegacy(&args, "");
+        let other_output = run(other, &args, "");
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(other_output.status.code(), Some(0));
+        assert_eq!(
+            stdout(&output) == stdout(&other_output),
+            portable,
+            "{}",
+            name
+        );
+    }
+    let output = mixlegacy(&["run", &fixture("portable.mixal")], "");
+    assert_eq!(stdout(&output), "0000005050     0479001600\n");
+}
+
+#[test]
+fn batch_runs_every_job() {
+    let output = mixlegacy(&["batch", &fixture("jobs.txt")], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "HELLO FROM MIX\nFIRST CARD\nSECOND CARD\n");
+    let stderr = stderr(&output);
+    assert!(stderr.contains("jobs.txt:5: machine did not halt after 100 steps"));
+    assert!(stderr.contains("1 of 3 jobs failed"));
+}
+
+#[test]
+fn batch_checks_every_job_before_running() {
+    let output = mixlegacy(&["batch", &fixture("bad_jobs.txt")], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("bad_jobs.txt:2: missing value for --input"));
+}
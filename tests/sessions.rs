@@ -0,0 +1,233 @@
+//! Replays of the recorded challenge sessions in `tests/sessions`.
+//!
+//! A session file starts with `key: value` headers, `status` for the
+//! exit code and optionally `args` for the blank-separated arguments,
+//! and comment lines starting with `#`. Then come the sections
+//! `--- input`, `--- stdout` and `--- stderr`, holding the exact
+//! bytes fed to and expected from the binary, and `--- report`, the
+//! expected halt reason and key memory cells written by `--report`.
+//!
+//! New sessions are recorded with `cargo run --example record_session`.
+//! The unit test `recorded_sessions_catch_program_changes` of the
+//! binary checks that a change to its `PROGRAM` makes them diverge.
+//!
+//! With the feature `csmantle` the binary dumps its memory to stdout,
+//! so the sessions are only replayed without it.
+
+#![cfg(not(feature = "csmantle"))]
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// A recorded session.
+#[derive(Clone, Debug)]
+struct Session {
+    name: String,
+    args: Vec<String>,
+    status: i32,
+    input: String,
+    stdout: String,
+    stderr: String,
+    report: String,
+}
+
+fn sessions_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/sessions")
+}
+
+/// Parse a session file.
+fn parse_session(name: &str, text: &str) -> Result<Session, String> {
+    let (headers, sections) = text
+        .split_once("--- input\n")
+        .ok_or("missing section `input`")?;
+    let (input, rest) = sections
+        .split_once("--- stdout\n")
+        .ok_or("missing section `stdout`")?;
+    let (stdout, rest) = rest
+        .split_once("--- stderr\n")
+        .ok_or("missing section `stderr`")?;
+    let (stderr, report) = rest
+        .split_once("--- report\n")
+        .ok_or("missing section `report`")?;
+
+    let mut args = Vec::new();
+    let mut status = None;
+    for (i, line) in headers.lines().enumerate() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        match line.split_once(": ") {
+            Some(("args", value)) => args = value.split_whitespace().map(String::from).collect(),
+            Some(("status", value)) => {
+                status = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("bad status `{}`", value))?,
+                )
+            }
+            _ => return Err(format!("line {}: bad header `{}`", i + 1, line)),
+        }
+    }
+    Ok(Session {
+        name: name.to_string(),
+        args,
+        status: status.ok_or("missing header `status`")?,
+        input: input.to_string(),
+        stdout: stdout.to_string(),
+        stderr: stderr.to_string(),
+        report: report.to_string(),
+    })
+}
+
+/// Load all sessions, in the order of their names.
+fn load_sessions() -> Vec<Session> {
+    let mut paths: Vec<_> = fs::read_dir(sessions_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "session"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy();
+            let text = fs::read_to_string(path).unwrap();
+            parse_session(&name, &text).unwrap_or_else(|err| panic!("{}: {}", name, err))
+        })
+        .collect()
+}
+
+/// Describe the first line where `actual` differs from `expected`.
+fn line_diff(what: &str, expected: &str, actual: &str) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+    let mut expected_lines = expected.split_inclusive('\n');
+    let mut actual_lines = actual.split_inclusive('\n');
+    let mut number = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => number += 1,
+            (e, a) => {
+                return Some(format!(
+                    "{} line {}: expected {:?}, got {:?}",
+                    what, number, e, a
+                ))
+            }
+        }
+    }
+}
+
+/// Run a session against the binary and list its divergences.
+fn replay(session: &Session) -> Vec<String> {
+    let report_path = std::env::temp_dir().join(format!(
+        "modern_legacy-{}-{}.report",
+        std::process::id(),
+        session.name
+    ));
+    let _ = fs::remove_file(&report_path);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_modern_legacy"))
+        .args(&session.args)
+        .arg("--report")
+        .arg(&report_path)
+        .env_remove("CHALLENGE_KEY")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(session.input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    let mut divergences = Vec::new();
+    if output.status.code() != Some(session.status) {
+        divergences.push(format!(
+            "status: expected {}, got {:?}",
+            session.status,
+            output.status.code()
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    divergences.extend(line_diff("stdout", &session.stdout, &stdout));
+    divergences.extend(line_diff("stderr", &session.stderr, &stderr));
+    let report = fs::read_to_string(&report_path).unwrap_or_default();
+    let _ = fs::remove_file(&report_path);
+    divergences.extend(line_diff("report", &session.report, &report));
+    divergences
+}
+
+#[test]
+fn recorded_sessions_replay() {
+    let sessions = load_sessions();
+    assert!(sessions.len() >= 3, "only {} sessions", sessions.len());
+    let failures: Vec<_> = sessions
+        .iter()
+        .flat_map(|session| {
+            replay(session)
+                .into_iter()
+                .map(move |divergence| format!("{}: {}", session.name, divergence))
+        })
+        .collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn divergences_are_reported() {
+    let text = fs::read_to_string(sessions_dir().join("right.session")).unwrap();
+    let mut session = parse_session("right", &text).unwrap();
+    assert!(replay(&session).is_empty());
+
+    // The same answer is expected to be rejected.
+    session.status = 1;
+    session.stdout = session.stdout.replace(
+        "NOW MARCH BEYOND, AND REVIVE THE LEGACY.",
+        "THAT IS NOT CORRECT. TRY AGAIN :D       ",
+    );
+    session.report = session.report.replace("halt: halted", "halt: Patched");
+    assert_eq!(
+        replay(&session),
+        [
+            "status: expected 1, got Some(0)",
+            "stdout line 3: expected Some(\"THAT IS NOT CORRECT. TRY AGAIN :D       \\n\"), \
+             got Some(\"NOW MARCH BEYOND, AND REVIVE THE LEGACY.\\n\")",
+            "report line 1: expected Some(\"halt: Patched\\n\"), got Some(\"halt: halted\\n\")",
+        ]
+    );
+}
+
+#[test]
+fn malformed_sessions_are_rejected() {
+    let cases = [
+        (
+            "status: 0\n--- stdout\n--- stderr\n--- report\n",
+            "missing section `input`",
+        ),
+        (
+            "status: 0\n--- input\n--- stdout\n--- stderr\n",
+            "missing section `report`",
+        ),
+        (
+            "--- input\n--- stdout\n--- stderr\n--- report\n",
+            "missing header `status`",
+        ),
+        (
+            "status: x\n--- input\n--- stdout\n--- stderr\n--- report\n",
+            "bad status `x`",
+        ),
+        (
+            "# ok\nexit 0\n--- input\n--- stdout\n--- stderr\n--- report\n",
+            "line 2: bad header `exit 0`",
+        ),
+    ];
+    for (text, message) in cases {
+        assert_eq!(parse_session("bad", text).unwrap_err(), message);
+    }
+}